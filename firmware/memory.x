@@ -1,6 +1,8 @@
 MEMORY {
     BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
-    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100
+    FLASH : ORIGIN = 0x10000100, LENGTH = 1024K - 0x100
+    /* The second megabyte holds recorded clips, see src/flash.rs */
+    STORAGE : ORIGIN = 0x10100000, LENGTH = 1024K
     RAM   : ORIGIN = 0x20000000, LENGTH = 256K
 }
 
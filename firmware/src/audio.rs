@@ -0,0 +1,225 @@
+//! RP2040 implementations of the `voice_core` audio traits.
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+use critical_section::Mutex;
+use embedded_hal::PwmPin;
+use rp_pico::hal;
+use voice_core::capture::{Block, CaptureDma, DmaCapture, Underrun, BLOCK_LEN};
+use voice_core::playback::{DmaPlayback, PlaybackDma};
+use voice_core::{AudioSink, AudioSource};
+
+use hal::adc::DmaReadTarget;
+use hal::dma::{single_buffer, Channel, SingleChannel, WriteTarget, CH0, CH1};
+use hal::pac::{self, interrupt};
+
+type Transfer = single_buffer::Transfer<Channel<CH0>, DmaReadTarget<u16>, Block<BLOCK_LEN>>;
+type PlayTransfer = single_buffer::Transfer<Channel<CH1>, Block<BLOCK_LEN>, CarrierCc>;
+
+/// DMA channel 0 copying samples out of the ADC FIFO.
+///
+/// Raises `DMA_IRQ_0` whenever a block is complete.
+pub struct AdcDma {
+    idle: Option<(Channel<CH0>, DmaReadTarget<u16>)>,
+    transfer: Option<Transfer>,
+}
+
+impl AdcDma {
+    /// Use `channel` to read from an ADC FIFO built with `enable_dma`.
+    pub fn new(mut channel: Channel<CH0>, fifo: DmaReadTarget<u16>) -> Self {
+        channel.enable_irq0();
+        Self {
+            idle: Some((channel, fifo)),
+            transfer: None,
+        }
+    }
+}
+
+impl CaptureDma<BLOCK_LEN> for AdcDma {
+    fn start(&mut self, block: Block<BLOCK_LEN>) {
+        let (channel, fifo) = self.idle.take().expect("DMA transfer already in flight");
+        self.transfer = Some(single_buffer::Config::new(channel, fifo, block).start());
+    }
+
+    fn take_done(&mut self) -> Option<Block<BLOCK_LEN>> {
+        let transfer = self.transfer.as_mut()?;
+        transfer.check_irq0();
+        if !transfer.is_done() {
+            return None;
+        }
+        let (channel, fifo, block) = self.transfer.take()?.wait();
+        self.idle = Some((channel, fifo));
+        Some(block)
+    }
+}
+
+/// The compare register of the carrier slice, PWM0, written once per wrap of
+/// the pacer slice, PWM1.
+///
+/// DMA writes 16 bits, which the bus replicates into both halves of the
+/// register, so channel A and B of the carrier output the same level.
+pub struct CarrierCc;
+
+// Safety: the address is a peripheral register, valid for any number of
+// writes, and is not incremented.
+unsafe impl WriteTarget for CarrierCc {
+    type TransmittedWord = u16;
+
+    fn tx_treq() -> Option<u8> {
+        Some(pac::dma::ch::ch_ctrl_trig::TREQ_SEL_A::PWM_WRAP1 as u8)
+    }
+
+    fn tx_address_count(&mut self) -> (u32, u32) {
+        // Safety: only the address of the register is taken.
+        let pwm = unsafe { &*pac::PWM::ptr() };
+        (pwm.ch[0].cc.as_ptr() as u32, u32::MAX)
+    }
+
+    fn tx_increment(&self) -> bool {
+        false
+    }
+}
+
+/// DMA channel 1 feeding the carrier slice, paced by the pacer slice.
+///
+/// Raises `DMA_IRQ_0` whenever a block has been played.
+pub struct PwmDma {
+    idle: Option<(Channel<CH1>, CarrierCc)>,
+    transfer: Option<PlayTransfer>,
+}
+
+impl PwmDma {
+    /// Use `channel` to write to the carrier slice.
+    pub fn new(mut channel: Channel<CH1>) -> Self {
+        channel.enable_irq0();
+        Self {
+            idle: Some((channel, CarrierCc)),
+            transfer: None,
+        }
+    }
+}
+
+impl PlaybackDma<BLOCK_LEN> for PwmDma {
+    fn start(&mut self, block: Block<BLOCK_LEN>) {
+        let (channel, cc) = self.idle.take().expect("DMA transfer already in flight");
+        self.transfer = Some(single_buffer::Config::new(channel, block, cc).start());
+    }
+
+    fn take_done(&mut self) -> Option<Block<BLOCK_LEN>> {
+        let transfer = self.transfer.as_mut()?;
+        transfer.check_irq0();
+        if !transfer.is_done() {
+            return None;
+        }
+        let (channel, block, cc) = self.transfer.take()?.wait();
+        self.idle = Some((channel, cc));
+        Some(block)
+    }
+}
+
+/// ADC capture shared between the main loop and the DMA interrupt.
+pub type Capture = DmaCapture<AdcDma, BLOCK_LEN>;
+
+static CAPTURE: Mutex<RefCell<Option<Capture>>> = Mutex::new(RefCell::new(None));
+
+/// PWM playback shared between the main loop and the DMA interrupt.
+pub type Playback = DmaPlayback<PwmDma, BLOCK_LEN>;
+
+static PLAYBACK: Mutex<RefCell<Option<Playback>>> = Mutex::new(RefCell::new(None));
+
+/// Hand capture and playback over to the DMA interrupt and enable it.
+pub fn start(capture: Capture, playback: Playback) {
+    critical_section::with(|cs| {
+        CAPTURE.borrow_ref_mut(cs).replace(capture);
+        PLAYBACK.borrow_ref_mut(cs).replace(playback);
+    });
+    // Safety: the handler only touches CAPTURE and PLAYBACK, through the
+    // mutexes.
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::DMA_IRQ_0);
+    }
+}
+
+/// Run `f` on the shared capture.
+fn with_capture<R>(f: impl FnOnce(&mut Capture) -> R) -> R {
+    critical_section::with(|cs| {
+        let mut capture = CAPTURE.borrow_ref_mut(cs);
+        f(capture.as_mut().expect("capture not started"))
+    })
+}
+
+/// Run `f` on the shared playback.
+fn with_playback<R>(f: impl FnOnce(&mut Playback) -> R) -> R {
+    critical_section::with(|cs| {
+        let mut playback = PLAYBACK.borrow_ref_mut(cs);
+        f(playback.as_mut().expect("playback not started"))
+    })
+}
+
+#[interrupt]
+fn DMA_IRQ_0() {
+    critical_section::with(|cs| {
+        if let Some(capture) = CAPTURE.borrow_ref_mut(cs).as_mut() {
+            capture.on_complete();
+        }
+        if let Some(playback) = PLAYBACK.borrow_ref_mut(cs).as_mut() {
+            playback.on_complete();
+        }
+    });
+}
+
+/// Reads the samples captured by DMA.
+pub struct CaptureSource;
+
+impl AudioSource for CaptureSource {
+    type Error = Underrun;
+
+    fn available(&mut self) -> usize {
+        with_capture(|c| c.available())
+    }
+
+    fn read(&mut self) -> Result<u16, Underrun> {
+        with_capture(AudioSource::read)
+    }
+
+    fn read_block(&mut self, buf: &mut [u16]) -> Result<usize, Underrun> {
+        Ok(with_capture(|c| c.read_block(buf)))
+    }
+}
+
+/// Queues samples for DMA-paced playback on the carrier slice.
+pub struct PlaybackSink;
+
+impl AudioSink for PlaybackSink {
+    type Error = Infallible;
+
+    fn write(&mut self, sample: u16) -> Result<(), Infallible> {
+        with_playback(|p| p.write(sample))
+    }
+
+    fn write_block(&mut self, samples: &[u16]) -> Result<(), Infallible> {
+        with_playback(|p| AudioSink::write_block(p, samples))
+    }
+}
+
+/// Outputs samples as the duty cycle of a PWM channel.
+pub struct PwmSink<'a, P> {
+    channel: &'a mut P,
+}
+
+impl<'a, P: PwmPin<Duty = u16>> PwmSink<'a, P> {
+    /// Wrap a configured PWM channel.
+    pub fn new(channel: &'a mut P) -> Self {
+        Self { channel }
+    }
+}
+
+impl<P: PwmPin<Duty = u16>> AudioSink for PwmSink<'_, P> {
+    type Error = Infallible;
+
+    fn write(&mut self, sample: u16) -> Result<(), Infallible> {
+        self.channel.set_duty(voice_core::pwm::to_duty(sample));
+        Ok(())
+    }
+}
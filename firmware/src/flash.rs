@@ -0,0 +1,130 @@
+//! The Pico's QSPI flash as a `voice_core` [`NorFlash`].
+//!
+//! Code runs straight out of this flash (XIP), and the flash cannot be read
+//! while it is being erased or programmed. Every erase and program therefore
+//! runs from a function placed in RAM, with interrupts disabled, and calls
+//! only boot ROM routines until XIP is back on. DMA keeps running meanwhile,
+//! but a sector erase takes tens of milliseconds, which is longer than the
+//! capture and playback blocks last.
+
+use rp_pico::hal::rom_data;
+use voice_core::flash::{self, NorFlash};
+
+/// Offset of the clip store: the second megabyte, kept out of the firmware
+/// image by the `STORAGE` region in `memory.x`.
+pub const STORE_BASE: u32 = 0x10_0000;
+
+/// Size of the clip store.
+pub const STORE_LEN: u32 = 0x10_0000;
+
+/// Where the flash is mapped for reading.
+const XIP_BASE: usize = 0x1000_0000;
+
+/// Size of the W25Q16 on the Pico.
+const CAPACITY: usize = 2 * 1024 * 1024;
+
+/// Erase command for 64 KiB blocks, used by the ROM where alignment allows.
+const BLOCK_ERASE: u8 = 0xd8;
+const BLOCK_SIZE: u32 = 1 << 16;
+
+type RomFn = unsafe extern "C" fn();
+
+/// Boot ROM routines used while XIP is off, looked up beforehand because the
+/// lookup code itself lives in flash.
+struct Rom {
+    connect_internal_flash: RomFn,
+    flash_exit_xip: RomFn,
+    flash_range_erase: unsafe extern "C" fn(u32, usize, u32, u8),
+    flash_range_program: unsafe extern "C" fn(u32, *const u8, usize),
+    flash_flush_cache: RomFn,
+}
+
+/// Erase or program with XIP off, then restore XIP by rerunning boot2.
+///
+/// # Safety
+///
+/// Interrupts must be disabled, the other core must not be running from
+/// flash, and `boot2` must point to a copy of boot2 in RAM.
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn flash_op(rom: &Rom, boot2: RomFn, addr: u32, data: *const u8, len: usize, erase: bool) {
+    (rom.connect_internal_flash)();
+    (rom.flash_exit_xip)();
+    if erase {
+        (rom.flash_range_erase)(addr, len, BLOCK_SIZE, BLOCK_ERASE);
+    } else {
+        (rom.flash_range_program)(addr, data, len);
+    }
+    (rom.flash_flush_cache)();
+    boot2();
+}
+
+/// The on-board flash.
+pub struct PicoFlash {
+    rom: Rom,
+    // boot2 sets up fast XIP reads; a copy is kept in RAM to run it again
+    // after every operation
+    boot2: [u32; 64],
+}
+
+impl PicoFlash {
+    /// Look up the ROM routines and copy boot2 out of flash.
+    pub fn new() -> Self {
+        let mut boot2 = [0; 64];
+        // Safety: the first 256 bytes of flash are boot2, mapped at XIP_BASE.
+        unsafe {
+            core::ptr::copy_nonoverlapping(XIP_BASE as *const u32, boot2.as_mut_ptr(), 64);
+        }
+        Self {
+            rom: Rom {
+                connect_internal_flash: rom_data::connect_internal_flash::ptr(),
+                flash_exit_xip: rom_data::flash_exit_xip::ptr(),
+                flash_range_erase: rom_data::flash_range_erase::ptr(),
+                flash_range_program: rom_data::flash_range_program::ptr(),
+                flash_flush_cache: rom_data::flash_flush_cache::ptr(),
+            },
+            boot2,
+        }
+    }
+
+    fn run(&mut self, addr: u32, data: *const u8, len: usize, erase: bool) {
+        // Safety: boot2 is position independent, and it and the routines are
+        // only called with interrupts off on the only running core.
+        cortex_m::interrupt::free(|_| unsafe {
+            let boot2: RomFn = core::mem::transmute(self.boot2.as_ptr() as usize + 1);
+            flash_op(&self.rom, boot2, addr, data, len, erase);
+        });
+    }
+}
+
+impl NorFlash for PicoFlash {
+    type Error = flash::Error;
+    const WRITE_SIZE: usize = 256;
+    const ERASE_SIZE: usize = 4096;
+
+    fn capacity(&self) -> usize {
+        CAPACITY
+    }
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), flash::Error> {
+        flash::check_read(CAPACITY, offset, bytes.len())?;
+        // Safety: the range was checked to lie inside the mapped flash.
+        unsafe {
+            let src = (XIP_BASE + offset as usize) as *const u8;
+            core::ptr::copy_nonoverlapping(src, bytes.as_mut_ptr(), bytes.len());
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), flash::Error> {
+        flash::check_write::<Self>(CAPACITY, offset, bytes.len())?;
+        self.run(offset, bytes.as_ptr(), bytes.len(), false);
+        Ok(())
+    }
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), flash::Error> {
+        flash::check_erase::<Self>(CAPACITY, from, to)?;
+        self.run(from, core::ptr::null(), (to - from) as usize, true);
+        Ok(())
+    }
+}
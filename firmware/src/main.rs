@@ -0,0 +1,198 @@
+//! # voice-tool firmware
+//!
+//! Captures the microphone on GPIO27 with the ADC in free-running mode,
+//! moved out of the FIFO by DMA into ping-pong blocks, runs every block
+//! through the shared `voice_core` pipeline, and plays the result through a
+//! DMA-paced PWM carrier on GPIO16. The LED on PWM4 shows the level. The
+//! clip store in the second megabyte of flash is mounted, or formatted on
+//! first boot, before audio starts.
+//!
+//! It may need to be adapted to your particular board layout and/or pin assignment.
+//!
+//! See the `Cargo.toml` file for Copyright and license details.
+
+#![no_std]
+#![no_main]
+
+// Ensure we halt the program on panic (if we don't mention this crate it won't
+// be linked)
+use panic_halt as _;
+
+// Alias for our HAL crate
+use rp_pico::hal as hal;
+
+// Some traits we need
+use embedded_hal::PwmPin;
+use hal::dma::DMAExt;
+use hal::Clock;
+
+// Audio traits and the shared processing code
+use voice_core::adc;
+use voice_core::capture::{DmaCapture, BLOCK_LEN};
+use voice_core::pipeline::{self, Pipeline, WINDOW_CAPACITY};
+use voice_core::playback::DmaPlayback;
+use voice_core::pwm;
+use voice_core::store::{self, Store};
+use voice_core::AudioSink;
+
+mod audio;
+use audio::{AdcDma, CaptureSource, PlaybackSink, PwmDma, PwmSink};
+
+mod flash;
+use flash::{PicoFlash, STORE_BASE, STORE_LEN};
+
+// A shorter alias for the Peripheral Access Crate, which provides low-level
+// register access
+use hal::pac;
+
+/// External high-speed crystal on the Raspberry Pi Pico board is 12 MHz. Adjust
+/// if your board has a different frequency
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Entry point to our bare-metal application.
+///
+/// The `#[rp2040_hal::entry]` macro ensures the Cortex-M start-up code calls this function
+/// as soon as all global variables and the spinlock are initialised.
+///
+/// The function mounts the clip store, configures the RP2040 peripherals and
+/// starts capture and playback, then runs the pipeline in an infinite loop.
+#[hal::entry]
+fn main() -> ! {
+    // Grab our singleton objects
+    let mut pac = pac::Peripherals::take().unwrap();
+    let core = pac::CorePeripherals::take().unwrap();
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    ).ok().unwrap();
+
+    // The delay object lets us wait for specified amounts of time (in
+    // milliseconds)
+    let _delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().to_Hz());
+
+    // The single-cycle I/O block controls our GPIO pins
+    let sio = hal::Sio::new(pac.SIO);
+
+    // Set the pins to their default state
+    let pins = rp_pico::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    // Open the clip store in the spare half of the flash, setting it up on
+    // first boot. This has to happen before audio starts, as flash
+    // operations hold off the DMA interrupt. For the same reason nothing
+    // records into it from the main loop yet: a VOX driving the store is out
+    // of scope until sector erases can be kept from stalling capture.
+    let mut flash = PicoFlash::new();
+    let _store = match Store::mount(&mut flash, STORE_BASE, STORE_LEN) {
+        Err(store::Error::NotFormatted) => Store::format(&mut flash, STORE_BASE, STORE_LEN),
+        mounted => mounted,
+    }
+    .unwrap();
+
+    // Init PWMs
+    let mut pwm_slices = hal::pwm::Slices::new(pac.PWM, &mut pac.RESETS);
+
+    // Configure PWM4
+    let pwm = &mut pwm_slices.pwm4;
+    pwm.set_ph_correct();
+    pwm.enable();
+
+    // Output channel B on PWM4 to the LED pin
+    let channel = &mut pwm.channel_b;
+    channel.output_to(pins.led);
+
+    // PWM0 is the audio carrier, output on GPIO16; it starts at mid-rail
+    let carrier = pwm::Slice::CARRIER;
+    let audio = &mut pwm_slices.pwm0;
+    audio.set_div_int(carrier.div_int);
+    audio.set_div_frac(carrier.div_frac);
+    audio.set_top(carrier.top);
+    audio.channel_a.set_duty(carrier.duty(2048));
+    audio.channel_a.output_to(pins.gpio16);
+    audio.enable();
+
+    // PWM1 has no pin; it wraps once per sample to pace playback DMA
+    let pacer = adc::SAMPLE_RATE.pacer();
+    let pacer_slice = &mut pwm_slices.pwm1;
+    pacer_slice.set_div_int(pacer.div_int);
+    pacer_slice.set_div_frac(pacer.div_frac);
+    pacer_slice.set_top(pacer.top);
+    pacer_slice.enable();
+
+    // Enable ADC
+    let mut adc = hal::Adc::new(pac.ADC, &mut pac.RESETS);
+
+    // Configure GPIO27 as an ADC input
+    let mut adc_pin_1 = hal::adc::AdcPin::new(pins.gpio27.into_floating_input());
+
+    // Grab the DMA channels; channel 0 moves samples out of the ADC FIFO,
+    // channel 1 moves them into the audio carrier
+    let dma = pac.DMA.split(&mut pac.RESETS);
+
+    // Configure free-running mode:
+    let divider = adc::SAMPLE_RATE.oversampled_divider();
+    let mut adc_fifo = adc
+        .build_fifo()
+        // Set the clock divider for OVERSAMPLE times the configured sample
+        // rate; the pipeline decimates back down to it. The divider is
+        // computed by voice-core, so the rest of the signal chain and the
+        // simulator agree on the rate.
+        .clock_divider(divider.int, divider.frac)
+        // sample the microphone
+        .set_channel(&mut adc_pin_1)
+        // Uncomment this line to produce 8-bit samples, instead of 12 bit (lower bits are discarded)
+        //.shift_8bit()
+        // let DMA drain the FIFO
+        .enable_dma()
+        // don't start sampling until DMA is ready
+        .prepare();
+
+    // DMA fills one block while the main loop works through the other
+    let block_a = cortex_m::singleton!(: [u16; BLOCK_LEN] = [0; BLOCK_LEN]).unwrap();
+    let block_b = cortex_m::singleton!(: [u16; BLOCK_LEN] = [0; BLOCK_LEN]).unwrap();
+    let adc_dma = AdcDma::new(dma.ch0, adc_fifo.dma_read_target());
+    let capture = DmaCapture::new(adc_dma, block_a, block_b);
+
+    // playback keeps one more block so it can start with a block of slack
+    let out_a = cortex_m::singleton!(: [u16; BLOCK_LEN] = [0; BLOCK_LEN]).unwrap();
+    let out_b = cortex_m::singleton!(: [u16; BLOCK_LEN] = [0; BLOCK_LEN]).unwrap();
+    let out_c = cortex_m::singleton!(: [u16; BLOCK_LEN] = [0; BLOCK_LEN]).unwrap();
+    let playback = DmaPlayback::new(PwmDma::new(dma.ch1), carrier.top, [out_a, out_b, out_c]);
+    audio::start(capture, playback);
+
+    // start sampling
+    adc_fifo.resume();
+
+    let mut source = CaptureSource;
+    let mut sink = PlaybackSink;
+    let mut led = PwmSink::new(channel);
+
+    // decimate to the sample rate and take out the microphone bias, metering
+    // the level over the last 100 samples
+    let mut pipeline: Pipeline<WINDOW_CAPACITY> =
+        Pipeline::new(&pipeline::Config::default(), adc::SAMPLE_RATE);
+
+    loop {
+        // filter the captured samples through the chain and queue them for
+        // playback
+        pipeline.step(&mut source, &mut sink).unwrap();
+        // the LED shows the current level as its brightness
+        led.write(pipeline.window().average()).unwrap();
+    }
+}
+
+// End of file
\ No newline at end of file
@@ -0,0 +1,120 @@
+//! Host cycle counts for the averaging and decimation filters.
+//!
+//! Run with `cargo bench -p voice-core --bench decimate`. The counts are for
+//! the host CPU, not the M0+, so they only compare the filters with each
+//! other; what they show is how each one's cost grows with its length.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use voice_core::decimate::{Cic, Decimator};
+use voice_core::Window;
+
+/// Samples pushed per measurement.
+const SAMPLES: usize = 1 << 16;
+
+/// A timestamp in CPU cycles where the host has a cycle counter, and in
+/// nanoseconds otherwise.
+fn now(start: Instant) -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let _ = start;
+        // SAFETY: `rdtsc` is available on every x86-64 CPU.
+        unsafe { core::arch::x86_64::_rdtsc() }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        start.elapsed().as_nanos() as u64
+    }
+}
+
+const UNIT: &str = if cfg!(target_arch = "x86_64") {
+    "cycles"
+} else {
+    "ns"
+};
+
+/// The best of several runs of `f` over `input`, per sample.
+fn measure(name: &str, input: &[u16], mut f: impl FnMut(u16)) {
+    let start = Instant::now();
+    let best = (0..10)
+        .map(|_| {
+            let before = now(start);
+            for &s in input {
+                f(black_box(s));
+            }
+            now(start) - before
+        })
+        .min()
+        .unwrap();
+    println!(
+        "{name:<36} {:>8.2} {UNIT}/sample",
+        best as f64 / input.len() as f64
+    );
+}
+
+/// The window as it was: every average re-sums the whole buffer.
+struct Resum<const N: usize> {
+    buf: [u16; N],
+    len: usize,
+    pos: usize,
+}
+
+impl<const N: usize> Resum<N> {
+    fn push(&mut self, sample: u16) {
+        self.buf[self.pos] = sample;
+        self.pos += 1;
+        if self.pos >= self.len {
+            self.pos = 0;
+        }
+    }
+
+    fn average(&self) -> u16 {
+        let sum: usize = self.buf[..self.len].iter().map(|&s| usize::from(s)).sum();
+        (sum / self.len) as u16
+    }
+}
+
+fn main() {
+    let mut seed = 1u32;
+    let input: Vec<u16> = (0..SAMPLES)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 20) as u16
+        })
+        .collect();
+
+    for len in [10, 100, 1000] {
+        let mut resum = Resum::<1000> {
+            buf: [0; 1000],
+            len,
+            pos: 0,
+        };
+        measure(&format!("re-summed average of {len}"), &input, |s| {
+            resum.push(s);
+            black_box(resum.average());
+        });
+        let mut window: Window<1000> = Window::new(len);
+        measure(&format!("running-sum average of {len}"), &input, |s| {
+            window.push(s);
+            black_box(window.average());
+        });
+    }
+
+    let mut cic: Cic<3> = Cic::new(4);
+    measure("3-stage CIC, by 4", &input, |s| {
+        black_box(cic.push(s));
+    });
+    let mut cic: Cic<4> = Cic::new(16);
+    measure("4-stage CIC, by 16", &input, |s| {
+        black_box(cic.push(s));
+    });
+    let mut decimator: Decimator<3, 9> = Decimator::new(4, 0.425);
+    measure("decimator, 3 stages by 4, 9 taps", &input, |s| {
+        black_box(decimator.push(s));
+    });
+    let mut decimator: Decimator<4, 11> = Decimator::new(16, 0.425);
+    measure("decimator, 4 stages by 16, 11 taps", &input, |s| {
+        black_box(decimator.push(s));
+    });
+}
@@ -0,0 +1,16 @@
+//! Parameters of the RP2040 ADC shared by the firmware and the host tools.
+
+use crate::SampleRate;
+
+/// Frequency of the ADC clock (`clk_adc`), in Hz.
+pub const CLOCK_HZ: u32 = 48_000_000;
+
+/// Number of samples the ADC FIFO holds before it overflows.
+pub const FIFO_DEPTH: usize = 8;
+
+/// Rate the firmware samples the microphone at.
+pub const SAMPLE_RATE: SampleRate = SampleRate::Hz16000;
+
+/// Number of conversions the ADC makes per sample; the pipeline decimates
+/// them down to the sample rate.
+pub const OVERSAMPLE: u32 = 4;
@@ -0,0 +1,216 @@
+//! IMA (DVI) ADPCM, packed in blocks like WAV format tag `0x0011`.
+//!
+//! Each sample is coded as a 4-bit step from a prediction of it, so the
+//! codec squeezes signed 16-bit samples 4:1. Samples come in centered on
+//! zero: ADC samples go through DC removal, or [`to_pcm16`](crate::to_pcm16),
+//! first.
+//!
+//! A block starts with a 4-byte header, the first sample as is and the step
+//! index the coder carries in, followed by two samples per byte, low nibble
+//! first. Every block can be decoded on its own, which is what lets tools
+//! seek in a WAV file of them; a block of `N` bytes holds
+//! [`samples_per_block(N)`](samples_per_block) samples.
+
+/// Block size used for recordings: what most tools write for mono speech.
+pub const BLOCK_ALIGN: usize = 256;
+
+/// Size of the block header.
+const HEADER: usize = 4;
+
+/// Step sizes, indexed by the step index.
+const STEPS: [i16; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+/// Change of the step index after each code, by magnitude.
+const INDEX_STEPS: [i8; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+
+/// Largest step index.
+const MAX_INDEX: u8 = STEPS.len() as u8 - 1;
+
+/// Number of samples in a block of `block_align` bytes.
+pub const fn samples_per_block(block_align: usize) -> usize {
+    (block_align - HEADER) * 2 + 1
+}
+
+/// Errors from decoding a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The block is shorter than its header.
+    ShortBlock,
+    /// The header holds a step index past the end of the table.
+    BadStepIndex,
+}
+
+/// Predictor state shared by the encoder and the decoder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct State {
+    /// Prediction of the next sample: the last decoded one.
+    pub predictor: i16,
+    /// Index of the current step size.
+    pub index: u8,
+}
+
+impl State {
+    /// Decode a 4-bit code, returning the sample.
+    pub fn decode(&mut self, code: u8) -> i16 {
+        let step = i32::from(STEPS[usize::from(self.index)]);
+        let mut diff = step >> 3;
+        if code & 4 != 0 {
+            diff += step;
+        }
+        if code & 2 != 0 {
+            diff += step >> 1;
+        }
+        if code & 1 != 0 {
+            diff += step >> 2;
+        }
+        let predictor = if code & 8 != 0 {
+            i32::from(self.predictor) - diff
+        } else {
+            i32::from(self.predictor) + diff
+        };
+        self.predictor = predictor.clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+        let index = self.index as i8 + INDEX_STEPS[usize::from(code & 7)];
+        self.index = index.clamp(0, MAX_INDEX as i8) as u8;
+        self.predictor
+    }
+
+    /// Encode a sample as a 4-bit code, updating the state as the decoder
+    /// will.
+    pub fn encode(&mut self, sample: i16) -> u8 {
+        let mut diff = i32::from(sample) - i32::from(self.predictor);
+        let mut code = 0;
+        if diff < 0 {
+            code = 8;
+            diff = -diff;
+        }
+        let mut step = i32::from(STEPS[usize::from(self.index)]);
+        for bit in [4, 2, 1] {
+            if diff >= step {
+                code |= bit;
+                diff -= step;
+            }
+            step >>= 1;
+        }
+        self.decode(code);
+        code
+    }
+}
+
+/// Encoder that packs samples into blocks of `N` bytes.
+#[derive(Debug, Clone)]
+pub struct Encoder<const N: usize = BLOCK_ALIGN> {
+    state: State,
+    block: [u8; N],
+    // samples in the current block
+    fill: usize,
+}
+
+impl<const N: usize> Default for Encoder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Encoder<N> {
+    /// Samples in each block.
+    pub const SAMPLES: usize = samples_per_block(N);
+
+    /// An encoder at the start of a stream.
+    pub const fn new() -> Self {
+        assert!(N > HEADER, "block too small");
+        Self {
+            state: State {
+                predictor: 0,
+                index: 0,
+            },
+            block: [0; N],
+            fill: 0,
+        }
+    }
+
+    /// Add a sample, returning the block once it is complete.
+    pub fn push(&mut self, sample: i16) -> Option<&[u8; N]> {
+        if self.fill == 0 {
+            // the header holds the first sample exactly
+            self.state.predictor = sample;
+            self.block[..2].copy_from_slice(&sample.to_le_bytes());
+            self.block[2] = self.state.index;
+            self.block[3] = 0;
+        } else {
+            let code = self.state.encode(sample);
+            let at = HEADER + (self.fill - 1) / 2;
+            if self.fill % 2 == 1 {
+                self.block[at] = code;
+            } else {
+                self.block[at] |= code << 4;
+            }
+        }
+        self.fill += 1;
+        if self.fill == Self::SAMPLES {
+            self.fill = 0;
+            return Some(&self.block);
+        }
+        None
+    }
+
+    /// Encode `samples`, handing each completed block to `f`.
+    pub fn encode(&mut self, samples: &[i16], mut f: impl FnMut(&[u8; N])) {
+        for &sample in samples {
+            if let Some(block) = self.push(sample) {
+                f(block);
+            }
+        }
+    }
+
+    /// End the stream, returning the partial block holding the samples
+    /// pushed since the last full one, if there are any.
+    ///
+    /// A trailing odd nibble is zero; a decoder has to be told the real
+    /// number of samples, as the WAV `fact` chunk does.
+    pub fn flush(&mut self) -> Option<&[u8]> {
+        if self.fill == 0 {
+            return None;
+        }
+        let len = HEADER + self.fill / 2;
+        self.fill = 0;
+        Some(&self.block[..len])
+    }
+}
+
+/// The state a block starts from: its first sample and step index.
+pub fn parse_header(header: &[u8; HEADER]) -> Result<State, Error> {
+    if header[2] > MAX_INDEX {
+        return Err(Error::BadStepIndex);
+    }
+    Ok(State {
+        predictor: i16::from_le_bytes([header[0], header[1]]),
+        index: header[2],
+    })
+}
+
+/// Decode a block into `out`, returning the number of samples written.
+///
+/// A short final block decodes to as many samples as it has codes for;
+/// samples that do not fit into `out` are skipped.
+pub fn decode_block(block: &[u8], out: &mut [i16]) -> Result<usize, Error> {
+    let header = block.get(..HEADER).ok_or(Error::ShortBlock)?;
+    let mut state = parse_header(header.try_into().unwrap())?;
+    let codes = block[HEADER..]
+        .iter()
+        .flat_map(|&byte| [byte & 0x0f, byte >> 4]);
+    let mut n = 0;
+    for (slot, sample) in out
+        .iter_mut()
+        .zip(core::iter::once(state.predictor).chain(codes.map(|c| state.decode(c))))
+    {
+        *slot = sample;
+        n += 1;
+    }
+    Ok(n)
+}
@@ -0,0 +1,191 @@
+//! Automatic gain control: brings the microphone to a consistent loudness.
+//!
+//! How loud the microphone comes out depends mostly on how far away the
+//! speaker is, which on a handheld device varies by 20 dB or more. The
+//! [`Agc`] follows the peak level of the signal and scales it so those
+//! peaks land on a target level:
+//!
+//! - when the level rises, the level follower catches up over the attack
+//!   time, so the gain comes down quickly;
+//! - when the level falls, the follower holds the last peak for the hold
+//!   time, so the gain doesn't pump up in the gaps between words, and then
+//!   decays over the release time;
+//! - the gain never goes above a maximum, so very quiet input stays quiet
+//!   rather than being brought up to a roar of noise;
+//! - samples below the noise floor don't move the follower, so the gain
+//!   stays where the last speech left it through pauses rather than slowly
+//!   raising the background hiss to the target.
+//!
+//! Everything runs in fixed point; the configuration in decibels and
+//! milliseconds is converted once, when the stage is made.
+
+use crate::math;
+use crate::process::Processor;
+use crate::SampleRate;
+
+/// Fraction bits of the level follower.
+const LEVEL_FRAC: u32 = 8;
+
+/// Fraction bits of the gain.
+const GAIN_FRAC: u32 = 12;
+
+/// Fraction bits of the attack and release coefficients.
+const COEF_FRAC: u32 = 16;
+
+/// Full scale of the signed 16-bit samples.
+const FULL_SCALE: f64 = 32767.0;
+
+/// Settings for an [`Agc`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Peak level the output is brought to, in dB relative to full scale.
+    pub target_dbfs: i32,
+    /// Most the signal is amplified, in dB.
+    pub max_gain_db: i32,
+    /// Level below which the input counts as background noise, which leaves
+    /// the gain alone, in dB relative to full scale.
+    pub noise_floor_dbfs: i32,
+    /// Time constant of the level follower when the level rises, in ms.
+    pub attack_ms: u32,
+    /// How long the level follower holds a peak, in ms.
+    pub hold_ms: u32,
+    /// Time constant of the level follower when the level falls, in ms.
+    pub release_ms: u32,
+}
+
+impl Default for Config {
+    /// Settings for speech into the device's microphone.
+    fn default() -> Self {
+        Self {
+            target_dbfs: -6,
+            max_gain_db: 30,
+            noise_floor_dbfs: -54,
+            attack_ms: 5,
+            hold_ms: 250,
+            release_ms: 400,
+        }
+    }
+}
+
+/// Automatic gain control on signed 16-bit samples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Agc {
+    /// Follower coefficients, in Q16.
+    attack: u32,
+    release: u32,
+    /// Hold time in samples.
+    hold: u32,
+    /// Target peak level.
+    target: u32,
+    /// Largest gain, in Q12.
+    max_gain: u32,
+    /// Noise floor, in Q8 like the level.
+    floor: u32,
+    /// Followed peak level, in Q8.
+    level: u32,
+    /// Samples left before the level starts to release.
+    held: u32,
+    /// Gain applied, in Q12.
+    gain: u32,
+}
+
+impl Agc {
+    /// Create an AGC for signals at `rate`, starting at unity gain.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the target is above full scale, the maximum gain is negative
+    /// or over 40 dB, or the noise floor is above the target.
+    pub const fn new(config: &Config, rate: SampleRate) -> Self {
+        assert!(config.target_dbfs <= 0, "target must be at most full scale");
+        assert!(
+            config.max_gain_db >= 0 && config.max_gain_db <= 40,
+            "maximum gain must be between 0 and 40 dB"
+        );
+        assert!(
+            config.noise_floor_dbfs < config.target_dbfs,
+            "noise floor must be below the target"
+        );
+        let fs = rate.hz() as f64;
+        Self {
+            attack: coefficient(config.attack_ms, fs),
+            release: coefficient(config.release_ms, fs),
+            hold: (config.hold_ms as u64 * rate.hz() as u64 / 1000) as u32,
+            target: math::round(FULL_SCALE * math::db_to_gain(config.target_dbfs as f64)) as u32,
+            max_gain: math::round(
+                (1 << GAIN_FRAC) as f64 * math::db_to_gain(config.max_gain_db as f64),
+            ) as u32,
+            floor: math::round(
+                (FULL_SCALE * math::db_to_gain(config.noise_floor_dbfs as f64))
+                    * (1 << LEVEL_FRAC) as f64,
+            ) as u32,
+            level: 0,
+            held: 0,
+            gain: 1 << GAIN_FRAC,
+        }
+    }
+
+    /// The gain being applied, in 1/4096ths.
+    pub fn gain(&self) -> u32 {
+        self.gain
+    }
+
+    /// The followed peak level of the input, on the scale of the samples.
+    pub fn level(&self) -> u16 {
+        (self.level >> LEVEL_FRAC) as u16
+    }
+}
+
+/// One-pole coefficient for a time constant of `ms` at `fs`, in Q16: the
+/// fraction of the way to the input the follower moves each sample.
+const fn coefficient(ms: u32, fs: f64) -> u32 {
+    let one = (1 << COEF_FRAC) as f64;
+    if ms == 0 {
+        return one as u32;
+    }
+    let samples = ms as f64 * fs / 1000.0;
+    let c = math::round(one * (1.0 - math::exp(-1.0 / samples))) as u32;
+    // even the slowest follower must move
+    if c == 0 {
+        1
+    } else {
+        c
+    }
+}
+
+/// How far the follower moves towards a level `distance` away. Rounding up
+/// means it always gets there, however slow it is.
+fn step(distance: u32, coefficient: u32) -> u32 {
+    let step = u64::from(distance) * u64::from(coefficient);
+    step.div_ceil(1 << COEF_FRAC) as u32
+}
+
+impl Processor for Agc {
+    fn process(&mut self, sample: i16) -> i16 {
+        let peak = u32::from(sample.unsigned_abs()) << LEVEL_FRAC;
+        if peak >= self.level {
+            self.level += step(peak - self.level, self.attack);
+            self.held = self.hold;
+        } else if peak < self.floor {
+            // background noise: leave the level, and so the gain, alone
+        } else if self.held > 0 {
+            self.held -= 1;
+        } else {
+            self.level -= step(self.level - peak, self.release);
+        }
+
+        if self.level >= self.floor {
+            let level = (self.level >> LEVEL_FRAC).max(1);
+            self.gain = ((self.target << GAIN_FRAC) / level).min(self.max_gain);
+        }
+
+        let y = (i64::from(sample) * i64::from(self.gain)) >> GAIN_FRAC;
+        y.clamp(i16::MIN.into(), i16::MAX.into()) as i16
+    }
+
+    fn reset(&mut self) {
+        self.level = 0;
+        self.held = 0;
+        self.gain = 1 << GAIN_FRAC;
+    }
+}
@@ -0,0 +1,402 @@
+//! Second-order IIR filters ("biquads") from the RBJ Audio EQ Cookbook.
+//!
+//! A [`Design`] works out the normalized coefficients of one section in
+//! `f64`, from a sample rate, a frequency and a Q (and a gain in dB for the
+//! peaking and shelving shapes). Every design is a `const fn`, so a filter
+//! whose parameters are known is designed at compile time; one configured at
+//! run time from the active [`SampleRate`] pays for the floating point once.
+//!
+//! The design is then quantized into a section that runs in fixed point:
+//!
+//! - [`Q15`] keeps 16-bit coefficients and state. It is cheap, and accurate
+//!   for filters whose features sit above roughly a hundredth of the sample
+//!   rate. Below that the poles crowd towards `z = 1`, where 16-bit
+//!   coefficients can't place them precisely.
+//! - [`Q31`] keeps 32-bit coefficients and extended-precision feedback
+//!   state, at the cost of 64-bit multiplies. It is the one to use for low
+//!   corners and narrow notches.
+//!
+//! Both are direct form I, which cannot overflow internally as long as the
+//! output doesn't, and both saturate the output rather than wrapping.
+//! Coefficients are stored with just enough integer bits for the largest
+//! one, as in CMSIS-DSP's `postShift`, so boosts and shelves of up to +18 dB
+//! fit. Sections implement [`Processor`], so they slot into a
+//! [`Chain`](crate::process::Chain), and a [`Cascade`] runs several in turn
+//! for higher orders.
+
+use crate::math;
+use crate::process::Processor;
+use crate::SampleRate;
+
+/// Most integer bits a quantized coefficient can have, so coefficients must
+/// lie within ±16.
+const MAX_INT_BITS: u32 = 4;
+
+/// Fraction bits of the [`Q31`] feedback state.
+const STATE_FRAC: u32 = 14;
+
+/// Normalized coefficients of a biquad section,
+///
+/// ```text
+///         b0 + b1·z⁻¹ + b2·z⁻²
+/// H(z) = ----------------------
+///          1 + a1·z⁻¹ + a2·z⁻²
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Design {
+    /// Feed-forward coefficients.
+    pub b: [f64; 3],
+    /// Feedback coefficients `a1` and `a2`; `a0` is 1.
+    pub a: [f64; 2],
+}
+
+impl Design {
+    /// Normalize the cookbook's unnormalized coefficients by `a0`.
+    const fn normalized(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self {
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [a[1] / a[0], a[2] / a[0]],
+        }
+    }
+
+    /// `cos(w0)` and `alpha` for a design, checking its parameters.
+    const fn prewarp(rate: SampleRate, hz: f64, q: f64) -> (f64, f64) {
+        let fs = rate.hz() as f64;
+        assert!(
+            hz > 0.0 && hz < fs / 2.0,
+            "frequency must be between 0 and half the sample rate"
+        );
+        assert!(q > 0.0, "Q must be positive");
+        let (sin, cos) = math::sin_cos(math::omega(hz, fs));
+        (cos, sin / (2.0 * q))
+    }
+
+    /// Low-pass with its corner at `hz`. A `q` of `1/√2` gives a
+    /// Butterworth response.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `hz` is between 0 and half the sample rate and `q` is
+    /// positive, as do all the designs.
+    pub const fn low_pass(rate: SampleRate, hz: f64, q: f64) -> Self {
+        let (cos, alpha) = Self::prewarp(rate, hz, q);
+        let b1 = 1.0 - cos;
+        Self::normalized(
+            [b1 / 2.0, b1, b1 / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// High-pass with its corner at `hz`.
+    pub const fn high_pass(rate: SampleRate, hz: f64, q: f64) -> Self {
+        let (cos, alpha) = Self::prewarp(rate, hz, q);
+        let b1 = 1.0 + cos;
+        Self::normalized(
+            [b1 / 2.0, -b1, b1 / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// Band-pass centered on `hz`, with unity gain at the center and a
+    /// bandwidth of `hz / q`.
+    pub const fn band_pass(rate: SampleRate, hz: f64, q: f64) -> Self {
+        let (cos, alpha) = Self::prewarp(rate, hz, q);
+        Self::normalized([alpha, 0.0, -alpha], [1.0 + alpha, -2.0 * cos, 1.0 - alpha])
+    }
+
+    /// Notch removing `hz`, with a bandwidth of `hz / q`.
+    pub const fn notch(rate: SampleRate, hz: f64, q: f64) -> Self {
+        let (cos, alpha) = Self::prewarp(rate, hz, q);
+        Self::normalized(
+            [1.0, -2.0 * cos, 1.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// Peaking EQ, boosting or cutting by `gain_db` around `hz`.
+    pub const fn peaking(rate: SampleRate, hz: f64, q: f64, gain_db: f64) -> Self {
+        let (cos, alpha) = Self::prewarp(rate, hz, q);
+        let a = math::db_to_gain(gain_db / 2.0);
+        Self::normalized(
+            [1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a],
+            [1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a],
+        )
+    }
+
+    /// Low shelf, boosting or cutting by `gain_db` below `hz`.
+    pub const fn low_shelf(rate: SampleRate, hz: f64, q: f64, gain_db: f64) -> Self {
+        let (cos, alpha) = Self::prewarp(rate, hz, q);
+        let a = math::db_to_gain(gain_db / 2.0);
+        let root = 2.0 * math::sqrt(a) * alpha;
+        Self::normalized(
+            [
+                a * ((a + 1.0) - (a - 1.0) * cos + root),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                a * ((a + 1.0) - (a - 1.0) * cos - root),
+            ],
+            [
+                (a + 1.0) + (a - 1.0) * cos + root,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                (a + 1.0) + (a - 1.0) * cos - root,
+            ],
+        )
+    }
+
+    /// High shelf, boosting or cutting by `gain_db` above `hz`.
+    pub const fn high_shelf(rate: SampleRate, hz: f64, q: f64, gain_db: f64) -> Self {
+        let (cos, alpha) = Self::prewarp(rate, hz, q);
+        let a = math::db_to_gain(gain_db / 2.0);
+        let root = 2.0 * math::sqrt(a) * alpha;
+        Self::normalized(
+            [
+                a * ((a + 1.0) + (a - 1.0) * cos + root),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - root),
+            ],
+            [
+                (a + 1.0) - (a - 1.0) * cos + root,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - root,
+            ],
+        )
+    }
+
+    /// Q of section `k` of a Butterworth filter built from `sections`
+    /// sections, so that cascading low-pass (or high-pass) sections at the
+    /// same corner with each of these gives a maximally flat filter of order
+    /// `2·sections`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is not less than `sections`.
+    pub const fn butterworth_q(sections: usize, k: usize) -> f64 {
+        assert!(k < sections, "section out of range");
+        let order = 2 * sections;
+        let angle = core::f64::consts::PI * (2 * k + 1) as f64 / (2 * order) as f64;
+        1.0 / (2.0 * math::cos(angle))
+    }
+
+    /// Integer bits needed for the largest coefficient.
+    const fn int_bits(&self) -> u32 {
+        let c = [self.b[0], self.b[1], self.b[2], self.a[0], self.a[1]];
+        let mut max = 0.0;
+        let mut i = 0;
+        while i < c.len() {
+            let m = math::abs(c[i]);
+            if m > max {
+                max = m;
+            }
+            i += 1;
+        }
+        let mut bits = 0;
+        // leave room for the coefficient to round up
+        while max >= (1 << bits) as f64 * (1.0 - 1.0 / 32768.0) {
+            bits += 1;
+        }
+        assert!(
+            bits <= MAX_INT_BITS,
+            "biquad coefficients must lie within ±16"
+        );
+        bits
+    }
+
+    /// Quantize to a [`Q15`] section.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a coefficient is outside ±16.
+    pub const fn q15(&self) -> Q15 {
+        let frac = 15 - self.int_bits();
+        let scale = (1u32 << frac) as f64;
+        Q15 {
+            b: [
+                quantize(self.b[0], scale) as i16,
+                quantize(self.b[1], scale) as i16,
+                quantize(self.b[2], scale) as i16,
+            ],
+            a: [
+                quantize(-self.a[0], scale) as i16,
+                quantize(-self.a[1], scale) as i16,
+            ],
+            frac,
+            x: [0; 2],
+            y: [0; 2],
+            error: 0,
+        }
+    }
+
+    /// Quantize to a [`Q31`] section.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a coefficient is outside ±16.
+    pub const fn q31(&self) -> Q31 {
+        let frac = 31 - self.int_bits();
+        let scale = (1u64 << frac) as f64;
+        Q31 {
+            b: [
+                quantize(self.b[0], scale) as i32,
+                quantize(self.b[1], scale) as i32,
+                quantize(self.b[2], scale) as i32,
+            ],
+            a: [
+                quantize(-self.a[0], scale) as i32,
+                quantize(-self.a[1], scale) as i32,
+            ],
+            frac,
+            x: [0; 2],
+            y: [0; 2],
+        }
+    }
+}
+
+/// `c` scaled and rounded to the nearest integer.
+const fn quantize(c: f64, scale: f64) -> i64 {
+    math::round(c * scale)
+}
+
+/// A biquad section with 16-bit coefficients and state.
+///
+/// The fraction the output drops is fed back into the next sample, which
+/// keeps quantization from adding an offset or sustaining limit cycles.
+/// Made with [`Design::q15`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Q15 {
+    b: [i16; 3],
+    /// Negated `a1` and `a2`.
+    a: [i16; 2],
+    /// Fraction bits of the coefficients.
+    frac: u32,
+    x: [i16; 2],
+    y: [i16; 2],
+    error: i64,
+}
+
+impl Q15 {
+    /// The quantized `b0`, `b1`, `b2`, `-a1` and `-a2`, and how many
+    /// fraction bits they have.
+    pub fn coefficients(&self) -> ([i16; 5], u32) {
+        let [b0, b1, b2] = self.b;
+        let [a1, a2] = self.a;
+        ([b0, b1, b2, a1, a2], self.frac)
+    }
+}
+
+impl Processor for Q15 {
+    fn process(&mut self, sample: i16) -> i16 {
+        let products = [
+            i32::from(self.b[0]) * i32::from(sample),
+            i32::from(self.b[1]) * i32::from(self.x[0]),
+            i32::from(self.b[2]) * i32::from(self.x[1]),
+            i32::from(self.a[0]) * i32::from(self.y[0]),
+            i32::from(self.a[1]) * i32::from(self.y[1]),
+        ];
+        let mut acc = self.error;
+        for p in products {
+            acc += i64::from(p);
+        }
+        let y = acc >> self.frac;
+        self.error = acc - (y << self.frac);
+        let y = math::clamp_i16(y);
+        self.x = [sample, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+
+    fn reset(&mut self) {
+        self.x = [0; 2];
+        self.y = [0; 2];
+        self.error = 0;
+    }
+}
+
+/// A biquad section with 32-bit coefficients, and feedback state carrying
+/// 14 bits below the output's least significant bit.
+///
+/// Made with [`Design::q31`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Q31 {
+    b: [i32; 3],
+    /// Negated `a1` and `a2`.
+    a: [i32; 2],
+    /// Fraction bits of the coefficients.
+    frac: u32,
+    x: [i16; 2],
+    /// Past outputs, with `STATE_FRAC` fraction bits.
+    y: [i32; 2],
+}
+
+impl Q31 {
+    /// The quantized `b0`, `b1`, `b2`, `-a1` and `-a2`, and how many
+    /// fraction bits they have.
+    pub fn coefficients(&self) -> ([i32; 5], u32) {
+        let [b0, b1, b2] = self.b;
+        let [a1, a2] = self.a;
+        ([b0, b1, b2, a1, a2], self.frac)
+    }
+}
+
+impl Processor for Q31 {
+    fn process(&mut self, sample: i16) -> i16 {
+        let x = |s: i16| i64::from(s) << STATE_FRAC;
+        let acc = i64::from(self.b[0]) * x(sample)
+            + i64::from(self.b[1]) * x(self.x[0])
+            + i64::from(self.b[2]) * x(self.x[1])
+            + i64::from(self.a[0]) * i64::from(self.y[0])
+            + i64::from(self.a[1]) * i64::from(self.y[1]);
+        let limit = i64::from(i16::MAX) << STATE_FRAC;
+        let y =
+            ((acc + (1 << (self.frac - 1))) >> self.frac).clamp(-limit - (1 << STATE_FRAC), limit);
+        self.x = [sample, self.x[0]];
+        self.y = [y as i32, self.y[0]];
+        math::clamp_i16((y + (1 << (STATE_FRAC - 1))) >> STATE_FRAC)
+    }
+
+    fn reset(&mut self) {
+        self.x = [0; 2];
+        self.y = [0; 2];
+    }
+}
+
+/// `N` sections run one after the other, for filters of order `2·N`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cascade<S, const N: usize> {
+    sections: [S; N],
+}
+
+impl<S, const N: usize> Cascade<S, N> {
+    /// Cascade `sections`, first to last.
+    pub const fn new(sections: [S; N]) -> Self {
+        Self { sections }
+    }
+
+    /// The sections, first to last.
+    pub fn sections(&self) -> &[S; N] {
+        &self.sections
+    }
+
+    /// Mutable access to the sections, e.g. to redesign one.
+    pub fn sections_mut(&mut self) -> &mut [S; N] {
+        &mut self.sections
+    }
+}
+
+impl<S: Processor, const N: usize> Processor for Cascade<S, N> {
+    fn process(&mut self, sample: i16) -> i16 {
+        self.sections
+            .iter_mut()
+            .fold(sample, |sample, section| section.process(sample))
+    }
+
+    fn process_block(&mut self, samples: &mut [i16]) {
+        for section in &mut self.sections {
+            section.process_block(samples);
+        }
+    }
+
+    fn reset(&mut self) {
+        for section in &mut self.sections {
+            section.reset();
+        }
+    }
+}
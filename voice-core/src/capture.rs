@@ -0,0 +1,171 @@
+//! Gap-free ADC capture through DMA into a pair of ping-pong blocks.
+//!
+//! The DMA engine copies samples out of the ADC FIFO into one block while the
+//! other one, filled earlier, is read by the signal chain. When a block is
+//! complete the DMA completion interrupt calls [`DmaCapture::on_complete`],
+//! which immediately starts DMA on the spare block and hands the full one to
+//! the reader. As long as the reader finishes a block before the next one is
+//! complete, no sample is lost, and the few microseconds of interrupt latency
+//! between two blocks are absorbed by the ADC FIFO.
+//!
+//! This module only deals with the hand-off between the interrupt and the
+//! reader; moving the samples is left to a [`CaptureDma`] implementation, so
+//! the logic can be exercised on the host with a mock channel.
+
+use crate::AudioSource;
+
+/// Number of samples the firmware captures between two completion interrupts.
+pub const BLOCK_LEN: usize = 256;
+
+/// A block of samples DMA can write into.
+pub type Block<const N: usize> = &'static mut [u16; N];
+
+/// A DMA channel transferring ADC samples into blocks.
+pub trait CaptureDma<const N: usize> {
+    /// Start filling `block`.
+    ///
+    /// Only called when no transfer is in flight.
+    fn start(&mut self, block: Block<N>);
+
+    /// If the transfer in flight has completed, return its block.
+    ///
+    /// Also acknowledges the completion interrupt.
+    fn take_done(&mut self) -> Option<Block<N>>;
+}
+
+/// Returned when reading a sample before any block is complete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Underrun;
+
+/// The two ping-pong blocks and where each of them is.
+///
+/// At any time one block is being filled by DMA (unless capture stalled) and
+/// the other is either queued behind it, or full and waiting to be read.
+pub struct DmaCapture<D, const N: usize> {
+    dma: D,
+    busy: bool,
+    queued: Option<Block<N>>,
+    // full blocks, oldest first
+    ready: [Option<Block<N>>; 2],
+    // samples of ready[0] already read
+    read_pos: usize,
+    stalls: u32,
+}
+
+impl<D: CaptureDma<N>, const N: usize> DmaCapture<D, N> {
+    /// Start capturing into `first`, with `second` queued behind it.
+    pub fn new(mut dma: D, first: Block<N>, second: Block<N>) -> Self {
+        dma.start(first);
+        Self {
+            dma,
+            busy: true,
+            queued: Some(second),
+            ready: [None, None],
+            read_pos: 0,
+            stalls: 0,
+        }
+    }
+
+    /// Number of times DMA ran out of blocks because the reader fell behind.
+    ///
+    /// Capture restarts as soon as a block is freed, but samples are lost if
+    /// the ADC FIFO overflowed in the meantime.
+    pub fn stalls(&self) -> u32 {
+        self.stalls
+    }
+
+    /// Whether DMA is currently filling a block.
+    pub fn is_capturing(&self) -> bool {
+        self.busy
+    }
+
+    /// Handle the DMA completion interrupt.
+    ///
+    /// Moves DMA on to the queued block, if there is one, and makes the
+    /// completed block available for reading.
+    pub fn on_complete(&mut self) {
+        let Some(done) = self.dma.take_done() else {
+            return;
+        };
+        match self.queued.take() {
+            Some(next) => self.dma.start(next),
+            None => {
+                self.busy = false;
+                self.stalls += 1;
+            }
+        }
+        if self.ready[0].is_none() {
+            self.ready[0] = Some(done);
+        } else {
+            self.ready[1] = Some(done);
+        }
+    }
+
+    /// Hand the oldest ready block back to DMA.
+    fn release(&mut self) {
+        let Some(block) = self.ready[0].take() else {
+            return;
+        };
+        self.ready.swap(0, 1);
+        self.read_pos = 0;
+        if self.busy {
+            self.queued = Some(block);
+        } else {
+            self.dma.start(block);
+            self.busy = true;
+        }
+    }
+
+    /// Number of captured samples not read yet.
+    pub fn available(&self) -> usize {
+        match &self.ready {
+            [None, _] => 0,
+            [Some(_), None] => N - self.read_pos,
+            [Some(_), Some(_)] => 2 * N - self.read_pos,
+        }
+    }
+
+    /// Copy as many captured samples as fit into `buf`, returning how many
+    /// were copied.
+    pub fn read_block(&mut self, buf: &mut [u16]) -> usize {
+        let mut copied = 0;
+        while copied < buf.len() {
+            let Some(block) = &self.ready[0] else {
+                break;
+            };
+            let n = (N - self.read_pos).min(buf.len() - copied);
+            buf[copied..copied + n].copy_from_slice(&block[self.read_pos..self.read_pos + n]);
+            copied += n;
+            self.read_pos += n;
+            if self.read_pos == N {
+                self.release();
+            }
+        }
+        copied
+    }
+
+    /// Access the DMA channel.
+    pub fn dma(&mut self) -> &mut D {
+        &mut self.dma
+    }
+}
+
+impl<D: CaptureDma<N>, const N: usize> AudioSource for DmaCapture<D, N> {
+    type Error = Underrun;
+
+    fn available(&mut self) -> usize {
+        DmaCapture::available(self)
+    }
+
+    fn read(&mut self) -> Result<u16, Underrun> {
+        let mut sample = [0];
+        match DmaCapture::read_block(self, &mut sample) {
+            1 => Ok(sample[0]),
+            _ => Err(Underrun),
+        }
+    }
+
+    fn read_block(&mut self, buf: &mut [u16]) -> Result<usize, Underrun> {
+        Ok(DmaCapture::read_block(self, buf))
+    }
+}
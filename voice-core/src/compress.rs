@@ -0,0 +1,226 @@
+//! Dynamic range compression: turns loud passages down by a ratio.
+//!
+//! Where the [`Agc`](crate::agc::Agc) brings everything to one level, a
+//! [`Compressor`] leaves the signal alone below its threshold and only
+//! squeezes what goes over it: every `ratio` dB the input rises above the
+//! threshold come out as one. The makeup gain then brings the whole signal
+//! back up, so it ends up louder on average without the peaks getting any
+//! closer to clipping. Follow it with a [`Limiter`](crate::limit::Limiter)
+//! to catch what still gets through.
+//!
+//! It is a feed-forward design working in decibels, after Giannoulis, Massberg
+//! and Reiss:
+//!
+//! - the level of each sample is taken in dB, and the static curve gives
+//!   the gain reduction it calls for, bending from no reduction to the full
+//!   ratio over the knee rather than all at once at the threshold;
+//! - the reduction is followed by a peak detector whose release is the
+//!   release time, so it holds up between the crests of a waveform;
+//! - and that is smoothed over the attack time, so the gain doesn't jump.
+//!
+//! Logarithms and exponentials come from [`math`]'s interpolated tables,
+//! which are good to a thousandth of a dB; everything else is integer
+//! arithmetic.
+
+use crate::math;
+use crate::process::Processor;
+use crate::SampleRate;
+
+/// Fraction bits of levels and gains in dB.
+const DB_FRAC: u32 = 8;
+
+/// Extra fraction bits the gain reduction is followed with, so slow
+/// release times still move it smoothly.
+const FOLLOW_BITS: u32 = 8;
+
+/// Fraction bits of base-two logarithms, as [`math::log2_q16`] gives them.
+const LOG_FRAC: u32 = 16;
+
+/// Fraction bits of the linear gain.
+const GAIN_FRAC: u32 = 16;
+
+/// Fraction bits of the attack and release coefficients.
+pub(crate) const COEF_FRAC: u32 = 16;
+
+/// Fraction bits of the slope of the curve above the knee.
+const SLOPE_FRAC: u32 = 16;
+
+/// dB per doubling, `20·log10(2)`, in Q16.
+const DB_PER_OCTAVE: i64 = (6.020_599_913_279_624 * 65536.0 + 0.5) as i64;
+
+/// Doublings per dB, in Q16.
+const OCTAVES_PER_DB: i64 = (65536.0 / 6.020_599_913_279_624 + 0.5) as i64;
+
+/// `log2` of full scale, in Q16.
+const LOG2_FULL_SCALE: i32 = (math::ln(32767.0) / core::f64::consts::LN_2 * 65536.0 + 0.5) as i32;
+
+/// Most makeup gain, so the linear gain fits comfortably in 32 bits.
+const MAX_MAKEUP_DB: i32 = 24;
+
+/// Level of a sample of magnitude `x` in dB relative to full scale, in Q8.
+fn level(x: u16) -> i32 {
+    let octaves = math::log2_q16(u32::from(x.max(1))) - LOG2_FULL_SCALE;
+    ((i64::from(octaves) * DB_PER_OCTAVE) >> (LOG_FRAC + 16 - DB_FRAC)) as i32
+}
+
+/// The linear gain of `db`, in Q8 dB, in Q16.
+fn gain(db: i32) -> u32 {
+    math::exp2_q16(((i64::from(db) * OCTAVES_PER_DB) >> DB_FRAC) as i32)
+}
+
+/// Settings for a [`Compressor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Level above which the signal is compressed, in dB relative to full
+    /// scale.
+    pub threshold_dbfs: i32,
+    /// How many dB of input above the threshold make one dB of output.
+    pub ratio: u32,
+    /// Width of the knee, centred on the threshold, in dB. Zero is a hard
+    /// knee.
+    pub knee_db: u32,
+    /// Time constant of the gain coming down, in ms.
+    pub attack_ms: u32,
+    /// Time constant of the gain going back up, in ms.
+    pub release_ms: u32,
+    /// Gain applied after compression, in dB.
+    pub makeup_db: i32,
+}
+
+impl Default for Config {
+    /// Settings for speech on its way out of the speaker: gentle 3:1
+    /// compression of the loudest 18 dB, and that made up.
+    fn default() -> Self {
+        Self {
+            threshold_dbfs: -18,
+            ratio: 3,
+            knee_db: 6,
+            attack_ms: 5,
+            release_ms: 150,
+            makeup_db: 6,
+        }
+    }
+}
+
+/// Feed-forward compressor on signed 16-bit samples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compressor {
+    /// Threshold and knee width, in Q8 dB.
+    threshold: i32,
+    knee: i32,
+    /// `1 - 1/ratio`, in Q16.
+    slope: i64,
+    /// Makeup gain, in Q8 dB.
+    makeup: i32,
+    /// Follower coefficients, in Q16.
+    attack: u32,
+    release: u32,
+    /// Peak-held gain reduction, in Q16 dB.
+    peak: i32,
+    /// Smoothed gain reduction, in Q16 dB.
+    reduction: i32,
+}
+
+impl Compressor {
+    /// Create a compressor for signals at `rate`, not yet reducing the
+    /// gain.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the threshold is above full scale, the ratio is zero, or
+    /// the makeup gain is negative or over 24 dB.
+    pub const fn new(config: &Config, rate: SampleRate) -> Self {
+        assert!(
+            config.threshold_dbfs <= 0,
+            "threshold must be at most full scale"
+        );
+        assert!(config.ratio >= 1, "ratio must be at least 1");
+        assert!(
+            config.makeup_db >= 0 && config.makeup_db <= MAX_MAKEUP_DB,
+            "makeup gain must be between 0 and 24 dB"
+        );
+        let fs = rate.hz() as f64;
+        let one = 1 << SLOPE_FRAC;
+        Self {
+            threshold: config.threshold_dbfs << DB_FRAC,
+            knee: (config.knee_db << DB_FRAC) as i32,
+            slope: one - (one + config.ratio as i64 / 2) / config.ratio as i64,
+            makeup: config.makeup_db << DB_FRAC,
+            attack: coefficient(config.attack_ms, fs),
+            release: coefficient(config.release_ms, fs),
+            peak: 0,
+            reduction: 0,
+        }
+    }
+
+    /// The gain reduction being applied, in 1/256ths of a dB.
+    pub fn reduction(&self) -> u32 {
+        (self.reduction >> FOLLOW_BITS) as u32
+    }
+
+    /// The gain reduction the static curve calls for at `level`, in Q8 dB.
+    fn curve(&self, level: i32) -> i32 {
+        let over = i64::from(level - self.threshold);
+        let knee = i64::from(self.knee);
+        let reduction = if 2 * over <= -knee {
+            0
+        } else if 2 * over < knee {
+            // a quadratic from the start of the knee, meeting the straight
+            // line at its end
+            let into = over + knee / 2;
+            self.slope * into * into / (2 * knee)
+        } else {
+            self.slope * over
+        };
+        (reduction >> SLOPE_FRAC) as i32
+    }
+}
+
+/// One-pole coefficient for a time constant of `ms` at `fs`, in Q16: the
+/// fraction of the way to the input the follower moves each sample.
+pub(crate) const fn coefficient(ms: u32, fs: f64) -> u32 {
+    let one = (1 << COEF_FRAC) as f64;
+    if ms == 0 {
+        return one as u32;
+    }
+    let samples = ms as f64 * fs / 1000.0;
+    let c = math::round(one * (1.0 - math::exp(-1.0 / samples))) as u32;
+    // even the slowest follower must move
+    if c == 0 {
+        1
+    } else {
+        c
+    }
+}
+
+/// Move `from` towards `to` by `coefficient`, always by at least one step
+/// so it gets there.
+fn approach(from: i32, to: i32, coefficient: u32) -> i32 {
+    let step = (u64::from(to.abs_diff(from)) * u64::from(coefficient)).div_ceil(1 << COEF_FRAC);
+    if to > from {
+        from + step as i32
+    } else {
+        from - step as i32
+    }
+}
+
+impl Processor for Compressor {
+    fn process(&mut self, sample: i16) -> i16 {
+        let target = self.curve(level(sample.unsigned_abs().min(32767))) << FOLLOW_BITS;
+        self.peak = if target >= self.peak {
+            target
+        } else {
+            approach(self.peak, target, self.release)
+        };
+        self.reduction = approach(self.reduction, self.peak, self.attack);
+
+        let gain = gain(self.makeup - (self.reduction >> FOLLOW_BITS));
+        let y = (i64::from(sample) * i64::from(gain)) >> GAIN_FRAC;
+        y.clamp(i16::MIN.into(), i16::MAX.into()) as i16
+    }
+
+    fn reset(&mut self) {
+        self.peak = 0;
+        self.reduction = 0;
+    }
+}
@@ -0,0 +1,134 @@
+//! DC blocker: the high-pass that takes the ADC stream to signed samples.
+//!
+//! The microphone sits on a mid-rail bias, so the ADC's 12-bit samples hover
+//! around 2048 plus whatever offset the bias network and the ADC add. The
+//! blocker is the one-pole, one-zero high-pass
+//!
+//! ```text
+//! y[n] = x[n] - x[n-1] + R·y[n-1]
+//! ```
+//!
+//! with a zero at DC and the pole `R = 1 - 2π·fc/fs` just inside it, so
+//! everything below the corner `fc` (a few tens of Hz for voice) goes and
+//! everything above passes unchanged.
+//!
+//! The filter runs in fixed point with the pole in Q16. The accumulator keeps
+//! the fraction the output drops and feeds it back in on the next sample, so
+//! rounding never builds up into an offset of its own: a constant input
+//! settles to exactly zero. Outputs are signed 16-bit samples on the scale of
+//! [`to_pcm16`], with the four bits below the ADC's resolution filled in by
+//! the filter. Input already on that scale, such as a
+//! [`Decimator`](crate::decimate::Decimator)'s, goes in through
+//! [`push_pcm16`](DcBlocker::push_pcm16) with those bits of its own.
+
+use crate::{to_pcm16, SampleRate};
+
+/// Corner frequency suitable for voice, in Hz.
+pub const VOICE_CORNER_HZ: u32 = 20;
+
+/// Fraction bits of the pole and the accumulator.
+const FRAC: u32 = 16;
+
+/// Shift from the accumulator to the 16-bit output scale, which has four
+/// fraction bits below the ADC's 12.
+const OUTPUT_SHIFT: u32 = FRAC - 4;
+
+/// Smallest ratio of sample rate to corner accepted. A higher corner could
+/// overflow the feedback product, and would make the filter a tone control
+/// rather than a DC blocker anyway.
+const MIN_RATIO: u32 = 64;
+
+/// `2π` in Q16.
+const TWO_PI: u64 = 411_775;
+
+/// One-pole high-pass taking 12-bit offset-binary ADC samples to signed
+/// 16-bit samples centered on zero.
+///
+/// This is the first stage of every [`Chain`](crate::process::Chain).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DcBlocker {
+    /// `1 - R` in Q16.
+    feedback: i32,
+    /// Previous input, on the 16-bit scale.
+    last: i32,
+    /// Output in Q16 ADC steps, plus the fraction not yet output.
+    acc: i32,
+    /// Whether a sample has been seen since the last reset.
+    started: bool,
+}
+
+impl DcBlocker {
+    /// Create a blocker with its -3 dB point at `corner_hz` for signals at
+    /// `rate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `corner_hz` is zero, or above `rate / 64`.
+    pub const fn new(corner_hz: u32, rate: SampleRate) -> Self {
+        assert!(
+            corner_hz > 0 && corner_hz <= rate.hz() / MIN_RATIO,
+            "DC blocker corner must be in 1..=rate/64 Hz"
+        );
+        let hz = rate.hz() as u64;
+        let feedback = (corner_hz as u64 * TWO_PI + hz / 2) / hz;
+        Self {
+            feedback: feedback as i32,
+            last: 0,
+            acc: 0,
+            started: false,
+        }
+    }
+
+    /// The pole `R`, in Q16.
+    pub const fn pole(&self) -> i32 {
+        (1 << FRAC) - self.feedback
+    }
+
+    /// Filter one ADC sample.
+    ///
+    /// The first sample after creation or a [`reset`](Self::reset) is taken
+    /// as the level the input has been sitting at, so the output starts at
+    /// zero rather than with a step from mid-rail.
+    pub fn push(&mut self, sample: u16) -> i16 {
+        self.push_pcm16(to_pcm16(sample))
+    }
+
+    /// Filter one signed 16-bit sample on the scale of [`to_pcm16`], as
+    /// [`push`](Self::push) does an ADC sample.
+    pub fn push_pcm16(&mut self, sample: i16) -> i16 {
+        let x = i32::from(sample);
+        if !self.started {
+            self.last = x;
+            self.started = true;
+        }
+        let y = self.acc >> OUTPUT_SHIFT;
+        self.acc += (x - self.last) << OUTPUT_SHIFT;
+        // `feedback·y` is on the output scale, four bits finer than the
+        // accumulator's steps
+        self.acc -= (self.feedback * y) >> (FRAC - OUTPUT_SHIFT);
+        self.last = x;
+        (self.acc >> OUTPUT_SHIFT).clamp(i32::from(i16::MIN), i32::from(i16::MAX)) as i16
+    }
+
+    /// Filter every sample of `input` into `output`, as far as both go.
+    pub fn push_block(&mut self, input: &[u16], output: &mut [i16]) {
+        for (out, &sample) in output.iter_mut().zip(input) {
+            *out = self.push(sample);
+        }
+    }
+
+    /// Filter every signed sample of `input` into `output`, as far as both
+    /// go.
+    pub fn push_pcm16_block(&mut self, input: &[i16], output: &mut [i16]) {
+        for (out, &sample) in output.iter_mut().zip(input) {
+            *out = self.push_pcm16(sample);
+        }
+    }
+
+    /// Forget the input so far.
+    pub fn reset(&mut self) {
+        self.last = 0;
+        self.acc = 0;
+        self.started = false;
+    }
+}
@@ -0,0 +1,324 @@
+//! Decimation of an oversampled ADC stream down to the voice rate.
+//!
+//! Running the ADC several times faster than the voice rate and filtering
+//! the excess away pushes the anti-aliasing job into software, where it is
+//! much sharper than the RC filter in front of the ADC, and averages down
+//! the ADC's noise at the same time. A [`Cic`] (cascaded
+//! integrator–comb) filter does the bulk of it with nothing but additions:
+//! each stage is a running sum, like [`Window`](crate::Window)'s, split into
+//! an integrator at the input rate and a comb at the output rate. Its
+//! response droops across the passband, so a [`Decimator`] follows it with
+//! a short FIR at the output rate whose taps are fitted to undo the droop.
+
+use crate::math;
+
+/// Largest gain a [`Cic`] can have, so a full-scale sum fits in 32 bits.
+const MAX_GAIN: u64 = 1 << 20;
+
+/// Fraction bits of the compensation taps.
+const TAP_FRAC: u32 = 14;
+
+/// Frequencies across the passband the compensation is fitted at.
+const FIT_POINTS: usize = 64;
+
+/// A CIC decimator with `STAGES` stages, taking 12-bit offset-binary samples
+/// in and giving one signed sum out for every `ratio` samples.
+///
+/// Each output is the input, centered on mid-rail, filtered by `STAGES`
+/// boxcars of `ratio` samples. It isn't normalized, so it is `ratio^STAGES`
+/// times the average level. The integrators wrap freely; the combs undo the
+/// wrapping exactly as long as the true output fits in 32 bits, which
+/// [`new`](Cic::new) checks. A new filter has seen nothing but mid-rail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cic<const STAGES: usize> {
+    integrators: [u32; STAGES],
+    /// Integrator output each comb saw last time.
+    combs: [u32; STAGES],
+    ratio: u32,
+    phase: u32,
+}
+
+impl<const STAGES: usize> Cic<STAGES> {
+    /// Create a CIC filter decimating by `ratio`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `STAGES` or `ratio` is zero, or if the gain,
+    /// `ratio^STAGES`, is over 2²⁰.
+    pub const fn new(ratio: u32) -> Self {
+        assert!(
+            STAGES > 0 && ratio > 0,
+            "need at least one stage and sample"
+        );
+        assert!(
+            gain(STAGES, ratio) <= MAX_GAIN,
+            "CIC gain must be at most 2^20"
+        );
+        Self {
+            integrators: [0; STAGES],
+            combs: [0; STAGES],
+            ratio,
+            phase: 0,
+        }
+    }
+
+    /// Number of input samples per output sample.
+    pub const fn ratio(&self) -> u32 {
+        self.ratio
+    }
+
+    /// What a constant input, relative to mid-rail, is multiplied by,
+    /// `ratio^STAGES`.
+    pub const fn gain(&self) -> u32 {
+        gain(STAGES, self.ratio) as u32
+    }
+
+    /// Take one input sample, returning an output sample if this completes
+    /// one. Samples above [`SAMPLE_MAX`](crate::SAMPLE_MAX) count as full
+    /// scale.
+    pub fn push(&mut self, sample: u16) -> Option<i32> {
+        let sample = sample.min(crate::SAMPLE_MAX);
+        let mut acc = (i32::from(sample) - 2048) as u32;
+        for integrator in &mut self.integrators {
+            *integrator = integrator.wrapping_add(acc);
+            acc = *integrator;
+        }
+        self.phase += 1;
+        if self.phase < self.ratio {
+            return None;
+        }
+        self.phase = 0;
+        for comb in &mut self.combs {
+            let delayed = *comb;
+            *comb = acc;
+            acc = acc.wrapping_sub(delayed);
+        }
+        Some(acc as i32)
+    }
+
+    /// Forget the signal so far.
+    pub fn reset(&mut self) {
+        self.integrators = [0; STAGES];
+        self.combs = [0; STAGES];
+        self.phase = 0;
+    }
+}
+
+/// `ratio^stages`, saturated to one past [`MAX_GAIN`].
+const fn gain(stages: usize, ratio: u32) -> u64 {
+    let mut gain = 1;
+    let mut i = 0;
+    while i < stages {
+        gain *= ratio as u64;
+        if gain > MAX_GAIN {
+            return MAX_GAIN + 1;
+        }
+        i += 1;
+    }
+    gain
+}
+
+/// Gain of a `stages` stage CIC decimating by `ratio` at `f`, in cycles per
+/// output sample.
+const fn droop(stages: usize, ratio: u32, f: f64) -> f64 {
+    if f == 0.0 {
+        return 1.0;
+    }
+    let x = core::f64::consts::PI * f;
+    let stage = math::sin(x) / (ratio as f64 * math::sin(x / ratio as f64));
+    let mut gain = 1.0;
+    let mut i = 0;
+    while i < stages {
+        gain *= stage;
+        i += 1;
+    }
+    math::abs(gain)
+}
+
+/// Least-squares fit of a symmetric `TAPS` tap FIR to `scale / droop(f)`
+/// over `0..=passband`, in `TAP_FRAC` fixed point, with its DC gain made
+/// exactly `scale` as far as the fixed point goes.
+const fn compensation<const TAPS: usize>(
+    stages: usize,
+    ratio: u32,
+    passband: f64,
+    scale: f64,
+) -> [i32; TAPS] {
+    // the response of a symmetric filter is c0 + Σ 2·ck·cos(2πfk), so fit
+    // the `half` cosine weights ck with the normal equations
+    let half = TAPS / 2 + 1;
+    let mut a = [[0.0; TAPS]; TAPS];
+    let mut b = [0.0; TAPS];
+    let mut point = 0;
+    while point < FIT_POINTS {
+        let f = passband * point as f64 / (FIT_POINTS - 1) as f64;
+        let target = scale / droop(stages, ratio, f);
+        let mut basis = [0.0; TAPS];
+        let mut k = 0;
+        while k < half {
+            basis[k] = if k == 0 {
+                1.0
+            } else {
+                2.0 * math::cos(2.0 * core::f64::consts::PI * f * k as f64)
+            };
+            k += 1;
+        }
+        let mut i = 0;
+        while i < half {
+            b[i] += basis[i] * target;
+            let mut j = 0;
+            while j < half {
+                a[i][j] += basis[i] * basis[j];
+                j += 1;
+            }
+            i += 1;
+        }
+        point += 1;
+    }
+
+    // the normal equations are positive definite, so plain Gauss–Jordan
+    // elimination needs no pivoting
+    let mut col = 0;
+    while col < half {
+        let mut row = 0;
+        while row < half {
+            if row != col {
+                let k = a[row][col] / a[col][col];
+                let mut j = col;
+                while j < half {
+                    a[row][j] -= k * a[col][j];
+                    j += 1;
+                }
+                b[row] -= k * b[col];
+            }
+            row += 1;
+        }
+        col += 1;
+    }
+
+    let one = (1u32 << TAP_FRAC) as f64;
+    let mut taps = [0; TAPS];
+    let mut sum = 0;
+    let mut n = 0;
+    while n < TAPS {
+        let k = if n < half { half - 1 - n } else { n + 1 - half };
+        taps[n] = math::round(b[k] / a[k][k] * one) as i32;
+        sum += taps[n];
+        n += 1;
+    }
+    // the fit and the rounding each leave the DC gain a little off, which
+    // would shift a steady level; the center tap takes up the difference
+    taps[half - 1] += math::round(scale * one) as i32 - sum;
+    taps
+}
+
+/// A [`Cic`] decimator followed by a `TAPS` tap FIR compensating its
+/// passband droop, taking 12-bit offset-binary samples to signed 16-bit
+/// samples at `1/ratio` of the rate, on the scale of
+/// [`to_pcm16`](crate::to_pcm16).
+///
+/// The FIR works on the CIC output scaled to 16 bits and gives its result
+/// on that scale, so the extra resolution oversampling brings, in the four
+/// bits below the ADC's, comes out with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decimator<const STAGES: usize, const TAPS: usize> {
+    cic: Cic<STAGES>,
+    /// How far to shift the centered CIC output right to get 16-bit
+    /// samples; what this leaves of the gain is folded into the taps.
+    shift: u32,
+    taps: [i32; TAPS],
+    history: [i32; TAPS],
+    pos: usize,
+}
+
+impl<const STAGES: usize, const TAPS: usize> Decimator<STAGES, TAPS> {
+    /// Create a decimator by `ratio`, compensating the CIC's droop up to
+    /// `passband`, as a fraction of the output rate.
+    ///
+    /// A passband of 0.425 (3.4 kHz out of 8 kHz) is flat to within about
+    /// ±0.15 dB with 9 taps; fewer taps or a wider passband trade flatness
+    /// for speed or bandwidth.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `TAPS` is even, if `passband` is not between 0 and 0.5,
+    /// or if the CIC can't be made (see [`Cic::new`]).
+    pub const fn new(ratio: u32, passband: f64) -> Self {
+        assert!(TAPS % 2 == 1, "compensation needs an odd number of taps");
+        assert!(
+            passband > 0.0 && passband < 0.5,
+            "passband must be below half the output rate"
+        );
+        let cic = Cic::new(ratio);
+        let gain = cic.gain();
+        // 4 bits up from 12 to 16, and the largest power of two of the gain
+        // down, leaving a gain between 1/2 and 1
+        let shift = 31 - gain.leading_zeros();
+        let scale = (1u32 << shift) as f64 / gain as f64;
+        Self {
+            cic,
+            shift,
+            taps: compensation(STAGES, ratio, passband, scale),
+            history: [0; TAPS],
+            pos: 0,
+        }
+    }
+
+    /// Number of input samples per output sample.
+    pub const fn ratio(&self) -> u32 {
+        self.cic.ratio()
+    }
+
+    /// The compensation taps, with 14 fraction bits.
+    pub const fn taps(&self) -> &[i32; TAPS] {
+        &self.taps
+    }
+
+    /// Take one input sample, returning an output sample if this completes
+    /// one.
+    pub fn push(&mut self, sample: u16) -> Option<i16> {
+        let sum = self.cic.push(sample)?;
+        self.history[self.pos] = ((i64::from(sum) << 4) >> self.shift) as i32;
+        self.pos += 1;
+        if self.pos == TAPS {
+            self.pos = 0;
+        }
+
+        // the history runs oldest to newest from `pos`; the taps are
+        // symmetric, so their order doesn't matter
+        let (newer, older) = self.history.split_at(self.pos);
+        let acc: i64 = older
+            .iter()
+            .chain(newer)
+            .zip(&self.taps)
+            .map(|(&x, &tap)| i64::from(x) * i64::from(tap))
+            .sum();
+        // back to the 16-bit scale, rounding
+        let out = (acc + (1 << (TAP_FRAC - 1))) >> TAP_FRAC;
+        Some(math::clamp_i16(out))
+    }
+
+    /// Run `input` through the decimator into `output`, as far as both go.
+    ///
+    /// Returns how many input samples were taken and how many output
+    /// samples were written. Input stops being taken once `output` is full.
+    pub fn push_block(&mut self, input: &[u16], output: &mut [i16]) -> (usize, usize) {
+        let mut written = 0;
+        let mut read = 0;
+        while read < input.len() && written < output.len() {
+            if let Some(sample) = self.push(input[read]) {
+                output[written] = sample;
+                written += 1;
+            }
+            read += 1;
+        }
+        (read, written)
+    }
+
+    /// Forget the signal so far.
+    pub fn reset(&mut self) {
+        self.cic.reset();
+        self.history = [0; TAPS];
+        self.pos = 0;
+    }
+}
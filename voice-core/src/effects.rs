@@ -0,0 +1,141 @@
+//! Voice changer effects, and a selector to switch between them.
+//!
+//! Each effect is a [`Processor`] of its own and can go anywhere in a
+//! chain; [`Effects`] holds one of each, runs whichever is selected, and
+//! passes the signal through untouched when none is, so it can sit between
+//! capture and the PWM output permanently:
+//!
+//! - [`PitchShifter`] moves the voice up or down by overlap-adding grains
+//!   read back from a delay line at a different speed, each lined up with
+//!   the last, leaving its timing alone;
+//! - [`RingModulator`] multiplies it by a sine, for the metallic voices of
+//!   old science fiction;
+//! - [`Robot`] throws away the phase of every short frame and puts them
+//!   back together at a fixed rate, which turns any voice into a monotone
+//!   buzz with the same words in it;
+//! - [`Bitcrusher`] throws away resolution and sample rate, for the sound
+//!   of a cheap toy.
+//!
+//! The pitch shifter and the robot take their grains and frames from the
+//! same `N`: 256 suits both at 8 kHz, though 128 halves what the robot
+//! costs.
+//!
+//! The effects made of delays, [`Echo`](echo::Echo) and
+//! [`Reverb`](reverb::Reverb), are for adding after whichever of these is
+//! running rather than instead of them, and their memory is sized to
+//! suit, so they are chained on their own.
+
+pub mod crush;
+pub mod echo;
+pub mod reverb;
+pub mod ring;
+pub mod robot;
+pub mod shift;
+
+use crate::process::Processor;
+use crate::SampleRate;
+
+use self::crush::Bitcrusher;
+use self::ring::RingModulator;
+use self::robot::Robot;
+use self::shift::PitchShifter;
+
+/// Which effect an [`Effects`] runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Effect {
+    /// None: the signal passes through.
+    #[default]
+    Off,
+    /// [`PitchShifter`].
+    PitchShift,
+    /// [`RingModulator`].
+    RingModulator,
+    /// [`Robot`].
+    Robot,
+    /// [`Bitcrusher`].
+    Bitcrusher,
+}
+
+/// Settings for each of the effects in an [`Effects`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Settings for the pitch shifter.
+    pub shift: shift::Config,
+    /// Settings for the ring modulator.
+    pub ring: ring::Config,
+    /// Settings for the robot voice.
+    pub robot: robot::Config,
+    /// Settings for the bitcrusher.
+    pub crush: crush::Config,
+}
+
+/// One of each effect, running whichever is selected. `N` is the grain
+/// length of the pitch shifter and the frame length of the robot voice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Effects<const N: usize> {
+    effect: Effect,
+    shift: PitchShifter<N>,
+    ring: RingModulator,
+    robot: Robot<N>,
+    crush: Bitcrusher,
+}
+
+impl<const N: usize> Effects<N> {
+    /// Create the effects for signals at `rate`, with none selected.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the effects would, with its settings.
+    pub const fn new(config: &Config, rate: SampleRate) -> Self {
+        Self {
+            effect: Effect::Off,
+            shift: PitchShifter::new(&config.shift),
+            ring: RingModulator::new(&config.ring, rate),
+            robot: Robot::new(&config.robot, rate),
+            crush: Bitcrusher::new(&config.crush, rate),
+        }
+    }
+
+    /// The effect running.
+    pub fn effect(&self) -> Effect {
+        self.effect
+    }
+
+    /// Switch to `effect`, which starts from silence rather than from
+    /// wherever it was left.
+    pub fn select(&mut self, effect: Effect) {
+        if effect != self.effect {
+            self.effect = effect;
+            self.reset();
+        }
+    }
+}
+
+impl<const N: usize> Processor for Effects<N> {
+    fn process(&mut self, sample: i16) -> i16 {
+        match self.effect {
+            Effect::Off => sample,
+            Effect::PitchShift => self.shift.process(sample),
+            Effect::RingModulator => self.ring.process(sample),
+            Effect::Robot => self.robot.process(sample),
+            Effect::Bitcrusher => self.crush.process(sample),
+        }
+    }
+
+    fn process_block(&mut self, samples: &mut [i16]) {
+        match self.effect {
+            Effect::Off => {}
+            Effect::PitchShift => self.shift.process_block(samples),
+            Effect::RingModulator => self.ring.process_block(samples),
+            Effect::Robot => self.robot.process_block(samples),
+            Effect::Bitcrusher => self.crush.process_block(samples),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.shift.reset();
+        self.ring.reset();
+        self.robot.reset();
+        self.crush.reset();
+    }
+}
@@ -0,0 +1,102 @@
+//! Bit crushing: fewer bits and a lower sample rate.
+//!
+//! Each sample is rounded to the nearest of `2^bits` levels, which adds the
+//! hiss and grit of a cheap converter, and only every so often is a new one
+//! taken, the last being held in between, which folds everything above half
+//! the crushed rate back down as inharmonic whine. The crushed rate needn't
+//! divide the sample rate: samples are taken when a phase accumulator
+//! wraps, so the gaps between them vary by one.
+//!
+//! # Cost
+//!
+//! An addition and a shift or two a sample.
+
+use crate::process::Processor;
+use crate::SampleRate;
+
+/// A full cycle of the phase, which a new sample is taken on completing.
+const CYCLE: u64 = 1 << 32;
+
+/// Settings for a [`Bitcrusher`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Bits kept of each sample, from 1 to 16.
+    pub bits: u32,
+    /// Rate new samples are taken at, in Hz, up to the sample rate.
+    pub rate_hz: u32,
+}
+
+impl Default for Config {
+    /// Six bits at 2 kHz: an old toy's speech chip.
+    fn default() -> Self {
+        Self {
+            bits: 6,
+            rate_hz: 2000,
+        }
+    }
+}
+
+/// Bitcrusher on signed 16-bit samples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitcrusher {
+    /// Bits dropped from each sample.
+    shift: u32,
+    /// How far the phase moves each sample, out of [`CYCLE`].
+    increment: u64,
+    phase: u64,
+    /// The sample being held.
+    held: i16,
+}
+
+impl Bitcrusher {
+    /// Create a bitcrusher for signals at `rate`, which takes the first
+    /// sample it is given.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bits are not 1 to 16, or the crushed rate is zero or
+    /// above the sample rate.
+    pub const fn new(config: &Config, rate: SampleRate) -> Self {
+        assert!(
+            config.bits >= 1 && config.bits <= 16,
+            "bits must be from 1 to 16"
+        );
+        assert!(
+            config.rate_hz >= 1 && config.rate_hz <= rate.hz(),
+            "crushed rate must be from 1 Hz to the sample rate"
+        );
+        let increment = ((config.rate_hz as u64) << 32) / rate.hz() as u64;
+        Self {
+            shift: 16 - config.bits,
+            increment,
+            phase: CYCLE - increment,
+            held: 0,
+        }
+    }
+
+    /// `sample` rounded to the bits kept, short of going over full scale.
+    fn quantize(&self, sample: i16) -> i16 {
+        if self.shift == 0 {
+            return sample;
+        }
+        let step = 1 << self.shift;
+        let rounded = ((i32::from(sample) + step / 2) >> self.shift) << self.shift;
+        rounded.min(i32::from(i16::MAX) + 1 - step) as i16
+    }
+}
+
+impl Processor for Bitcrusher {
+    fn process(&mut self, sample: i16) -> i16 {
+        self.phase += self.increment;
+        if self.phase >= CYCLE {
+            self.phase -= CYCLE;
+            self.held = self.quantize(sample);
+        }
+        self.held
+    }
+
+    fn reset(&mut self) {
+        self.phase = CYCLE - self.increment;
+        self.held = 0;
+    }
+}
@@ -0,0 +1,173 @@
+//! Echo: the voice repeated after a delay, and again, fading away.
+//!
+//! The input goes into a ring buffer of `N` samples, the longest delay, and
+//! up to four taps read echoes back out of it, each at its own delay and
+//! level: one tap is a plain echo, a few close together a slap-back or a
+//! stairwell. A fraction of what was written the feedback delay ago goes
+//! back into the buffer along with the input, so each echo comes round
+//! again, quieter each time. The echoes are mixed with the dry voice.
+//!
+//! The buffer holds samples as they will be played, so feedback can't take
+//! them over full scale; it rounds towards zero, so echoes die away to
+//! nothing rather than circulating at the last bit forever.
+//!
+//! # Cost
+//!
+//! A multiply for each tap and one for the feedback a sample, and `2N`
+//! bytes for the buffer: a second at 16 kHz is 32 KB of the RP2040's
+//! 256 KB, which is as long as an echo wants to be.
+
+use crate::process::Processor;
+use crate::SampleRate;
+
+/// Fraction bits of the gains.
+const FRAC: u32 = 15;
+
+/// One in Q15.
+const ONE: i32 = 1 << FRAC;
+
+/// Most taps an [`Echo`] reads.
+pub const TAPS: usize = 4;
+
+/// Where an echo is read off the buffer, and how loud.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tap {
+    /// Delay of the echo, in ms.
+    pub delay_ms: u32,
+    /// Level of the echo, in percent of the voice. A tap at 0 is unused.
+    pub gain_percent: u32,
+}
+
+/// Settings for an [`Echo`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Delay around the feedback loop, in ms.
+    pub delay_ms: u32,
+    /// How much of the signal that delay ago goes back in, in percent.
+    pub feedback_percent: u32,
+    /// Where the echoes are read off.
+    pub taps: [Tap; TAPS],
+    /// How much of the output is echoes, in percent; the rest is the dry
+    /// voice.
+    pub mix_percent: u32,
+}
+
+impl Default for Config {
+    /// A single echo a quarter of a second later, coming back three or four
+    /// times.
+    fn default() -> Self {
+        Self {
+            delay_ms: 250,
+            feedback_percent: 40,
+            taps: [
+                Tap {
+                    delay_ms: 250,
+                    gain_percent: 100,
+                },
+                Tap::default(),
+                Tap::default(),
+                Tap::default(),
+            ],
+            mix_percent: 40,
+        }
+    }
+}
+
+/// `ms` at `rate`, in samples, checked against the buffer.
+const fn samples(ms: u32, rate: SampleRate, len: usize) -> usize {
+    let n = (ms as u64 * rate.hz() as u64 / 1000) as usize;
+    assert!(
+        n >= 1 && n <= len,
+        "delays must be from a sample to the length of the buffer"
+    );
+    n
+}
+
+/// `percent` in Q15.
+const fn gain(percent: u32) -> i32 {
+    ((percent << FRAC) / 100) as i32
+}
+
+/// Multi-tap echo with feedback on signed 16-bit samples, with a buffer of
+/// `N` samples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Echo<const N: usize> {
+    buffer: [i16; N],
+    /// Where the next sample goes in `buffer`.
+    pos: usize,
+    /// Delay and gain, in Q15, of each tap in use.
+    taps: [(usize, i32); TAPS],
+    /// Number of taps in use.
+    used: usize,
+    /// Feedback delay and gain, in Q15.
+    delay: usize,
+    feedback: i32,
+    /// How much of the echoes and the dry signal to mix, in Q15.
+    wet: i32,
+    dry: i32,
+}
+
+impl<const N: usize> Echo<N> {
+    /// Create an echo for signals at `rate`, its buffer silent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a delay in use is under a sample or longer than `N`, the
+    /// feedback is 100% or more, or a gain or the mix is over 100%.
+    pub const fn new(config: &Config, rate: SampleRate) -> Self {
+        assert!(config.feedback_percent < 100, "feedback must be below 100%");
+        assert!(config.mix_percent <= 100, "mix must be at most 100%");
+        let mut taps = [(0, 0); TAPS];
+        let mut used = 0;
+        let mut i = 0;
+        while i < TAPS {
+            let tap = config.taps[i];
+            assert!(tap.gain_percent <= 100, "tap gains must be at most 100%");
+            if tap.gain_percent > 0 {
+                taps[used] = (samples(tap.delay_ms, rate, N), gain(tap.gain_percent));
+                used += 1;
+            }
+            i += 1;
+        }
+        let wet = gain(config.mix_percent);
+        Self {
+            buffer: [0; N],
+            pos: 0,
+            taps,
+            used,
+            delay: samples(config.delay_ms, rate, N),
+            feedback: gain(config.feedback_percent),
+            wet,
+            dry: ONE - wet,
+        }
+    }
+
+    /// The sample written `delay` samples ago.
+    fn ago(&self, delay: usize) -> i32 {
+        i32::from(self.buffer[(self.pos + N - delay) % N])
+    }
+}
+
+impl<const N: usize> Processor for Echo<N> {
+    fn process(&mut self, sample: i16) -> i16 {
+        let x = i32::from(sample);
+        let echoes: i64 = self.taps[..self.used]
+            .iter()
+            .map(|&(delay, gain)| i64::from(self.ago(delay)) * i64::from(gain))
+            .sum();
+        let echoes = (echoes >> FRAC).clamp(i16::MIN.into(), i16::MAX.into()) as i32;
+
+        // towards zero, so the feedback always loses something
+        let fed = x + self.ago(self.delay) * self.feedback / ONE;
+        self.buffer[self.pos] = fed.clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+        self.pos = (self.pos + 1) % N;
+
+        let y = (x * self.dry + echoes * self.wet + (1 << (FRAC - 1))) >> FRAC;
+        y.clamp(i16::MIN.into(), i16::MAX.into()) as i16
+    }
+
+    fn reset(&mut self) {
+        self.buffer = [0; N];
+        self.pos = 0;
+    }
+}
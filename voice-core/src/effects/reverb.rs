@@ -0,0 +1,246 @@
+//! Reverb: the sound of a room, after Jezar's Freeverb.
+//!
+//! Freeverb is a Schroeder reverb, with Moorer's lowpass in the loops:
+//!
+//! - eight comb filters run in parallel, each a delay line of a few tens of
+//!   ms feeding back into itself through a one-pole lowpass. Each rings at
+//!   the harmonics of its own delay, and with delays that share no factors
+//!   the rings add up to the dense, even tail of a room. The feedback sets
+//!   how long the tail lasts, the room size, and the lowpass how much
+//!   faster its top end dies away, the damping, as it does off soft walls;
+//! - four allpass filters after them smear each echo of the combs out in
+//!   time without colouring it, so the tail doesn't flutter.
+//!
+//! Freeverb's delays are tuned in samples at 44.1 kHz; they are scaled to
+//! the sample rate, and the lines sized for the longest of them at the
+//! 16 kHz the voice tool runs at most, so a [`Reverb`] is about 11 KB.
+//!
+//! The combs are fed an eighth of the input, their average goes through
+//! the allpasses, and Freeverb's gain is made up at the end, which keeps
+//! the 16-bit lines clear of both clipping and the noise of their last
+//! bit. Everything that goes round a loop is rounded towards zero, so the
+//! tail dies away to nothing.
+//!
+//! # Cost
+//!
+//! Twenty or so multiplies and a few dozen additions a sample.
+
+use crate::math;
+use crate::process::Processor;
+use crate::SampleRate;
+
+/// Fraction bits of the gains.
+const FRAC: u32 = 15;
+
+/// One in Q15.
+const ONE: i32 = 1 << FRAC;
+
+/// Freeverb's comb delays, in samples at 44.1 kHz.
+const COMBS: [u32; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+
+/// Freeverb's allpass delays, in samples at 44.1 kHz.
+const ALLPASSES: [u32; 4] = [556, 441, 341, 225];
+
+/// The rate Freeverb's delays are tuned at.
+const TUNING_HZ: u32 = 44100;
+
+/// Highest sample rate the lines are sized for.
+const MAX_HZ: u32 = 16000;
+
+/// The length of a line for `delay` at 44.1 kHz, at `hz`.
+const fn scaled(delay: u32, hz: u32) -> usize {
+    ((delay * hz + TUNING_HZ / 2) / TUNING_HZ) as usize
+}
+
+/// Longest comb line.
+const COMB_LEN: usize = scaled(COMBS[7], MAX_HZ);
+
+/// Longest allpass line.
+const ALLPASS_LEN: usize = scaled(ALLPASSES[0], MAX_HZ);
+
+/// Bits the input to the combs is shifted down by: they get an eighth of
+/// it, rather than Freeverb's 0.015.
+const INPUT_SHIFT: u32 = 3;
+
+/// Gain of the allpasses' output, in Q15: Freeverb's 0.015 at the input and
+/// 3 at the output, less the eighth the combs were fed and the average
+/// taken of them.
+const WET_GAIN: i64 = (0.015 * 3.0 * 64.0 * ONE as f64 + 0.5) as i64;
+
+/// A comb filter with a lowpass in its loop, from Freeverb.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Comb {
+    line: [i16; COMB_LEN],
+    len: usize,
+    pos: usize,
+    /// State of the lowpass.
+    filter: i32,
+}
+
+impl Comb {
+    const fn new(len: usize) -> Self {
+        Self {
+            line: [0; COMB_LEN],
+            len,
+            pos: 0,
+            filter: 0,
+        }
+    }
+
+    /// Take `input`, giving what went in `len` samples ago with what has
+    /// come round since, and feed back `feedback` of it through a lowpass
+    /// of `damping`, both in Q15.
+    fn process(&mut self, input: i32, feedback: i32, damping: i32) -> i32 {
+        let y = i32::from(self.line[self.pos]);
+        self.filter = (y * (ONE - damping) + self.filter * damping) / ONE;
+        self.line[self.pos] = math::clamp_i16((input + self.filter * feedback / ONE).into());
+        self.pos = (self.pos + 1) % self.len;
+        y
+    }
+}
+
+/// Freeverb's allpass filter, which is one only approximately: its gain
+/// is one half.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Allpass {
+    line: [i16; ALLPASS_LEN],
+    len: usize,
+    pos: usize,
+}
+
+impl Allpass {
+    const fn new(len: usize) -> Self {
+        Self {
+            line: [0; ALLPASS_LEN],
+            len,
+            pos: 0,
+        }
+    }
+
+    fn process(&mut self, input: i32) -> i32 {
+        let delayed = i32::from(self.line[self.pos]);
+        self.line[self.pos] = math::clamp_i16((input + delayed / 2).into());
+        self.pos = (self.pos + 1) % self.len;
+        delayed - input
+    }
+}
+
+/// Settings for a [`Reverb`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Size of the room, in percent: how long the tail lasts, from about
+    /// half a second to several.
+    pub room_percent: u32,
+    /// How much faster the top end of the tail dies away, in percent.
+    pub damping_percent: u32,
+    /// How much of the output is the reverb, in percent; the rest is the
+    /// dry voice.
+    pub mix_percent: u32,
+}
+
+impl Default for Config {
+    /// Freeverb's defaults: a middling room, half damped, a third wet.
+    fn default() -> Self {
+        Self {
+            room_percent: 50,
+            damping_percent: 50,
+            mix_percent: 33,
+        }
+    }
+}
+
+/// Freeverb reverb on signed 16-bit samples, at up to 16 kHz.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reverb {
+    combs: [Comb; 8],
+    allpasses: [Allpass; 4],
+    /// Comb feedback and damping, in Q15.
+    feedback: i32,
+    damping: i32,
+    /// How much of the reverb and the dry signal to mix, in Q15.
+    wet: i32,
+    dry: i32,
+}
+
+impl Reverb {
+    /// Create a reverb for signals at `rate`, its room silent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is above 16 kHz, or the room size, damping or mix
+    /// is over 100%.
+    pub const fn new(config: &Config, rate: SampleRate) -> Self {
+        assert!(rate.hz() <= MAX_HZ, "reverb runs at up to 16 kHz");
+        assert!(
+            config.room_percent <= 100
+                && config.damping_percent <= 100
+                && config.mix_percent <= 100,
+            "room size, damping and mix must be at most 100%"
+        );
+        let hz = rate.hz();
+        let wet = ((config.mix_percent << FRAC) / 100) as i32;
+        Self {
+            combs: [
+                Comb::new(scaled(COMBS[0], hz)),
+                Comb::new(scaled(COMBS[1], hz)),
+                Comb::new(scaled(COMBS[2], hz)),
+                Comb::new(scaled(COMBS[3], hz)),
+                Comb::new(scaled(COMBS[4], hz)),
+                Comb::new(scaled(COMBS[5], hz)),
+                Comb::new(scaled(COMBS[6], hz)),
+                Comb::new(scaled(COMBS[7], hz)),
+            ],
+            allpasses: [
+                Allpass::new(scaled(ALLPASSES[0], hz)),
+                Allpass::new(scaled(ALLPASSES[1], hz)),
+                Allpass::new(scaled(ALLPASSES[2], hz)),
+                Allpass::new(scaled(ALLPASSES[3], hz)),
+            ],
+            // Freeverb's scaling: feedback from 0.7 to 0.98, damping up
+            // to 0.4
+            feedback: (((7000 + 28 * config.room_percent) << FRAC) / 10000) as i32,
+            damping: (((4 * config.damping_percent) << FRAC) / 1000) as i32,
+            wet,
+            dry: ONE - wet,
+        }
+    }
+
+    /// How many samples after the input the first of the reverb comes.
+    pub const fn predelay(&self) -> usize {
+        self.combs[0].len
+    }
+}
+
+impl Processor for Reverb {
+    fn process(&mut self, sample: i16) -> i16 {
+        let x = i32::from(sample);
+        let input = x >> INPUT_SHIFT;
+        let (feedback, damping) = (self.feedback, self.damping);
+        let sum: i32 = self
+            .combs
+            .iter_mut()
+            .map(|comb| comb.process(input, feedback, damping))
+            .sum();
+        let mut reverb = sum / self.combs.len() as i32;
+        for allpass in &mut self.allpasses {
+            reverb = allpass.process(reverb);
+        }
+        let reverb = (i64::from(reverb) * WET_GAIN) >> FRAC;
+        let y =
+            (i64::from(x) * i64::from(self.dry) + reverb * i64::from(self.wet) + (1 << (FRAC - 1)))
+                >> FRAC;
+        math::clamp_i16(y)
+    }
+
+    fn reset(&mut self) {
+        for comb in &mut self.combs {
+            comb.line = [0; COMB_LEN];
+            comb.pos = 0;
+            comb.filter = 0;
+        }
+        for allpass in &mut self.allpasses {
+            allpass.line = [0; ALLPASS_LEN];
+            allpass.pos = 0;
+        }
+    }
+}
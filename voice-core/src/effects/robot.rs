@@ -0,0 +1,143 @@
+//! Robotization: every short frame of the voice played as one click, at a
+//! fixed rate.
+//!
+//! Frames of `N` samples are taken every `rate / pitch_hz` samples and
+//! transformed, the phase of every bin is thrown away, keeping only its
+//! magnitude, and the frame is transformed back. With no phase every
+//! frequency peaks at once, so what comes back is a single click with the
+//! spectrum of the frame: the formants that make the words, and, as echoes
+//! either side of it, the voice's own pitch. Tapered to one period of the
+//! robot's pitch, which takes off the echoes, and added back together one
+//! period apart, the clicks make a buzz at the configured pitch, saying
+//! what the voice said, in a monotone.
+//!
+//! # Cost
+//!
+//! A transform there and back every period, which is several times as
+//! often as the [`Suppressor`](crate::denoise::Suppressor) does them, so
+//! keep the frames short: at 8 kHz and 120 Hz, 128 points, 16 ms, are
+//! enough to hold the formants and take about a third of the CPU. The output is
+//! `N - 1` samples behind the input, and it takes about `20N` bytes.
+
+use crate::fft::{Bin, Fft, Frames, Window};
+use crate::math;
+use crate::process::Processor;
+use crate::SampleRate;
+
+/// Fraction bits of the output gain.
+const GAIN_FRAC: u32 = 15;
+
+/// Settings for a [`Robot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Pitch of the robot voice, in Hz.
+    pub pitch_hz: u32,
+}
+
+impl Default for Config {
+    /// A low, male-sounding monotone.
+    fn default() -> Self {
+        Self { pitch_hz: 120 }
+    }
+}
+
+/// Robot voice on signed 16-bit samples, from frames of `N`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Robot<const N: usize> {
+    fft: Fft<N>,
+    frames: Frames<N>,
+    bins: [Bin; N],
+    /// Scales the clicks to the level of the voice, in Q15.
+    gain: i64,
+    /// The taper a click is cut down to a period with, in Q15, by distance
+    /// from its middle.
+    taper: [u16; N],
+    /// The clicks added up, as a ring the output is read from and cleared
+    /// behind.
+    out: [i32; N],
+    /// Next output sample in `out`.
+    pos: usize,
+}
+
+impl<const N: usize> Robot<N> {
+    /// Create a robot voice for signals at `rate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is not a power of two from 4 to 4096 (see
+    /// [`Fft::new`]), or a period of the pitch is longer than a frame.
+    pub const fn new(config: &Config, rate: SampleRate) -> Self {
+        assert!(config.pitch_hz > 0, "pitch must be above zero");
+        let period = (rate.hz() / config.pitch_hz) as usize;
+        assert!(
+            period >= 1 && period <= N,
+            "period of the pitch must fit in a frame"
+        );
+        // a click has the energy of the Hann-windowed frame it came from,
+        // 3/8 of the frame's; one click a period has the energy of the
+        // voice over that period
+        let gain = math::sqrt(8.0 * period as f64 / (3.0 * N as f64));
+        let one = (1 << GAIN_FRAC) as f64;
+        let mut taper = [0; N];
+        let mut k = 0;
+        while 2 * k < period {
+            let x = core::f64::consts::PI * 2.0 * k as f64 / period as f64;
+            taper[k] = (one * (0.5 + 0.5 * math::cos(x)) + 0.5) as u16;
+            k += 1;
+        }
+        Self {
+            fft: Fft::new(Window::Hann),
+            frames: Frames::new(period),
+            bins: [Bin { re: 0, im: 0 }; N],
+            gain: (one * gain + 0.5) as i64,
+            taper,
+            out: [0; N],
+            pos: 0,
+        }
+    }
+
+    /// How many samples later the click for a frame comes out than the
+    /// middle of the frame.
+    pub const fn latency(&self) -> usize {
+        N - 1
+    }
+
+    /// Turn a frame into a click, and add it into the output.
+    fn frame(&mut self, frame: &[i16; N]) {
+        self.fft.transform(frame, &mut self.bins);
+        for bin in &mut self.bins[..=N / 2] {
+            *bin = Bin {
+                re: bin.magnitude() as i32,
+                im: 0,
+            };
+        }
+        let mut click = [0; N];
+        self.fft.inverse(&mut self.bins, &mut click);
+        // the click is centred on the start of the frame, half of it
+        // wrapped round to the end; the middle of the frame is where it
+        // belongs
+        for (i, &x) in click.iter().enumerate() {
+            let taper = i64::from(self.taper[i.min(N - i)]);
+            let y = (((i64::from(x) * taper) >> GAIN_FRAC) * self.gain) >> GAIN_FRAC;
+            let at = (self.pos + (i + N / 2) % N) % N;
+            self.out[at] += y as i32;
+        }
+    }
+}
+
+impl<const N: usize> Processor for Robot<N> {
+    fn process(&mut self, sample: i16) -> i16 {
+        if let Some(frame) = self.frames.push(sample).copied() {
+            self.frame(&frame);
+        }
+        let out = core::mem::take(&mut self.out[self.pos]);
+        self.pos = (self.pos + 1) % N;
+        out.clamp(i16::MIN.into(), i16::MAX.into()) as i16
+    }
+
+    fn reset(&mut self) {
+        self.frames.reset();
+        self.out = [0; N];
+        self.pos = 0;
+    }
+}
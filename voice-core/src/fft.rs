@@ -0,0 +1,456 @@
+//! Fixed-point FFT of real signals, for looking at the spectrum.
+//!
+//! An [`Fft`] takes a frame of `N` signed 16-bit samples, tapers it with a
+//! [`Window`], and works out the `N/2 + 1` bins of its spectrum from DC to
+//! half the sample rate. It does this with a complex radix-2 FFT of half the
+//! length, packing the even samples into the real parts and the odd ones
+//! into the imaginary parts, followed by a pass that pulls the two halves'
+//! spectra apart again, which is about half the work of a complex FFT of
+//! the full length.
+//!
+//! Samples, window and twiddle factors are Q15. The butterflies work on 32
+//! bits, which holds the growth of up to 4096 points without scaling, and
+//! what a shorter transform leaves of those bits carries extra fraction
+//! bits through the butterflies, so rounding doesn't swamp quiet signals.
+//! The bins come out at the full scale of the DFT,
+//!
+//! ```text
+//! X[k] = Σ w[n]·x[n]·e^(-2πi·kn/N)
+//! ```
+//!
+//! in the units of the samples. A sine of amplitude `A` on a bin comes out with a
+//! magnitude of `A·S/2`, where `S` is the sum of the window,
+//! [`Fft::window_sum`].
+//!
+//! [`Fft::inverse`] takes a spectrum back to a frame, so a spectrum can be
+//! changed and turned back into sound, and [`Frames`] cuts a sample stream
+//! into overlapping frames to feed the transform.
+//!
+//! On the RP2040 memory goes on the tables, `4N` bytes in the [`Fft`], the
+//! bins, `4N + 8` bytes, and the frame, `2N` bytes: about 5 KB for the 512
+//! points that give 15.6 Hz bins at 8 kHz.
+
+use core::f64::consts::PI;
+
+use crate::math;
+
+/// Fraction bits of the samples, window and twiddle factors.
+const FRAC: u32 = 15;
+
+/// Largest frame, so the bins fit in 32 bits.
+const MAX_LEN: usize = 4096;
+
+/// A taper applied to a frame before transforming it, trading how finely
+/// the spectrum resolves close frequencies against how far a strong one
+/// leaks into the bins around it.
+///
+/// The windows are periodic, the form for spectral analysis: a Hann or
+/// Hamming window overlapped by half adds up to a constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Window {
+    /// No taper: the sharpest peaks, and leakage falling off slowly, at
+    /// 6 dB an octave.
+    Rectangular,
+    /// Raised cosine, `0.5 - 0.5·cos`: the usual choice.
+    Hann,
+    /// Raised cosine on a pedestal, `0.54 - 0.46·cos`, which cancels the
+    /// nearest sidelobe, the highest of the Hann window's.
+    Hamming,
+    /// Three-term cosine, `0.42 - 0.5·cos + 0.08·cos(2·)`: the widest peaks
+    /// and the lowest leakage.
+    Blackman,
+}
+
+impl Window {
+    /// The window at sample `n` of a frame of `len`.
+    pub const fn coefficient(self, n: usize, len: usize) -> f64 {
+        let x = 2.0 * PI * n as f64 / len as f64;
+        match self {
+            Window::Rectangular => 1.0,
+            Window::Hann => 0.5 - 0.5 * math::cos(x),
+            Window::Hamming => 0.54 - 0.46 * math::cos(x),
+            Window::Blackman => 0.42 - 0.5 * math::cos(x) + 0.08 * math::cos(2.0 * x),
+        }
+    }
+}
+
+/// One bin of a spectrum.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Bin {
+    /// Real part.
+    pub re: i32,
+    /// Imaginary part.
+    pub im: i32,
+}
+
+impl Bin {
+    /// Squared magnitude.
+    pub fn power(self) -> u64 {
+        let re = i64::from(self.re);
+        let im = i64::from(self.im);
+        (re * re + im * im) as u64
+    }
+
+    /// Magnitude, rounded down.
+    pub fn magnitude(self) -> u32 {
+        self.power().isqrt() as u32
+    }
+
+    const fn add(self, other: Bin) -> Bin {
+        Bin {
+            re: self.re + other.re,
+            im: self.im + other.im,
+        }
+    }
+
+    const fn sub(self, other: Bin) -> Bin {
+        Bin {
+            re: self.re - other.re,
+            im: self.im - other.im,
+        }
+    }
+
+    const fn conj(self) -> Bin {
+        Bin {
+            re: self.re,
+            im: -self.im,
+        }
+    }
+}
+
+/// Magnitude of each of `bins` into `out`.
+///
+/// # Panics
+///
+/// Panics if `out` is shorter than `bins`.
+pub fn magnitude(bins: &[Bin], out: &mut [u32]) {
+    for (out, bin) in out[..bins.len()].iter_mut().zip(bins) {
+        *out = bin.magnitude();
+    }
+}
+
+/// Squared magnitude of each of `bins` into `out`.
+///
+/// # Panics
+///
+/// Panics if `out` is shorter than `bins`.
+pub fn power(bins: &[Bin], out: &mut [u64]) {
+    for (out, bin) in out[..bins.len()].iter_mut().zip(bins) {
+        *out = bin.power();
+    }
+}
+
+/// Q15 product, rounded.
+fn mul(x: i32, c: i32) -> i32 {
+    ((i64::from(x) * i64::from(c) + (1 << (FRAC - 1))) >> FRAC) as i32
+}
+
+/// Complex product of `x` and the twiddle factor `(c, s)`.
+fn twiddle(x: Bin, c: i32, s: i32) -> Bin {
+    Bin {
+        re: mul(x.re, c) - mul(x.im, s),
+        im: mul(x.re, s) + mul(x.im, c),
+    }
+}
+
+/// FFT of `N` real samples, tapered by a [`Window`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fft<const N: usize> {
+    /// `cos(2πk/N)` for `k` in `0..N`, in Q15, short of 1 at `k = 0`.
+    cos: [i16; N],
+    /// The window, in Q15, up to 1 as 32768.
+    window: [u16; N],
+    kind: Window,
+}
+
+impl<const N: usize> Fft<N> {
+    /// Create an FFT of `N` samples, tapering them with `window`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is not a power of two from 4 to 4096.
+    pub const fn new(window: Window) -> Self {
+        assert!(
+            N.is_power_of_two() && N >= 4 && N <= MAX_LEN,
+            "FFT length must be a power of two from 4 to 4096"
+        );
+        let one = (1u32 << FRAC) as f64;
+        let mut cos = [0; N];
+        let mut taper = [0; N];
+        let mut n = 0;
+        while n < N {
+            cos[n] = math::clamp_i16(math::round(one * math::cos(2.0 * PI * n as f64 / N as f64)));
+            taper[n] = math::round(one * window.coefficient(n, N)) as u16;
+            n += 1;
+        }
+        Self {
+            cos,
+            window: taper,
+            kind: window,
+        }
+    }
+
+    /// The window frames are tapered with.
+    pub const fn window(&self) -> Window {
+        self.kind
+    }
+
+    /// Sum of the window, in Q15: the DC gain it gives the transform.
+    pub fn window_sum(&self) -> u32 {
+        self.window.iter().map(|&w| u32::from(w)).sum()
+    }
+
+    /// Number of bins in a spectrum, `N/2 + 1`, from DC to half the sample
+    /// rate inclusive.
+    pub const fn bins(&self) -> usize {
+        N / 2 + 1
+    }
+
+    /// `cos(2πk/N)` in Q15, exactly 1 at `k = 0`, where the table falls
+    /// just short.
+    fn cos(&self, k: usize) -> i32 {
+        if k == 0 {
+            1 << FRAC
+        } else {
+            self.cos[k].into()
+        }
+    }
+
+    /// Twiddle factor `e^(-2πi·k/N)`, as its cosine and sine.
+    fn twiddle(&self, k: usize) -> (i32, i32) {
+        // sin(x) = cos(x - π/2), and the table wraps
+        (self.cos(k), -self.cos((k + 3 * N / 4) % N))
+    }
+
+    /// Transform `frame` into the first `N/2 + 1` of `bins`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bins` holds fewer than `N/2 + 1` bins.
+    pub fn transform(&self, frame: &[i16; N], bins: &mut [Bin]) {
+        let half = N / 2;
+        let bins = &mut bins[..=half];
+        // fraction bits the growth of the transform leaves room for
+        let guard = FRAC - N.trailing_zeros();
+
+        // the even samples as real parts and the odd as imaginary, windowed
+        // and in bit-reversed order for the butterflies
+        let bits = half.trailing_zeros();
+        for (i, bin) in bins[..half].iter_mut().enumerate() {
+            let j = i.reverse_bits() >> (usize::BITS - bits);
+            let taper = |n: usize| {
+                let shift = FRAC - guard;
+                (i32::from(frame[n]) * i32::from(self.window[n]) + (1 << (shift - 1))) >> shift
+            };
+            *bin = Bin {
+                re: taper(2 * j),
+                im: taper(2 * j + 1),
+            };
+        }
+
+        self.butterflies(&mut bins[..half], false);
+
+        // untangle the even and odd samples' spectra, E and O, from the
+        // packed one, Z: with Z' = conj(Z[half - k]),
+        //
+        //   2·E[k] = Z[k] + Z'    2·O[k] = -i·(Z[k] - Z')
+        //   X[k] = E[k] + W^k·O[k]    X[half - k] = conj(E[k] - W^k·O[k])
+        let z = bins[0];
+        bins[0] = scale(
+            Bin {
+                re: z.re + z.im,
+                im: 0,
+            },
+            guard,
+        );
+        bins[half] = scale(
+            Bin {
+                re: z.re - z.im,
+                im: 0,
+            },
+            guard,
+        );
+        for k in 1..=half / 2 {
+            let z = bins[k];
+            let mirror = bins[half - k].conj();
+            let even = z.add(mirror);
+            let d = z.sub(mirror);
+            let (c, s) = self.twiddle(k);
+            let odd = twiddle(
+                Bin {
+                    re: d.im,
+                    im: -d.re,
+                },
+                c,
+                s,
+            );
+            bins[k] = scale(even.add(odd), guard + 1);
+            bins[half - k] = scale(even.sub(odd), guard + 1).conj();
+        }
+    }
+
+    /// Transform the first `N/2 + 1` of `bins` back into a frame of
+    /// samples, using `bins` as scratch space.
+    ///
+    /// This undoes [`transform`](Fft::transform) but for the window: the
+    /// frame comes back tapered. It is left in 32 bits, so frames that
+    /// overlap can be added up before they are saturated back to samples.
+    /// The bins must be no larger than those of a frame of samples, as they
+    /// are after being scaled by gains of at most 1.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bins` holds fewer than `N/2 + 1` bins.
+    pub fn inverse(&self, bins: &mut [Bin], frame: &mut [i32; N]) {
+        let half = N / 2;
+        let bins = &mut bins[..=half];
+        // fraction bits the bins leave room for, doubled as they are by
+        // packing
+        let guard = FRAC - 3 - N.trailing_zeros();
+
+        // pack the even and odd samples' spectra back into one, the
+        // untangling run backwards: with X' = conj(X[half - k]),
+        //
+        //   2·E[k] = X[k] + X'    2·O[k] = W^-k·(X[k] - X')
+        //   Z[k] = E[k] + i·O[k]    Z[half - k] = conj(E[k]) + i·conj(O[k])
+        let (first, last) = (bins[0].re << guard, bins[half].re << guard);
+        bins[0] = Bin {
+            re: first + last,
+            im: first - last,
+        };
+        for k in 1..=half / 2 {
+            let x = Bin {
+                re: bins[k].re << guard,
+                im: bins[k].im << guard,
+            };
+            let mirror = Bin {
+                re: bins[half - k].re << guard,
+                im: -(bins[half - k].im << guard),
+            };
+            let even = x.add(mirror);
+            let (c, s) = self.twiddle(k);
+            let odd = twiddle(x.sub(mirror), c, -s);
+            bins[k] = Bin {
+                re: even.re - odd.im,
+                im: even.im + odd.re,
+            };
+            bins[half - k] = Bin {
+                re: even.re + odd.im,
+                im: odd.re - even.im,
+            };
+        }
+
+        let bits = half.trailing_zeros();
+        for i in 0..half {
+            let j = i.reverse_bits() >> (usize::BITS - bits);
+            if i < j {
+                bins.swap(i, j);
+            }
+        }
+        self.butterflies(&mut bins[..half], true);
+
+        // the butterflies took out the 1/half; the packing's 2 and the
+        // guard bits are left
+        for (pair, z) in frame.chunks_exact_mut(2).zip(&bins[..half]) {
+            let z = scale(*z, guard + 1);
+            pair[0] = z.re;
+            pair[1] = z.im;
+        }
+    }
+
+    /// Radix-2 decimation-in-time butterflies over `z`, in bit-reversed
+    /// order. The inverse transform turns the twiddles the other way and
+    /// halves every stage, which divides by the length.
+    fn butterflies(&self, z: &mut [Bin], inverse: bool) {
+        let mut len = 2;
+        while len <= z.len() {
+            let stride = N / len;
+            for block in z.chunks_exact_mut(len) {
+                let (lower, upper) = block.split_at_mut(len / 2);
+                for (k, (a, b)) in lower.iter_mut().zip(upper).enumerate() {
+                    let (c, s) = self.twiddle(k * stride);
+                    if inverse {
+                        let t = twiddle(*b, c, -s);
+                        *b = scale(a.sub(t), 1);
+                        *a = scale(a.add(t), 1);
+                    } else {
+                        let t = twiddle(*b, c, s);
+                        *b = a.sub(t);
+                        *a = a.add(t);
+                    }
+                }
+            }
+            len *= 2;
+        }
+    }
+}
+
+/// `x` shifted right by `shift`, rounded.
+fn scale(x: Bin, shift: u32) -> Bin {
+    if shift == 0 {
+        return x;
+    }
+    let round = 1 << (shift - 1);
+    Bin {
+        re: (x.re + round) >> shift,
+        im: (x.im + round) >> shift,
+    }
+}
+
+/// Cuts a stream of samples into frames of `N`, a new one every `hop`
+/// samples, each overlapping the last by `N - hop`.
+///
+/// A new `Frames` holds silence, so the first frame comes after `hop`
+/// samples and ends with them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frames<const N: usize> {
+    buf: [i16; N],
+    hop: usize,
+    /// Samples of the next frame taken so far.
+    count: usize,
+}
+
+impl<const N: usize> Frames<N> {
+    /// Create frames of `N` samples every `hop` samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hop` is zero or more than `N`.
+    pub const fn new(hop: usize) -> Self {
+        assert!(
+            hop > 0 && hop <= N,
+            "hop must be from 1 to the frame length"
+        );
+        Self {
+            buf: [0; N],
+            hop,
+            count: 0,
+        }
+    }
+
+    /// Samples between the starts of successive frames.
+    pub const fn hop(&self) -> usize {
+        self.hop
+    }
+
+    /// Take one sample, returning the frame it completes, oldest sample
+    /// first, if it does.
+    pub fn push(&mut self, sample: i16) -> Option<&[i16; N]> {
+        if self.count == 0 {
+            self.buf.copy_within(self.hop.., 0);
+        }
+        self.buf[N - self.hop + self.count] = sample;
+        self.count += 1;
+        if self.count < self.hop {
+            return None;
+        }
+        self.count = 0;
+        Some(&self.buf)
+    }
+
+    /// Forget the samples so far, going back to silence.
+    pub fn reset(&mut self) {
+        self.buf = [0; N];
+        self.count = 0;
+    }
+}
@@ -0,0 +1,101 @@
+//! Access to NOR flash, shaped like the `embedded-storage` `NorFlash` traits.
+//!
+//! NOR flash reads like memory, but writing is split in two: erasing sets
+//! every bit of a whole sector to 1, and programming can then only clear
+//! bits, a page at a time. Programming over bytes that were already
+//! programmed leaves each bit cleared if it was cleared either time, so a
+//! page can be programmed again as long as the new data only clears more
+//! bits; the clip store relies on this to fill in index entries piecemeal.
+
+/// Value of an erased byte.
+pub const ERASED: u8 = 0xff;
+
+/// Flash that can be read, erased a sector at a time and programmed.
+///
+/// Offsets are relative to the start of the flash.
+pub trait NorFlash {
+    /// Error returned when an operation fails.
+    type Error;
+
+    /// Programming granularity: offsets and lengths passed to
+    /// [`write`](Self::write) are multiples of it.
+    const WRITE_SIZE: usize;
+
+    /// Erase granularity: offsets passed to [`erase`](Self::erase) are
+    /// multiples of it.
+    const ERASE_SIZE: usize;
+
+    /// Size of the flash, in bytes.
+    fn capacity(&self) -> usize;
+
+    /// Read `bytes.len()` bytes starting at `offset`.
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Program `bytes` at `offset`, clearing the bits that are 0 in `bytes`.
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    /// Erase every sector from `from` up to, but not including, `to`.
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error>;
+}
+
+impl<T: NorFlash + ?Sized> NorFlash for &mut T {
+    type Error = T::Error;
+    const WRITE_SIZE: usize = T::WRITE_SIZE;
+    const ERASE_SIZE: usize = T::ERASE_SIZE;
+
+    fn capacity(&self) -> usize {
+        T::capacity(self)
+    }
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        T::read(self, offset, bytes)
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        T::write(self, offset, bytes)
+    }
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        T::erase(self, from, to)
+    }
+}
+
+/// An operation that breaks the granularity rules or falls outside the
+/// flash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// An offset or length is not a multiple of the granularity.
+    NotAligned,
+    /// The operation reaches past the end of the flash.
+    OutOfBounds,
+}
+
+/// Check that `len` bytes at `offset` fit into a flash of `capacity` bytes.
+pub fn check_read(capacity: usize, offset: u32, len: usize) -> Result<(), Error> {
+    match (offset as usize).checked_add(len) {
+        Some(end) if end <= capacity => Ok(()),
+        _ => Err(Error::OutOfBounds),
+    }
+}
+
+/// Check a [`NorFlash::write`] of `len` bytes at `offset` against the rules
+/// of `F`.
+pub fn check_write<F: NorFlash>(capacity: usize, offset: u32, len: usize) -> Result<(), Error> {
+    if !(offset as usize).is_multiple_of(F::WRITE_SIZE) || !len.is_multiple_of(F::WRITE_SIZE) {
+        return Err(Error::NotAligned);
+    }
+    check_read(capacity, offset, len)
+}
+
+/// Check a [`NorFlash::erase`] from `from` to `to` against the rules of `F`.
+pub fn check_erase<F: NorFlash>(capacity: usize, from: u32, to: u32) -> Result<(), Error> {
+    if from > to {
+        return Err(Error::OutOfBounds);
+    }
+    if !(from as usize).is_multiple_of(F::ERASE_SIZE)
+        || !(to as usize).is_multiple_of(F::ERASE_SIZE)
+    {
+        return Err(Error::NotAligned);
+    }
+    check_read(capacity, from, (to - from) as usize)
+}
@@ -0,0 +1,166 @@
+//! G.711 µ-law and A-law companding, the 8-bit formats of telephony.
+//!
+//! Both squeeze a linear sample into a sign, a 3-bit segment (roughly the
+//! exponent) and a 4-bit step within the segment, so quiet samples keep
+//! more precision than loud ones. µ-law covers 14 bits of linear range and
+//! A-law 13, more than the 12 the ADC produces, so ADC samples near
+//! mid-rail come back exactly and loud ones to within about 2% of full
+//! scale.
+//!
+//! The codes follow the ITU tables bit for bit, including the inversions the
+//! standard applies on the line: a silent µ-law channel is `0xff` and a
+//! silent A-law one `0xd5`. [`Law::encode`] and [`Law::decode`] work on
+//! 12-bit offset-binary samples, taking off the mid-rail bias on the way in
+//! and putting it back on the way out.
+
+use crate::{from_pcm16, to_pcm16, SAMPLE_MAX};
+
+// code layout
+const SIGN: u8 = 0x80;
+const SEGMENT: u8 = 0x70;
+const STEP: u8 = 0x0f;
+
+/// Bias µ-law adds so that segment boundaries fall on powers of two.
+const ULAW_BIAS: i32 = 0x84;
+
+/// Largest 14-bit magnitude µ-law encodes.
+const ULAW_CLIP: i32 = 8159;
+
+/// Upper ends of the µ-law segments, on the biased 14-bit magnitude.
+const ULAW_SEGMENTS: [i32; 8] = [0x3f, 0x7f, 0xff, 0x1ff, 0x3ff, 0x7ff, 0xfff, 0x1fff];
+
+/// Upper ends of the A-law segments, on the 13-bit magnitude.
+const ALAW_SEGMENTS: [i32; 8] = [0x1f, 0x3f, 0x7f, 0xff, 0x1ff, 0x3ff, 0x7ff, 0xfff];
+
+/// The segment `magnitude` falls into, or 8 past the last one.
+fn segment(magnitude: i32, ends: &[i32; 8]) -> u8 {
+    ends.iter().position(|&end| magnitude <= end).unwrap_or(8) as u8
+}
+
+/// Encode a signed 16-bit sample as µ-law.
+pub fn ulaw_encode(sample: i16) -> u8 {
+    let mut magnitude = i32::from(sample) >> 2;
+    let mask = if magnitude < 0 {
+        magnitude = -magnitude;
+        0x7f
+    } else {
+        0xff
+    };
+    let magnitude = magnitude.min(ULAW_CLIP) + (ULAW_BIAS >> 2);
+    let seg = segment(magnitude, &ULAW_SEGMENTS);
+    if seg == 8 {
+        return 0x7f ^ mask;
+    }
+    let step = (magnitude >> (seg + 1)) as u8 & STEP;
+    ((seg << 4) | step) ^ mask
+}
+
+/// Decode a µ-law code to a signed 16-bit sample.
+pub fn ulaw_decode(code: u8) -> i16 {
+    let code = !code;
+    let seg = (code & SEGMENT) >> 4;
+    let t = ((i32::from(code & STEP) << 3) + ULAW_BIAS) << seg;
+    if code & SIGN != 0 {
+        (ULAW_BIAS - t) as i16
+    } else {
+        (t - ULAW_BIAS) as i16
+    }
+}
+
+/// Encode a signed 16-bit sample as A-law.
+pub fn alaw_encode(sample: i16) -> u8 {
+    let mut magnitude = i32::from(sample) >> 3;
+    let mask = if magnitude >= 0 {
+        0xd5
+    } else {
+        // one's complement, so that -1 lands next to 0
+        magnitude = -magnitude - 1;
+        0x55
+    };
+    let seg = segment(magnitude, &ALAW_SEGMENTS);
+    if seg == 8 {
+        return 0x7f ^ mask;
+    }
+    let shift = if seg < 2 { 1 } else { seg };
+    let step = (magnitude >> shift) as u8 & STEP;
+    ((seg << 4) | step) ^ mask
+}
+
+/// Decode an A-law code to a signed 16-bit sample.
+pub fn alaw_decode(code: u8) -> i16 {
+    let code = code ^ 0x55;
+    let seg = (code & SEGMENT) >> 4;
+    let mut t = i32::from(code & STEP) << 4;
+    t = match seg {
+        0 => t + 8,
+        1 => t + 0x108,
+        _ => (t + 0x108) << (seg - 1),
+    };
+    if code & SIGN != 0 {
+        t as i16
+    } else {
+        -t as i16
+    }
+}
+
+/// One of the two G.711 companding laws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Law {
+    /// µ-law, used in North America and Japan.
+    MuLaw,
+    /// A-law, used everywhere else.
+    ALaw,
+}
+
+impl Law {
+    /// WAV format tag of the encoding.
+    pub const fn tag(self) -> u16 {
+        match self {
+            Law::MuLaw => 0x0007,
+            Law::ALaw => 0x0006,
+        }
+    }
+
+    /// Encode a 12-bit offset-binary sample.
+    pub fn encode(self, sample: u16) -> u8 {
+        match self {
+            Law::MuLaw => ulaw_encode(to_pcm16(sample)),
+            Law::ALaw => alaw_encode(to_pcm16(sample)),
+        }
+    }
+
+    /// Decode a code to a 12-bit offset-binary sample.
+    ///
+    /// Decoded samples sit in the middle of their steps. µ-law steps are
+    /// symmetric about zero, so its samples are rounded to the nearest
+    /// 12-bit value; A-law steps are symmetric about half an ADC step below
+    /// zero, which truncating the low bits matches.
+    pub fn decode(self, code: u8) -> u16 {
+        match self {
+            Law::MuLaw => {
+                let sample = i32::from(ulaw_decode(code));
+                let rounded = if sample < 0 {
+                    -((8 - sample) >> 4)
+                } else {
+                    (sample + 8) >> 4
+                };
+                (rounded + 2048).min(i32::from(SAMPLE_MAX)) as u16
+            }
+            Law::ALaw => from_pcm16(alaw_decode(code)),
+        }
+    }
+
+    /// Encode every sample of `samples` into `codes`, as far as both go.
+    pub fn encode_block(self, samples: &[u16], codes: &mut [u8]) {
+        for (code, &sample) in codes.iter_mut().zip(samples) {
+            *code = self.encode(sample);
+        }
+    }
+
+    /// Decode every code of `codes` into `samples`, as far as both go.
+    pub fn decode_block(self, codes: &[u8], samples: &mut [u16]) {
+        for (sample, &code) in samples.iter_mut().zip(codes) {
+            *sample = self.decode(code);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! [`AudioSource`] and [`AudioSink`] implementations backed by WAV files, for
+//! running the signal chain on a development machine.
+//!
+//! WAV samples are signed and centered on zero, while the chain works on
+//! 12-bit offset-binary samples like the ADC produces; the conversion keeps
+//! the 12 most significant bits. Files in the encodings `hound` does not
+//! read, µ-law, A-law and IMA ADPCM, are decoded by the [`wav`](crate::wav)
+//! module instead.
+
+use std::convert::Infallible;
+use std::fs::File;
+use std::io::{BufWriter, Cursor};
+use std::path::Path;
+
+use crate::wav::{self, WavReader};
+use crate::{AudioSink, AudioSource, SampleRate};
+
+/// Errors from the WAV backed source and sink.
+#[derive(Debug)]
+pub enum Error {
+    /// The WAV file could not be read or written.
+    Wav(hound::Error),
+    /// The compressed WAV file could not be decoded.
+    Compressed(wav::Error<Infallible>),
+    /// The source has no samples left.
+    EndOfStream,
+}
+
+impl From<hound::Error> for Error {
+    fn from(e: hound::Error) -> Self {
+        Error::Wav(e)
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Wav(e) => write!(f, "{e}"),
+            Error::Compressed(e) => write!(f, "{e:?}"),
+            Error::EndOfStream => f.write_str("end of stream"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Convert a signed sample of `bits` bits to a 12-bit offset-binary sample.
+fn to_sample(value: i32, bits: u16) -> u16 {
+    let centered = if bits >= 12 {
+        value >> (bits - 12)
+    } else {
+        value << (12 - bits)
+    };
+    (centered + 2048).clamp(0, 4095) as u16
+}
+
+/// An [`AudioSource`] that plays back the first channel of a WAV file.
+///
+/// The whole file is decoded up front, so every remaining sample is always
+/// [`available`](AudioSource::available).
+#[derive(Debug, Clone)]
+pub struct WavSource {
+    samples: Vec<u16>,
+    pos: usize,
+    sample_rate: u32,
+}
+
+impl WavSource {
+    /// Open and decode a WAV file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let bytes = std::fs::read(path).map_err(hound::Error::IoError)?;
+        let reader = match hound::WavReader::new(Cursor::new(&bytes)) {
+            Err(hound::Error::Unsupported) => return Self::decode(&bytes),
+            reader => reader?,
+        };
+        let spec = reader.spec();
+        let channels = usize::from(spec.channels.max(1));
+        let samples = match spec.sample_format {
+            hound::SampleFormat::Int => reader
+                .into_samples::<i32>()
+                .step_by(channels)
+                .map(|s| s.map(|v| to_sample(v, spec.bits_per_sample)))
+                .collect::<Result<_, _>>()?,
+            hound::SampleFormat::Float => reader
+                .into_samples::<f32>()
+                .step_by(channels)
+                .map(|s| s.map(|v| to_sample((v * 32768.0) as i32, 16)))
+                .collect::<Result<_, _>>()?,
+        };
+        Ok(Self::from_samples(samples, spec.sample_rate))
+    }
+
+    /// Decode a file `hound` does not handle.
+    fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = WavReader::new(bytes).map_err(Error::Compressed)?;
+        let mut samples = vec![0; reader.len() as usize];
+        let n = reader
+            .read_samples(&mut samples)
+            .map_err(Error::Compressed)?;
+        samples.truncate(n);
+        Ok(Self::from_samples(samples, reader.sample_rate()))
+    }
+
+    /// Wrap already decoded 12-bit samples.
+    pub fn from_samples(samples: Vec<u16>, sample_rate: u32) -> Self {
+        Self {
+            samples,
+            pos: 0,
+            sample_rate,
+        }
+    }
+
+    /// Sample rate recorded in the file, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// All samples of the file, including the ones already read.
+    pub fn samples(&self) -> &[u16] {
+        &self.samples
+    }
+
+    /// Whether every sample has been read.
+    pub fn is_finished(&self) -> bool {
+        self.pos >= self.samples.len()
+    }
+}
+
+impl AudioSource for WavSource {
+    type Error = Error;
+
+    fn available(&mut self) -> usize {
+        self.samples.len() - self.pos
+    }
+
+    fn read(&mut self) -> Result<u16, Error> {
+        let sample = *self.samples.get(self.pos).ok_or(Error::EndOfStream)?;
+        self.pos += 1;
+        Ok(sample)
+    }
+}
+
+/// An [`AudioSink`] that writes a mono 16-bit WAV file.
+///
+/// Call [`finalize`](WavSink::finalize) when done; dropping the sink also
+/// finalizes the file but ignores any error.
+pub struct WavSink {
+    writer: hound::WavWriter<BufWriter<File>>,
+}
+
+impl WavSink {
+    /// Create (or truncate) a WAV file at `path`.
+    pub fn create<P: AsRef<Path>>(path: P, sample_rate: SampleRate) -> Result<Self, Error> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: sample_rate.hz(),
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        Ok(Self {
+            writer: hound::WavWriter::create(path, spec)?,
+        })
+    }
+
+    /// Flush the samples and fix up the WAV header.
+    pub fn finalize(self) -> Result<(), Error> {
+        Ok(self.writer.finalize()?)
+    }
+}
+
+impl AudioSink for WavSink {
+    type Error = Error;
+
+    fn write(&mut self, sample: u16) -> Result<(), Error> {
+        Ok(self.writer.write_sample(crate::to_pcm16(sample))?)
+    }
+}
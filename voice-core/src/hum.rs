@@ -0,0 +1,298 @@
+//! Mains hum removal, for a microphone picking up 50 or 60 Hz.
+//!
+//! A [`HumRemover`] listens for hum at both mains frequencies, and once it
+//! has heard one clearly, runs the signal through a cascade of narrow
+//! notches at it and its first few harmonics. Until then, or once the hum
+//! goes away again, it leaves the signal alone.
+//!
+//! Detection measures the power every 10 Hz from 30 to 80 Hz with a
+//! Goertzel filter each, over blocks a whole number of tenths of a second
+//! long. Every one of those frequencies goes a whole number of cycles into
+//! such a block, so none leaks into the others' measurements, and a mains
+//! frequency standing well clear of its neighbours within 20 Hz is hum
+//! rather than the broadband background, which puts about as much into
+//! each. Only the fundamentals are looked at: the harmonics share their
+//! range with the pitch of voices, but nobody speaks as low as 60 Hz. A
+//! frequency has to win a few blocks running before the notches move to
+//! it, so a moment of something else there doesn't switch them back and
+//! forth.
+//!
+//! # Cost
+//!
+//! Each sample costs six Goertzel steps, one 64-bit multiply apiece, and
+//! once hum has been found, four Q31 biquads. Redesigning the notches when
+//! the detection changes goes through the soft-float design code, but that
+//! only happens a block or so after the mains changes.
+
+use crate::biquad::{Cascade, Design, Q31};
+use crate::math;
+use crate::process::Processor;
+use crate::SampleRate;
+
+/// Number of notches: the fundamental and the harmonics after it.
+pub const HARMONICS: usize = 4;
+
+/// Fraction bits of the Goertzel coefficients. Close to 0 Hz the cosine
+/// hardly changes with frequency, so it needs plenty of them to put the
+/// filter within a fraction of a hertz.
+const COEF_FRAC: u32 = 28;
+
+/// Longest detection block. A resonator at `ω` driven at amplitude `A` for
+/// `N` samples reaches at most `A·N/sin ω`, which for 30 Hz at 44.1 kHz
+/// and a block this long is under 2³⁸: enough headroom for its product
+/// with the distance of the coefficient from two, at most 2²⁰ in Q28 up to
+/// 80 Hz at 8 kHz, to fit in 64 bits.
+const MAX_BLOCK: u32 = 1 << 15;
+
+/// How far the Goertzel state is shifted down before squaring, so the
+/// power fits in 64 bits.
+const POWER_SHIFT: u32 = 8;
+
+/// Frequencies measured for detection, in Hz: both mains frequencies and
+/// their neighbours.
+const PROBES: [u32; 6] = [30, 40, 50, 60, 70, 80];
+
+/// A mains frequency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mains {
+    /// 50 Hz, as in most of the world.
+    Hz50,
+    /// 60 Hz, as in the Americas.
+    Hz60,
+}
+
+impl Mains {
+    /// The frequency in Hz.
+    pub const fn hz(self) -> u32 {
+        match self {
+            Mains::Hz50 => 50,
+            Mains::Hz60 => 60,
+        }
+    }
+}
+
+/// Settings for a [`HumRemover`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Width of each notch, in Hz. Wider notches take out more of the
+    /// signal around the hum, but tolerate mains running off its nominal
+    /// frequency.
+    pub bandwidth_hz: u32,
+    /// Length of a detection block, in tenths of a second.
+    pub block_tenths: u32,
+    /// How many blocks running a frequency has to win, or no hum has to be
+    /// heard, before the notches follow.
+    pub confirm_blocks: u32,
+    /// How far the hum has to stand above the background at the
+    /// frequencies around it, in dB.
+    pub margin_db: u32,
+    /// Quietest hum worth removing, as the amplitude of the fundamental in
+    /// dB relative to full scale.
+    pub min_level_dbfs: i32,
+}
+
+impl Default for Config {
+    /// Settings for hum picked up on the bench.
+    fn default() -> Self {
+        Self {
+            bandwidth_hz: 4,
+            block_tenths: 5,
+            confirm_blocks: 2,
+            margin_db: 10,
+            min_level_dbfs: -66,
+        }
+    }
+}
+
+/// Power at one frequency over a block, by the Goertzel algorithm: a
+/// resonator at the frequency, whose state at the end of the block gives
+/// the power of that one DFT bin.
+///
+/// The resonator's coefficient `2·cos(ω)` is kept as its distance from
+/// two, `4·sin²(ω/2)`, which is small at mains frequencies: the feedback
+/// `2·s - d·s` then needs only a multiply by a 20-bit number, however large
+/// the state grows over a long block of loud hum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Goertzel {
+    /// `2 - 2·cos(ω)`, in Q28.
+    distance: i64,
+    s: [i64; 2],
+}
+
+impl Goertzel {
+    const fn new(hz: f64, rate: SampleRate) -> Self {
+        let half = math::sin(math::omega(hz, rate.hz() as f64) / 2.0);
+        Self {
+            distance: math::round(4.0 * half * half * (1u32 << COEF_FRAC) as f64),
+            s: [0; 2],
+        }
+    }
+
+    /// `2·cos(ω)·s`.
+    fn feedback(&self, s: i64) -> i64 {
+        2 * s - ((self.distance * s) >> COEF_FRAC)
+    }
+
+    fn push(&mut self, sample: i16) {
+        let s = i64::from(sample) + self.feedback(self.s[0]) - self.s[1];
+        self.s = [s, self.s[0]];
+    }
+
+    /// Power of the block so far, `|X|²` shifted down by
+    /// `2·POWER_SHIFT`, and start the next.
+    fn finish(&mut self) -> u64 {
+        let [s1, s2] = self.s.map(|s| s >> POWER_SHIFT);
+        self.s = [0; 2];
+        let cross = self.feedback(s1) * s2;
+        (s1 * s1 + s2 * s2 - cross).max(0) as u64
+    }
+}
+
+/// A notch at `harmonic` times `mains`, `bandwidth_hz` wide.
+const fn notch(mains: Mains, harmonic: u32, bandwidth_hz: u32, rate: SampleRate) -> Q31 {
+    let hz = (mains.hz() * harmonic) as f64;
+    Design::notch(rate, hz, hz / bandwidth_hz as f64).q31()
+}
+
+/// Notches at `mains` and its harmonics.
+const fn notches(mains: Mains, bandwidth_hz: u32, rate: SampleRate) -> Cascade<Q31, HARMONICS> {
+    Cascade::new([
+        notch(mains, 1, bandwidth_hz, rate),
+        notch(mains, 2, bandwidth_hz, rate),
+        notch(mains, 3, bandwidth_hz, rate),
+        notch(mains, 4, bandwidth_hz, rate),
+    ])
+}
+
+/// Detects 50 or 60 Hz hum in signed 16-bit samples and notches it out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HumRemover {
+    rate: SampleRate,
+    bandwidth_hz: u32,
+    /// Samples per block.
+    block: u32,
+    confirm: u32,
+    /// How many times the background's power the hum's must be.
+    margin: u64,
+    /// Least power the hum must have, on the Goertzel scale.
+    min_power: u64,
+
+    /// One for each of `PROBES`.
+    probes: [Goertzel; PROBES.len()],
+    /// Samples so far in this block.
+    count: u32,
+    /// What the last blocks heard, and how many running.
+    heard: Option<Mains>,
+    runs: u32,
+    detected: Option<Mains>,
+    notches: Cascade<Q31, HARMONICS>,
+}
+
+impl HumRemover {
+    /// Create a hum remover for signals at `rate`, which hasn't detected
+    /// any hum yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a block is empty or longer than 2¹⁵ samples, or the
+    /// bandwidth is zero.
+    pub const fn new(config: &Config, rate: SampleRate) -> Self {
+        let block = config.block_tenths * rate.hz() / 10;
+        assert!(
+            block > 0 && block <= MAX_BLOCK,
+            "detection block must be from 1 to 32768 samples"
+        );
+        assert!(config.bandwidth_hz > 0, "notches need a bandwidth");
+        // a sine of amplitude A comes out of the Goertzel filter with a
+        // magnitude of A·N/2
+        let amplitude = 32767.0 * math::db_to_gain(config.min_level_dbfs as f64);
+        let magnitude = amplitude * block as f64 / 2.0 / (1 << POWER_SHIFT) as f64;
+        Self {
+            rate,
+            bandwidth_hz: config.bandwidth_hz,
+            block,
+            confirm: config.confirm_blocks,
+            margin: (math::db_to_gain(2.0 * config.margin_db as f64) + 0.5) as u64,
+            min_power: (magnitude * magnitude) as u64,
+            probes: [
+                Goertzel::new(PROBES[0] as f64, rate),
+                Goertzel::new(PROBES[1] as f64, rate),
+                Goertzel::new(PROBES[2] as f64, rate),
+                Goertzel::new(PROBES[3] as f64, rate),
+                Goertzel::new(PROBES[4] as f64, rate),
+                Goertzel::new(PROBES[5] as f64, rate),
+            ],
+            count: 0,
+            heard: None,
+            runs: 0,
+            detected: None,
+            notches: notches(Mains::Hz50, config.bandwidth_hz, rate),
+        }
+    }
+
+    /// The mains frequency whose hum is being removed, if any.
+    pub fn detected(&self) -> Option<Mains> {
+        self.detected
+    }
+
+    /// Judge a finished block.
+    fn block(&mut self) {
+        let power = self.probes.each_mut().map(Goertzel::finish);
+        let stands_out = |mains: Mains| {
+            let hz = mains.hz();
+            let at = |probe: usize| power[probe];
+            let hum = (0..PROBES.len()).find(|&p| PROBES[p] == hz).map_or(0, at);
+            let neighbours = (0..PROBES.len())
+                .filter(|&p| PROBES[p] != hz && PROBES[p].abs_diff(hz) <= 20)
+                .map(at)
+                .max()
+                .unwrap_or(0);
+            hum >= self.min_power && hum > neighbours.saturating_mul(self.margin)
+        };
+        let heard = [Mains::Hz50, Mains::Hz60]
+            .into_iter()
+            .find(|&m| stands_out(m));
+
+        if heard == self.heard {
+            self.runs = self.runs.saturating_add(1);
+        } else {
+            self.heard = heard;
+            self.runs = 1;
+        }
+        if self.runs >= self.confirm && heard != self.detected {
+            self.detected = heard;
+            if let Some(mains) = heard {
+                self.notches = notches(mains, self.bandwidth_hz, self.rate);
+            }
+        }
+    }
+}
+
+impl Processor for HumRemover {
+    fn process(&mut self, sample: i16) -> i16 {
+        for probe in &mut self.probes {
+            probe.push(sample);
+        }
+        self.count += 1;
+        if self.count == self.block {
+            self.count = 0;
+            self.block();
+        }
+        if self.detected.is_some() {
+            self.notches.process(sample)
+        } else {
+            sample
+        }
+    }
+
+    fn reset(&mut self) {
+        for probe in &mut self.probes {
+            probe.s = [0; 2];
+        }
+        self.count = 0;
+        self.heard = None;
+        self.runs = 0;
+        self.detected = None;
+        self.notches.reset();
+    }
+}
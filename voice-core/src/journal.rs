@@ -0,0 +1,91 @@
+//! Page-sized chunks of clip data that survive power loss.
+//!
+//! The clip store programs clip data a page at a time, and each page is a
+//! chunk: up to [`PAYLOAD`] bytes of data followed by a trailer naming the
+//! recording it belongs to, its position in that recording, its length and a
+//! CRC. The chunk is programmed first with its commit marker still erased;
+//! only once that has completed is the marker programmed on its own. A chunk
+//! counts only if its marker is set and its CRC matches, so a page that was
+//! being programmed when power was lost is never mistaken for data, however
+//! far the program got.
+//!
+//! A recording that was cut off therefore ends at its last valid chunk: the
+//! store finds its length by walking chunks from the start until one is
+//! missing, torn, or belongs to another recording.
+
+use crate::flash::ERASED;
+use crate::store::PAGE_SIZE;
+
+/// Bytes of clip data in one chunk.
+pub const PAYLOAD: usize = PAGE_SIZE - 16;
+
+// trailer layout
+const TAG: usize = PAYLOAD;
+const SEQ: usize = PAYLOAD + 8;
+const LEN: usize = PAYLOAD + 10;
+const COMMIT: usize = PAYLOAD + 11;
+const CRC: usize = PAYLOAD + 12;
+
+/// Value of the commit marker once the chunk is complete.
+const COMMITTED: u8 = 0x00;
+
+/// CRC-32 (IEEE 802.3, as used by zip and PNG) of `bytes`.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Fill in the trailer of a chunk holding `len` bytes of data, as chunk
+/// `seq` of the recording tagged `tag`.
+///
+/// Pads the payload with erased bytes and leaves the commit marker erased.
+pub fn seal(page: &mut [u8; PAGE_SIZE], tag: u64, seq: u16, len: usize) {
+    assert!(len <= PAYLOAD, "chunk payload too long");
+    page[len..PAYLOAD].fill(ERASED);
+    page[TAG..SEQ].copy_from_slice(&tag.to_le_bytes());
+    page[SEQ..LEN].copy_from_slice(&seq.to_le_bytes());
+    page[LEN] = len as u8;
+    page[COMMIT] = ERASED;
+    let crc = crc32(&page[..COMMIT]);
+    page[CRC..].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// A page that only programs the commit marker of the chunk it is
+/// programmed over.
+pub fn commit_marker() -> [u8; PAGE_SIZE] {
+    let mut page = [ERASED; PAGE_SIZE];
+    page[COMMIT] = COMMITTED;
+    page
+}
+
+/// Trailer of a valid chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    /// Tag of the recording the chunk was written by.
+    pub tag: u64,
+    /// Position of the chunk in the recording.
+    pub seq: u16,
+    /// Bytes of data in the chunk.
+    pub len: usize,
+}
+
+/// The trailer of `page`, if it is a committed chunk with a matching CRC.
+pub fn open(page: &[u8; PAGE_SIZE]) -> Option<Chunk> {
+    let crc = u32::from_le_bytes(page[CRC..].try_into().unwrap());
+    let len = usize::from(page[LEN]);
+    if page[COMMIT] != COMMITTED || len > PAYLOAD || crc32(&page[..COMMIT]) != crc {
+        return None;
+    }
+    Some(Chunk {
+        tag: u64::from_le_bytes(page[TAG..SEQ].try_into().unwrap()),
+        seq: u16::from_le_bytes([page[SEQ], page[SEQ + 1]]),
+        len,
+    })
+}
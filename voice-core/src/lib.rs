@@ -0,0 +1,150 @@
+//! # voice-core
+//!
+//! The hardware-independent part of the voice tool: the traits that connect
+//! the signal chain to the outside world, and the processing that sits
+//! between them.
+//!
+//! Processing works on signed 16-bit samples: a [`process::Chain`] takes the
+//! ADC stream through a [`dc::DcBlocker`] into that form and on through its
+//! [`process::Processor`] stages, such as the fixed-point filters in
+//! [`biquad`].
+//!
+//! Samples cross the [`AudioSource`] / [`AudioSink`] boundary as 12-bit
+//! offset-binary values (0..=4095, mid-rail at 2048), which is what the
+//! RP2040 ADC produces. The firmware implements these traits on top of DMA
+//! capture from the ADC FIFO and DMA-paced PWM playback; with the `std`
+//! feature enabled, the [`host`] module implements them on top of WAV files
+//! so the whole chain can run on a development machine.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub mod adc;
+pub mod adpcm;
+pub mod agc;
+pub mod biquad;
+pub mod capture;
+pub mod compress;
+pub mod dc;
+pub mod decimate;
+pub mod denoise;
+pub mod effects;
+pub mod fft;
+pub mod flash;
+pub mod g711;
+#[cfg(feature = "std")]
+pub mod host;
+pub mod hum;
+pub mod journal;
+pub mod limit;
+pub mod math;
+pub mod pipeline;
+pub mod pitch;
+pub mod playback;
+pub mod player;
+pub mod process;
+pub mod pwm;
+pub mod rate;
+pub mod resample;
+pub mod store;
+pub mod stretch;
+pub mod vad;
+pub mod vox;
+pub mod wav;
+pub mod window;
+
+pub use pipeline::Pipeline;
+pub use rate::SampleRate;
+pub use window::Window;
+
+/// Largest value a 12-bit sample can take.
+pub const SAMPLE_MAX: u16 = 4095;
+
+/// Convert a 12-bit offset-binary sample to a signed 16-bit sample centered
+/// on zero.
+pub const fn to_pcm16(sample: u16) -> i16 {
+    let sample = if sample > SAMPLE_MAX {
+        SAMPLE_MAX
+    } else {
+        sample
+    };
+    ((sample as i16) - 2048) << 4
+}
+
+/// Convert a signed 16-bit sample to a 12-bit offset-binary sample, keeping
+/// the 12 most significant bits.
+pub const fn from_pcm16(sample: i16) -> u16 {
+    ((sample >> 4) + 2048) as u16
+}
+
+/// Something that produces 12-bit samples, such as the ADC FIFO.
+pub trait AudioSource {
+    /// Error returned when reading fails.
+    type Error;
+
+    /// Number of samples that can be read right now without blocking.
+    fn available(&mut self) -> usize;
+
+    /// Read the next sample.
+    fn read(&mut self) -> Result<u16, Self::Error>;
+
+    /// Read up to `buf.len()` of the samples available right now, returning
+    /// how many were read.
+    ///
+    /// Sources that produce samples in blocks should override this to copy
+    /// them out in one go.
+    fn read_block(&mut self, buf: &mut [u16]) -> Result<usize, Self::Error> {
+        let n = self.available().min(buf.len());
+        for sample in &mut buf[..n] {
+            *sample = self.read()?;
+        }
+        Ok(n)
+    }
+}
+
+/// Something that consumes 12-bit samples, such as a PWM output.
+pub trait AudioSink {
+    /// Error returned when writing fails.
+    type Error;
+
+    /// Output one sample.
+    fn write(&mut self, sample: u16) -> Result<(), Self::Error>;
+
+    /// Output every sample in `samples`.
+    ///
+    /// Sinks that consume samples in blocks should override this to copy
+    /// them in one go.
+    fn write_block(&mut self, samples: &[u16]) -> Result<(), Self::Error> {
+        for &sample in samples {
+            self.write(sample)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: AudioSource + ?Sized> AudioSource for &mut T {
+    type Error = T::Error;
+
+    fn available(&mut self) -> usize {
+        T::available(self)
+    }
+
+    fn read(&mut self) -> Result<u16, Self::Error> {
+        T::read(self)
+    }
+
+    fn read_block(&mut self, buf: &mut [u16]) -> Result<usize, Self::Error> {
+        T::read_block(self, buf)
+    }
+}
+
+impl<T: AudioSink + ?Sized> AudioSink for &mut T {
+    type Error = T::Error;
+
+    fn write(&mut self, sample: u16) -> Result<(), Self::Error> {
+        T::write(self, sample)
+    }
+
+    fn write_block(&mut self, samples: &[u16]) -> Result<(), Self::Error> {
+        T::write_block(self, samples)
+    }
+}
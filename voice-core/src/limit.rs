@@ -0,0 +1,153 @@
+//! Brick-wall peak limiting: a ceiling the output never goes over.
+//!
+//! Clipping a loud peak is harsh, and scaling the whole signal down so the
+//! loudest peak fits leaves everything else too quiet. A [`Limiter`] turns
+//! the gain down just around the peaks that would go over its ceiling. It
+//! delays the signal so it can see them coming, and brings the gain down
+//! smoothly over the samples before each one, reaching exactly what the
+//! peak needs as it comes out. Afterwards the gain recovers over the
+//! release time.
+//!
+//! The gain each sample would need to come out at the ceiling is held at
+//! its minimum over the look-ahead window, and that is averaged over the
+//! window again. Every sample going into the average is at most what the
+//! peak needs, so the average is too, and the ceiling holds exactly; and an
+//! average of a step is a ramp, so the gain slides down rather than jumping.
+//!
+//! # Cost
+//!
+//! The minimum is found by scanning the window, so each sample costs a
+//! pass over `N` gains. A few milliseconds of look-ahead is plenty for
+//! speech.
+
+use crate::compress::{self, COEF_FRAC};
+use crate::math;
+use crate::process::Processor;
+use crate::SampleRate;
+
+/// Fraction bits of the gain, which goes from 0 to 1.
+const GAIN_FRAC: u32 = 15;
+
+/// Unity gain.
+const UNITY: u32 = 1 << GAIN_FRAC;
+
+/// Settings for a [`Limiter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Highest peak level let out, in dB relative to full scale.
+    pub ceiling_dbfs: i32,
+    /// Time constant of the gain going back up after a peak, in ms.
+    pub release_ms: u32,
+}
+
+impl Default for Config {
+    /// Settings for keeping speech off the rails of the PWM output and the
+    /// encoders.
+    fn default() -> Self {
+        Self {
+            ceiling_dbfs: -1,
+            release_ms: 60,
+        }
+    }
+}
+
+/// Look-ahead peak limiter on signed 16-bit samples.
+///
+/// `N` is the length of the look-ahead window: the gain ramps down over `N`
+/// samples, and the output is delayed by `N - 1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limiter<const N: usize> {
+    /// Highest magnitude let out.
+    ceiling: u32,
+    /// Release coefficient, in Q16.
+    release: u32,
+    /// The input, waiting to come out.
+    delay: [i16; N],
+    /// Gain each sample in the window needs, in Q15.
+    needed: [u32; N],
+    /// Minimum of `needed` as each sample came in, in Q15.
+    held: [u32; N],
+    /// Sum of `held`.
+    sum: u32,
+    /// Where the next sample goes in all three.
+    pos: usize,
+    /// Gain applied, in Q15.
+    gain: u32,
+}
+
+impl<const N: usize> Limiter<N> {
+    /// Create a limiter for signals at `rate`, with nothing yet in its
+    /// window.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ceiling is above full scale or below -40 dBFS, or the
+    /// window is empty or longer than 4096 samples.
+    pub const fn new(config: &Config, rate: SampleRate) -> Self {
+        assert!(N >= 1 && N <= 4096, "window must be 1 to 4096 samples");
+        assert!(
+            config.ceiling_dbfs <= 0 && config.ceiling_dbfs >= -40,
+            "ceiling must be between -40 and 0 dBFS"
+        );
+        let release = compress::coefficient(config.release_ms, rate.hz() as f64);
+        Self {
+            ceiling: (32767.0 * math::db_to_gain(config.ceiling_dbfs as f64)) as u32,
+            release,
+            delay: [0; N],
+            needed: [UNITY; N],
+            held: [UNITY; N],
+            sum: UNITY * N as u32,
+            pos: 0,
+            gain: UNITY,
+        }
+    }
+
+    /// How many samples the output lags the input.
+    pub const fn latency(&self) -> usize {
+        N - 1
+    }
+
+    /// The gain being applied, in 1/32768ths.
+    pub fn gain(&self) -> u32 {
+        self.gain
+    }
+}
+
+impl<const N: usize> Processor for Limiter<N> {
+    fn process(&mut self, sample: i16) -> i16 {
+        let magnitude = u32::from(sample.unsigned_abs());
+        self.needed[self.pos] = if magnitude > self.ceiling {
+            (self.ceiling << GAIN_FRAC) / magnitude
+        } else {
+            UNITY
+        };
+        let least = self.needed.iter().copied().min().unwrap_or(UNITY);
+        self.sum = self.sum - self.held[self.pos] + least;
+        self.held[self.pos] = least;
+        // rounding down keeps the average at or under every gain in it
+        let target = self.sum / N as u32;
+
+        if target < self.gain {
+            self.gain = target;
+        } else {
+            let step = u64::from(target - self.gain) * u64::from(self.release);
+            self.gain += step.div_ceil(1 << COEF_FRAC) as u32;
+        }
+
+        self.delay[self.pos] = sample;
+        self.pos = (self.pos + 1) % N;
+        // the oldest sample, which went in N - 1 samples ago; dividing
+        // rounds towards zero, so negative peaks stay under the ceiling too
+        let out = self.delay[self.pos];
+        (i32::from(out) * self.gain as i32 / UNITY as i32) as i16
+    }
+
+    fn reset(&mut self) {
+        self.delay = [0; N];
+        self.needed = [UNITY; N];
+        self.held = [UNITY; N];
+        self.sum = UNITY * N as u32;
+        self.pos = 0;
+        self.gain = UNITY;
+    }
+}
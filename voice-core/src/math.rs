@@ -0,0 +1,251 @@
+//! Floating-point functions for designing filters, usable in `const`
+//! context.
+//!
+//! `core` has no `sin`, `sqrt`, `exp` or `ln`, and the M0+ has no FPU to
+//! run them quickly anyway, so designs are worked out in `f64` by these
+//! `const fn`s: at compile time when the parameters are constants, or once
+//! when a stage is configured otherwise. The hot paths only ever see the
+//! fixed-point results. Each is accurate to within a few ulps over the
+//! ranges filter design needs.
+//!
+//! The stages that need logarithms while running, to work in decibels or
+//! cents, have [`log2_q16`] and [`exp2_q16`] instead: fixed point, from
+//! tables of 33 entries with linear interpolation between them, good to
+//! about 1/4000 of an octave.
+
+use core::f64::consts::{FRAC_PI_2, LN_2, PI, SQRT_2};
+
+/// `ln(10)`.
+const LN_10: f64 = core::f64::consts::LN_10;
+
+/// Round to the nearest integer, halves away from zero.
+pub(crate) const fn round(x: f64) -> i64 {
+    if x < 0.0 {
+        -((0.5 - x) as i64)
+    } else {
+        (x + 0.5) as i64
+    }
+}
+
+/// `|x|`.
+pub(crate) const fn abs(x: f64) -> f64 {
+    if x < 0.0 {
+        -x
+    } else {
+        x
+    }
+}
+
+/// `x` limited to the range of an `i16`.
+pub(crate) const fn clamp_i16(x: i64) -> i16 {
+    if x > i16::MAX as i64 {
+        i16::MAX
+    } else if x < i16::MIN as i64 {
+        i16::MIN
+    } else {
+        x as i16
+    }
+}
+
+/// Sine and cosine of `x`, in radians.
+pub const fn sin_cos(x: f64) -> (f64, f64) {
+    // reduce to within π/4 of a multiple of π/2
+    let quadrant = round(x / FRAC_PI_2);
+    let r = x - quadrant as f64 * FRAC_PI_2;
+    let r2 = r * r;
+
+    // Taylor series, which need 10 terms at most at π/4
+    let mut sin = r;
+    let mut cos = 1.0;
+    let mut sin_term = r;
+    let mut cos_term = 1.0;
+    let mut n = 1;
+    while n <= 10 {
+        let k = (2 * n) as f64;
+        cos_term *= -r2 / ((k - 1.0) * k);
+        sin_term *= -r2 / (k * (k + 1.0));
+        cos += cos_term;
+        sin += sin_term;
+        n += 1;
+    }
+
+    match quadrant & 3 {
+        0 => (sin, cos),
+        1 => (cos, -sin),
+        2 => (-sin, -cos),
+        _ => (-cos, sin),
+    }
+}
+
+/// Sine of `x`, in radians.
+pub const fn sin(x: f64) -> f64 {
+    sin_cos(x).0
+}
+
+/// Cosine of `x`, in radians.
+pub const fn cos(x: f64) -> f64 {
+    sin_cos(x).1
+}
+
+/// Square root of `x`, or NaN if `x` is negative.
+pub const fn sqrt(x: f64) -> f64 {
+    if x < 0.0 {
+        return f64::NAN;
+    }
+    if x == 0.0 || x == f64::INFINITY {
+        return x;
+    }
+    // halving the exponent gets within a factor of two, and each Newton
+    // step doubles the number of good bits from there
+    let mut y = f64::from_bits((x.to_bits() >> 1) + (1023 << 51));
+    let mut i = 0;
+    while i < 7 {
+        y = 0.5 * (y + x / y);
+        i += 1;
+    }
+    y
+}
+
+/// `e` raised to `x`.
+pub const fn exp(x: f64) -> f64 {
+    if x > 709.0 {
+        return f64::INFINITY;
+    }
+    if x < -745.0 {
+        return 0.0;
+    }
+    // exp(x) = 2^k · exp(r), with |r| ≤ ln(2)/2
+    let k = round(x / LN_2);
+    let r = x - k as f64 * LN_2;
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut n = 1;
+    while n <= 16 {
+        term *= r / n as f64;
+        sum += term;
+        n += 1;
+    }
+    let mut k = k;
+    while k > 0 {
+        sum *= 2.0;
+        k -= 1;
+    }
+    while k < 0 {
+        sum *= 0.5;
+        k += 1;
+    }
+    sum
+}
+
+/// Natural logarithm of `x`, or NaN if `x` isn't positive.
+pub const fn ln(x: f64) -> f64 {
+    if x.is_nan() || x <= 0.0 {
+        return f64::NAN;
+    }
+    if x == f64::INFINITY {
+        return x;
+    }
+    // ln(x) = k·ln(2) + ln(m), with m within √2 of 1
+    let bits = x.to_bits();
+    let mut k = ((bits >> 52) & 0x7ff) as i64 - 1023;
+    let mut m = f64::from_bits((bits & ((1 << 52) - 1)) | (1023 << 52));
+    if k == -1023 {
+        // subnormal: normalise by hand
+        m = x * (1u64 << 54) as f64;
+        let bits = m.to_bits();
+        k = ((bits >> 52) & 0x7ff) as i64 - 1023 - 54;
+        m = f64::from_bits((bits & ((1 << 52) - 1)) | (1023 << 52));
+    }
+    if m > SQRT_2 {
+        m *= 0.5;
+        k += 1;
+    }
+    // ln(m) = 2·atanh(s), with |s| ≤ 0.172
+    let s = (m - 1.0) / (m + 1.0);
+    let s2 = s * s;
+    let mut sum = 0.0;
+    let mut power = s;
+    let mut n = 1;
+    while n <= 41 {
+        sum += power / n as f64;
+        power *= s2;
+        n += 2;
+    }
+    k as f64 * LN_2 + 2.0 * sum
+}
+
+/// The amplitude ratio of `db` decibels.
+pub const fn db_to_gain(db: f64) -> f64 {
+    exp(db * (LN_10 / 20.0))
+}
+
+/// `2π·hz/rate`, the angular frequency of `hz` at a sample rate.
+pub const fn omega(hz: f64, rate: f64) -> f64 {
+    2.0 * PI * hz / rate
+}
+
+/// Interpolation table segments, as a power of two.
+const SEGMENT_BITS: u32 = 5;
+
+/// `log2(1 + i/32)`, in Q16.
+const LOG2: [i32; 33] = log2_table();
+
+/// `2^(i/32)`, in Q16.
+const EXP2: [u32; 33] = exp2_table();
+
+const fn log2_table() -> [i32; 33] {
+    let mut table = [0; 33];
+    let mut i = 0;
+    while i < table.len() {
+        let x = 1.0 + i as f64 / 32.0;
+        table[i] = round(ln(x) / LN_2 * 65536.0) as i32;
+        i += 1;
+    }
+    table
+}
+
+const fn exp2_table() -> [u32; 33] {
+    let mut table = [0; 33];
+    let mut i = 0;
+    while i < table.len() {
+        let x = i as f64 / 32.0;
+        table[i] = round(exp(x * LN_2) * 65536.0) as u32;
+        i += 1;
+    }
+    table
+}
+
+/// Base-two logarithm of `x`, in Q16.
+///
+/// # Panics
+///
+/// Panics if `x` is zero.
+pub fn log2_q16(x: u32) -> i32 {
+    assert!(x > 0, "logarithm of zero");
+    let octave = 31 - x.leading_zeros();
+    // the bits below the leading one, as a fraction in Q31
+    let fraction = (x << (31 - octave)) & 0x7fff_ffff;
+    let i = (fraction >> (31 - SEGMENT_BITS)) as usize;
+    let within = ((fraction >> (15 - SEGMENT_BITS)) & 0xffff) as i32;
+    let (a, b) = (LOG2[i], LOG2[i + 1]);
+    ((octave as i32) << 16) + a + (((b - a) * within) >> 16)
+}
+
+/// Two raised to `x`, for `x` in Q16, in Q16. Saturates at `u32::MAX`.
+pub fn exp2_q16(x: i32) -> u32 {
+    let octave = x >> 16;
+    let fraction = (x & 0xffff) as u32;
+    let i = (fraction >> (16 - SEGMENT_BITS)) as usize;
+    let within = fraction & ((1 << (16 - SEGMENT_BITS)) - 1);
+    let (a, b) = (EXP2[i], EXP2[i + 1]);
+    let y = a + (((b - a) * within) >> (16 - SEGMENT_BITS));
+    if octave >= 16 {
+        u32::MAX
+    } else if octave >= 0 {
+        (u64::from(y) << octave).min(u32::MAX.into()) as u32
+    } else if octave > -32 {
+        y >> -octave
+    } else {
+        0
+    }
+}
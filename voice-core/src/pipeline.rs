@@ -0,0 +1,177 @@
+//! The capture → process → output loop run by the firmware.
+
+use crate::adc::OVERSAMPLE;
+use crate::dc::{DcBlocker, VOICE_CORNER_HZ};
+use crate::decimate::Decimator;
+use crate::denoise::{self, Suppressor};
+use crate::effects::{self, Effect, Effects};
+use crate::process::{Chain, Then};
+use crate::{from_pcm16, AudioSink, AudioSource, SampleRate, Window, SAMPLE_MAX};
+
+/// Size of the buffer behind the firmware's level meter.
+pub const WINDOW_CAPACITY: usize = WINDOW_LEN;
+
+/// Number of samples the firmware averages the level over.
+pub const WINDOW_LEN: usize = 100;
+
+/// Frame length of the noise suppressor.
+const DENOISE_LEN: usize = 256;
+
+/// Grain and frame length of the effects: long enough for a period of
+/// the robot's pitch at 44.1 kHz.
+const EFFECTS_LEN: usize = 512;
+
+/// The stages of a [`Pipeline`]'s chain, after the DC blocker: the noise
+/// suppressor, then the voice changer.
+pub type Stages = Then<Suppressor<DENOISE_LEN>, Effects<EFFECTS_LEN>>;
+
+/// Settings for a [`Pipeline`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Number of samples the level is averaged over.
+    pub window_len: usize,
+    /// Whether the noise suppressor is switched on. Switched off, it passes
+    /// the signal straight through, with no delay.
+    pub suppress_noise: bool,
+    /// Settings for the noise suppressor.
+    pub denoise: denoise::Config,
+    /// Which voice changer effect runs.
+    pub effect: Effect,
+    /// Settings for the effects.
+    pub effects: effects::Config,
+}
+
+impl Default for Config {
+    /// The configuration the firmware runs with: the level averaged over
+    /// [`WINDOW_LEN`] samples, and no noise suppression or effect.
+    fn default() -> Self {
+        Self {
+            window_len: WINDOW_LEN,
+            suppress_noise: false,
+            denoise: denoise::Config::default(),
+            effect: Effect::Off,
+            effects: effects::Config::default(),
+        }
+    }
+}
+
+/// Error from one [`Pipeline::step`], tagged with the side that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<S, K> {
+    /// Reading from the source failed.
+    Source(S),
+    /// Writing to the sink failed.
+    Sink(K),
+}
+
+/// Passband the decimator keeps flat, as a fraction of the sample rate:
+/// 3.4 kHz at 8 kHz.
+const PASSBAND: f64 = 0.425;
+
+/// Moves samples from an [`AudioSource`] running at
+/// [`OVERSAMPLE`] times the sample rate through a [`Decimator`] and a
+/// processing [`Chain`], and into an [`AudioSink`] at the sample rate.
+///
+/// The chain's [`DcBlocker`] comes first after decimation, so everything
+/// else works on the signal with the microphone bias taken out. The noise
+/// [`Suppressor`] follows it, switched on or off by the config, and then
+/// the [`Effects`], running whichever the config selects. A rolling
+/// average of the rectified output measures its level, which the firmware
+/// shows on the LED.
+///
+/// The firmware calls [`step`](Pipeline::step) in its main loop; the host
+/// tools call it the same way so they exercise exactly the same code.
+#[derive(Debug, Clone)]
+pub struct Pipeline<const N: usize> {
+    decimator: Decimator<3, 9>,
+    chain: Chain<Stages>,
+    window: Window<N>,
+}
+
+impl<const N: usize> Pipeline<N> {
+    /// Create a pipeline with `config` for signals captured at
+    /// [`OVERSAMPLE`] times `rate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config.window_len` is zero or more than `N`, or if any of
+    /// the effects would, with their settings.
+    pub fn new(config: &Config, rate: SampleRate) -> Self {
+        let mut suppressor = Suppressor::new(&config.denoise, rate);
+        suppressor.set_enabled(config.suppress_noise);
+        let mut effects = Effects::new(&config.effects, rate);
+        effects.select(config.effect);
+        Self {
+            decimator: Decimator::new(OVERSAMPLE, PASSBAND),
+            chain: Chain::with_stages(
+                DcBlocker::new(VOICE_CORNER_HZ, rate),
+                Then::new(suppressor, effects),
+            ),
+            window: Window::new(config.window_len),
+        }
+    }
+
+    /// The processing chain samples go through.
+    pub fn chain(&self) -> &Chain<Stages> {
+        &self.chain
+    }
+
+    /// Mutable access to the processing chain, e.g. to switch noise
+    /// suppression on or off, or to select another effect.
+    pub fn chain_mut(&mut self) -> &mut Chain<Stages> {
+        &mut self.chain
+    }
+
+    /// The rolling window the level is averaged in, from silence at 0 to
+    /// a full-scale square wave at [`SAMPLE_MAX`].
+    pub fn window(&self) -> &Window<N> {
+        &self.window
+    }
+
+    /// Mutable access to the rolling window, e.g. to change its length.
+    pub fn window_mut(&mut self) -> &mut Window<N> {
+        &mut self.window
+    }
+
+    /// Run one iteration of the loop.
+    ///
+    /// Takes every sample the source has ready and decimates them, runs the
+    /// samples that come out through the chain, writes the result to the
+    /// sink and pushes its level into the window. Returns the number of
+    /// samples read, [`OVERSAMPLE`] times the number written over the long
+    /// run.
+    pub fn step<S, K>(
+        &mut self,
+        source: &mut S,
+        sink: &mut K,
+    ) -> Result<usize, Error<S::Error, K::Error>>
+    where
+        S: AudioSource,
+        K: AudioSink,
+    {
+        // decimate every sample that is ready and filter the result through
+        // the chain, a block at a time
+        let mut raw = [0; 32];
+        let mut decimated = [0; 32];
+        let mut signed = [0; 32];
+        let mut block = [0; 32];
+        let mut read = 0;
+        loop {
+            let n = source.read_block(&mut raw).map_err(Error::Source)?;
+            if n == 0 {
+                break;
+            }
+            let (_, m) = self.decimator.push_block(&raw[..n], &mut decimated);
+            self.chain
+                .push_pcm16_block(&decimated[..m], &mut signed[..m]);
+            for (sample, &x) in block[..m].iter_mut().zip(&signed[..m]) {
+                // 16 bits of magnitude to the 12 of the window
+                self.window.push((x.unsigned_abs() >> 3).min(SAMPLE_MAX));
+                *sample = from_pcm16(x);
+            }
+            sink.write_block(&block[..m]).map_err(Error::Sink)?;
+            read += n;
+        }
+        Ok(read)
+    }
+}
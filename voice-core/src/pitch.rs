@@ -0,0 +1,249 @@
+//! Pitch detection: the fundamental frequency of a voice, and the note
+//! nearest it.
+//!
+//! [`Yin`] runs the YIN estimator of de Cheveigné and Kawahara on one frame
+//! at a time, which is meant to be each block the capture DMA hands over,
+//! so it runs in step with capture and needs no buffering of its own. For
+//! each lag it takes the squared difference between the first half of the
+//! frame and the half starting that far along, which dips towards zero at
+//! the period and its multiples. Dividing each difference by the mean of
+//! those at shorter lags evens the dips out, so the first that goes under a
+//! threshold is the period rather than one of its multiples, and refuses
+//! the near-zero lags where everything looks periodic. A parabola through
+//! the dip and its neighbours then puts the period between samples.
+//!
+//! How deep the dip is says how periodic the frame is, and so how much to
+//! trust the estimate: a steady vowel dips close to zero, breathy or noisy
+//! sounds hardly at all. Frames that never get under the threshold, like
+//! silence, hiss and most consonants, give no pitch.
+//!
+//! [`note`] turns a frequency into the nearest equal-tempered note and how
+//! many cents off it is, for a tuner.
+//!
+//! # Cost
+//!
+//! The differences take `N/2` multiply-adds at every lag up to the longest
+//! period, about 15 000 for a 256-sample frame at 8 kHz down to 70 Hz, and
+//! each lag costs a 64-bit division on top. That's a few percent of the CPU
+//! at the capture rate.
+
+use core::fmt;
+
+use crate::math;
+use crate::SampleRate;
+
+/// Fraction bits of the normalised differences and the confidence.
+const FRAC: u32 = 15;
+
+/// One, in Q15.
+const ONE: u32 = 1 << FRAC;
+
+/// How far samples are shifted down before differencing, so the sums fit
+/// in 64 bits after normalising.
+const SAMPLE_SHIFT: u32 = 2;
+
+/// Fraction bits of the interpolated period.
+const PERIOD_FRAC: u32 = 8;
+
+/// Note number of A4, which is 440 Hz.
+const A4: i32 = 69;
+
+/// Settings for a [`Yin`] pitch tracker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Lowest pitch looked for, in Hz.
+    pub min_hz: u32,
+    /// Highest pitch looked for, in Hz.
+    pub max_hz: u32,
+    /// How far the normalised difference has to dip to count as the period,
+    /// in percent. Higher finds pitch in rougher voices, but takes more
+    /// noise for it.
+    pub threshold_percent: u32,
+}
+
+impl Default for Config {
+    /// Settings for speaking and singing voices.
+    fn default() -> Self {
+        Self {
+            min_hz: 70,
+            max_hz: 1000,
+            threshold_percent: 10,
+        }
+    }
+}
+
+/// A pitch estimate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pitch {
+    /// The fundamental frequency, in thousandths of a Hz.
+    pub millihertz: u32,
+    /// How periodic the frame was, in 1/32768ths: 32768 for a perfectly
+    /// steady waveform, falling towards 0 as it gets noisier.
+    pub confidence: u16,
+}
+
+impl Pitch {
+    /// The fundamental frequency, rounded to the nearest Hz.
+    pub fn hz(&self) -> u32 {
+        (self.millihertz + 500) / 1000
+    }
+
+    /// The nearest note, if it has a MIDI note number.
+    pub fn note(&self) -> Option<Note> {
+        note(self.millihertz)
+    }
+}
+
+/// An equal-tempered note, tuned to A4 at 440 Hz, and how far a frequency
+/// is from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Note {
+    /// MIDI note number, 69 for A4.
+    pub number: u8,
+    /// How far off the note the frequency is, in cents, from -50 to 50.
+    pub cents: i8,
+}
+
+impl Note {
+    /// The name of the note, with sharps: `"C"`, `"C#"`, `"D"` and so on.
+    pub const fn name(&self) -> &'static str {
+        const NAMES: [&str; 12] = [
+            "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+        ];
+        NAMES[self.number as usize % 12]
+    }
+
+    /// The octave of the note in scientific pitch notation, where middle C
+    /// is C4.
+    pub const fn octave(&self) -> i8 {
+        (self.number / 12) as i8 - 1
+    }
+}
+
+impl fmt::Display for Note {
+    /// Formats as the name and octave, like `A4` or `C#3`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.name(), self.octave())
+    }
+}
+
+/// The note nearest `millihertz`, or `None` if it is zero or off either end
+/// of the MIDI range.
+pub fn note(millihertz: u32) -> Option<Note> {
+    if millihertz == 0 {
+        return None;
+    }
+    // octaves from A4, in Q16, to cents
+    let octaves = math::log2_q16(millihertz) - math::log2_q16(440_000);
+    let cents = (i64::from(octaves) * 1200 + (1 << 15)) >> 16;
+    let semitones = (cents + 50).div_euclid(100);
+    let number = i64::from(A4) + semitones;
+    if !(0..=127).contains(&number) {
+        return None;
+    }
+    Some(Note {
+        number: number as u8,
+        cents: (cents - semitones * 100) as i8,
+    })
+}
+
+/// The squared difference between the first half of `frame` and the half
+/// starting `lag` samples along.
+fn difference<const N: usize>(frame: &[i16; N], lag: usize) -> u64 {
+    let window = N / 2;
+    frame[..window]
+        .iter()
+        .zip(&frame[lag..lag + window])
+        .map(|(&a, &b)| {
+            let e = (i32::from(a) >> SAMPLE_SHIFT) - (i32::from(b) >> SAMPLE_SHIFT);
+            u64::from(e.unsigned_abs() * e.unsigned_abs())
+        })
+        .sum()
+}
+
+/// YIN pitch tracker for frames of `N` signed 16-bit samples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Yin<const N: usize> {
+    rate: u32,
+    /// Shortest and longest lag searched, in samples.
+    min_lag: usize,
+    max_lag: usize,
+    /// Threshold on the normalised difference, in Q15.
+    threshold: u32,
+    /// Normalised difference at each lag, in Q15.
+    difference: [u32; N],
+}
+
+impl<const N: usize> Yin<N> {
+    /// Create a pitch tracker for signals at `rate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is odd or outside 32 to 1024, the range of pitches is
+    /// empty, the highest is above a quarter of the sample rate, or the
+    /// period of the lowest doesn't fit in half a frame.
+    pub const fn new(config: &Config, rate: SampleRate) -> Self {
+        assert!(
+            N >= 32 && N <= 1024 && N.is_multiple_of(2),
+            "frames must be an even 32 to 1024 samples"
+        );
+        assert!(
+            config.min_hz > 0 && config.min_hz < config.max_hz,
+            "pitch range must not be empty"
+        );
+        assert!(
+            config.max_hz <= rate.hz() / 4,
+            "highest pitch must be at most a quarter of the sample rate"
+        );
+        let max_lag = rate.hz().div_ceil(config.min_hz) as usize;
+        assert!(
+            max_lag < N / 2,
+            "lowest pitch's period must fit in half a frame"
+        );
+        Self {
+            rate: rate.hz(),
+            min_lag: (rate.hz() / config.max_hz) as usize,
+            max_lag,
+            threshold: config.threshold_percent * ONE / 100,
+            difference: [0; N],
+        }
+    }
+
+    /// Estimate the pitch of `frame`, or `None` if it isn't periodic enough
+    /// to have one.
+    pub fn estimate(&mut self, frame: &[i16; N]) -> Option<Pitch> {
+        let mut sum = 0u64;
+        self.difference[0] = ONE;
+        for lag in 1..=self.max_lag + 1 {
+            let d = difference(frame, lag);
+            sum += d;
+            self.difference[lag] = if sum == 0 {
+                ONE
+            } else {
+                ((d * lag as u64) << FRAC).div_ceil(sum) as u32
+            };
+        }
+
+        let d = &self.difference;
+        let mut lag = (self.min_lag..=self.max_lag).find(|&lag| d[lag] < self.threshold)?;
+        while lag < self.max_lag && d[lag + 1] < d[lag] {
+            lag += 1;
+        }
+
+        // the vertex of the parabola through the dip and its neighbours, in
+        // the raw differences, which normalising would skew
+        let [a, b, c] = [lag - 1, lag, lag + 1].map(|lag| difference(frame, lag) as i64);
+        let curvature = a - 2 * b + c;
+        let offset = if curvature > 0 {
+            (((a - c) << (PERIOD_FRAC - 1)) / curvature).clamp(-128, 128)
+        } else {
+            0
+        };
+        let period = ((lag as i64) << PERIOD_FRAC) + offset;
+        let millihertz = ((u64::from(self.rate) * 1000) << PERIOD_FRAC) / period as u64;
+        Some(Pitch {
+            millihertz: millihertz as u32,
+            confidence: (ONE - d[lag].min(ONE)) as u16,
+        })
+    }
+}
@@ -0,0 +1,196 @@
+//! Paced PWM playback through DMA from a small ring of blocks.
+//!
+//! The signal chain writes samples into a block, already scaled to compare
+//! values for the carrier slice. Full blocks are queued for a DMA channel,
+//! which writes one value into the carrier's compare register every time the
+//! pacer slice wraps (see [`pwm`](crate::pwm)). When a block has been played
+//! the DMA completion interrupt calls [`DmaPlayback::on_complete`], which
+//! starts the next queued block and hands the played one back to the writer.
+//!
+//! Output starts once two blocks are queued, so the writer has a whole block
+//! time of slack against the DMA channel: with capture and playback running
+//! at the same rate, a block written right after a capture block completes is
+//! queued long before the one in front of it has finished playing. If the
+//! queue runs dry anyway the carrier holds its last value, and playback waits
+//! for two full blocks again before restarting.
+//!
+//! Like [`capture`](crate::capture), this module only deals with the hand-off;
+//! moving the values is left to a [`PlaybackDma`] implementation.
+
+use crate::capture::Block;
+use crate::{pwm, AudioSink};
+
+/// Number of blocks playback cycles through.
+pub const BLOCKS: usize = 3;
+
+/// Number of full blocks queued before output starts.
+const PRIME: usize = 2;
+
+/// A DMA channel writing blocks of compare values to the carrier slice.
+pub trait PlaybackDma<const N: usize> {
+    /// Start playing `block`.
+    ///
+    /// Only called when no transfer is in flight.
+    fn start(&mut self, block: Block<N>);
+
+    /// If the transfer in flight has completed, return its block.
+    ///
+    /// Also acknowledges the completion interrupt.
+    fn take_done(&mut self) -> Option<Block<N>>;
+}
+
+/// The playback blocks and where each of them is.
+///
+/// Each block is either being played by DMA, queued behind it, being written,
+/// or empty.
+pub struct DmaPlayback<D, const N: usize> {
+    dma: D,
+    top: u16,
+    busy: bool,
+    // empty blocks, the first one being written
+    empty: [Option<Block<N>>; BLOCKS],
+    // samples of empty[0] already written
+    write_pos: usize,
+    // full blocks waiting for DMA, oldest first
+    queued: [Option<Block<N>>; BLOCKS],
+    underruns: u32,
+    overruns: u32,
+}
+
+impl<D: PlaybackDma<N>, const N: usize> DmaPlayback<D, N> {
+    /// Prepare to play through `dma` on a carrier slice wrapping at `top`.
+    ///
+    /// Nothing is output until two blocks have been written.
+    pub fn new(dma: D, top: u16, blocks: [Block<N>; BLOCKS]) -> Self {
+        let [a, b, c] = blocks;
+        Self {
+            dma,
+            top,
+            busy: false,
+            empty: [Some(a), Some(b), Some(c)],
+            write_pos: 0,
+            queued: [None, None, None],
+            underruns: 0,
+            overruns: 0,
+        }
+    }
+
+    /// Number of times DMA ran out of queued blocks, including when the
+    /// writer stopped for good.
+    pub fn underruns(&self) -> u32 {
+        self.underruns
+    }
+
+    /// Number of samples dropped because every block was full.
+    pub fn overruns(&self) -> u32 {
+        self.overruns
+    }
+
+    /// Whether DMA is currently playing a block.
+    pub fn is_playing(&self) -> bool {
+        self.busy
+    }
+
+    /// Number of full blocks waiting to be played.
+    pub fn queued(&self) -> usize {
+        self.queued.iter().filter(|b| b.is_some()).count()
+    }
+
+    /// Number of samples that can be written right now without any being
+    /// dropped.
+    pub fn space(&self) -> usize {
+        let empty = self.empty.iter().filter(|b| b.is_some()).count();
+        (empty * N).saturating_sub(self.write_pos)
+    }
+
+    /// Handle the DMA completion interrupt.
+    ///
+    /// Moves DMA on to the next queued block, if there is one, and hands the
+    /// played block back to the writer.
+    pub fn on_complete(&mut self) {
+        let Some(done) = self.dma.take_done() else {
+            return;
+        };
+        self.busy = false;
+        if self.queued[0].is_some() {
+            self.start_next();
+        } else {
+            self.underruns += 1;
+        }
+        // behind the block being written, if any
+        if let Some(slot) = self.empty.iter_mut().find(|b| b.is_none()) {
+            *slot = Some(done);
+        }
+    }
+
+    /// Start DMA on the oldest queued block.
+    fn start_next(&mut self) {
+        if let Some(block) = self.queued[0].take() {
+            self.queued.rotate_left(1);
+            self.dma.start(block);
+            self.busy = true;
+        }
+    }
+
+    /// Queue samples for output, returning how many fitted.
+    ///
+    /// Samples that find every block full are dropped and counted as
+    /// [overruns](Self::overruns).
+    pub fn write_block(&mut self, samples: &[u16]) -> usize {
+        let mut written = 0;
+        while written < samples.len() {
+            let Some(block) = &mut self.empty[0] else {
+                break;
+            };
+            let n = (N - self.write_pos).min(samples.len() - written);
+            for (duty, &sample) in block[self.write_pos..self.write_pos + n]
+                .iter_mut()
+                .zip(&samples[written..written + n])
+            {
+                *duty = pwm::scale(sample, self.top);
+            }
+            written += n;
+            self.write_pos += n;
+            if self.write_pos == N {
+                self.submit();
+            }
+        }
+        self.overruns += (samples.len() - written) as u32;
+        written
+    }
+
+    /// Queue the block that was just filled, and start output if enough is
+    /// queued.
+    fn submit(&mut self) {
+        let Some(block) = self.empty[0].take() else {
+            return;
+        };
+        self.empty.rotate_left(1);
+        self.write_pos = 0;
+        if let Some(slot) = self.queued.iter_mut().find(|b| b.is_none()) {
+            *slot = Some(block);
+        }
+        if !self.busy && self.queued() >= PRIME {
+            self.start_next();
+        }
+    }
+
+    /// Access the DMA channel.
+    pub fn dma(&mut self) -> &mut D {
+        &mut self.dma
+    }
+}
+
+impl<D: PlaybackDma<N>, const N: usize> AudioSink for DmaPlayback<D, N> {
+    type Error = core::convert::Infallible;
+
+    fn write(&mut self, sample: u16) -> Result<(), Self::Error> {
+        DmaPlayback::write_block(self, &[sample]);
+        Ok(())
+    }
+
+    fn write_block(&mut self, samples: &[u16]) -> Result<(), Self::Error> {
+        DmaPlayback::write_block(self, samples);
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+//! Clip playback at a chosen speed.
+//!
+//! A [`Player`] takes a clip's samples from an [`AudioSource`], such as a
+//! decoder reading it out of the store, through a [`Stretcher`] and into an
+//! [`AudioSink`], so a memo can be played faster or slower, keeping its
+//! pitch or not as the [`stretch::Config`] says. It only writes as much as
+//! the sink has room for, so it can be called every time round the main
+//! loop with however much of the playback queue is free.
+//!
+//! A clip recorded at another rate than the output runs, a prompt from a
+//! WAV file at 44.1 kHz, say, or a memo made before the rate was changed,
+//! goes through a [`Resampler`] to the output rate first, with `TAPS` taps
+//! and up to `PHASES` phases; one at the output rate doesn't.
+//!
+//! The clip ends when its source has nothing more available. The player
+//! then pushes the silence the resampler and the stretcher need to let go
+//! of the last of it, and [is finished](Player::is_finished) once
+//! everything has come out.
+
+use crate::pipeline::Error;
+use crate::resample::Resampler;
+use crate::stretch::{self, Stretcher};
+use crate::{from_pcm16, to_pcm16, AudioSink, AudioSource};
+
+/// Samples moved at a time.
+const BLOCK: usize = 32;
+
+/// Plays a clip through a [`Resampler`] with `TAPS` taps and up to `PHASES`
+/// phases, if it needs one, and a [`Stretcher`] holding up to `N` samples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Player<const N: usize, const TAPS: usize, const PHASES: usize> {
+    resampler: Resampler<TAPS, PHASES>,
+    /// Whether the clip's rate differs from the output's.
+    resampling: bool,
+    stretcher: Stretcher<N>,
+    /// Clip samples read but not yet resampled.
+    clip: [i16; BLOCK],
+    clip_pos: usize,
+    clip_len: usize,
+    /// Samples at the output rate not yet taken by the stretcher.
+    input: [i16; BLOCK],
+    input_pos: usize,
+    input_len: usize,
+    /// Samples of silence, at the clip's rate, still to push once the clip
+    /// has ended.
+    silence: usize,
+    ended: bool,
+    finished: bool,
+}
+
+impl<const N: usize, const TAPS: usize, const PHASES: usize> Player<N, TAPS, PHASES> {
+    /// Create a player for a clip recorded at `clip_hz`, playing at
+    /// `output_hz`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Resampler`] would, going from `clip_hz` to
+    /// `output_hz`, or the [`Stretcher`] would, with `config`.
+    pub const fn new(config: &stretch::Config, clip_hz: u32, output_hz: u32) -> Self {
+        let resampler = Resampler::new(clip_hz, output_hz);
+        let mut player = Self {
+            resampler,
+            resampling: clip_hz != output_hz,
+            stretcher: Stretcher::new(config),
+            clip: [0; BLOCK],
+            clip_pos: 0,
+            clip_len: 0,
+            input: [0; BLOCK],
+            input_pos: 0,
+            input_len: 0,
+            silence: 0,
+            ended: false,
+            finished: false,
+        };
+        player.silence = player.flush_len();
+        player
+    }
+
+    /// The stretcher, e.g. to change the speed.
+    pub fn stretcher_mut(&mut self) -> &mut Stretcher<N> {
+        &mut self.stretcher
+    }
+
+    /// Whether the whole clip has been played out.
+    pub const fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Get ready to play another clip, at the same rate, from its start.
+    pub fn reset(&mut self) {
+        self.resampler.reset();
+        self.stretcher.reset();
+        self.clip_pos = 0;
+        self.clip_len = 0;
+        self.input_pos = 0;
+        self.input_len = 0;
+        self.silence = self.flush_len();
+        self.ended = false;
+        self.finished = false;
+    }
+
+    /// Play up to `room` samples of the clip from `clip` into `sink`.
+    ///
+    /// Returns how many samples were read from `clip`.
+    pub fn step<S, K>(
+        &mut self,
+        clip: &mut S,
+        sink: &mut K,
+        room: usize,
+    ) -> Result<usize, Error<S::Error, K::Error>>
+    where
+        S: AudioSource,
+        K: AudioSink,
+    {
+        let mut raw = [0; BLOCK];
+        let mut signed = [0; BLOCK];
+        let mut block = [0; BLOCK];
+        let mut read = 0;
+        let mut written = 0;
+        while written < room && !self.finished {
+            if self.input_pos == self.input_len {
+                if self.clip_pos == self.clip_len {
+                    self.clip_pos = 0;
+                    self.clip_len = 0;
+                    if !self.ended {
+                        let n = clip.read_block(&mut raw).map_err(Error::Source)?;
+                        for (x, &sample) in self.clip.iter_mut().zip(&raw[..n]) {
+                            *x = to_pcm16(sample);
+                        }
+                        self.clip_len = n;
+                        self.ended = n == 0;
+                        read += n;
+                    }
+                    if self.ended {
+                        let n = self.silence.min(BLOCK);
+                        self.clip[..n].fill(0);
+                        self.clip_len = n;
+                        self.silence -= n;
+                    }
+                }
+                let pending = &self.clip[self.clip_pos..self.clip_len];
+                let (taken, made) = if self.resampling {
+                    self.resampler.push_block(pending, &mut self.input)
+                } else {
+                    self.input[..pending.len()].copy_from_slice(pending);
+                    (pending.len(), pending.len())
+                };
+                self.clip_pos += taken;
+                self.input_pos = 0;
+                self.input_len = made;
+            }
+            let out = (room - written).min(BLOCK);
+            let (taken, made) = self.stretcher.push_block(
+                &self.input[self.input_pos..self.input_len],
+                &mut signed[..out],
+            );
+            self.input_pos += taken;
+            for (sample, &x) in block.iter_mut().zip(&signed[..made]) {
+                *sample = from_pcm16(x);
+            }
+            sink.write_block(&block[..made]).map_err(Error::Sink)?;
+            written += made;
+            if made == 0
+                && self.input_pos == self.input_len
+                && self.clip_pos == self.clip_len
+                && self.ended
+                && self.silence == 0
+            {
+                self.finished = true;
+            }
+        }
+        Ok(read)
+    }
+
+    /// Samples of silence, at the clip's rate, that push the end of a clip
+    /// all the way out: `N/4` at the output rate for the stretcher, and the
+    /// resampler's taps ahead of them.
+    const fn flush_len(&self) -> usize {
+        if !self.resampling {
+            return N / 4;
+        }
+        let (up, down) = self.resampler.ratio();
+        TAPS + (N / 4 * down as usize).div_ceil(up as usize)
+    }
+}
@@ -0,0 +1,198 @@
+//! The processing chain between capture and output.
+//!
+//! Processing works on signed 16-bit samples centered on zero. A [`Chain`]
+//! starts with a [`DcBlocker`], which takes the 12-bit offset-binary ADC
+//! stream into that form, and continues with any number of [`Processor`]
+//! stages joined with [`then`](Chain::then). Stages are plain structs held
+//! by value, so a chain is a single struct of known size with no allocation
+//! or dynamic dispatch, and is as cheap to run as the stages written out by
+//! hand.
+
+use crate::dc::DcBlocker;
+
+/// One stage of processing on signed 16-bit samples.
+pub trait Processor {
+    /// Process one sample.
+    fn process(&mut self, sample: i16) -> i16;
+
+    /// Process every sample of `samples` in place.
+    ///
+    /// Stages that work a block at a time should override this.
+    fn process_block(&mut self, samples: &mut [i16]) {
+        for sample in samples {
+            *sample = self.process(*sample);
+        }
+    }
+
+    /// Forget the signal so far, as if the stage had just been created.
+    fn reset(&mut self) {}
+
+    /// Run `next` on the output of this stage.
+    fn then<P: Processor>(self, next: P) -> Then<Self, P>
+    where
+        Self: Sized,
+    {
+        Then { first: self, next }
+    }
+}
+
+/// The empty stage, which passes samples through.
+impl Processor for () {
+    fn process(&mut self, sample: i16) -> i16 {
+        sample
+    }
+
+    fn process_block(&mut self, _samples: &mut [i16]) {}
+}
+
+impl<T: Processor + ?Sized> Processor for &mut T {
+    fn process(&mut self, sample: i16) -> i16 {
+        T::process(self, sample)
+    }
+
+    fn process_block(&mut self, samples: &mut [i16]) {
+        T::process_block(self, samples)
+    }
+
+    fn reset(&mut self) {
+        T::reset(self)
+    }
+}
+
+/// Two stages run one after the other, made with [`Processor::then`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Then<A, B> {
+    first: A,
+    next: B,
+}
+
+impl<A, B> Then<A, B> {
+    /// Run `first` and then `next`, as [`Processor::then`] does.
+    pub const fn new(first: A, next: B) -> Self {
+        Self { first, next }
+    }
+
+    /// The stage run first.
+    pub fn first(&self) -> &A {
+        &self.first
+    }
+
+    /// Mutable access to the stage run first.
+    pub fn first_mut(&mut self) -> &mut A {
+        &mut self.first
+    }
+
+    /// The stage run on the output of the first.
+    pub fn next(&self) -> &B {
+        &self.next
+    }
+
+    /// Mutable access to the stage run on the output of the first.
+    pub fn next_mut(&mut self) -> &mut B {
+        &mut self.next
+    }
+}
+
+impl<A: Processor, B: Processor> Processor for Then<A, B> {
+    fn process(&mut self, sample: i16) -> i16 {
+        self.next.process(self.first.process(sample))
+    }
+
+    fn process_block(&mut self, samples: &mut [i16]) {
+        self.first.process_block(samples);
+        self.next.process_block(samples);
+    }
+
+    fn reset(&mut self) {
+        self.first.reset();
+        self.next.reset();
+    }
+}
+
+/// A [`DcBlocker`] followed by processing stages, taking ADC samples to
+/// processed signed samples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chain<P = ()> {
+    dc: DcBlocker,
+    stages: P,
+}
+
+impl Chain {
+    /// Create a chain that only removes DC.
+    pub const fn new(dc: DcBlocker) -> Self {
+        Self { dc, stages: () }
+    }
+}
+
+impl<P: Processor> Chain<P> {
+    /// Create a chain running `stages` after `dc`.
+    pub const fn with_stages(dc: DcBlocker, stages: P) -> Self {
+        Self { dc, stages }
+    }
+
+    /// Add `stage` to the end of the chain.
+    pub fn then<Q: Processor>(self, stage: Q) -> Chain<Then<P, Q>> {
+        Chain {
+            dc: self.dc,
+            stages: self.stages.then(stage),
+        }
+    }
+
+    /// The DC blocker at the head of the chain.
+    pub fn dc_blocker(&self) -> &DcBlocker {
+        &self.dc
+    }
+
+    /// Mutable access to the DC blocker, e.g. to replace it with one for a
+    /// different corner.
+    pub fn dc_blocker_mut(&mut self) -> &mut DcBlocker {
+        &mut self.dc
+    }
+
+    /// The stages after the DC blocker.
+    pub fn stages(&self) -> &P {
+        &self.stages
+    }
+
+    /// Mutable access to the stages after the DC blocker, e.g. to change
+    /// their settings.
+    pub fn stages_mut(&mut self) -> &mut P {
+        &mut self.stages
+    }
+
+    /// Run one ADC sample through the chain.
+    pub fn push(&mut self, sample: u16) -> i16 {
+        self.stages.process(self.dc.push(sample))
+    }
+
+    /// Run every sample of `input` through the chain into `output`, as far
+    /// as both go, returning how many samples that was.
+    pub fn push_block(&mut self, input: &[u16], output: &mut [i16]) -> usize {
+        let n = input.len().min(output.len());
+        self.dc.push_block(&input[..n], &mut output[..n]);
+        self.stages.process_block(&mut output[..n]);
+        n
+    }
+
+    /// Run one signed 16-bit sample on the scale of
+    /// [`to_pcm16`](crate::to_pcm16), such as a
+    /// [`Decimator`](crate::decimate::Decimator)'s, through the chain.
+    pub fn push_pcm16(&mut self, sample: i16) -> i16 {
+        self.stages.process(self.dc.push_pcm16(sample))
+    }
+
+    /// Run every signed sample of `input` through the chain into `output`,
+    /// as far as both go, returning how many samples that was.
+    pub fn push_pcm16_block(&mut self, input: &[i16], output: &mut [i16]) -> usize {
+        let n = input.len().min(output.len());
+        self.dc.push_pcm16_block(&input[..n], &mut output[..n]);
+        self.stages.process_block(&mut output[..n]);
+        n
+    }
+
+    /// Reset every stage.
+    pub fn reset(&mut self) {
+        self.dc.reset();
+        self.stages.reset();
+    }
+}
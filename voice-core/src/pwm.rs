@@ -0,0 +1,128 @@
+//! Mapping from samples to PWM duty cycles, and the PWM slice timing used for
+//! audio output.
+//!
+//! Audio is played through two PWM slices. The *carrier* slice drives the
+//! output pin at an ultrasonic frequency, so an RC low-pass filter (or the
+//! speaker itself) leaves only the audio band, and its compare value sets the
+//! output level. The *pacer* slice has no pin; it wraps once per sample
+//! period, and each wrap requests a DMA transfer of the next compare value
+//! into the carrier slice. The sample clock is therefore as exact as the
+//! system clock, with no interrupt in the path.
+
+use crate::SAMPLE_MAX;
+
+/// Duty value that a full-scale sample is mapped to on the LED.
+pub const DUTY_RANGE: u16 = 25000;
+
+/// Scale a 12-bit sample to the LED duty range.
+pub const fn to_duty(sample: u16) -> u16 {
+    sample * (DUTY_RANGE / 4096)
+}
+
+/// System clock set up by `init_clocks_and_plls`, which the PWM slices run
+/// from.
+pub const SYS_CLOCK_HZ: u32 = 125_000_000;
+
+/// Scale a 12-bit sample to a compare value for a slice wrapping at `top`,
+/// so that 0 is always off and full scale just short of always on.
+pub const fn scale(sample: u16, top: u16) -> u16 {
+    let sample = if sample > SAMPLE_MAX {
+        SAMPLE_MAX
+    } else {
+        sample
+    };
+    ((sample as u32 * (top as u32 + 1)) >> 12) as u16
+}
+
+/// Recover the 12-bit sample that [`scale`] mapped to `duty`.
+///
+/// Exact when the slice has at least 12 bits of resolution.
+pub const fn unscale(duty: u16, top: u16) -> u16 {
+    let wrap = top as u32 + 1;
+    ((duty as u32) << 12).div_ceil(wrap) as u16
+}
+
+/// Clock divider and wrap value of a PWM slice.
+///
+/// The slice counts from 0 to `top` and wraps, advancing once every
+/// `div_int + div_frac / 16` system clock cycles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Slice {
+    /// Integer part of the clock divider, 1..=255.
+    pub div_int: u8,
+    /// Fractional part of the clock divider, in 1/16ths.
+    pub div_frac: u8,
+    /// Counter value the slice wraps after.
+    pub top: u16,
+}
+
+impl Slice {
+    /// The audio carrier: full 12-bit resolution at the undivided system
+    /// clock, about 30.5 kHz at 125 MHz.
+    pub const CARRIER: Slice = Slice {
+        div_int: 1,
+        div_frac: 0,
+        top: 4095,
+    };
+
+    /// The slice that wraps at the rate closest to `rate_hz` from a
+    /// `sys_clock_hz` system clock.
+    ///
+    /// Every divider is tried, keeping the one whose best wrap value gives
+    /// the smallest error; among equally good ones the smallest divider wins.
+    ///
+    /// # Panics
+    ///
+    /// Panics if even the slowest slice wraps faster than `rate_hz`.
+    pub const fn pacer(sys_clock_hz: u32, rate_hz: u32) -> Slice {
+        let target = sys_clock_hz as u64 * 16;
+        let rate = rate_hz as u64;
+        let mut best = 0;
+        let mut best_wrap = 0;
+        let mut best_error = u64::MAX;
+        let mut div = 16;
+        while div < 256 * 16 {
+            let step = div * rate;
+            let wrap = (target + step / 2) / step;
+            if wrap >= 2 && wrap <= 1 << 16 {
+                let period = step * wrap;
+                let error = period.abs_diff(target);
+                if error < best_error {
+                    best = div;
+                    best_wrap = wrap;
+                    best_error = error;
+                }
+            }
+            div += 1;
+        }
+        assert!(best_error != u64::MAX, "rate too low for a PWM slice");
+        Slice {
+            div_int: (best / 16) as u8,
+            div_frac: (best % 16) as u8,
+            top: (best_wrap - 1) as u16,
+        }
+    }
+
+    /// Time between two wraps, in 1/16ths of a system clock cycle.
+    pub const fn period(self) -> u32 {
+        (self.div_int as u32 * 16 + self.div_frac as u32) * (self.top as u32 + 1)
+    }
+
+    /// Wrap rate from a `sys_clock_hz` system clock, in mHz.
+    pub const fn rate_millihz(self, sys_clock_hz: u32) -> u64 {
+        let period = self.period() as u64;
+        (sys_clock_hz as u64 * 16 * 1000 + period / 2) / period
+    }
+
+    /// Error of the wrap rate against `rate_hz`, in parts per million.
+    pub const fn error_ppm(self, sys_clock_hz: u32, rate_hz: u32) -> i32 {
+        let period = self.period() as i64;
+        let rate_uhz = (sys_clock_hz as i64 * 16 * 1_000_000 + period / 2) / period;
+        crate::rate::error_ppm(rate_uhz, rate_hz)
+    }
+
+    /// Compare value for a 12-bit sample on this slice.
+    pub const fn duty(self, sample: u16) -> u16 {
+        scale(sample, self.top)
+    }
+}
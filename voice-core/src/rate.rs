@@ -0,0 +1,169 @@
+//! Sample rates, and the ADC clock dividers that produce them.
+//!
+//! Everything that cares about timing — the ADC, filters designed for a
+//! corner frequency, file headers — takes a [`SampleRate`], so there is a
+//! single place where the rate of the signal chain is decided.
+
+use crate::{adc, pwm};
+
+/// The sample rates the signal chain supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SampleRate {
+    /// 8 kHz, telephone quality.
+    Hz8000,
+    /// 11.025 kHz, a quarter of CD rate.
+    Hz11025,
+    /// 16 kHz, wideband voice.
+    Hz16000,
+    /// 22.05 kHz, half of CD rate.
+    Hz22050,
+    /// 32 kHz.
+    Hz32000,
+    /// 44.1 kHz, CD rate.
+    Hz44100,
+}
+
+impl SampleRate {
+    /// Every supported rate, slowest first.
+    pub const ALL: [SampleRate; 6] = [
+        SampleRate::Hz8000,
+        SampleRate::Hz11025,
+        SampleRate::Hz16000,
+        SampleRate::Hz22050,
+        SampleRate::Hz32000,
+        SampleRate::Hz44100,
+    ];
+
+    /// Nominal rate in Hz.
+    pub const fn hz(self) -> u32 {
+        match self {
+            SampleRate::Hz8000 => 8000,
+            SampleRate::Hz11025 => 11025,
+            SampleRate::Hz16000 => 16000,
+            SampleRate::Hz22050 => 22050,
+            SampleRate::Hz32000 => 32000,
+            SampleRate::Hz44100 => 44100,
+        }
+    }
+
+    /// The supported rate of exactly `hz` Hz, if there is one.
+    pub const fn from_hz(hz: u32) -> Option<SampleRate> {
+        match hz {
+            8000 => Some(SampleRate::Hz8000),
+            11025 => Some(SampleRate::Hz11025),
+            16000 => Some(SampleRate::Hz16000),
+            22050 => Some(SampleRate::Hz22050),
+            32000 => Some(SampleRate::Hz32000),
+            44100 => Some(SampleRate::Hz44100),
+            _ => None,
+        }
+    }
+
+    /// The ADC clock divider closest to this rate.
+    pub const fn divider(self) -> Divider {
+        Divider::for_rate(adc::CLOCK_HZ, self.hz())
+    }
+
+    /// The ADC clock divider closest to [`OVERSAMPLE`](adc::OVERSAMPLE)
+    /// times this rate, which the firmware captures at.
+    pub const fn oversampled_divider(self) -> Divider {
+        Divider::for_rate(adc::CLOCK_HZ, self.hz() * adc::OVERSAMPLE)
+    }
+
+    /// Rate the ADC actually samples at with [`divider`](Self::divider), in
+    /// mHz.
+    pub const fn achieved_millihz(self) -> u64 {
+        self.divider().rate_millihz(adc::CLOCK_HZ)
+    }
+
+    /// How far the achieved rate is off the nominal one, in parts per
+    /// million.
+    pub const fn error_ppm(self) -> i32 {
+        self.divider().error_ppm(adc::CLOCK_HZ, self.hz())
+    }
+
+    /// The PWM slice setup that wraps closest to once per sample at this
+    /// rate, used to pace playback.
+    pub const fn pacer(self) -> pwm::Slice {
+        pwm::Slice::pacer(pwm::SYS_CLOCK_HZ, self.hz())
+    }
+}
+
+/// Integer and fractional parts of the ADC free-running clock divider, as
+/// passed to `clock_divider`.
+///
+/// A conversion is started every `1 + int + frac / 256` ADC clock cycles. The
+/// fractional part is applied by first-order delta-sigma, so individual
+/// conversions jitter by one cycle but the average rate is exact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Divider {
+    /// Integer part.
+    pub int: u16,
+    /// Fractional part, in 1/256ths.
+    pub frac: u8,
+}
+
+impl Divider {
+    /// Shortest period the ADC supports: a conversion takes 96 cycles.
+    pub const MIN_PERIOD: u32 = 96 * 256;
+
+    /// Build a divider from a period in 1/256ths of a clock cycle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the period is shorter than a conversion or does not fit the
+    /// divider register.
+    pub const fn from_period(period: u32) -> Divider {
+        assert!(
+            period >= Self::MIN_PERIOD,
+            "period shorter than a conversion"
+        );
+        let int = period / 256 - 1;
+        assert!(int <= u16::MAX as u32, "period too long for the divider");
+        Divider {
+            int: int as u16,
+            frac: (period % 256) as u8,
+        }
+    }
+
+    /// The divider giving the rate closest to `rate_hz` from a `clock_hz`
+    /// ADC clock.
+    pub const fn for_rate(clock_hz: u32, rate_hz: u32) -> Divider {
+        let ticks = clock_hz as u64 * 256;
+        let rate = rate_hz as u64;
+        Divider::from_period(((ticks + rate / 2) / rate) as u32)
+    }
+
+    /// Time between two conversions, in 1/256ths of an ADC clock cycle.
+    pub const fn period(self) -> u32 {
+        (1 + self.int as u32) * 256 + self.frac as u32
+    }
+
+    /// Sample rate this divider gives from a `clock_hz` ADC clock, in mHz.
+    pub const fn rate_millihz(self, clock_hz: u32) -> u64 {
+        let period = self.period() as u64;
+        (clock_hz as u64 * 256 * 1000 + period / 2) / period
+    }
+
+    /// Error of the rate this divider gives against `rate_hz`, in parts per
+    /// million.
+    pub const fn error_ppm(self, clock_hz: u32, rate_hz: u32) -> i32 {
+        let period = self.period() as i64;
+        let rate_uhz = (clock_hz as i64 * 256 * 1_000_000 + period / 2) / period;
+        error_ppm(rate_uhz, rate_hz)
+    }
+}
+
+/// Error of a rate of `rate_uhz` µHz against `target_hz`, in parts per
+/// million.
+pub(crate) const fn error_ppm(rate_uhz: i64, target_hz: u32) -> i32 {
+    // an error in µHz divided by the rate in Hz is an error in ppm
+    let target = target_hz as i64;
+    let error = rate_uhz - target * 1_000_000;
+    let rounded = if error >= 0 {
+        (error + target / 2) / target
+    } else {
+        (error - target / 2) / target
+    };
+    rounded as i32
+}
@@ -0,0 +1,234 @@
+//! Sample-rate conversion between any two rates.
+//!
+//! Everything else in the chain runs at one rate, the ADC's. But clips get
+//! recorded at whatever rate the device was set to, prompts come in from
+//! WAV files at 16 or 44.1 kHz, and a transmit path wants 8 kHz whatever
+//! the microphone ran at. A [`Resampler`] goes from one rate to another
+//! whose ratio reduces to `up/down`, streaming, so it can sit anywhere a
+//! block goes past: 10 kHz to 8 kHz is 4/5, 16 kHz to 8 kHz is 1/2,
+//! 44.1 kHz to 8 kHz is 80/441. The [`Player`](crate::player::Player) puts
+//! one in front of any clip recorded at another rate than the output's.
+//!
+//! In principle the input is zero-stuffed up by `up`, low-pass filtered at
+//! the lower of the two Nyquist frequencies, and every `down`th sample of
+//! the result kept. A polyphase filter does exactly that without working
+//! out anything that is thrown away: the prototype filter is split into
+//! `up` phases of `TAPS` taps, one for each fraction of an input sample an
+//! output can fall at, and each output is a single `TAPS` tap FIR over the
+//! most recent input with the phase it needs. The taps are worked out once,
+//! when the resampler is made, as a Kaiser-windowed sinc with β = 7, which
+//! keeps the stopband about 70 dB down; each phase is scaled to pass DC
+//! without any ripple between them.
+//!
+//! The cutoff sits at the lower Nyquist frequency, with the transition
+//! band spread evenly either side of it: about `4.3/TAPS` of the input rate
+//! wide. Whatever lies between the new Nyquist frequency and the top of
+//! the transition band folds back into the top of the transition band
+//! rather than the passband, so it is the passband and the rate it aliases
+//! into that set `TAPS`. Keeping 3.4 kHz flat with the aliases out of it
+//! takes 64 taps from 16 kHz down to 8 kHz, 40 from 10 kHz, and 160 from
+//! 44.1 kHz; going up takes 32 taps from 8 kHz, whatever the rate above.
+//!
+//! The output lags the input by about `TAPS/2` input samples.
+//!
+//! # Cost
+//!
+//! Each output sample is `TAPS` multiply-adds. 16 kHz down to 8 kHz with
+//! 64 taps is half a million a second, a few percent of the CPU; 44.1 kHz
+//! down with 160 taps is two and a half times that. The taps take
+//! `2·TAPS·PHASES` bytes, 25 KB for 44.1 kHz to 8 kHz, so they are best
+//! made at compile time into a `static`; designing them at run time costs
+//! a sine and a Bessel function per tap in software floating point.
+
+use crate::math;
+
+/// Fraction bits of the taps.
+const TAP_FRAC: u32 = 15;
+
+/// Kaiser window parameter, trading transition width for stopband depth.
+const BETA: f64 = 7.0;
+
+/// Converts signed 16-bit samples from one rate to another, with `TAPS`
+/// taps per phase and room for up to `PHASES` phases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resampler<const TAPS: usize, const PHASES: usize> {
+    /// Each phase's taps in Q15, in the order they meet the history, oldest
+    /// first.
+    taps: [[i16; TAPS]; PHASES],
+    /// The last `TAPS` input samples, oldest first from `pos`.
+    history: [i16; TAPS],
+    pos: usize,
+    up: u32,
+    down: u32,
+    /// Where the next output falls after the newest input sample, in
+    /// `1/up`ths of an input sample; `up` or more until more input comes.
+    phase: u32,
+}
+
+impl<const TAPS: usize, const PHASES: usize> Resampler<TAPS, PHASES> {
+    /// Create a resampler from `from_hz` to `to_hz`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either rate is zero, if `TAPS` is zero, or if the ratio
+    /// between the rates, in lowest terms, needs more than `PHASES` phases
+    /// on top.
+    pub const fn new(from_hz: u32, to_hz: u32) -> Self {
+        assert!(from_hz > 0 && to_hz > 0, "rates must be above zero");
+        assert!(TAPS > 0, "need at least one tap");
+        let common = gcd(from_hz, to_hz);
+        let up = to_hz / common;
+        let down = from_hz / common;
+        assert!(up as usize <= PHASES, "ratio needs more phases");
+        Self {
+            taps: design(up, down),
+            history: [0; TAPS],
+            pos: 0,
+            up,
+            down,
+            phase: up,
+        }
+    }
+
+    /// The ratio of the output rate to the input rate, in lowest terms, as
+    /// `(up, down)`.
+    pub const fn ratio(&self) -> (u32, u32) {
+        (self.up, self.down)
+    }
+
+    /// Run `input` through the resampler into `output`, as far as both go.
+    ///
+    /// Returns how many input samples were taken and how many output
+    /// samples were written. Input stops being taken once `output` is full.
+    pub fn push_block(&mut self, input: &[i16], output: &mut [i16]) -> (usize, usize) {
+        let mut read = 0;
+        let mut written = 0;
+        loop {
+            if self.phase < self.up {
+                if written == output.len() {
+                    break;
+                }
+                output[written] = self.filter(self.phase as usize);
+                written += 1;
+                self.phase += self.down;
+            } else {
+                if read == input.len() {
+                    break;
+                }
+                self.history[self.pos] = input[read];
+                self.pos += 1;
+                if self.pos == TAPS {
+                    self.pos = 0;
+                }
+                read += 1;
+                self.phase -= self.up;
+            }
+        }
+        (read, written)
+    }
+
+    /// Forget the signal so far.
+    pub fn reset(&mut self) {
+        self.history = [0; TAPS];
+        self.pos = 0;
+        self.phase = self.up;
+    }
+
+    /// The output at `phase` after the newest input sample.
+    fn filter(&self, phase: usize) -> i16 {
+        let (newer, older) = self.history.split_at(self.pos);
+        let acc: i64 = older
+            .iter()
+            .chain(newer)
+            .zip(&self.taps[phase])
+            .map(|(&x, &tap)| i64::from(x) * i64::from(tap))
+            .sum();
+        let out = (acc + (1 << (TAP_FRAC - 1))) >> TAP_FRAC;
+        out.clamp(i16::MIN.into(), i16::MAX.into()) as i16
+    }
+}
+
+/// Greatest common divisor.
+const fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Modified Bessel function of the first kind, of order zero.
+const fn bessel_i0(x: f64) -> f64 {
+    let q = x * x / 4.0;
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut k = 1.0;
+    while term > 1e-12 * sum {
+        term *= q / (k * k);
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+/// The taps of a polyphase filter going up by `up` and down by `down`, in
+/// Q15, each phase reversed to run oldest first.
+///
+/// Each phase is scaled to sum to just under one, 32767, so that the single
+/// tap of one at the center of an interpolating filter still fits; the
+/// rounding is taken up by the phase's largest tap, as in the decimator.
+const fn design<const TAPS: usize, const PHASES: usize>(
+    up: u32,
+    down: u32,
+) -> [[i16; TAPS]; PHASES] {
+    let len = TAPS * up as usize;
+    let center = (len - 1) as f64 / 2.0;
+    // in cycles per input sample
+    let cutoff = if up < down {
+        0.5 * up as f64 / down as f64
+    } else {
+        0.5
+    };
+    let norm = bessel_i0(BETA);
+    let one = ((1 << TAP_FRAC) - 1) as f64;
+
+    let mut taps = [[0; TAPS]; PHASES];
+    let mut phase = 0;
+    while phase < up as usize {
+        let mut row = [0.0; TAPS];
+        let mut sum = 0.0;
+        let mut largest = 0;
+        let mut k = 0;
+        while k < TAPS {
+            // tap `k` weighs the input `k` samples before the newest, and
+            // this phase's output is `phase/up` of a sample after it
+            let m = (phase + k * up as usize) as f64 - center;
+            let x = 2.0 * cutoff * m / up as f64;
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                math::sin(core::f64::consts::PI * x) / (core::f64::consts::PI * x)
+            };
+            let r = if center == 0.0 { 0.0 } else { m / center };
+            let window = bessel_i0(BETA * math::sqrt(1.0 - r * r)) / norm;
+            row[k] = sinc * window;
+            sum += row[k];
+            if math::abs(row[k]) > math::abs(row[largest]) {
+                largest = k;
+            }
+            k += 1;
+        }
+
+        let mut total = 0;
+        let mut k = 0;
+        while k < TAPS {
+            let tap = math::round(row[k] / sum * one) as i32;
+            taps[phase][TAPS - 1 - k] = tap as i16;
+            total += tap;
+            k += 1;
+        }
+        let tap = taps[phase][TAPS - 1 - largest] as i32 + (one as i32 - total);
+        taps[phase][TAPS - 1 - largest] = math::clamp_i16(tap as i64);
+        phase += 1;
+    }
+    taps
+}
@@ -0,0 +1,676 @@
+//! Recorded clips, stored one after another in a region of flash.
+//!
+//! The first two sectors of the region hold the clip index, the rest holds
+//! clip data. Every clip starts on a sector boundary and is recorded
+//! sequentially; each sector is erased when recording enters it, so apart
+//! from formatting, which erases the whole region, nothing past the last
+//! clip has to be erased up front. Deleting a clip only marks its index
+//! entry; [`Store::compact`] moves the remaining clips down over the freed
+//! sectors.
+//!
+//! The index is a header followed by fixed-size entries, allocated in order.
+//! An entry is programmed when recording starts, its length when recording
+//! finishes, and a flag when the clip is deleted; each of these only clears
+//! bits, so the index never has to be erased while clips are being recorded.
+//! Compaction writes a new index into the other index sector and bumps its
+//! generation; [`Store::mount`] uses whichever index is newest.
+//!
+//! Clip data is programmed as [`journal`] chunks, a page each, so that
+//! recording survives power loss. Every step that matters is a program that
+//! either completes or is ignored: an entry counts once a flag is cleared
+//! after the rest of it was programmed, and its length once another flag is
+//! cleared after the length was. A recording that was never finished,
+//! because the recorder was dropped or power was lost, is cut back to its
+//! last valid chunk: its length is found by walking its chunks, and
+//! mounting moves the next clip past whatever it programmed. Only
+//! [`Store::compact`] can lose data to a power cut.
+
+use crate::flash::{NorFlash, ERASED};
+use crate::g711::Law;
+use crate::journal::{self, PAYLOAD};
+use crate::SampleRate;
+
+/// Size of the pages the store programs.
+pub const PAGE_SIZE: usize = 256;
+
+/// Size of a header or entry in the index.
+const ENTRY_SIZE: usize = 32;
+
+const MAGIC: [u8; 8] = *b"VOICLIP3";
+
+// entry flags, each cleared once: when the entry starts being programmed,
+// when the clip is deleted, once the rest of the entry is programmed, and
+// once the length is
+const ALLOCATED: u8 = 1 << 0;
+const DELETED: u8 = 1 << 1;
+const VALID: u8 = 1 << 2;
+const FINISHED: u8 = 1 << 3;
+
+// length of a clip that is still being recorded
+const UNSET: u32 = u32::MAX;
+
+/// How the bytes of a clip encode samples.
+///
+/// The tags are the WAV format tags of the same encodings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Codec {
+    /// Signed 16-bit little-endian PCM.
+    Pcm16,
+    /// IMA ADPCM in blocks of [`BLOCK_ALIGN`](crate::adpcm::BLOCK_ALIGN) bytes.
+    ImaAdpcm,
+    /// G.711 µ-law, a byte per sample.
+    MuLaw,
+    /// G.711 A-law, a byte per sample.
+    ALaw,
+}
+
+impl Codec {
+    /// WAV format tag of the encoding.
+    pub const fn tag(self) -> u16 {
+        match self {
+            Codec::Pcm16 => 0x0001,
+            Codec::ImaAdpcm => 0x0011,
+            Codec::MuLaw => Law::MuLaw.tag(),
+            Codec::ALaw => Law::ALaw.tag(),
+        }
+    }
+
+    /// The encoding with WAV format tag `tag`, if it is supported.
+    pub const fn from_tag(tag: u16) -> Option<Codec> {
+        match tag {
+            0x0001 => Some(Codec::Pcm16),
+            0x0011 => Some(Codec::ImaAdpcm),
+            0x0007 => Some(Codec::MuLaw),
+            0x0006 => Some(Codec::ALaw),
+            _ => None,
+        }
+    }
+}
+
+/// A finished clip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clip {
+    /// Index entry of the clip; stays the same until the store is compacted.
+    pub id: u16,
+    /// Offset of the clip data from the start of the region.
+    pub start: u32,
+    /// Length of the clip data, in bytes. A clip whose recording was cut
+    /// off ends at its last valid chunk.
+    pub len: u32,
+    /// Rate the clip was recorded at.
+    pub sample_rate: SampleRate,
+    /// Encoding of the clip data.
+    pub codec: Codec,
+    /// Caller-defined time the recording started.
+    pub timestamp: u32,
+}
+
+/// Errors from the clip store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+    /// The flash failed.
+    Flash(E),
+    /// The region is not sector aligned, too small, or does not fit the
+    /// flash.
+    Geometry,
+    /// Neither index sector holds an index.
+    NotFormatted,
+    /// An index entry or chunk holds values the store never writes.
+    Corrupt,
+    /// Every index entry is in use; compacting frees deleted ones.
+    IndexFull,
+    /// The region is full.
+    Full,
+    /// There is no finished clip with that id.
+    NoSuchClip,
+}
+
+/// Index entry fields, as stored.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    flags: u8,
+    codec: u16,
+    start: u32,
+    len: u32,
+    sample_rate: u32,
+    timestamp: u32,
+}
+
+impl Entry {
+    fn parse(bytes: &[u8; ENTRY_SIZE]) -> Self {
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        Self {
+            flags: bytes[0],
+            codec: u16::from_le_bytes([bytes[2], bytes[3]]),
+            start: word(4),
+            len: word(8),
+            sample_rate: word(12),
+            timestamp: word(16),
+        }
+    }
+
+    fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [ERASED; ENTRY_SIZE];
+        bytes[0] = self.flags;
+        bytes[2..4].copy_from_slice(&self.codec.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.start.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.len.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.sample_rate.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.timestamp.to_le_bytes());
+        bytes
+    }
+
+    fn is_deleted(&self) -> bool {
+        self.flags & DELETED == 0
+    }
+
+    fn is_finished(&self) -> bool {
+        self.flags & FINISHED == 0
+    }
+
+    /// Whether the entry was programmed in full and not deleted since; an
+    /// entry that was cut off while being programmed is dead.
+    fn is_live(&self) -> bool {
+        self.flags & VALID == 0 && !self.is_deleted()
+    }
+
+    fn clip<E>(&self, id: u16, len: u32) -> Result<Clip, Error<E>> {
+        Ok(Clip {
+            id,
+            start: self.start,
+            len,
+            sample_rate: SampleRate::from_hz(self.sample_rate).ok_or(Error::Corrupt)?,
+            codec: Codec::from_tag(self.codec).ok_or(Error::Corrupt)?,
+            timestamp: self.timestamp,
+        })
+    }
+}
+
+/// A clip store in the region of flash from `base` to `base + len`.
+pub struct Store<F> {
+    flash: F,
+    base: u32,
+    len: u32,
+    // which index sector is current, and its generation
+    index: u32,
+    generation: u32,
+    // entries allocated in the current index
+    slots: u16,
+    // offset of the first sector past the last clip
+    head: u32,
+    page: [u8; PAGE_SIZE],
+}
+
+impl<F: NorFlash> Store<F> {
+    const SECTOR: u32 = F::ERASE_SIZE as u32;
+    const DATA: u32 = 2 * Self::SECTOR;
+
+    fn new(flash: F, base: u32, len: u32) -> Result<Self, Error<F::Error>> {
+        let sector = Self::SECTOR;
+        let aligned = PAGE_SIZE.is_multiple_of(F::WRITE_SIZE)
+            && F::ERASE_SIZE.is_multiple_of(PAGE_SIZE)
+            && F::ERASE_SIZE >= 2 * ENTRY_SIZE
+            && base.is_multiple_of(sector)
+            && len.is_multiple_of(sector)
+            // chunk sequence numbers are 16 bits
+            && len as usize / PAGE_SIZE <= 1 << 16;
+        let fits = base as usize + len as usize <= flash.capacity();
+        if !aligned || !fits || len <= Self::DATA {
+            return Err(Error::Geometry);
+        }
+        Ok(Self {
+            flash,
+            base,
+            len,
+            index: 0,
+            generation: 0,
+            slots: 0,
+            head: Self::DATA,
+            page: [ERASED; PAGE_SIZE],
+        })
+    }
+
+    /// Erase the region and start with an empty store.
+    ///
+    /// The data sectors are erased too: the new index starts again from
+    /// generation 0, so chunks left over from before would carry the same
+    /// tags as the new clips recorded over them.
+    pub fn format(flash: F, base: u32, len: u32) -> Result<Self, Error<F::Error>> {
+        let mut store = Self::new(flash, base, len)?;
+        store.erase(0, len)?;
+        store.write_header(0, 0)?;
+        Ok(store)
+    }
+
+    /// Open a store written earlier, recovering any interrupted recording.
+    pub fn mount(flash: F, base: u32, len: u32) -> Result<Self, Error<F::Error>> {
+        let mut store = Self::new(flash, base, len)?;
+        let mut newest = None;
+        for index in 0..2 {
+            let header = store.read_array::<ENTRY_SIZE>(index * Self::SECTOR)?;
+            if header[..8] != MAGIC {
+                continue;
+            }
+            let generation = u32::from_le_bytes(header[8..12].try_into().unwrap());
+            if newest.is_none_or(|(_, g)| generation > g) {
+                newest = Some((index, generation));
+            }
+        }
+        let (index, generation) = newest.ok_or(Error::NotFormatted)?;
+        store.index = index;
+        store.generation = generation;
+
+        while store.slots < store.max_slots() {
+            let id = store.slots;
+            let bytes = store.read_array::<ENTRY_SIZE>(store.entry_offset(id))?;
+            if bytes.iter().all(|&b| b == ERASED) {
+                break;
+            }
+            store.slots += 1;
+            let entry = Entry::parse(&bytes);
+            if !entry.is_live() {
+                continue;
+            }
+            if entry.start < Self::DATA || entry.start >= len {
+                return Err(Error::Corrupt);
+            }
+            let pages = if entry.is_finished() {
+                entry.len.div_ceil(PAYLOAD as u32)
+            } else {
+                // cut off: anything it programmed past its last valid chunk
+                // is either in the same sector, which the next clip starts
+                // past, or in the one after, which the next clip erases
+                let (len, pages) = store.scan(id, entry.start)?;
+                if len == 0 {
+                    store.program(store.entry_offset(id), &[!(ALLOCATED | DELETED)])?;
+                }
+                pages
+            };
+            if pages > (len - entry.start) / PAGE_SIZE as u32 {
+                return Err(Error::Corrupt);
+            }
+            store.head = store
+                .head
+                .max(store.round_up(entry.start + pages * PAGE_SIZE as u32));
+        }
+        Ok(store)
+    }
+
+    /// Give the flash back.
+    pub fn into_inner(self) -> F {
+        self.flash
+    }
+
+    /// Number of clips the index can hold.
+    pub fn max_slots(&self) -> u16 {
+        (F::ERASE_SIZE / ENTRY_SIZE - 1) as u16
+    }
+
+    /// Number of index entries in use, including deleted clips.
+    pub fn slots(&self) -> u16 {
+        self.slots
+    }
+
+    /// Bytes of clip data left for recording, before compacting.
+    pub fn free(&self) -> u32 {
+        (self.len - self.head) / PAGE_SIZE as u32 * PAYLOAD as u32
+    }
+
+    /// The finished, undeleted clips, oldest first.
+    pub fn clips(&mut self) -> Clips<'_, F> {
+        Clips {
+            store: self,
+            next: 0,
+        }
+    }
+
+    /// The clip with id `id`.
+    pub fn clip(&mut self, id: u16) -> Result<Clip, Error<F::Error>> {
+        if id >= self.slots {
+            return Err(Error::NoSuchClip);
+        }
+        self.resolve(id)?.ok_or(Error::NoSuchClip)
+    }
+
+    /// The clip in slot `id`, if it is live.
+    fn resolve(&mut self, id: u16) -> Result<Option<Clip>, Error<F::Error>> {
+        let entry = self.entry(id)?;
+        if !entry.is_live() {
+            return Ok(None);
+        }
+        let len = if entry.is_finished() {
+            entry.len
+        } else {
+            self.scan(id, entry.start)?.0
+        };
+        entry.clip(id, len).map(Some)
+    }
+
+    /// Tag of the chunks of a clip recorded in slot `id` of the current
+    /// index, telling them apart from stale chunks of clips that used the
+    /// same space before. Every bit of the generation is kept, so a tag is
+    /// never reused however many times the store is compacted.
+    fn tag(&self, id: u16) -> u64 {
+        (u64::from(self.generation) << 16) | u64::from(id)
+    }
+
+    /// Walk the chunks of the unfinished clip in slot `id`, returning its
+    /// length and how many chunks it has.
+    ///
+    /// The clip ends at the first chunk that is short, or followed by a page
+    /// that is not its next chunk.
+    fn scan(&mut self, id: u16, start: u32) -> Result<(u32, u32), Error<F::Error>> {
+        let tag = self.tag(id);
+        let (mut len, mut pages) = (0, 0);
+        while start + pages * (PAGE_SIZE as u32) < self.len {
+            let page = self.read_array::<PAGE_SIZE>(start + pages * PAGE_SIZE as u32)?;
+            match journal::open(&page) {
+                Some(chunk) if chunk.tag == tag && u32::from(chunk.seq) == pages => {
+                    len += chunk.len as u32;
+                    pages += 1;
+                    if chunk.len < PAYLOAD {
+                        break;
+                    }
+                }
+                _ => break,
+            }
+        }
+        Ok((len, pages))
+    }
+
+    /// Read clip data starting `offset` bytes into `clip`, returning how
+    /// many bytes were read.
+    pub fn read(
+        &mut self,
+        clip: &Clip,
+        offset: u32,
+        buf: &mut [u8],
+    ) -> Result<usize, Error<F::Error>> {
+        let n = (clip.len.saturating_sub(offset) as usize).min(buf.len());
+        let mut done = 0;
+        while done < n {
+            let at = offset as usize + done;
+            let (seq, within) = (at / PAYLOAD, at % PAYLOAD);
+            let page = self.read_array::<PAGE_SIZE>(clip.start + (seq * PAGE_SIZE) as u32)?;
+            // chunks keep the tag they were recorded with when compaction
+            // moves them, so only their position is checked
+            let chunk = journal::open(&page).ok_or(Error::Corrupt)?;
+            if usize::from(chunk.seq) != seq || within >= chunk.len {
+                return Err(Error::Corrupt);
+            }
+            let m = (chunk.len - within).min(n - done);
+            buf[done..done + m].copy_from_slice(&page[within..within + m]);
+            done += m;
+        }
+        Ok(n)
+    }
+
+    /// Start recording a new clip after the last one.
+    pub fn record(
+        &mut self,
+        sample_rate: SampleRate,
+        codec: Codec,
+        timestamp: u32,
+    ) -> Result<Recorder<'_, F>, Error<F::Error>> {
+        if self.slots == self.max_slots() {
+            return Err(Error::IndexFull);
+        }
+        if self.head >= self.len {
+            return Err(Error::Full);
+        }
+        let id = self.slots;
+        let start = self.head;
+        let entry = Entry {
+            flags: !ALLOCATED,
+            codec: codec.tag(),
+            start,
+            len: UNSET,
+            sample_rate: sample_rate.hz(),
+            timestamp,
+        };
+        let offset = self.entry_offset(id);
+        self.slots += 1;
+        self.program(offset, &entry.to_bytes())?;
+        self.program(offset, &[!(ALLOCATED | VALID)])?;
+        Ok(Recorder {
+            store: self,
+            id,
+            start,
+            len: 0,
+            pages: 0,
+            fill: 0,
+        })
+    }
+
+    /// Delete the clip with id `id`.
+    ///
+    /// The space it used is reclaimed by the next [`compact`](Self::compact).
+    pub fn delete(&mut self, id: u16) -> Result<(), Error<F::Error>> {
+        let clip = self.clip(id)?;
+        self.program(self.entry_offset(clip.id), &[!(ALLOCATED | DELETED)])
+    }
+
+    /// Move every clip down over the space of deleted ones, and rewrite the
+    /// index without the deleted entries.
+    ///
+    /// Clip ids change. Not safe against power loss: a cut while clips are
+    /// being moved loses the clips that had not been moved yet.
+    pub fn compact(&mut self) -> Result<(), Error<F::Error>> {
+        let old = self.index;
+        let new = 1 - old;
+        self.erase(new * Self::SECTOR, (new + 1) * Self::SECTOR)?;
+        let mut dest = Self::DATA;
+        let mut kept = 0;
+        for slot in 0..self.slots {
+            let Some(clip) = self.resolve(slot)? else {
+                continue;
+            };
+            let mut entry = self.entry(slot)?;
+            let len = clip.len.div_ceil(PAYLOAD as u32) * PAGE_SIZE as u32;
+            if entry.start != dest {
+                self.move_data(entry.start, dest, len)?;
+                entry.start = dest;
+            }
+            dest += self.round_up(len);
+            // a clip whose recording was cut off is finished by the move
+            entry.flags = !(ALLOCATED | VALID | FINISHED);
+            entry.len = clip.len;
+            self.program(Self::entry_offset_in(new, kept), &entry.to_bytes())?;
+            kept += 1;
+        }
+        self.write_header(new, self.generation.wrapping_add(1))?;
+        self.erase(old * Self::SECTOR, (old + 1) * Self::SECTOR)?;
+        self.slots = kept;
+        self.head = dest;
+        Ok(())
+    }
+
+    /// Copy `len` bytes of chunks from `src` to the lower `dest`, a page at
+    /// a time, erasing each destination sector as it is entered.
+    fn move_data(&mut self, src: u32, dest: u32, len: u32) -> Result<(), Error<F::Error>> {
+        let mut offset = 0;
+        while offset < len {
+            if (dest + offset).is_multiple_of(Self::SECTOR) {
+                self.erase(dest + offset, dest + offset + Self::SECTOR)?;
+            }
+            let mut page = [ERASED; PAGE_SIZE];
+            self.flash
+                .read(self.base + src + offset, &mut page)
+                .map_err(Error::Flash)?;
+            self.flash
+                .write(self.base + dest + offset, &page)
+                .map_err(Error::Flash)?;
+            offset += PAGE_SIZE as u32;
+        }
+        Ok(())
+    }
+
+    fn round_up(&self, offset: u32) -> u32 {
+        offset.div_ceil(Self::SECTOR) * Self::SECTOR
+    }
+
+    fn entry_offset(&self, id: u16) -> u32 {
+        Self::entry_offset_in(self.index, id)
+    }
+
+    fn entry_offset_in(index: u32, id: u16) -> u32 {
+        index * Self::SECTOR + (u32::from(id) + 1) * ENTRY_SIZE as u32
+    }
+
+    fn entry(&mut self, id: u16) -> Result<Entry, Error<F::Error>> {
+        let bytes = self.read_array::<ENTRY_SIZE>(self.entry_offset(id))?;
+        Ok(Entry::parse(&bytes))
+    }
+
+    fn write_header(&mut self, index: u32, generation: u32) -> Result<(), Error<F::Error>> {
+        let mut header = [ERASED; ENTRY_SIZE];
+        header[..8].copy_from_slice(&MAGIC);
+        header[8..12].copy_from_slice(&generation.to_le_bytes());
+        self.program(index * Self::SECTOR, &header)?;
+        self.index = index;
+        self.generation = generation;
+        Ok(())
+    }
+
+    fn read_array<const N: usize>(&mut self, offset: u32) -> Result<[u8; N], Error<F::Error>> {
+        let mut bytes = [0; N];
+        self.flash
+            .read(self.base + offset, &mut bytes)
+            .map_err(Error::Flash)?;
+        Ok(bytes)
+    }
+
+    /// Program `bytes` at `offset` in the region, leaving the rest of the
+    /// page they are in untouched.
+    fn program(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Error<F::Error>> {
+        let page_start = offset - offset % PAGE_SIZE as u32;
+        let at = (offset - page_start) as usize;
+        let mut page = [ERASED; PAGE_SIZE];
+        page[at..at + bytes.len()].copy_from_slice(bytes);
+        self.flash
+            .write(self.base + page_start, &page)
+            .map_err(Error::Flash)
+    }
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Error<F::Error>> {
+        self.flash
+            .erase(self.base + from, self.base + to)
+            .map_err(Error::Flash)
+    }
+}
+
+/// Iterator over the finished, undeleted clips of a [`Store`].
+pub struct Clips<'a, F> {
+    store: &'a mut Store<F>,
+    next: u16,
+}
+
+impl<F: NorFlash> Iterator for Clips<'_, F> {
+    type Item = Result<Clip, Error<F::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next < self.store.slots {
+            let id = self.next;
+            self.next += 1;
+            match self.store.resolve(id) {
+                Ok(Some(clip)) => return Some(Ok(clip)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
+
+/// A clip being recorded.
+///
+/// Data is programmed a chunk at a time as it comes in. Call
+/// [`finish`](Recorder::finish) to write out the last partial chunk and the
+/// length; a recorder dropped without finishing leaves a clip that ends at
+/// the last chunk it programmed, or none at all if it programmed none.
+pub struct Recorder<'a, F: NorFlash> {
+    store: &'a mut Store<F>,
+    id: u16,
+    start: u32,
+    len: u32,
+    // chunks programmed so far
+    pages: u32,
+    // bytes of the current chunk held in store.page
+    fill: usize,
+}
+
+impl<F: NorFlash> Recorder<'_, F> {
+    /// Id the clip will have.
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// Bytes recorded so far.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Whether nothing has been recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Append `bytes` to the clip.
+    ///
+    /// Fails with [`Error::Full`] once the region is full; the bytes that
+    /// fitted are kept.
+    pub fn write(&mut self, mut bytes: &[u8]) -> Result<(), Error<F::Error>> {
+        while !bytes.is_empty() {
+            if self.start + self.pages * PAGE_SIZE as u32 >= self.store.len {
+                return Err(Error::Full);
+            }
+            let n = (PAYLOAD - self.fill).min(bytes.len());
+            self.store.page[self.fill..self.fill + n].copy_from_slice(&bytes[..n]);
+            self.fill += n;
+            self.len += n as u32;
+            bytes = &bytes[n..];
+            if self.fill == PAYLOAD {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Program the chunk being filled, then its commit marker.
+    fn flush(&mut self) -> Result<(), Error<F::Error>> {
+        if self.fill == 0 {
+            return Ok(());
+        }
+        let store = &mut *self.store;
+        let tag = store.tag(self.id);
+        journal::seal(&mut store.page, tag, self.pages as u16, self.fill);
+        let offset = self.start + self.pages * PAGE_SIZE as u32;
+        if offset.is_multiple_of(Store::<F>::SECTOR) {
+            store.erase(offset, offset + Store::<F>::SECTOR)?;
+            // the next clip starts past this sector even if this one is
+            // never finished
+            store.head = offset + Store::<F>::SECTOR;
+        }
+        store
+            .flash
+            .write(store.base + offset, &store.page)
+            .map_err(Error::Flash)?;
+        store
+            .flash
+            .write(store.base + offset, &journal::commit_marker())
+            .map_err(Error::Flash)?;
+        self.pages += 1;
+        self.fill = 0;
+        Ok(())
+    }
+
+    /// Write out the rest of the clip and its length.
+    pub fn finish(mut self) -> Result<Clip, Error<F::Error>> {
+        self.flush()?;
+        let store = &mut *self.store;
+        let offset = store.entry_offset(self.id);
+        store.program(offset + 8, &self.len.to_le_bytes())?;
+        store.program(offset, &[!(ALLOCATED | VALID | FINISHED)])?;
+        store.clip(self.id)
+    }
+}
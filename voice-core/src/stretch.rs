@@ -0,0 +1,323 @@
+//! Playback at a different speed: time-stretching that keeps the pitch, and
+//! plain resampling that doesn't.
+//!
+//! A [`Stretcher`] sits between whatever decodes a clip and the output,
+//! as it does in a [`Player`](crate::player::Player), taking samples in at
+//! one rate and giving them out at `1/speed` times it, from half to double
+//! speed. In [`Mode::Wsola`] it keeps the pitch of the
+//! voice, so a memo can be skimmed at double speed or picked through at
+//! half without anyone turning into a chipmunk or a giant; in
+//! [`Mode::Resample`] it plays the samples back faster or slower, like a
+//! tape, pitch and all.
+//!
+//! WSOLA, waveform-similarity overlap-add after Verhelst and Roelands,
+//! cuts the input into Hann-windowed grains of `N/4` samples and lays them
+//! back down overlapping by half, `N/8` samples apart. Taking the grains
+//! from the input `speed` times as far apart as they are laid down changes
+//! the duration without touching what is in each grain, and so keeps the
+//! pitch; but grains from arbitrary places don't line up where they
+//! overlap, and the voice would come out rough. So each grain is taken from
+//! up to `N/16` samples either side of where it would be, wherever its
+//! start best matches how the last grain would have gone on: the least sum
+//! of absolute differences over the overlap. At normal speed that is
+//! exactly where the last grain left off, and the input comes out
+//! untouched. `N/16` lines up voices down to a pitch of `16/N` of the
+//! sample rate: 1024 samples at 8 kHz reaches 62 Hz, with grains of 32 ms.
+//!
+//! Resampling reads the input `speed` samples apart, interpolating between
+//! them. Speeding up that way puts whatever is above half the sample rate
+//! over `speed` beyond the new Nyquist frequency, where it folds back down;
+//! there's little of voice up there, but it is a tape effect rather than a
+//! clean one.
+//!
+//! A clip's last grain or so stays in the stretcher until more input
+//! pushes it out, so follow the end of one with `N/4` samples of silence.
+//!
+//! # Cost
+//!
+//! Every `N/8` samples out WSOLA windows a grain, a multiply a sample, and
+//! searches `N/8 + 1` starts over `N/8` samples, about `N²/64`
+//! subtractions: 16 000 for 1024 samples, every 128, which is a few
+//! percent of the CPU at 8 kHz. The buffers take `6N` bytes.
+
+use crate::math;
+
+/// Fraction bits of the speed, the read position and the window.
+const FRAC: u32 = 15;
+
+/// Slowest and fastest speeds, in percent.
+const MIN_SPEED: u32 = 50;
+const MAX_SPEED: u32 = 200;
+
+/// How a [`Stretcher`] changes speed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Waveform-similarity overlap-add, which keeps the pitch.
+    #[default]
+    Wsola,
+    /// Resampling, which changes the pitch with the speed.
+    Resample,
+}
+
+/// Settings for a [`Stretcher`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Playback speed, in percent, from 50 to 200.
+    pub speed_percent: u32,
+    /// How the speed is changed.
+    pub mode: Mode,
+}
+
+impl Default for Config {
+    /// Normal speed, keeping the pitch when it changes.
+    fn default() -> Self {
+        Self {
+            speed_percent: 100,
+            mode: Mode::Wsola,
+        }
+    }
+}
+
+/// Speed changer on signed 16-bit samples, holding up to `N` samples of
+/// input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stretcher<const N: usize> {
+    mode: Mode,
+    /// Speed, in Q15.
+    speed: u32,
+    /// The first half of a periodic Hann window of `N/4`, in Q15; the
+    /// second half is one less this.
+    window: [u16; N],
+    /// Input not yet done with, oldest first, and how much of it there is.
+    input: [i16; N],
+    filled: usize,
+    /// Where in `input` the next grain would be taken from, or the next
+    /// sample read when resampling, in Q15.
+    next: u32,
+    /// Where in `input` the last grain would have gone on.
+    natural: usize,
+    /// The second half of the last grain, waiting to be overlapped.
+    tail: [i16; N],
+    /// Output ready to go, and how much of it has gone.
+    ready: [i16; N],
+    ready_len: usize,
+    ready_pos: usize,
+}
+
+impl<const N: usize> Stretcher<N> {
+    /// Grain length.
+    const GRAIN: usize = N / 4;
+
+    /// Samples between grains in the output, and the overlap between them.
+    const HOP: usize = N / 8;
+
+    /// How far a grain may be moved either way to line it up.
+    const SEARCH: usize = N / 16;
+
+    /// Create a speed changer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is not a power of two from 64 to 4096, or the speed is
+    /// outside 50% to 200%.
+    pub const fn new(config: &Config) -> Self {
+        assert!(
+            N.is_power_of_two() && N >= 64 && N <= 4096,
+            "buffer must be a power of two from 64 to 4096 samples"
+        );
+        let one = (1u32 << FRAC) as f64;
+        let mut window = [0; N];
+        let mut i = 0;
+        while i < Self::HOP {
+            let x = 2.0 * core::f64::consts::PI * i as f64 / Self::GRAIN as f64;
+            window[i] = (one * (0.5 - 0.5 * math::cos(x)) + 0.5) as u16;
+            i += 1;
+        }
+        let mut stretcher = Self {
+            mode: config.mode,
+            speed: speed(config.speed_percent),
+            window,
+            input: [0; N],
+            filled: 0,
+            next: 0,
+            natural: 0,
+            tail: [0; N],
+            ready: [0; N],
+            ready_len: 0,
+            ready_pos: 0,
+        };
+        stretcher.start();
+        stretcher
+    }
+
+    /// How the speed is changed.
+    pub const fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The playback speed, in percent.
+    pub const fn speed_percent(&self) -> u32 {
+        (self.speed * 100 + (1 << (FRAC - 1))) >> FRAC
+    }
+
+    /// Change the playback speed, carrying on from where it is.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `speed_percent` is outside 50% to 200%.
+    pub fn set_speed(&mut self, speed_percent: u32) {
+        self.speed = speed(speed_percent);
+    }
+
+    /// Run `input` through into `output`, as far as both go.
+    ///
+    /// Returns how many input samples were taken and how many output
+    /// samples were written. Input stops being taken once `output` is full.
+    pub fn push_block(&mut self, input: &[i16], output: &mut [i16]) -> (usize, usize) {
+        let mut read = 0;
+        let mut written = 0;
+        loop {
+            let ready = &self.ready[self.ready_pos..self.ready_len];
+            let n = ready.len().min(output.len() - written);
+            output[written..written + n].copy_from_slice(&ready[..n]);
+            self.ready_pos += n;
+            written += n;
+            if written == output.len() {
+                break;
+            }
+            let made = match self.mode {
+                Mode::Wsola => self.grain(),
+                Mode::Resample => self.resample(),
+            };
+            if made {
+                continue;
+            }
+            let n = (input.len() - read).min(N - self.filled);
+            if n == 0 {
+                break;
+            }
+            self.input[self.filled..self.filled + n].copy_from_slice(&input[read..read + n]);
+            self.filled += n;
+            read += n;
+        }
+        (read, written)
+    }
+
+    /// Forget the input so far.
+    pub fn reset(&mut self) {
+        self.input = [0; N];
+        self.tail = [0; N];
+        self.ready = [0; N];
+        self.ready_len = 0;
+        self.ready_pos = 0;
+        self.start();
+    }
+
+    /// Set up the input for the first grain or sample. WSOLA starts with
+    /// room to search before the first grain, in silence.
+    const fn start(&mut self) {
+        let lead = match self.mode {
+            Mode::Wsola => Self::SEARCH,
+            Mode::Resample => 0,
+        };
+        self.filled = lead;
+        self.next = (lead as u32) << FRAC;
+        self.natural = lead;
+    }
+
+    /// Sample `i` of the grain starting at `start`, windowed. The falling
+    /// half is what the rising half leaves of the sample, so where grains
+    /// line up exactly they add back up to it exactly.
+    fn taper(&self, start: usize, i: usize) -> i32 {
+        let rising = |x: i32, i: usize| (x * i32::from(self.window[i]) + (1 << (FRAC - 1))) >> FRAC;
+        let x = i32::from(self.input[start + i]);
+        if i < Self::HOP {
+            rising(x, i)
+        } else {
+            x - rising(x, i - Self::HOP)
+        }
+    }
+
+    /// Drop the first `n` samples of input.
+    fn consume(&mut self, n: usize) {
+        self.input.copy_within(n..self.filled, 0);
+        self.filled -= n;
+    }
+
+    /// Overlap-add the next grain, if there is the input for it.
+    fn grain(&mut self) -> bool {
+        let nominal = (self.next >> FRAC) as usize;
+        if self.filled < (nominal + Self::SEARCH + Self::GRAIN).max(self.natural + Self::HOP) {
+            return false;
+        }
+
+        // outwards from where the grain would be, so the nearest of equal
+        // matches wins
+        let mismatch = |start: usize| -> u32 {
+            self.input[start..start + Self::HOP]
+                .iter()
+                .zip(&self.input[self.natural..self.natural + Self::HOP])
+                .map(|(&a, &b)| i32::from(a).abs_diff(b.into()))
+                .sum()
+        };
+        let mut best = (mismatch(nominal), nominal);
+        for offset in 1..=Self::SEARCH {
+            for start in [nominal + offset, nominal - offset] {
+                let m = mismatch(start);
+                if m < best.0 {
+                    best = (m, start);
+                }
+            }
+        }
+        let start = best.1;
+
+        for i in 0..Self::HOP {
+            let (rising, falling) = (self.taper(start, i), self.taper(start, Self::HOP + i));
+            let y = i32::from(self.tail[i]) + rising;
+            self.ready[i] = y.clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+            self.tail[i] = falling as i16;
+        }
+        self.ready_len = Self::HOP;
+        self.ready_pos = 0;
+
+        self.natural = start + Self::HOP;
+        self.next += self.speed * Self::HOP as u32;
+        let done = self
+            .natural
+            .min((self.next >> FRAC) as usize - Self::SEARCH);
+        self.consume(done);
+        self.natural -= done;
+        self.next -= (done as u32) << FRAC;
+        true
+    }
+
+    /// Interpolate the next sample, if there is the input for it.
+    fn resample(&mut self) -> bool {
+        let whole = (self.next >> FRAC) as usize;
+        if self.filled < whole + 2 {
+            let done = whole.min(self.filled);
+            self.consume(done);
+            self.next -= (done as u32) << FRAC;
+            return false;
+        }
+        let fraction = (self.next & ((1 << FRAC) - 1)) as i32;
+        let (a, b) = (
+            i32::from(self.input[whole]),
+            i32::from(self.input[whole + 1]),
+        );
+        self.ready[0] = (a + (((b - a) * fraction + (1 << (FRAC - 1))) >> FRAC)) as i16;
+        self.ready_len = 1;
+        self.ready_pos = 0;
+        self.next += self.speed;
+        true
+    }
+}
+
+/// `percent` in Q15, checked.
+const fn speed(percent: u32) -> u32 {
+    assert!(
+        percent >= MIN_SPEED && percent <= MAX_SPEED,
+        "speed must be from 50% to 200%"
+    );
+    ((percent << FRAC) + 50) / 100
+}
@@ -0,0 +1,234 @@
+//! Voice activity detection: is someone speaking right now?
+//!
+//! The [`Vad`] cuts the signal into frames of [`FRAME_MS`] and decides for
+//! each whether it holds speech, from two measurements:
+//!
+//! - its energy, compared with an estimate of the background noise. The
+//!   estimate follows the quietest frames straight down and creeps up
+//!   slowly, so it adapts to the room without being dragged up by the
+//!   speech itself;
+//! - its zero-crossing rate. Voiced speech concentrates its energy below a
+//!   couple of kHz and crosses zero comparatively rarely, where broadband
+//!   noise, such as wind or a rustle against the microphone, crosses it at
+//!   close to half the sample rate. Frames that cross too often are taken
+//!   for noise however loud they are.
+//!
+//! A frame that passes both is active. Speech keeps being reported for a
+//! hangover time after the last active frame, which bridges the gaps
+//! between words and the quiet, noise-like consonants at their ends.
+//!
+//! Energies are worked with in decibels, as 1/256ths of a dB relative to
+//! full scale, so thresholds are simple differences.
+
+use crate::math;
+use crate::process::Processor;
+use crate::SampleRate;
+
+/// Length of a frame, in ms.
+pub const FRAME_MS: u32 = 10;
+
+/// Fraction bits of the decibel values.
+const DB_FRAC: u32 = 8;
+
+/// `10·log10(2)` in Q8, to take base-2 logarithms to decibels.
+const DB_PER_OCTAVE: i32 = 771;
+
+/// `log2` of the mean square of a full-scale square wave.
+const FULL_SCALE_LOG2: i32 = 30;
+
+/// How much slower the noise floor rises during speech than in pauses.
+const SPEECH_RISE_DIVISOR: i32 = 8;
+
+/// Settings for a [`Vad`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// How far above the noise floor a frame's energy must be to count as
+    /// speech, in dB.
+    pub threshold_db: u32,
+    /// Most zero crossings per second a frame can have to count as speech.
+    pub max_crossings_hz: u32,
+    /// How fast the noise floor estimate rises when the frames get louder,
+    /// in dB per second. It falls as fast as the frames do.
+    pub floor_rise_db_per_s: u32,
+    /// How long speech is still reported after the last active frame, in ms.
+    pub hangover_ms: u32,
+}
+
+impl Default for Config {
+    /// Settings for speech into the device's microphone.
+    fn default() -> Self {
+        Self {
+            threshold_db: 9,
+            max_crossings_hz: 3000,
+            floor_rise_db_per_s: 3,
+            hangover_ms: 300,
+        }
+    }
+}
+
+/// What the detector made of one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    /// Whether there is speech, counting the hangover.
+    pub speech: bool,
+    /// Whether this frame on its own looked like speech.
+    pub active: bool,
+    /// Mean energy of the frame, in 1/256ths of a dB relative to full scale.
+    pub energy: i32,
+    /// The noise floor estimate the frame was compared with, in the same
+    /// units.
+    pub floor: i32,
+    /// Number of times the frame crossed zero.
+    pub crossings: u32,
+}
+
+/// Voice activity detector on signed 16-bit samples.
+///
+/// As a [`Processor`] it passes samples through unchanged, so it can sit in
+/// a [`Chain`](crate::process::Chain) and be asked whether there is speech
+/// as the samples go by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vad {
+    /// Samples per frame.
+    frame_len: u32,
+    /// Threshold over the floor, in Q8 dB.
+    threshold: i32,
+    max_crossings: u32,
+    /// Most the floor rises per frame, in Q8 dB.
+    rise: i32,
+    /// Hangover, in frames.
+    hangover: u32,
+
+    /// Samples so far in this frame.
+    count: u32,
+    /// Sum of squares so far in this frame.
+    energy: u64,
+    crossings: u32,
+    /// Whether the last sample was negative.
+    negative: bool,
+
+    /// Noise floor estimate in Q8 dB, once there is one.
+    floor: Option<i32>,
+    /// Frames of hangover left.
+    held: u32,
+    speech: bool,
+}
+
+impl Vad {
+    /// Create a detector for signals at `rate`.
+    pub const fn new(config: &Config, rate: SampleRate) -> Self {
+        let frame_len = rate.hz() * FRAME_MS / 1000;
+        Self {
+            frame_len,
+            threshold: (config.threshold_db << DB_FRAC) as i32,
+            max_crossings: config.max_crossings_hz * FRAME_MS / 1000,
+            rise: ((config.floor_rise_db_per_s << DB_FRAC) * FRAME_MS / 1000) as i32,
+            hangover: config.hangover_ms / FRAME_MS,
+            count: 0,
+            energy: 0,
+            crossings: 0,
+            negative: false,
+            floor: None,
+            held: 0,
+            speech: false,
+        }
+    }
+
+    /// Whether there was speech in the last complete frame.
+    pub fn is_speech(&self) -> bool {
+        self.speech
+    }
+
+    /// The noise floor estimate in 1/256ths of a dB relative to full scale,
+    /// or `None` before the first frame.
+    pub fn floor(&self) -> Option<i32> {
+        self.floor
+    }
+
+    /// Number of samples in a frame.
+    pub fn frame_len(&self) -> u32 {
+        self.frame_len
+    }
+
+    /// Take one sample, returning the decision for the frame it completes,
+    /// if it does.
+    pub fn push(&mut self, sample: i16) -> Option<Frame> {
+        let square = i32::from(sample) * i32::from(sample);
+        self.energy += square as u64;
+        let negative = sample < 0;
+        if negative != self.negative {
+            self.crossings += 1;
+        }
+        self.negative = negative;
+        self.count += 1;
+        if self.count < self.frame_len {
+            return None;
+        }
+
+        let energy = db((self.energy / u64::from(self.frame_len)) as u32);
+        let crossings = self.crossings;
+        self.count = 0;
+        self.energy = 0;
+        self.crossings = 0;
+
+        let floor = self.floor.unwrap_or(energy);
+        let active = energy - floor >= self.threshold && crossings <= self.max_crossings;
+        if active {
+            self.held = self.hangover;
+            self.speech = true;
+        } else if self.held > 0 {
+            self.held -= 1;
+        } else {
+            self.speech = false;
+        }
+
+        // follow the quietest frames down at once, and anything else up
+        // slowly, more slowly still while someone is talking
+        let rise = if active {
+            self.rise / SPEECH_RISE_DIVISOR
+        } else {
+            self.rise
+        };
+        self.floor = Some(if energy < floor {
+            energy
+        } else {
+            floor + (energy - floor).min(rise.max(1))
+        });
+
+        Some(Frame {
+            speech: self.speech,
+            active,
+            energy,
+            floor,
+            crossings,
+        })
+    }
+
+    /// Forget the signal so far, including the noise floor.
+    pub fn reset(&mut self) {
+        self.count = 0;
+        self.energy = 0;
+        self.crossings = 0;
+        self.negative = false;
+        self.floor = None;
+        self.held = 0;
+        self.speech = false;
+    }
+}
+
+/// Mean square `energy` in Q8 dB relative to full scale.
+fn db(energy: u32) -> i32 {
+    let log2 = math::log2_q16(energy.max(1)) - (FULL_SCALE_LOG2 << 16);
+    (log2 * DB_PER_OCTAVE) >> 16
+}
+
+impl Processor for Vad {
+    fn process(&mut self, sample: i16) -> i16 {
+        self.push(sample);
+        sample
+    }
+
+    fn reset(&mut self) {
+        Vad::reset(self);
+    }
+}
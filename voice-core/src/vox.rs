@@ -0,0 +1,262 @@
+//! Voice-operated recording: record while someone is speaking.
+//!
+//! A [`Vox`] watches the processed sample stream with a [`Vad`] and drives a
+//! [`Sink`] through the recordings it finds:
+//!
+//! ```text
+//!          arm                speech             no speech
+//! Idle ─────────▶ Armed ─────────────▶ Recording ─────────▶ Hangover
+//!   ▲               ▲                       ▲                  │
+//!   │               │                       └──── speech ──────┤
+//!   └── disarm ─────┴────────────── silence timeout ───────────┘
+//! ```
+//!
+//! While armed it keeps the last moments of sound in a pre-roll ring
+//! buffer, and a recording starts with that, so the beginning of the first
+//! syllable, from before the detector was sure, isn't lost. A recording
+//! stops once there has been no speech for the silence timeout, which
+//! counts from when the detector stops reporting speech, after its own
+//! hangover. Disarming stops a recording in progress.
+//!
+//! The firmware doesn't run a VOX: recording into the clip store from the
+//! main loop is out of scope for now. Erasing a flash sector takes tens of
+//! milliseconds with interrupts off, longer than a capture block lasts, so
+//! the store can't be driven from the loop until erases are kept from
+//! stalling capture. Until then VOX recording runs on the host, into any
+//! [`Sink`].
+
+use crate::vad::Vad;
+use crate::SampleRate;
+
+/// Where a [`Vox`] sends what it records.
+pub trait Sink {
+    /// Error returned when recording fails.
+    type Error;
+
+    /// A recording starts.
+    fn start(&mut self) -> Result<(), Self::Error>;
+
+    /// Append `samples` to the recording.
+    fn write(&mut self, samples: &[i16]) -> Result<(), Self::Error>;
+
+    /// The recording is over.
+    fn stop(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<T: Sink + ?Sized> Sink for &mut T {
+    type Error = T::Error;
+
+    fn start(&mut self) -> Result<(), Self::Error> {
+        T::start(self)
+    }
+
+    fn write(&mut self, samples: &[i16]) -> Result<(), Self::Error> {
+        T::write(self, samples)
+    }
+
+    fn stop(&mut self) -> Result<(), Self::Error> {
+        T::stop(self)
+    }
+}
+
+/// Settings for a [`Vox`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// How much sound from before speech was detected starts a recording,
+    /// in ms.
+    pub pre_roll_ms: u32,
+    /// How long without speech stops a recording, in ms.
+    pub silence_timeout_ms: u32,
+}
+
+impl Default for Config {
+    /// Settings for dictating notes.
+    fn default() -> Self {
+        Self {
+            pre_roll_ms: 500,
+            silence_timeout_ms: 1500,
+        }
+    }
+}
+
+/// What a [`Vox`] is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum State {
+    /// Switched off.
+    Idle,
+    /// Listening for speech, keeping the pre-roll.
+    Armed,
+    /// Recording speech.
+    Recording,
+    /// Still recording, waiting to see if the speech comes back.
+    Hangover,
+}
+
+/// Voice-operated recording with an `N` sample pre-roll buffer.
+#[derive(Debug, Clone)]
+pub struct Vox<const N: usize> {
+    vad: Vad,
+    state: State,
+    /// Pre-roll, in samples.
+    pre_roll: usize,
+    /// Silence timeout, in samples.
+    timeout: u32,
+    /// Samples of silence timeout left, in hangover.
+    remaining: u32,
+
+    buf: [i16; N],
+    /// Where the next sample goes.
+    pos: usize,
+    /// How many samples the buffer holds.
+    filled: usize,
+}
+
+impl<const N: usize> Vox<N> {
+    /// Create an idle VOX for signals at `rate`, detecting speech with `vad`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pre-roll doesn't fit in `N` samples at `rate`.
+    pub const fn new(config: &Config, vad: Vad, rate: SampleRate) -> Self {
+        let pre_roll = (config.pre_roll_ms as u64 * rate.hz() as u64 / 1000) as usize;
+        assert!(pre_roll <= N, "pre-roll must fit in the buffer");
+        Self {
+            vad,
+            state: State::Idle,
+            pre_roll,
+            timeout: (config.silence_timeout_ms as u64 * rate.hz() as u64 / 1000) as u32,
+            remaining: 0,
+            buf: [0; N],
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    /// What the VOX is doing.
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Whether a recording is in progress.
+    pub fn is_recording(&self) -> bool {
+        matches!(self.state, State::Recording | State::Hangover)
+    }
+
+    /// The speech detector.
+    pub fn vad(&self) -> &Vad {
+        &self.vad
+    }
+
+    /// Start listening for speech, if idle.
+    pub fn arm(&mut self) {
+        if self.state == State::Idle {
+            self.state = State::Armed;
+            self.clear();
+        }
+    }
+
+    /// Stop listening, stopping the recording if there is one.
+    pub fn disarm<S: Sink>(&mut self, sink: &mut S) -> Result<(), S::Error> {
+        let recording = self.is_recording();
+        self.state = State::Idle;
+        if recording {
+            sink.stop()?;
+        }
+        Ok(())
+    }
+
+    /// Take one sample, recording it into `sink` if a recording is in
+    /// progress.
+    ///
+    /// The speech detector sees every sample, even when idle, so its noise
+    /// floor is ready when the VOX is armed.
+    pub fn push<S: Sink>(&mut self, sample: i16, sink: &mut S) -> Result<(), S::Error> {
+        let frame = self.vad.push(sample);
+        let speech = frame.map(|frame| frame.speech);
+        match self.state {
+            State::Idle => {}
+            State::Armed => {
+                self.keep(sample);
+                if speech == Some(true) {
+                    // stay armed, to try again, if the recording can't start
+                    sink.start()?;
+                    self.state = State::Recording;
+                    if self.pre_roll == 0 {
+                        sink.write(&[sample])?;
+                    }
+                    self.drain(sink)?;
+                }
+            }
+            State::Recording => {
+                sink.write(&[sample])?;
+                if speech == Some(false) {
+                    self.state = State::Hangover;
+                    self.remaining = self.timeout;
+                }
+            }
+            State::Hangover => {
+                sink.write(&[sample])?;
+                self.remaining = self.remaining.saturating_sub(1);
+                if speech == Some(true) {
+                    self.state = State::Recording;
+                } else if self.remaining == 0 {
+                    self.state = State::Armed;
+                    self.clear();
+                    sink.stop()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Take every sample of `samples`, as [`push`](Vox::push) does.
+    pub fn push_block<S: Sink>(&mut self, samples: &[i16], sink: &mut S) -> Result<(), S::Error> {
+        for &sample in samples {
+            self.push(sample, sink)?;
+        }
+        Ok(())
+    }
+
+    /// Go back to idle and forget the signal so far, without telling the
+    /// sink about a recording in progress.
+    pub fn reset(&mut self) {
+        self.vad.reset();
+        self.state = State::Idle;
+        self.remaining = 0;
+        self.clear();
+    }
+
+    /// Add a sample to the pre-roll, dropping the oldest once it holds
+    /// enough.
+    fn keep(&mut self, sample: i16) {
+        if self.pre_roll == 0 {
+            return;
+        }
+        self.buf[self.pos] = sample;
+        self.pos += 1;
+        if self.pos == self.pre_roll {
+            self.pos = 0;
+        }
+        self.filled = (self.filled + 1).min(self.pre_roll);
+    }
+
+    /// Write out the pre-roll, oldest first, and empty it.
+    fn drain<S: Sink>(&mut self, sink: &mut S) -> Result<(), S::Error> {
+        let ring = &self.buf[..self.pre_roll];
+        if self.filled < self.pre_roll {
+            sink.write(&ring[..self.filled])?;
+        } else {
+            let (newer, older) = ring.split_at(self.pos);
+            sink.write(older)?;
+            sink.write(newer)?;
+        }
+        self.clear();
+        Ok(())
+    }
+
+    /// Empty the pre-roll.
+    fn clear(&mut self) {
+        self.pos = 0;
+        self.filled = 0;
+    }
+}
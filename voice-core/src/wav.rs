@@ -0,0 +1,613 @@
+//! Streaming RIFF/WAVE writer and reader for mono clips.
+//!
+//! The writer emits the header up front with the sizes left at zero, streams
+//! the data out behind it, and patches the sizes in when it is finalized, so
+//! a clip can be exported while it is still being read out of flash. The
+//! reader walks the chunks up to `data` and then streams samples out of it,
+//! skipping chunks it does not know. Neither buffers more than a few bytes.
+//!
+//! Both handle the encodings of [`Codec`]: 16-bit PCM, µ-law and A-law with
+//! a `fact` chunk, and IMA ADPCM with the extended `fmt ` chunk that carries
+//! the samples per block. They convert between those and 12-bit
+//! offset-binary samples, or pass already encoded data through as is.
+
+use core::convert::Infallible;
+
+use crate::adpcm::{self, Encoder};
+use crate::g711::Law;
+use crate::store::Codec;
+use crate::{from_pcm16, to_pcm16};
+
+/// Where a [`WavWriter`] puts the file.
+pub trait ByteSink {
+    /// Error returned when writing fails.
+    type Error;
+
+    /// Append `bytes`.
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    /// Overwrite bytes already written, starting `offset` bytes from the
+    /// first.
+    fn patch(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl<T: ByteSink + ?Sized> ByteSink for &mut T {
+    type Error = T::Error;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        T::write(self, bytes)
+    }
+
+    fn patch(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        T::patch(self, offset, bytes)
+    }
+}
+
+#[cfg(feature = "std")]
+impl ByteSink for Vec<u8> {
+    type Error = Infallible;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Infallible> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn patch(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Infallible> {
+        let offset = offset as usize;
+        self[offset..offset + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// The buffer of a [`SliceSink`] is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferFull;
+
+/// A [`ByteSink`] that fills a buffer.
+#[derive(Debug)]
+pub struct SliceSink<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> SliceSink<'a> {
+    /// Sink writing to the start of `buf`.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    /// The bytes written so far.
+    pub fn written(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl ByteSink for SliceSink<'_> {
+    type Error = BufferFull;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), BufferFull> {
+        let end = self.len + bytes.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(BufferFull)?
+            .copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    fn patch(&mut self, offset: u32, bytes: &[u8]) -> Result<(), BufferFull> {
+        let offset = offset as usize;
+        self.buf[..self.len]
+            .get_mut(offset..offset + bytes.len())
+            .ok_or(BufferFull)?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Where a [`WavReader`] gets the file from.
+pub trait ByteSource {
+    /// Error returned when reading fails.
+    type Error;
+
+    /// Read up to `buf.len()` bytes, returning how many were read; 0 means
+    /// the end was reached.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+impl<T: ByteSource + ?Sized> ByteSource for &mut T {
+    type Error = T::Error;
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        T::read(self, buf)
+    }
+}
+
+impl ByteSource for &[u8] {
+    type Error = Infallible;
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+        let n = buf.len().min(self.len());
+        buf[..n].copy_from_slice(&self[..n]);
+        *self = &self[n..];
+        Ok(n)
+    }
+}
+
+/// Errors from reading or writing a WAV file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+    /// The sink or source failed.
+    Io(E),
+    /// The file is not RIFF/WAVE, or has no `fmt ` chunk before its data.
+    NotWav,
+    /// The file is not mono, or uses an encoding this module does not
+    /// handle.
+    Unsupported,
+    /// The file ends inside a header.
+    Truncated,
+    /// An IMA ADPCM block header is invalid.
+    Corrupt,
+}
+
+/// Fields of the `fmt ` chunk for a mono file.
+#[derive(Debug, Clone, Copy)]
+struct Format {
+    codec: Codec,
+    sample_rate: u32,
+    block_align: u16,
+    bits: u16,
+}
+
+impl Format {
+    fn new(codec: Codec, sample_rate: u32) -> Self {
+        let (block_align, bits) = match codec {
+            Codec::Pcm16 => (2, 16),
+            Codec::MuLaw | Codec::ALaw => (1, 8),
+            Codec::ImaAdpcm => (adpcm::BLOCK_ALIGN as u16, 4),
+        };
+        Self {
+            codec,
+            sample_rate,
+            block_align,
+            bits,
+        }
+    }
+
+    fn samples_per_block(&self) -> u32 {
+        match self.codec {
+            Codec::ImaAdpcm => adpcm::samples_per_block(self.block_align.into()) as u32,
+            _ => 1,
+        }
+    }
+
+    fn byte_rate(&self) -> u32 {
+        let rate = u64::from(self.sample_rate) * u64::from(self.block_align);
+        (rate / u64::from(self.samples_per_block())) as u32
+    }
+
+    /// Samples in `len` bytes of data.
+    fn samples_in(&self, len: u32) -> u32 {
+        let block = u32::from(self.block_align);
+        let partial = len % block;
+        let tail = match self.codec {
+            Codec::ImaAdpcm if partial >= 4 => (partial - 4) * 2 + 1,
+            _ => 0,
+        };
+        len / block * self.samples_per_block() + tail
+    }
+}
+
+/// Writes a mono WAV file to a [`ByteSink`].
+///
+/// Either hand it samples with [`write_samples`](Self::write_samples) and
+/// let it encode them, or hand it already encoded data with
+/// [`write_data`](Self::write_data); mixing the two in one IMA ADPCM file
+/// breaks the block structure.
+pub struct WavWriter<S> {
+    sink: S,
+    format: Format,
+    // offsets of the sizes to patch at the end
+    fact: Option<u32>,
+    data: u32,
+    data_len: u32,
+    samples: u32,
+    adpcm: Encoder,
+}
+
+impl<S: ByteSink> WavWriter<S> {
+    /// Start a file of `codec` data at `sample_rate`, writing its header.
+    pub fn new(mut sink: S, codec: Codec, sample_rate: u32) -> Result<Self, Error<S::Error>> {
+        let format = Format::new(codec, sample_rate);
+        let mut header = [0; 60];
+        let mut len = 0;
+        let mut put = |bytes: &[u8]| {
+            header[len..len + bytes.len()].copy_from_slice(bytes);
+            len += bytes.len();
+        };
+        put(b"RIFF");
+        put(&0u32.to_le_bytes());
+        put(b"WAVE");
+        put(b"fmt ");
+        let fmt_len: u32 = match codec {
+            Codec::Pcm16 => 16,
+            Codec::MuLaw | Codec::ALaw => 18,
+            Codec::ImaAdpcm => 20,
+        };
+        put(&fmt_len.to_le_bytes());
+        put(&codec.tag().to_le_bytes());
+        put(&1u16.to_le_bytes());
+        put(&sample_rate.to_le_bytes());
+        put(&format.byte_rate().to_le_bytes());
+        put(&format.block_align.to_le_bytes());
+        put(&format.bits.to_le_bytes());
+        match codec {
+            Codec::Pcm16 => {}
+            Codec::MuLaw | Codec::ALaw => put(&0u16.to_le_bytes()),
+            Codec::ImaAdpcm => {
+                put(&2u16.to_le_bytes());
+                put(&(format.samples_per_block() as u16).to_le_bytes());
+            }
+        }
+        // every format but PCM needs the sample count spelled out
+        let fact = (codec != Codec::Pcm16).then(|| {
+            put(b"fact");
+            put(&4u32.to_le_bytes());
+            put(&0u32.to_le_bytes());
+            20 + fmt_len + 8
+        });
+        put(b"data");
+        put(&0u32.to_le_bytes());
+        let data = fact.map_or(20 + fmt_len, |at| at + 4) + 4;
+        sink.write(&header[..len]).map_err(Error::Io)?;
+        Ok(Self {
+            sink,
+            format,
+            fact,
+            data,
+            data_len: 0,
+            samples: 0,
+            adpcm: Encoder::new(),
+        })
+    }
+
+    /// Encoding of the data.
+    pub fn codec(&self) -> Codec {
+        self.format.codec
+    }
+
+    /// Samples written so far.
+    pub fn len(&self) -> u32 {
+        self.samples
+    }
+
+    /// Whether no samples have been written yet.
+    pub fn is_empty(&self) -> bool {
+        self.samples == 0
+    }
+
+    /// Encode and append 12-bit offset-binary samples.
+    pub fn write_samples(&mut self, samples: &[u16]) -> Result<(), Error<S::Error>> {
+        match self.format.codec {
+            Codec::Pcm16 => {
+                let mut bytes = [0; 64];
+                for chunk in samples.chunks(bytes.len() / 2) {
+                    for (pair, &sample) in bytes.chunks_exact_mut(2).zip(chunk) {
+                        pair.copy_from_slice(&to_pcm16(sample).to_le_bytes());
+                    }
+                    self.append(&bytes[..chunk.len() * 2])?;
+                }
+            }
+            Codec::MuLaw | Codec::ALaw => {
+                let law = if self.format.codec == Codec::MuLaw {
+                    Law::MuLaw
+                } else {
+                    Law::ALaw
+                };
+                let mut bytes = [0; 64];
+                for chunk in samples.chunks(bytes.len()) {
+                    law.encode_block(chunk, &mut bytes);
+                    self.append(&bytes[..chunk.len()])?;
+                }
+            }
+            Codec::ImaAdpcm => {
+                for &sample in samples {
+                    if let Some(block) = self.adpcm.push(to_pcm16(sample)) {
+                        self.sink.write(block).map_err(Error::Io)?;
+                        self.data_len += block.len() as u32;
+                    }
+                }
+            }
+        }
+        self.samples += samples.len() as u32;
+        Ok(())
+    }
+
+    /// Append data that is already encoded, holding `samples` samples.
+    pub fn write_data(&mut self, bytes: &[u8], samples: u32) -> Result<(), Error<S::Error>> {
+        self.append(bytes)?;
+        self.samples += samples;
+        Ok(())
+    }
+
+    fn append(&mut self, bytes: &[u8]) -> Result<(), Error<S::Error>> {
+        self.sink.write(bytes).map_err(Error::Io)?;
+        self.data_len += bytes.len() as u32;
+        Ok(())
+    }
+
+    /// Write out any partial IMA ADPCM block, pad the data to an even
+    /// length, and patch in the sizes.
+    pub fn finalize(mut self) -> Result<S, Error<S::Error>> {
+        if let Some(block) = self.adpcm.flush() {
+            self.sink.write(block).map_err(Error::Io)?;
+            self.data_len += block.len() as u32;
+        }
+        let pad = self.data_len % 2;
+        if pad == 1 {
+            self.sink.write(&[0]).map_err(Error::Io)?;
+        }
+        let riff_len = self.data + 4 + self.data_len + pad - 8;
+        let patches = [
+            (Some(4), riff_len),
+            (self.fact, self.samples),
+            (Some(self.data), self.data_len),
+        ];
+        for (offset, value) in patches {
+            if let Some(offset) = offset {
+                self.sink
+                    .patch(offset, &value.to_le_bytes())
+                    .map_err(Error::Io)?;
+            }
+        }
+        Ok(self.sink)
+    }
+}
+
+/// Reads a mono WAV file from a [`ByteSource`].
+pub struct WavReader<R> {
+    source: R,
+    format: Format,
+    len: u32,
+    // data bytes and samples not read yet
+    data_left: u32,
+    samples_left: u32,
+    // IMA ADPCM decoder, the bytes left in its block, and the sample of the
+    // high nibble of the last byte read
+    adpcm: adpcm::State,
+    block_left: u32,
+    pending: Option<i16>,
+}
+
+impl<R: ByteSource> WavReader<R> {
+    /// Read the header, up to the start of the data.
+    pub fn new(mut source: R) -> Result<Self, Error<R::Error>> {
+        let mut riff = [0; 12];
+        read_exact(&mut source, &mut riff)?;
+        if riff[..4] != *b"RIFF" || riff[8..] != *b"WAVE" {
+            return Err(Error::NotWav);
+        }
+        let mut format = None;
+        let mut fact = None;
+        loop {
+            let mut chunk = [0; 8];
+            read_exact(&mut source, &mut chunk)?;
+            let len = u32::from_le_bytes(chunk[4..].try_into().unwrap());
+            match &chunk[..4] {
+                b"fmt " => {
+                    let mut fmt = [0; 20];
+                    let n = (len as usize).min(fmt.len());
+                    if n < 16 {
+                        return Err(Error::NotWav);
+                    }
+                    read_exact(&mut source, &mut fmt[..n])?;
+                    skip(&mut source, len - n as u32 + len % 2)?;
+                    format = Some(parse_format(&fmt[..n])?);
+                }
+                b"fact" if len >= 4 => {
+                    let mut count = [0; 4];
+                    read_exact(&mut source, &mut count)?;
+                    skip(&mut source, len - 4 + len % 2)?;
+                    fact = Some(u32::from_le_bytes(count));
+                }
+                b"data" => {
+                    let format = format.ok_or(Error::NotWav)?;
+                    let len_samples = fact.unwrap_or_else(|| format.samples_in(len));
+                    return Ok(Self {
+                        source,
+                        format,
+                        len: len_samples,
+                        data_left: len,
+                        samples_left: len_samples,
+                        adpcm: adpcm::State::default(),
+                        block_left: 0,
+                        pending: None,
+                    });
+                }
+                _ => skip(&mut source, len + len % 2)?,
+            }
+        }
+    }
+
+    /// Encoding of the data.
+    pub fn codec(&self) -> Codec {
+        self.format.codec
+    }
+
+    /// Sample rate, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.format.sample_rate
+    }
+
+    /// Size of the blocks the data comes in, in bytes.
+    pub fn block_align(&self) -> u16 {
+        self.format.block_align
+    }
+
+    /// Number of samples in the file.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Whether the file has no samples.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Give the source back.
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+
+    /// Read the encoded data as is, returning how many bytes were read.
+    ///
+    /// Do not mix with [`read_samples`](Self::read_samples).
+    pub fn read_data(&mut self, buf: &mut [u8]) -> Result<usize, Error<R::Error>> {
+        let n = buf.len().min(self.data_left as usize);
+        let n = self.source.read(&mut buf[..n]).map_err(Error::Io)?;
+        self.data_left -= n as u32;
+        Ok(n)
+    }
+
+    /// Decode samples into `out` as 12-bit offset-binary samples, returning
+    /// how many were read; fewer than `out.len()` only at the end.
+    pub fn read_samples(&mut self, out: &mut [u16]) -> Result<usize, Error<R::Error>> {
+        let want = out.len().min(self.samples_left as usize);
+        let n = match self.format.codec {
+            Codec::Pcm16 => {
+                let mut bytes = [0; 64];
+                let mut n = 0;
+                while n < want {
+                    let m = (want - n).min(bytes.len() / 2);
+                    let got = self.fill(&mut bytes[..m * 2])? / 2;
+                    for (sample, pair) in out[n..n + got].iter_mut().zip(bytes.chunks_exact(2)) {
+                        *sample = from_pcm16(i16::from_le_bytes([pair[0], pair[1]]));
+                    }
+                    n += got;
+                    if got < m {
+                        break;
+                    }
+                }
+                n
+            }
+            Codec::MuLaw | Codec::ALaw => {
+                let law = if self.format.codec == Codec::MuLaw {
+                    Law::MuLaw
+                } else {
+                    Law::ALaw
+                };
+                let mut bytes = [0; 64];
+                let mut n = 0;
+                while n < want {
+                    let m = (want - n).min(bytes.len());
+                    let got = self.fill(&mut bytes[..m])?;
+                    law.decode_block(&bytes[..got], &mut out[n..n + got]);
+                    n += got;
+                    if got < m {
+                        break;
+                    }
+                }
+                n
+            }
+            Codec::ImaAdpcm => {
+                let mut n = 0;
+                while n < want {
+                    match self.next_adpcm()? {
+                        Some(sample) => out[n] = from_pcm16(sample),
+                        None => break,
+                    }
+                    n += 1;
+                }
+                n
+            }
+        };
+        self.samples_left -= n as u32;
+        Ok(n)
+    }
+
+    /// Read as much of `buf` as the data has left.
+    fn fill(&mut self, buf: &mut [u8]) -> Result<usize, Error<R::Error>> {
+        let want = buf.len().min(self.data_left as usize);
+        let mut n = 0;
+        while n < want {
+            let got = self.source.read(&mut buf[n..want]).map_err(Error::Io)?;
+            if got == 0 {
+                break;
+            }
+            n += got;
+        }
+        self.data_left -= n as u32;
+        Ok(n)
+    }
+
+    /// Decode the next IMA ADPCM sample.
+    fn next_adpcm(&mut self) -> Result<Option<i16>, Error<R::Error>> {
+        if let Some(sample) = self.pending.take() {
+            return Ok(Some(sample));
+        }
+        if self.block_left == 0 {
+            let block_len = u32::from(self.format.block_align).min(self.data_left);
+            let mut header = [0; 4];
+            if block_len < 4 || self.fill(&mut header)? < 4 {
+                return Ok(None);
+            }
+            self.adpcm = adpcm::parse_header(&header).map_err(|_| Error::Corrupt)?;
+            self.block_left = block_len - 4;
+            return Ok(Some(self.adpcm.predictor));
+        }
+        let mut byte = [0; 1];
+        if self.fill(&mut byte)? == 0 {
+            return Ok(None);
+        }
+        self.block_left -= 1;
+        let low = self.adpcm.decode(byte[0] & 0x0f);
+        self.pending = Some(self.adpcm.decode(byte[0] >> 4));
+        Ok(Some(low))
+    }
+}
+
+fn parse_format<E>(fmt: &[u8]) -> Result<Format, Error<E>> {
+    let half = |at: usize| u16::from_le_bytes([fmt[at], fmt[at + 1]]);
+    let tag = half(0);
+    let channels = half(2);
+    let sample_rate = u32::from_le_bytes(fmt[4..8].try_into().unwrap());
+    let block_align = half(12);
+    let bits = half(14);
+    let codec = Codec::from_tag(tag).ok_or(Error::Unsupported)?;
+    let supported = channels == 1
+        && match codec {
+            Codec::Pcm16 => bits == 16 && block_align == 2,
+            Codec::MuLaw | Codec::ALaw => bits == 8 && block_align == 1,
+            Codec::ImaAdpcm => bits == 4 && block_align > 4,
+        };
+    if !supported {
+        return Err(Error::Unsupported);
+    }
+    Ok(Format {
+        codec,
+        sample_rate,
+        block_align,
+        bits,
+    })
+}
+
+fn read_exact<R: ByteSource>(source: &mut R, buf: &mut [u8]) -> Result<(), Error<R::Error>> {
+    let mut n = 0;
+    while n < buf.len() {
+        match source.read(&mut buf[n..]).map_err(Error::Io)? {
+            0 => return Err(Error::Truncated),
+            got => n += got,
+        }
+    }
+    Ok(())
+}
+
+fn skip<R: ByteSource>(source: &mut R, mut len: u32) -> Result<(), Error<R::Error>> {
+    let mut scratch = [0; 32];
+    while len > 0 {
+        let n = (len as usize).min(scratch.len());
+        read_exact(source, &mut scratch[..n])?;
+        len -= n as u32;
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+//! Rolling window of recent samples, averaged to smooth out the ADC signal.
+
+/// A moving average over the last `len` samples, backed by an `N` sample
+/// buffer.
+///
+/// The window keeps a running sum, so pushing a sample and reading the
+/// average take the same time whatever the length. `len` can be changed at
+/// run time as long as it stays within `N`, so the amount of smoothing can
+/// be tuned without reallocating the buffer.
+#[derive(Debug, Clone)]
+pub struct Window<const N: usize> {
+    /// The last `N` samples, oldest at `pos`.
+    buf: [u16; N],
+    len: usize,
+    pos: usize,
+    /// Where the oldest of the last `len` samples is.
+    tail: usize,
+    /// Sum of the last `len` samples.
+    sum: u32,
+}
+
+impl<const N: usize> Window<N> {
+    /// Create an empty (all zero) window averaging over `len` samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is zero or larger than `N`, or if `N` is so large that
+    /// the sum of a full window could overflow.
+    pub const fn new(len: usize) -> Self {
+        assert!(
+            N <= (u32::MAX / u16::MAX as u32) as usize,
+            "window buffer too large"
+        );
+        assert!(len > 0 && len <= N, "window length must be in 1..=N");
+        Self {
+            buf: [0; N],
+            len,
+            pos: 0,
+            tail: N - len,
+            sum: 0,
+        }
+    }
+
+    /// Number of samples the window averages over.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Always `false`: a window averages over at least one sample.
+    pub const fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Change the number of samples averaged over.
+    ///
+    /// The average carries on over the most recent `len` samples, which
+    /// takes one pass over them to add up.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is zero or larger than `N`.
+    pub fn set_len(&mut self, len: usize) {
+        assert!(len > 0 && len <= N, "window length must be in 1..=N");
+        self.len = len;
+        self.tail = (self.pos + N - len) % N;
+        self.sum = (0..len)
+            .map(|i| u32::from(self.buf[(self.tail + i) % N]))
+            .sum();
+    }
+
+    /// Add a sample to the window, replacing the oldest one.
+    pub fn push(&mut self, sample: u16) {
+        self.sum = self.sum - u32::from(self.buf[self.tail]) + u32::from(sample);
+        self.buf[self.pos] = sample;
+        self.pos = next(self.pos, N);
+        self.tail = next(self.tail, N);
+    }
+
+    /// Mean of the samples in the window.
+    pub fn average(&self) -> u16 {
+        // The mean of u16 values always fits in a u16.
+        (self.sum / self.len as u32) as u16
+    }
+}
+
+/// The index after `i` in a ring of `n`, without a division.
+const fn next(i: usize, n: usize) -> usize {
+    if i + 1 == n {
+        0
+    } else {
+        i + 1
+    }
+}
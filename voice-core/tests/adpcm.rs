@@ -0,0 +1,142 @@
+use voice_core::adpcm::{self, Encoder, Error, State, BLOCK_ALIGN};
+use voice_core::{from_pcm16, to_pcm16};
+
+// A decaying chirp with a step in it, and what the IMA reference coder
+// (CPython's audioop) makes of it, starting from the first sample with step
+// index 0.
+const INPUT: [i16; 33] = [
+    0, 234, 912, 1993, 3415, 5077, 6810, 8366, 9412, 9570, 8497, 6021, 2299, -2047, -5937, -8062,
+    -7384, -3783, 1496, 6012, 13200, 10036, 4253, -177, 300, 5575, 11108, 11520, 6166, 790, 1744,
+    8001, 11382,
+];
+const CODES: [u8; 32] = [
+    7, 7, 7, 7, 7, 7, 7, 7, 4, 8, 10, 12, 12, 11, 9, 0, 3, 6, 3, 5, 9, 11, 10, 8, 4, 3, 8, 11, 12,
+    0, 4, 2,
+];
+const DECODED: [i16; 33] = [
+    0, 11, 41, 104, 240, 533, 1164, 2521, 5431, 9173, 8670, 6383, 2641, -1888, -6148, -7808, -7305,
+    -4103, 1302, 6458, 13824, 10883, 4643, 591, -145, 5882, 11555, 10819, 6132, 653, 1389, 7416,
+    11468,
+];
+
+fn reference_block() -> [u8; 20] {
+    let mut block = [0; 20];
+    for (i, pair) in CODES.chunks(2).enumerate() {
+        block[4 + i] = pair[0] | pair[1] << 4;
+    }
+    block
+}
+
+fn tone(len: usize) -> Vec<i16> {
+    (0..len)
+        .map(|i| (9000.0 * (i as f64 * 0.07).sin() + 3000.0 * (i as f64 * 0.31).sin()) as i16)
+        .collect()
+}
+
+#[test]
+fn encoder_matches_the_reference_coder() {
+    let mut encoder = Encoder::<20>::new();
+    assert_eq!(Encoder::<20>::SAMPLES, INPUT.len());
+    let mut blocks = Vec::new();
+    encoder.encode(&INPUT, |block| blocks.push(*block));
+    assert_eq!(blocks, [reference_block()]);
+}
+
+#[test]
+fn decoder_matches_the_reference_coder() {
+    let mut out = [0; 40];
+    assert_eq!(adpcm::decode_block(&reference_block(), &mut out), Ok(33));
+    assert_eq!(out[..33], DECODED);
+
+    let mut state = State::default();
+    let decoded: Vec<_> = CODES.iter().map(|&c| state.decode(c)).collect();
+    assert_eq!(decoded, DECODED[1..]);
+    assert_eq!(
+        state,
+        State {
+            predictor: 11468,
+            index: 70
+        }
+    );
+}
+
+#[test]
+fn blocks_carry_the_step_index_over() {
+    let mut encoder = Encoder::<20>::new();
+    let mut blocks = Vec::new();
+    encoder.encode(&[INPUT, INPUT].concat(), |block| blocks.push(*block));
+    assert_eq!(blocks.len(), 2);
+    // the second block starts from the first sample, as is, with the index
+    // the first one ended on
+    assert_eq!(blocks[1][..4], [0, 0, 70, 0]);
+}
+
+#[test]
+fn tone_round_trips_closely() {
+    let input = tone(10 * Encoder::<BLOCK_ALIGN>::SAMPLES);
+    let mut encoder = Encoder::<BLOCK_ALIGN>::new();
+    let mut output = Vec::new();
+    encoder.encode(&input, |block| {
+        let mut samples = [0; adpcm::samples_per_block(BLOCK_ALIGN)];
+        assert_eq!(adpcm::decode_block(block, &mut samples), Ok(samples.len()));
+        output.extend_from_slice(&samples);
+    });
+    assert_eq!(output.len(), input.len());
+
+    let signal: f64 = input.iter().map(|&s| f64::from(s).powi(2)).sum();
+    let noise: f64 = input
+        .iter()
+        .zip(&output)
+        .map(|(&a, &b)| (f64::from(a) - f64::from(b)).powi(2))
+        .sum();
+    let snr = 10.0 * (signal / noise).log10();
+    assert!(snr > 25.0, "SNR {snr:.1} dB");
+}
+
+#[test]
+fn partial_block_decodes_to_what_was_pushed() {
+    let input = tone(Encoder::<BLOCK_ALIGN>::SAMPLES + 10);
+    let mut encoder = Encoder::<BLOCK_ALIGN>::new();
+    let mut blocks = 0;
+    encoder.encode(&input, |_| blocks += 1);
+    assert_eq!(blocks, 1);
+
+    let tail = encoder.flush().unwrap().to_vec();
+    // header and 9 codes, the last byte half used
+    assert_eq!(tail.len(), 4 + 5);
+    let mut out = [0; 16];
+    assert_eq!(adpcm::decode_block(&tail, &mut out), Ok(11));
+    assert_eq!(out[0], input[input.len() - 10]);
+    assert_eq!(encoder.flush(), None);
+}
+
+#[test]
+fn decoding_stops_when_the_output_is_full() {
+    let mut out = [0; 5];
+    assert_eq!(adpcm::decode_block(&reference_block(), &mut out), Ok(5));
+    assert_eq!(out, DECODED[..5]);
+}
+
+#[test]
+fn bad_blocks_are_rejected() {
+    let mut out = [0; 8];
+    assert_eq!(
+        adpcm::decode_block(&[0, 0, 0], &mut out),
+        Err(Error::ShortBlock)
+    );
+    assert_eq!(
+        adpcm::decode_block(&[0, 0, 89, 0, 0], &mut out),
+        Err(Error::BadStepIndex)
+    );
+}
+
+#[test]
+fn adc_samples_convert_to_pcm16_and_back() {
+    for code in 0..=4095 {
+        assert_eq!(from_pcm16(to_pcm16(code)), code);
+    }
+    assert_eq!(to_pcm16(0), i16::MIN);
+    assert_eq!(to_pcm16(2048), 0);
+    assert_eq!(to_pcm16(4095), 32752);
+    assert_eq!(to_pcm16(5000), 32752);
+}
@@ -0,0 +1,169 @@
+use std::f64::consts::PI;
+
+use voice_core::agc::{Agc, Config};
+use voice_core::process::Processor;
+use voice_core::SampleRate;
+
+const RATE: SampleRate = SampleRate::Hz16000;
+
+/// Samples per millisecond at `RATE`.
+const MS: usize = 16;
+
+/// A 440 Hz tone at `dbfs`, lasting `ms`, starting at sample `start`.
+fn tone(dbfs: f64, ms: usize, start: usize) -> impl Iterator<Item = i16> {
+    let amplitude = 32767.0 * 10f64.powf(dbfs / 20.0);
+    let w = 2.0 * PI * 440.0 / f64::from(RATE.hz());
+    (start..start + ms * MS).map(move |i| (amplitude * (w * i as f64).sin()).round() as i16)
+}
+
+/// A tone whose level steps through `steps` of (dBFS, ms).
+fn steps(steps: &[(f64, usize)]) -> Vec<i16> {
+    let mut signal = Vec::new();
+    for &(dbfs, ms) in steps {
+        let start = signal.len();
+        signal.extend(tone(dbfs, ms, start));
+    }
+    signal
+}
+
+/// Peak level of `samples`, in dBFS.
+fn peak_dbfs(samples: &[i16]) -> f64 {
+    let peak = samples.iter().map(|s| s.unsigned_abs()).max().unwrap();
+    20.0 * (f64::from(peak) / 32767.0).log10()
+}
+
+fn run(agc: &mut Agc, input: &[i16]) -> Vec<i16> {
+    input.iter().map(|&s| agc.process(s)).collect()
+}
+
+/// Peak level of the output in a `ms` long stretch starting at `at_ms`.
+fn level_at(output: &[i16], at_ms: usize, ms: usize) -> f64 {
+    peak_dbfs(&output[at_ms * MS..(at_ms + ms) * MS])
+}
+
+#[test]
+fn levels_are_brought_to_the_target() {
+    for input in [-36.0, -24.0, -12.0, -6.0, -1.0] {
+        let mut agc = Agc::new(&Config::default(), RATE);
+        let output = run(&mut agc, &steps(&[(input, 1500)]));
+        let level = level_at(&output, 1000, 500);
+        assert!(
+            (level + 6.0).abs() < 0.5,
+            "{input} dBFS in: {level:.2} dBFS out"
+        );
+    }
+}
+
+#[test]
+fn gain_stops_at_the_maximum() {
+    // 40 dB below the target would need 40 dB of gain, but only 30 are
+    // allowed
+    let mut agc = Agc::new(&Config::default(), RATE);
+    let output = run(&mut agc, &steps(&[(-46.0, 1500)]));
+    assert_eq!(agc.gain(), (4096.0 * 10f64.powf(1.5)).round() as u32);
+    let level = level_at(&output, 1000, 500);
+    assert!((level + 16.0).abs() < 0.5, "{level:.2} dBFS");
+}
+
+#[test]
+fn loud_steps_are_caught_within_the_attack_time() {
+    let config = Config::default();
+    let mut agc = Agc::new(&config, RATE);
+    // quiet speech, then the speaker comes 24 dB closer
+    let output = run(&mut agc, &steps(&[(-30.0, 1000), (-6.0, 500)]));
+    // the step overshoots until the follower catches up...
+    assert!(level_at(&output, 1000, 3) > -1.0);
+    // ...which takes a few attack time constants, the follower only moving
+    // on the samples near the crests that are above it
+    let attack = config.attack_ms as usize;
+    assert!((level_at(&output, 1000 + 5 * attack, 5) + 6.0).abs() < 2.0);
+    assert!((level_at(&output, 1000 + 20 * attack, 100) + 6.0).abs() < 0.5);
+}
+
+#[test]
+fn quiet_steps_wait_for_the_hold_then_release() {
+    let config = Config::default();
+    let mut agc = Agc::new(&config, RATE);
+    let output = run(&mut agc, &steps(&[(-6.0, 1000), (-30.0, 4000)]));
+    let hold = config.hold_ms as usize;
+    // through the hold the gain stays put, so the output drops with the input
+    let held = level_at(&output, 1000 + 20, hold - 40);
+    assert!((held + 30.0).abs() < 0.5, "{held:.2} dBFS");
+    // after one release time constant it is partway back up
+    let released = level_at(&output, 1000 + hold + config.release_ms as usize, 30);
+    assert!((-24.0..-10.0).contains(&released), "{released:.2} dBFS");
+    // and after several it is at the target
+    let settled = level_at(&output, 4500, 500);
+    assert!((settled + 6.0).abs() < 0.5, "{settled:.2} dBFS");
+}
+
+#[test]
+fn noise_below_the_floor_is_not_brought_up() {
+    let mut agc = Agc::new(&Config::default(), RATE);
+    // speech, then a long pause with only faint hiss
+    let speech = run(&mut agc, &steps(&[(-12.0, 1000)]));
+    let gain = agc.gain();
+    let pause = run(&mut agc, &steps(&[(-60.0, 5000)]));
+    assert_eq!(agc.gain(), gain);
+    assert!(level_at(&pause, 4000, 1000) < -50.0);
+    assert!((level_at(&speech, 500, 500) + 6.0).abs() < 0.5);
+
+    // a fresh AGC stays at unity gain in silence
+    let mut agc = Agc::new(&Config::default(), RATE);
+    run(&mut agc, &steps(&[(-60.0, 2000)]));
+    assert_eq!(agc.gain(), 4096);
+}
+
+#[test]
+fn full_scale_input_is_not_wrapped() {
+    let config = Config {
+        max_gain_db: 40,
+        ..Config::default()
+    };
+    let mut agc = Agc::new(&config, RATE);
+    // a burst out of a quiet stretch, before the gain can come down
+    let output = run(&mut agc, &steps(&[(-40.0, 1000), (0.0, 5)]));
+    let burst = &output[1000 * MS..];
+    assert!(burst.contains(&i16::MAX) && burst.contains(&i16::MIN));
+    let input = steps(&[(-40.0, 1000), (0.0, 5)]);
+    for (x, y) in input.iter().zip(&output) {
+        assert!(i32::from(*x) * i32::from(*y) >= 0);
+    }
+}
+
+#[test]
+fn reset_starts_over() {
+    let mut agc = Agc::new(&Config::default(), RATE);
+    let input = steps(&[(-30.0, 300), (-3.0, 300)]);
+    let first = run(&mut agc, &input);
+    agc.reset();
+    assert_eq!((agc.gain(), agc.level()), (4096, 0));
+    assert_eq!(run(&mut agc, &input), first);
+}
+
+#[test]
+fn settings_scale_with_the_sample_rate() {
+    // the same tone at two rates ends up at the same place at the same time
+    let config = Config::default();
+    let mut slow = Agc::new(&config, SampleRate::Hz8000);
+    let mut fast = Agc::new(&config, SampleRate::Hz16000);
+    let w = |rate: SampleRate| 2.0 * PI * 300.0 / f64::from(rate.hz());
+    let level = 32767.0 * 10f64.powf(-30.0 / 20.0);
+    for i in 0..4000 {
+        slow.process((level * (w(SampleRate::Hz8000) * i as f64).sin()) as i16);
+    }
+    for i in 0..8000 {
+        fast.process((level * (w(SampleRate::Hz16000) * i as f64).sin()) as i16);
+    }
+    assert!(slow.gain().abs_diff(fast.gain()) < 4096 / 20);
+}
+
+#[test]
+#[should_panic]
+fn noise_floor_must_be_below_the_target() {
+    let config = Config {
+        noise_floor_dbfs: -3,
+        ..Config::default()
+    };
+    Agc::new(&config, RATE);
+}
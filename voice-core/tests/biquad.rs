@@ -0,0 +1,317 @@
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+use voice_core::biquad::{Cascade, Design, Q15, Q31};
+use voice_core::process::Processor;
+use voice_core::SampleRate;
+
+/// A design worked out with `std` floating point, straight from the
+/// cookbook, to check the `const` one against.
+fn cookbook(shape: &str, fs: f64, f0: f64, q: f64, gain_db: f64) -> Design {
+    let w0 = 2.0 * PI * f0 / fs;
+    let (sin, cos) = w0.sin_cos();
+    let alpha = sin / (2.0 * q);
+    let a = 10f64.powf(gain_db / 40.0);
+    let root = 2.0 * a.sqrt() * alpha;
+    let (b, den) = match shape {
+        "low_pass" => (
+            [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        ),
+        "high_pass" => (
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        ),
+        "band_pass" => ([alpha, 0.0, -alpha], [1.0 + alpha, -2.0 * cos, 1.0 - alpha]),
+        "notch" => (
+            [1.0, -2.0 * cos, 1.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        ),
+        "peaking" => (
+            [1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a],
+            [1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a],
+        ),
+        "low_shelf" => (
+            [
+                a * ((a + 1.0) - (a - 1.0) * cos + root),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                a * ((a + 1.0) - (a - 1.0) * cos - root),
+            ],
+            [
+                (a + 1.0) + (a - 1.0) * cos + root,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                (a + 1.0) + (a - 1.0) * cos - root,
+            ],
+        ),
+        "high_shelf" => (
+            [
+                a * ((a + 1.0) + (a - 1.0) * cos + root),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - root),
+            ],
+            [
+                (a + 1.0) - (a - 1.0) * cos + root,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - root,
+            ],
+        ),
+        _ => unreachable!(),
+    };
+    Design {
+        b: b.map(|b| b / den[0]),
+        a: [den[1] / den[0], den[2] / den[0]],
+    }
+}
+
+fn design(shape: &str, rate: SampleRate, f0: f64, q: f64, gain_db: f64) -> Design {
+    match shape {
+        "low_pass" => Design::low_pass(rate, f0, q),
+        "high_pass" => Design::high_pass(rate, f0, q),
+        "band_pass" => Design::band_pass(rate, f0, q),
+        "notch" => Design::notch(rate, f0, q),
+        "peaking" => Design::peaking(rate, f0, q, gain_db),
+        "low_shelf" => Design::low_shelf(rate, f0, q, gain_db),
+        "high_shelf" => Design::high_shelf(rate, f0, q, gain_db),
+        _ => unreachable!(),
+    }
+}
+
+const SHAPES: [&str; 7] = [
+    "low_pass",
+    "high_pass",
+    "band_pass",
+    "notch",
+    "peaking",
+    "low_shelf",
+    "high_shelf",
+];
+
+/// Gain of a design at `hz`, evaluated from its transfer function.
+fn reference_gain(d: &Design, rate: SampleRate, hz: f64) -> f64 {
+    let w = 2.0 * PI * hz / f64::from(rate.hz());
+    // |b0 + b1·z⁻¹ + b2·z⁻²| at z = e^jw
+    let mag = |c: [f64; 3]| {
+        let re = c[0] + c[1] * w.cos() + c[2] * (2.0 * w).cos();
+        let im = -c[1] * w.sin() - c[2] * (2.0 * w).sin();
+        re.hypot(im)
+    };
+    mag(d.b) / mag([1.0, d.a[0], d.a[1]])
+}
+
+/// Gain of `filter` at `hz`, measured by correlating its settled output
+/// with the input frequency.
+fn measured_gain(filter: &mut impl Processor, rate: SampleRate, hz: f64) -> f64 {
+    let amplitude = 4000.0;
+    let w = 2.0 * PI * hz / f64::from(rate.hz());
+    // long enough for a narrow or low filter to settle
+    let settle = rate.hz() as usize;
+    let len = rate.hz() as usize / 2;
+    let (mut re, mut im) = (0.0, 0.0);
+    for i in 0..settle + len {
+        let x = (amplitude * (w * i as f64).sin()).round() as i16;
+        let y = f64::from(filter.process(x));
+        if i >= settle {
+            re += y * (w * i as f64).cos();
+            im += y * (w * i as f64).sin();
+        }
+    }
+    2.0 * re.hypot(im) / len as f64 / amplitude
+}
+
+fn db(gain: f64) -> f64 {
+    20.0 * gain.log10()
+}
+
+/// Check a quantized section against its design at a spread of frequencies
+/// that aren't too deep in a stop band to measure.
+fn check<S: Processor>(d: &Design, section: impl Fn() -> S, rate: SampleRate, tolerance: f64) {
+    let nyquist = f64::from(rate.hz()) / 2.0;
+    for fraction in [0.003, 0.01, 0.03, 0.06, 0.1, 0.15, 0.2, 0.3, 0.4, 0.45] {
+        let hz = fraction * 2.0 * nyquist;
+        let reference = db(reference_gain(d, rate, hz));
+        if reference < -30.0 {
+            continue;
+        }
+        let measured = db(measured_gain(&mut section(), rate, hz));
+        assert!(
+            (measured - reference).abs() < tolerance,
+            "{d:?} at {hz} Hz: {measured:.3} dB, expected {reference:.3} dB"
+        );
+    }
+}
+
+#[test]
+fn designs_match_the_cookbook() {
+    for rate in SampleRate::ALL {
+        let fs = f64::from(rate.hz());
+        for shape in SHAPES {
+            for (f0, q, gain) in [(50.0, 0.7, 6.0), (1000.0, 2.0, -12.0), (3000.0, 0.5, 3.0)] {
+                let ours = design(shape, rate, f0, q, gain);
+                let theirs = cookbook(shape, fs, f0, q, gain);
+                let pairs = ours
+                    .b
+                    .iter()
+                    .chain(&ours.a)
+                    .zip(theirs.b.iter().chain(&theirs.a));
+                for (x, y) in pairs {
+                    assert!(
+                        (x - y).abs() < 1e-12,
+                        "{shape} {rate:?} {f0}: {ours:?} {theirs:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn q31_sections_match_the_design() {
+    for rate in [SampleRate::Hz8000, SampleRate::Hz16000, SampleRate::Hz44100] {
+        for shape in SHAPES {
+            // a low corner as well as mid-band ones
+            for (f0, q, gain) in [(60.0, 0.7, 9.0), (800.0, 3.0, -12.0), (2500.0, 0.7, 6.0)] {
+                let d = design(shape, rate, f0, q, gain);
+                check(&d, || d.q31(), rate, 0.05);
+            }
+        }
+    }
+}
+
+#[test]
+fn q15_sections_match_mid_band_designs() {
+    for rate in [SampleRate::Hz8000, SampleRate::Hz16000] {
+        for shape in SHAPES {
+            for (f0, q, gain) in [(500.0, 0.7, 9.0), (1200.0, 2.0, -12.0), (2500.0, 0.7, 6.0)] {
+                let d = design(shape, rate, f0, q, gain);
+                check(&d, || d.q15(), rate, 0.3);
+            }
+        }
+    }
+}
+
+#[test]
+fn butterworth_cascade_is_maximally_flat() {
+    let rate = SampleRate::Hz16000;
+    let design = |k| Design::low_pass(rate, 1000.0, Design::butterworth_q(2, k));
+    let filter = || Cascade::new([design(0).q31(), design(1).q31()]);
+    for hz in [100.0, 300.0, 600.0, 1000.0, 1500.0, 2000.0] {
+        let expected = reference_gain(&design(0), rate, hz) * reference_gain(&design(1), rate, hz);
+        let expected = db(expected);
+        let measured = db(measured_gain(&mut filter(), rate, hz));
+        assert!(
+            (measured - expected).abs() < 0.05,
+            "{hz} Hz: {measured:.3} dB, expected {expected:.3} dB"
+        );
+        // flat through the passband
+        if hz < 400.0 {
+            assert!(measured.abs() < 0.01, "{hz} Hz: {measured:.3} dB");
+        }
+    }
+    let corner = db(measured_gain(&mut filter(), rate, 1000.0));
+    assert!((corner + 3.01).abs() < 0.02, "{corner:.3} dB");
+    assert!((Design::butterworth_q(1, 0) - FRAC_1_SQRT_2).abs() < 1e-15);
+}
+
+#[test]
+fn designs_can_be_made_at_compile_time() {
+    const TELEPHONE: Cascade<Q15, 2> = Cascade::new([
+        Design::high_pass(SampleRate::Hz8000, 300.0, FRAC_1_SQRT_2).q15(),
+        Design::low_pass(SampleRate::Hz8000, 3400.0, FRAC_1_SQRT_2).q15(),
+    ]);
+    let at_run_time = Cascade::new([
+        Design::high_pass(SampleRate::Hz8000, 300.0, FRAC_1_SQRT_2).q15(),
+        Design::low_pass(SampleRate::Hz8000, 3400.0, FRAC_1_SQRT_2).q15(),
+    ]);
+    assert_eq!(TELEPHONE, at_run_time);
+}
+
+#[test]
+fn coefficients_keep_enough_integer_bits() {
+    // a low-pass needs one integer bit for a1, which is nearly -2
+    let (c, frac) = Design::low_pass(SampleRate::Hz16000, 1000.0, FRAC_1_SQRT_2)
+        .q15()
+        .coefficients();
+    assert_eq!(frac, 14);
+    assert!(c[3] > 1 << 14);
+    // an 18 dB shelf at a low corner needs four, for b1 near -2·10^(18/20)
+    let (c, frac) = Design::high_shelf(SampleRate::Hz44100, 50.0, FRAC_1_SQRT_2, 18.0)
+        .q31()
+        .coefficients();
+    assert_eq!(frac, 27);
+    assert!(c[1] < -15 << 27);
+}
+
+#[test]
+fn silence_decays_to_exact_zero() {
+    let rate = SampleRate::Hz16000;
+    let mut q15 = Design::low_pass(rate, 300.0, 5.0).q15();
+    let mut q31 = Design::low_pass(rate, 300.0, 5.0).q31();
+    for i in 0..1000 {
+        let x = if i % 50 < 25 { 20000 } else { -20000 };
+        q15.process(x);
+        q31.process(x);
+    }
+    let tail = |f: &mut dyn Processor| (0..rate.hz()).map(|_| f.process(0)).last();
+    assert_eq!(tail(&mut q15), Some(0));
+    assert_eq!(tail(&mut q31), Some(0));
+}
+
+#[test]
+fn full_scale_boosts_saturate() {
+    let rate = SampleRate::Hz8000;
+    let d = Design::peaking(rate, 1000.0, 1.0, 12.0);
+    let w = 2.0 * PI * 1000.0 / 8000.0;
+    let input: Vec<i16> = (0..800)
+        .map(|i| (30000.0 * (w * i as f64).sin()) as i16)
+        .collect();
+    for mut f in [Box::new(d.q15()) as Box<dyn Processor>, Box::new(d.q31())] {
+        let output: Vec<_> = input.iter().map(|&x| f.process(x)).collect();
+        let peak = output[400..].iter().max().unwrap();
+        let trough = output[400..].iter().min().unwrap();
+        assert_eq!((*peak, *trough), (i16::MAX, i16::MIN));
+        // clipped, not wrapped: the output follows the input's sign
+        for (x, y) in input[400..].iter().zip(&output[400..]) {
+            assert!(
+                i32::from(*x) * i32::from(*y) >= 0 || x.abs() < 5000,
+                "{x} {y}"
+            );
+        }
+    }
+}
+
+#[test]
+fn blocks_and_resets_behave_like_fresh_samples() {
+    let rate = SampleRate::Hz16000;
+    let input: Vec<i16> = (0..500)
+        .map(|i| (9000.0 * (i as f64 * 0.37).sin() + 4000.0 * (i as f64 * 0.05).cos()) as i16)
+        .collect();
+    let d = Design::band_pass(rate, 1000.0, 1.5);
+    let mut one = Cascade::new([d.q15(), d.q15()]);
+    let mut block = one.clone();
+    let expected: Vec<_> = input.iter().map(|&x| one.process(x)).collect();
+    let mut output = input.clone();
+    block.process_block(&mut output);
+    assert_eq!(output, expected);
+
+    one.reset();
+    let again: Vec<_> = input.iter().map(|&x| one.process(x)).collect();
+    assert_eq!(again, expected);
+
+    let mut q31: Q31 = d.q31();
+    let first: Vec<_> = input.iter().map(|&x| q31.process(x)).collect();
+    q31.reset();
+    let second: Vec<_> = input.iter().map(|&x| q31.process(x)).collect();
+    assert_eq!(first, second);
+}
+
+#[test]
+#[should_panic]
+fn frequency_must_be_below_nyquist() {
+    Design::low_pass(SampleRate::Hz8000, 4000.0, 0.7);
+}
+
+#[test]
+#[should_panic]
+fn coefficients_must_fit() {
+    // a 30 dB shelf is out of range
+    Design::high_shelf(SampleRate::Hz8000, 100.0, FRAC_1_SQRT_2, 30.0).q15();
+}
@@ -0,0 +1,159 @@
+use voice_core::capture::{Block, CaptureDma, DmaCapture, Underrun};
+use voice_core::AudioSource;
+
+const N: usize = 16;
+
+/// DMA channel that fills its block from a counting ADC, one conversion at a
+/// time. Conversions made while no block is in flight are lost.
+#[derive(Default)]
+struct MockDma {
+    block: Option<Block<N>>,
+    filled: usize,
+    done: bool,
+    next_sample: u16,
+    lost: usize,
+}
+
+impl MockDma {
+    /// Run one conversion, returning whether it raised the completion
+    /// interrupt.
+    fn convert(&mut self) -> bool {
+        let sample = self.next_sample;
+        self.next_sample = (self.next_sample + 1) % 4096;
+        match &mut self.block {
+            Some(block) if !self.done => {
+                block[self.filled] = sample;
+                self.filled += 1;
+                self.done = self.filled == N;
+                self.done
+            }
+            _ => {
+                self.lost += 1;
+                false
+            }
+        }
+    }
+}
+
+impl CaptureDma<N> for MockDma {
+    fn start(&mut self, block: Block<N>) {
+        assert!(
+            self.block.is_none(),
+            "started while a transfer is in flight"
+        );
+        self.block = Some(block);
+        self.filled = 0;
+        self.done = false;
+    }
+
+    fn take_done(&mut self) -> Option<Block<N>> {
+        if self.done {
+            self.done = false;
+            self.block.take()
+        } else {
+            None
+        }
+    }
+}
+
+fn block() -> Block<N> {
+    Box::leak(Box::new([0; N]))
+}
+
+fn capture() -> DmaCapture<MockDma, N> {
+    DmaCapture::new(MockDma::default(), block(), block())
+}
+
+/// Run `n` conversions, calling the interrupt handler when DMA completes.
+fn convert(capture: &mut DmaCapture<MockDma, N>, n: usize) {
+    for _ in 0..n {
+        if capture.dma().convert() {
+            capture.on_complete();
+        }
+    }
+}
+
+#[test]
+fn nothing_available_until_a_block_completes() {
+    let mut capture = capture();
+    convert(&mut capture, N - 1);
+    assert_eq!(capture.available(), 0);
+    assert_eq!(AudioSource::read(&mut capture), Err(Underrun));
+
+    convert(&mut capture, 1);
+    assert_eq!(capture.available(), N);
+}
+
+#[test]
+fn reader_keeping_up_sees_every_sample() {
+    let mut capture = capture();
+    let mut out = Vec::new();
+    let mut buf = [0; 5];
+    for _ in 0..50 {
+        convert(&mut capture, N);
+        loop {
+            let n = capture.read_block(&mut buf);
+            if n == 0 {
+                break;
+            }
+            out.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    assert_eq!(out.len(), 50 * N);
+    assert!(out.iter().enumerate().all(|(i, &s)| usize::from(s) == i));
+    assert_eq!(capture.stalls(), 0);
+    assert_eq!(capture.dma().lost, 0);
+}
+
+#[test]
+fn reader_has_one_block_time_to_catch_up() {
+    let mut capture = capture();
+    // the first block is complete and the second one almost
+    convert(&mut capture, N);
+    convert(&mut capture, N - 1);
+    assert_eq!(capture.available(), N);
+
+    let mut buf = [0; N / 2];
+    assert_eq!(capture.read_block(&mut buf), N / 2);
+    assert_eq!(capture.read_block(&mut buf), N / 2);
+    convert(&mut capture, 1);
+    assert_eq!(capture.available(), N);
+    assert_eq!(capture.stalls(), 0);
+    assert!(capture.is_capturing());
+}
+
+#[test]
+fn slow_reader_stalls_then_restarts() {
+    let mut capture = capture();
+    convert(&mut capture, 3 * N);
+    assert_eq!(capture.stalls(), 1);
+    assert!(!capture.is_capturing());
+    assert_eq!(capture.dma().lost, N);
+
+    // freeing a block restarts capture straight away
+    let mut buf = [0; N];
+    assert_eq!(capture.read_block(&mut buf), N);
+    assert!(capture.is_capturing());
+    assert_eq!(buf[0], 0);
+
+    assert_eq!(capture.read_block(&mut buf), N);
+    assert_eq!(buf[0], N as u16);
+
+    convert(&mut capture, N);
+    assert_eq!(capture.read_block(&mut buf), N);
+    assert_eq!(buf[0], 3 * N as u16);
+}
+
+#[test]
+fn audio_source_reads_in_order() {
+    let mut capture = capture();
+    convert(&mut capture, N);
+    let source: &mut dyn AudioSource<Error = Underrun> = &mut capture;
+    assert_eq!(source.available(), N);
+    assert_eq!(source.read(), Ok(0));
+    let mut buf = [0; 4];
+    assert_eq!(source.read_block(&mut buf), Ok(4));
+    assert_eq!(buf, [1, 2, 3, 4]);
+    assert_eq!(source.available(), N - 5);
+}
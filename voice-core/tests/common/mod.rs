@@ -0,0 +1,83 @@
+//! Test signals shared between the integration tests.
+
+// each test uses only some of these
+#![allow(dead_code)]
+
+use std::f64::consts::PI;
+
+/// Rate the speech-like signals are made at.
+const RATE: f64 = 8000.0;
+
+/// The successive states of a linear congruential generator started from
+/// `seed`.
+pub fn random(seed: u32) -> impl Iterator<Item = u32> {
+    let mut seed = seed;
+    std::iter::repeat_with(move || {
+        seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        seed
+    })
+}
+
+/// `len` samples of white noise spread evenly over ±`amplitude`.
+pub fn noise(len: usize, amplitude: f64, seed: u32) -> Vec<f64> {
+    random(seed)
+        .take(len)
+        .map(|x| amplitude * (f64::from(x >> 8) / f64::from(1u32 << 23) - 1.0))
+        .collect()
+}
+
+/// Something like speech at 8 kHz: syllables of a buzz with a wandering
+/// pitch and falling harmonics, a quarter of a second on and a bit less off.
+pub fn speech(len: usize, amplitude: f64) -> Vec<f64> {
+    let mut phase = 0.0;
+    (0..len)
+        .map(|i| {
+            let t = i as f64 / RATE;
+            let syllable = (t * 2.2).fract();
+            let envelope = if syllable < 0.55 {
+                (PI * syllable / 0.55).sin()
+            } else {
+                0.0
+            };
+            let pitch = 120.0 + 30.0 * (2.0 * PI * 0.7 * t).sin();
+            phase += 2.0 * PI * pitch / RATE;
+            let buzz: f64 = (1..=12).map(|k| (k as f64 * phase).sin() / k as f64).sum();
+            amplitude * envelope * buzz / 2.0
+        })
+        .collect()
+}
+
+/// `len` samples of a vowel at 8 kHz, peaking at ±1: a Rosenberg glottal
+/// pulse at `pitch(i)` Hz for sample `i`, through resonators at
+/// `formants`.
+pub fn vowel(len: usize, formants: [f64; 3], pitch: impl Fn(usize) -> f64) -> Vec<f64> {
+    let mut phase = 0.0f64;
+    let source: Vec<f64> = (0..=len)
+        .map(|i| {
+            phase += pitch(i) / RATE;
+            // open for 60% of the period, closed after
+            let p = phase.fract();
+            if p < 0.4 {
+                0.5 * (1.0 - (PI * p / 0.4).cos())
+            } else if p < 0.6 {
+                (PI / 2.0 * (p - 0.4) / 0.2).cos()
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    // the derivative of the flow is what radiates
+    let mut signal: Vec<f64> = source.windows(2).map(|w| w[1] - w[0]).collect();
+    for (f, bandwidth) in formants.into_iter().zip([80.0, 100.0, 120.0]) {
+        let r = (-PI * bandwidth / RATE).exp();
+        let (a1, a2) = (2.0 * r * (2.0 * PI * f / RATE).cos(), -r * r);
+        let (mut y1, mut y2) = (0.0, 0.0);
+        for x in &mut signal {
+            let y = *x + a1 * y1 + a2 * y2;
+            (y2, y1) = (y1, y);
+            *x = y;
+        }
+    }
+    let peak = signal.iter().fold(0.0f64, |m, x| m.max(x.abs()));
+    signal.iter().map(|x| x / peak).collect()
+}
@@ -0,0 +1,184 @@
+use std::f64::consts::PI;
+
+use voice_core::compress::{Compressor, Config};
+use voice_core::process::Processor;
+use voice_core::SampleRate;
+
+const RATE: SampleRate = SampleRate::Hz16000;
+
+/// Samples per millisecond at `RATE`.
+const MS: usize = 16;
+
+/// A tone whose level steps through `steps` of (dBFS, ms).
+fn steps(steps: &[(f64, usize)]) -> Vec<i16> {
+    let w = 2.0 * PI * 440.0 / f64::from(RATE.hz());
+    let mut signal = Vec::new();
+    for &(dbfs, ms) in steps {
+        let amplitude = 32767.0 * 10f64.powf(dbfs / 20.0);
+        let start = signal.len();
+        signal.extend(
+            (start..start + ms * MS).map(|i| (amplitude * (w * i as f64).sin()).round() as i16),
+        );
+    }
+    signal
+}
+
+/// Peak level of `samples`, in dBFS.
+fn peak_dbfs(samples: &[i16]) -> f64 {
+    let peak = samples.iter().map(|s| s.unsigned_abs()).max().unwrap();
+    20.0 * (f64::from(peak) / 32767.0).log10()
+}
+
+fn run(compressor: &mut Compressor, input: &[i16]) -> Vec<i16> {
+    input.iter().map(|&s| compressor.process(s)).collect()
+}
+
+/// The output level a steady tone at `input` dBFS settles to.
+fn settled(config: &Config, input: f64) -> f64 {
+    let mut compressor = Compressor::new(config, RATE);
+    let output = run(&mut compressor, &steps(&[(input, 1000)]));
+    peak_dbfs(&output[800 * MS..])
+}
+
+/// The textbook soft-knee curve.
+fn curve(config: &Config, input: f64) -> f64 {
+    let over = input - f64::from(config.threshold_dbfs);
+    let knee = f64::from(config.knee_db);
+    let slope = 1.0 - 1.0 / f64::from(config.ratio);
+    let reduction = if 2.0 * over <= -knee {
+        0.0
+    } else if 2.0 * over < knee {
+        slope * (over + knee / 2.0).powi(2) / (2.0 * knee)
+    } else {
+        slope * over
+    };
+    input - reduction + f64::from(config.makeup_db)
+}
+
+#[test]
+fn steady_tones_follow_the_static_curve() {
+    let hard = Config {
+        knee_db: 0,
+        ..Config::default()
+    };
+    let soft = Config {
+        knee_db: 12,
+        ..Config::default()
+    };
+    let steep = Config {
+        threshold_dbfs: -30,
+        ratio: 10,
+        makeup_db: 0,
+        ..Config::default()
+    };
+    for config in [Config::default(), hard, soft, steep] {
+        for input in [-48.0, -30.0, -24.0, -20.0, -18.0, -16.0, -12.0, -6.0, -1.0] {
+            let expected = curve(&config, input);
+            let level = settled(&config, input);
+            assert!(
+                (level - expected).abs() < 0.15,
+                "{config:?} {input} dBFS: {level:.2}, expected {expected:.2}"
+            );
+        }
+    }
+}
+
+#[test]
+fn below_the_threshold_only_the_makeup_applies() {
+    let mut compressor = Compressor::new(&Config::default(), RATE);
+    let input = steps(&[(-40.0, 500)]);
+    let output = run(&mut compressor, &input);
+    assert_eq!(compressor.reduction(), 0);
+    // 6 dB is just about a doubling
+    for (x, y) in input.iter().zip(&output) {
+        let expected = f64::from(*x) * 10f64.powf(6.0 / 20.0);
+        assert!((f64::from(*y) - expected).abs() <= 1.0, "{x} → {y}");
+    }
+}
+
+#[test]
+fn a_ratio_of_one_is_only_makeup() {
+    let config = Config {
+        ratio: 1,
+        makeup_db: 0,
+        ..Config::default()
+    };
+    let mut compressor = Compressor::new(&config, RATE);
+    let input = steps(&[(-30.0, 100), (0.0, 100), (-10.0, 100)]);
+    assert_eq!(run(&mut compressor, &input), input);
+}
+
+#[test]
+fn loud_steps_are_caught_over_the_attack_time() {
+    let config = Config::default();
+    let mut compressor = Compressor::new(&config, RATE);
+    let attack = config.attack_ms as usize;
+    run(&mut compressor, &steps(&[(-40.0, 200)]));
+    // from nothing to 12 dB over the threshold: 8 dB of reduction
+    let full = (12.0 * 2.0 / 3.0 * 256.0) as u32;
+    let mut input = steps(&[(-40.0, 200), (-6.0, 5 * attack)]).split_off(200 * MS);
+    run(
+        &mut compressor,
+        &input.drain(..attack * MS).collect::<Vec<_>>(),
+    );
+    let one = compressor.reduction();
+    assert!(
+        (0.55..0.7).contains(&(f64::from(one) / f64::from(full))),
+        "{one} of {full}"
+    );
+    run(&mut compressor, &input);
+    let five = compressor.reduction();
+    assert!(full - five < full / 50, "{five} of {full}");
+}
+
+#[test]
+fn quiet_steps_release_over_the_release_time() {
+    let config = Config::default();
+    let mut compressor = Compressor::new(&config, RATE);
+    let release = config.release_ms as usize;
+    let input = steps(&[(-6.0, 500), (-40.0, 5 * release)]);
+    run(&mut compressor, &input[..500 * MS]);
+    let full = compressor.reduction();
+    // the peak detector releases, and the attack smoothing lags it a little
+    run(&mut compressor, &input[500 * MS..(500 + release) * MS]);
+    let one = f64::from(compressor.reduction()) / f64::from(full);
+    assert!((0.3..0.45).contains(&one), "{one:.2}");
+    run(&mut compressor, &input[(500 + release) * MS..]);
+    let five = f64::from(compressor.reduction()) / f64::from(full);
+    assert!(five < 0.02, "{five:.3}");
+}
+
+#[test]
+fn full_scale_input_is_not_wrapped() {
+    let config = Config {
+        makeup_db: 24,
+        ..Config::default()
+    };
+    let mut compressor = Compressor::new(&config, RATE);
+    let input = steps(&[(-40.0, 100), (0.0, 5)]);
+    let output = run(&mut compressor, &input);
+    assert!(output.contains(&i16::MAX) && output.contains(&i16::MIN));
+    for (x, y) in input.iter().zip(&output) {
+        assert!(i32::from(*x) * i32::from(*y) >= 0);
+    }
+}
+
+#[test]
+fn reset_starts_over() {
+    let mut compressor = Compressor::new(&Config::default(), RATE);
+    let input = steps(&[(-30.0, 300), (-3.0, 300)]);
+    let first = run(&mut compressor, &input);
+    compressor.reset();
+    assert_eq!(compressor.reduction(), 0);
+    assert_eq!(run(&mut compressor, &input), first);
+}
+
+#[test]
+#[should_panic]
+fn makeup_must_be_in_range() {
+    let config = Config {
+        makeup_db: 30,
+        ..Config::default()
+    };
+    Compressor::new(&config, RATE);
+}
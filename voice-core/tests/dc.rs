@@ -0,0 +1,173 @@
+use std::f64::consts::PI;
+
+use voice_core::dc::{DcBlocker, VOICE_CORNER_HZ};
+use voice_core::{to_pcm16, SampleRate};
+
+/// ADC samples of a sine of `amplitude` steps at `hz`, riding on `bias`.
+fn sine(hz: f64, rate: SampleRate, amplitude: f64, bias: f64, len: usize) -> Vec<u16> {
+    let step = 2.0 * PI * hz / f64::from(rate.hz());
+    (0..len)
+        .map(|i| (bias + amplitude * (step * i as f64).sin()).round() as u16)
+        .collect()
+}
+
+/// Gain of the blocker at `hz`, measured as output RMS over input RMS once
+/// the filter has settled.
+fn measured_gain(corner: u32, rate: SampleRate, hz: f64) -> f64 {
+    let mut dc = DcBlocker::new(corner, rate);
+    // long enough to settle, and a whole number of low-frequency cycles
+    let len = 20 * rate.hz() as usize;
+    let input = sine(hz, rate, 1000.0, 2300.0, len);
+    let output: Vec<_> = input.iter().map(|&s| dc.push(s)).collect();
+    let tail = len / 2;
+    let rms =
+        |s: &mut dyn Iterator<Item = f64>| (s.map(|v| v * v).sum::<f64>() / tail as f64).sqrt();
+    let out = rms(&mut output[tail..].iter().map(|&s| f64::from(s)));
+    // the input on the output's scale, without its bias or rounding
+    out / (16.0 * 1000.0 / 2f64.sqrt())
+}
+
+/// Gain of the ideal filter with the blocker's quantized pole.
+fn reference_gain(dc: &DcBlocker, rate: SampleRate, hz: f64) -> f64 {
+    let r = f64::from(dc.pole()) / 65536.0;
+    let w = 2.0 * PI * hz / f64::from(rate.hz());
+    let zero = (2.0 - 2.0 * w.cos()).sqrt();
+    let pole = (1.0 - 2.0 * r * w.cos() + r * r).sqrt();
+    zero / pole
+}
+
+fn db(gain: f64) -> f64 {
+    20.0 * gain.log10()
+}
+
+#[test]
+fn corner_is_three_db_down() {
+    for rate in [SampleRate::Hz8000, SampleRate::Hz16000, SampleRate::Hz44100] {
+        let gain = measured_gain(VOICE_CORNER_HZ, rate, f64::from(VOICE_CORNER_HZ));
+        assert!((db(gain) + 3.0).abs() < 0.2, "{rate:?}: {:.2} dB", db(gain));
+    }
+}
+
+#[test]
+fn response_matches_the_ideal_filter() {
+    let rate = SampleRate::Hz16000;
+    let dc = DcBlocker::new(50, rate);
+    for hz in [5.0, 10.0, 25.0, 50.0, 100.0, 300.0, 1000.0, 3400.0, 7000.0] {
+        let measured = db(measured_gain(50, rate, hz));
+        let reference = db(reference_gain(&dc, rate, hz));
+        assert!(
+            (measured - reference).abs() < 0.1,
+            "{hz} Hz: {measured:.2} dB, expected {reference:.2} dB"
+        );
+    }
+}
+
+#[test]
+fn voice_band_passes_unchanged() {
+    let rate = SampleRate::Hz16000;
+    for hz in [300.0, 1000.0, 3400.0] {
+        let gain = db(measured_gain(VOICE_CORNER_HZ, rate, hz));
+        assert!(gain.abs() < 0.05, "{hz} Hz: {gain:.3} dB");
+    }
+}
+
+#[test]
+fn offset_settles_to_exactly_zero() {
+    let rate = SampleRate::Hz16000;
+    let mut dc = DcBlocker::new(VOICE_CORNER_HZ, rate);
+    // a step from one bias to another
+    for _ in 0..100 {
+        assert_eq!(dc.push(2048), 0);
+    }
+    let output: Vec<_> = (0..rate.hz()).map(|_| dc.push(2500)).collect();
+    assert_eq!(output[0], 452 * 16);
+    // a second is 126 time constants
+    assert!(output[rate.hz() as usize / 2..].iter().all(|&s| s == 0));
+    assert!(output.windows(2).all(|w| w[1] <= w[0]));
+}
+
+#[test]
+fn first_sample_sets_the_level() {
+    let mut dc = DcBlocker::new(VOICE_CORNER_HZ, SampleRate::Hz8000);
+    assert_eq!(dc.push(3900), 0);
+    assert_eq!(dc.push(3916), 16 * 16);
+
+    dc.reset();
+    assert_eq!(dc.push(100), 0);
+    assert_eq!(dc.push(100), 0);
+}
+
+#[test]
+fn noise_does_not_leave_an_offset() {
+    let mut dc = DcBlocker::new(VOICE_CORNER_HZ, SampleRate::Hz16000);
+    // a cheap pseudo-random dither around an off-center bias
+    let mut seed = 1u32;
+    let mut sum = 0i64;
+    let len = 200_000;
+    for i in 0..len {
+        seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let y = dc.push(1800 + (seed >> 24) as u16 % 64);
+        if i >= len / 2 {
+            sum += i64::from(y);
+        }
+    }
+    let mean = sum as f64 / (len / 2) as f64;
+    // well under one output step, let alone an ADC step
+    assert!(mean.abs() < 1.0, "mean {mean}");
+}
+
+#[test]
+fn full_scale_steps_saturate() {
+    let mut dc = DcBlocker::new(VOICE_CORNER_HZ, SampleRate::Hz16000);
+    dc.push(0);
+    assert_eq!(dc.push(4095), i16::MAX);
+    // and the filter carries on from where it really is
+    // which is just below zero, having decayed a little in between
+    assert!((-16 * 40..0).contains(&dc.push(0)));
+    dc.reset();
+    dc.push(4095);
+    assert_eq!(dc.push(0), i16::MIN);
+    // out of range samples count as full scale
+    dc.reset();
+    dc.push(4095);
+    assert_eq!(dc.push(u16::MAX), 0);
+}
+
+#[test]
+fn blocks_filter_like_single_samples() {
+    let rate = SampleRate::Hz16000;
+    let input = sine(440.0, rate, 1500.0, 2048.0, 1000);
+    let mut one = DcBlocker::new(VOICE_CORNER_HZ, rate);
+    let mut block = one.clone();
+    let expected: Vec<_> = input.iter().map(|&s| one.push(s)).collect();
+    let mut output = vec![0; 1000];
+    block.push_block(&input, &mut output);
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn signed_samples_filter_like_adc_samples() {
+    let rate = SampleRate::Hz16000;
+    let input = sine(440.0, rate, 1500.0, 2300.0, 1000);
+    let mut adc = DcBlocker::new(VOICE_CORNER_HZ, rate);
+    let mut signed = adc.clone();
+    let expected: Vec<_> = input.iter().map(|&s| adc.push(s)).collect();
+    let pcm: Vec<_> = input.iter().map(|&s| to_pcm16(s)).collect();
+    let mut output = vec![0; 1000];
+    signed.push_pcm16_block(&pcm, &mut output);
+    assert_eq!(output, expected);
+
+    // and a step to an offset finer than the ADC's settles to exactly zero
+    // too
+    signed.reset();
+    signed.push_pcm16(0);
+    let settled: Vec<_> = (0..16000).map(|_| signed.push_pcm16(-1203)).collect();
+    assert!(settled[0] < -1000);
+    assert_eq!(settled[15000..], [0; 1000]);
+}
+
+#[test]
+#[should_panic]
+fn corner_must_be_well_below_the_rate() {
+    DcBlocker::new(200, SampleRate::Hz8000);
+}
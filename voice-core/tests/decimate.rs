@@ -0,0 +1,223 @@
+mod common;
+
+use std::f64::consts::PI;
+
+use voice_core::decimate::{Cic, Decimator};
+use voice_core::to_pcm16;
+
+/// A cheap pseudo-random 12-bit sequence.
+fn noise(len: usize, seed: u32) -> Vec<u16> {
+    common::random(seed)
+        .take(len)
+        .map(|x| (x >> 20) as u16)
+        .collect()
+}
+
+/// ADC samples of a sine of `amplitude` steps around mid-rail, at `f`
+/// cycles per sample.
+fn sine(f: f64, amplitude: f64, len: usize) -> Vec<u16> {
+    (0..len)
+        .map(|i| (2048.0 + amplitude * (2.0 * PI * f * i as f64).sin()).round() as u16)
+        .collect()
+}
+
+/// A CIC written out the slow way: `stages` moving sums of `ratio` samples
+/// around mid-rail, keeping every `ratio`th result.
+fn naive_cic(input: &[u16], stages: usize, ratio: usize) -> Vec<i64> {
+    let mut signal: Vec<i64> = input.iter().map(|&s| i64::from(s) - 2048).collect();
+    for _ in 0..stages {
+        signal = (0..signal.len())
+            .map(|i| signal[i.saturating_sub(ratio - 1)..=i].iter().sum())
+            .collect();
+    }
+    signal.into_iter().skip(ratio - 1).step_by(ratio).collect()
+}
+
+/// Amplitude of the `f` cycles per sample component of `signal`, in ADC
+/// steps.
+fn amplitude(signal: &[i16], f: f64) -> f64 {
+    let (mut re, mut im) = (0.0, 0.0);
+    for (i, &s) in signal.iter().enumerate() {
+        let w = 2.0 * PI * f * i as f64;
+        re += f64::from(s) * w.cos();
+        im += f64::from(s) * w.sin();
+    }
+    2.0 * re.hypot(im) / signal.len() as f64 / 16.0
+}
+
+fn db(gain: f64) -> f64 {
+    20.0 * gain.log10()
+}
+
+#[test]
+fn cic_matches_the_naive_filter() {
+    let input = noise(4000, 7);
+    let mut cic: Cic<3> = Cic::new(5);
+    let output: Vec<_> = input.iter().filter_map(|&s| cic.push(s)).collect();
+    let expected: Vec<_> = naive_cic(&input, 3, 5)
+        .into_iter()
+        .map(|s| s as i32)
+        .collect();
+    assert_eq!(output, expected);
+    assert_eq!(cic.gain(), 125);
+}
+
+#[test]
+fn cic_wraps_without_losing_full_scale() {
+    // the largest gain, where a full-scale sum only just fits
+    let input: Vec<u16> = (0..2000)
+        .map(|i| if i % 700 < 400 { 4095 } else { 0 })
+        .collect();
+    let mut cic: Cic<4> = Cic::new(32);
+    assert_eq!(cic.gain(), 1 << 20);
+    let output: Vec<_> = input.iter().filter_map(|&s| cic.push(s)).collect();
+    let expected = naive_cic(&input, 4, 32);
+    assert_eq!(output.len(), expected.len());
+    for (ours, theirs) in output.iter().zip(&expected) {
+        assert_eq!(i64::from(*ours), *theirs);
+    }
+    assert!(output.contains(&(2047 << 20)));
+    assert!(output.contains(&(-2048 << 20)));
+}
+
+#[test]
+fn decimator_matches_a_float_reference() {
+    let input = noise(6000, 3);
+    // a gain of 125, which the shift alone can't normalize
+    let mut decimator: Decimator<3, 9> = Decimator::new(5, 0.425);
+    let output: Vec<_> = input.iter().filter_map(|&s| decimator.push(s)).collect();
+
+    // the same taps, applied in floating point to the CIC output on the
+    // 16-bit scale, shifted down by 2^6 of its gain of 125 as the decimator
+    // does
+    let taps = decimator.taps().map(|t| f64::from(t) / 16384.0);
+    let scaled: Vec<f64> = naive_cic(&input, 3, 5)
+        .into_iter()
+        .map(|s| ((s * 16) >> 6) as f64)
+        .collect();
+    assert_eq!(output.len(), scaled.len());
+    for (i, &out) in output.iter().enumerate() {
+        let filtered: f64 = (0..taps.len().min(i + 1))
+            .map(|k| taps[k] * scaled[i - k])
+            .sum();
+        let expected = filtered.clamp(-32768.0, 32767.0);
+        assert!(
+            (f64::from(out) - expected).abs() <= 1.0,
+            "{i}: {out}, expected {expected:.2}"
+        );
+    }
+    // and the taps are symmetric, summing to the gain the shift left
+    assert!(taps.iter().eq(taps.iter().rev()));
+    assert!((taps.iter().sum::<f64>() - 64.0 / 125.0).abs() < 1e-4);
+}
+
+#[test]
+fn passband_is_flat() {
+    let ratio = 4;
+    let mut worst: f64 = 0.0;
+    for f in [0.01, 0.05, 0.1, 0.2, 0.3, 0.4, 0.425] {
+        let mut decimator: Decimator<3, 9> = Decimator::new(ratio, 0.425);
+        let input = sine(f / ratio as f64, 1500.0, 80_000);
+        let output: Vec<_> = input.iter().filter_map(|&s| decimator.push(s)).collect();
+        let gain = db(amplitude(&output[100..], f) / 1500.0);
+        worst = worst.max(gain.abs());
+    }
+    assert!(worst < 0.2, "ripple {worst:.3} dB");
+
+    // without compensation the CIC alone would be 7 dB down at the edge
+    let mut cic: Cic<3> = Cic::new(ratio);
+    let input = sine(0.425 / ratio as f64, 1500.0, 80_000);
+    let output: Vec<_> = input
+        .iter()
+        .filter_map(|&s| cic.push(s))
+        .map(|s| (s / 4) as i16)
+        .collect();
+    let droop = db(amplitude(&output[100..], 0.425) / 1500.0);
+    assert!((-8.0..-7.0).contains(&droop), "{droop:.2} dB");
+}
+
+#[test]
+fn aliases_are_rejected() {
+    let ratio = 4;
+    // 500 Hz either side of the 8 kHz output rate, from a 32 kHz ADC, both
+    // of which would fold down onto 500 Hz
+    for input_hz in [7500.0, 8500.0, 15_500.0] {
+        let mut decimator: Decimator<3, 9> = Decimator::new(ratio, 0.425);
+        let input = sine(input_hz / 32_000.0, 1500.0, 160_000);
+        let output: Vec<_> = input.iter().filter_map(|&s| decimator.push(s)).collect();
+        let alias = db(amplitude(&output[100..], 500.0 / 8000.0) / 1500.0);
+        assert!(alias < -50.0, "{input_hz} Hz: {alias:.1} dB");
+    }
+}
+
+#[test]
+fn levels_come_through_unchanged() {
+    for level in [0, 1, 1000, 2048, 3000, 4095] {
+        let mut decimator: Decimator<3, 9> = Decimator::new(4, 0.425);
+        let output: Vec<_> = (0..400).filter_map(|_| decimator.push(level)).collect();
+        assert_eq!(output.len(), 100);
+        // once the step from mid-rail has passed through both filters
+        assert!(
+            output[12..].iter().all(|&s| s == to_pcm16(level)),
+            "{level}: {output:?}"
+        );
+    }
+    // out of range samples count as full scale
+    let mut decimator: Decimator<2, 5> = Decimator::new(3, 0.4);
+    let output: Vec<_> = (0..300).filter_map(|_| decimator.push(u16::MAX)).collect();
+    // to within the rounding of the taps' gain of 8/9, a sixteenth of a step
+    assert!(output[20..]
+        .iter()
+        .all(|&s| s.abs_diff(to_pcm16(4095)) <= 1));
+}
+
+#[test]
+fn resolution_below_the_adc_comes_through() {
+    // a level halfway between two ADC steps, which only the oversampling
+    // can see
+    let mut decimator: Decimator<3, 9> = Decimator::new(4, 0.425);
+    let output: Vec<_> = (0..400u16)
+        .filter_map(|i| decimator.push(2048 + i % 2))
+        .collect();
+    assert!(output[12..].iter().all(|&s| s == 8), "{output:?}");
+}
+
+#[test]
+fn blocks_decimate_like_single_samples() {
+    let input = noise(1001, 11);
+    let mut one: Decimator<3, 7> = Decimator::new(5, 0.4);
+    let mut block = one.clone();
+    let expected: Vec<_> = input.iter().filter_map(|&s| one.push(s)).collect();
+
+    let mut output = vec![0; 300];
+    let mut read = 0;
+    let mut written = 0;
+    for piece in input.chunks(37) {
+        let (r, w) = block.push_block(piece, &mut output[written..]);
+        assert_eq!(r, piece.len());
+        read += r;
+        written += w;
+    }
+    assert_eq!(read, input.len());
+    assert_eq!(output[..written], expected);
+
+    // input stops once the output is full
+    block.reset();
+    let mut short = [0; 3];
+    assert_eq!(block.push_block(&input, &mut short), (15, 3));
+    one.reset();
+    let again: Vec<_> = input[..15].iter().filter_map(|&s| one.push(s)).collect();
+    assert_eq!(short[..], again);
+}
+
+#[test]
+#[should_panic]
+fn cic_gain_must_fit() {
+    Cic::<4>::new(33);
+}
+
+#[test]
+#[should_panic]
+fn compensation_needs_odd_taps() {
+    Decimator::<3, 8>::new(4, 0.4);
+}
@@ -0,0 +1,235 @@
+mod common;
+
+use std::f64::consts::PI;
+
+use voice_core::denoise::{Config, Rule, Suppressor};
+use voice_core::process::Processor;
+use voice_core::SampleRate;
+
+use common::{noise, speech};
+
+const RATE: SampleRate = SampleRate::Hz8000;
+
+/// Samples per second at `RATE`.
+const SECOND: usize = 8000;
+
+/// Mains hum at 50 Hz with its odd harmonics.
+fn hum(len: usize, amplitude: f64) -> Vec<f64> {
+    (0..len)
+        .map(|i| {
+            let w = 2.0 * PI * 50.0 * i as f64 / SECOND as f64;
+            amplitude * (w.sin() + 0.5 * (3.0 * w).sin() + 0.25 * (5.0 * w).sin()) / 1.75
+        })
+        .collect()
+}
+
+fn mix(a: &[f64], b: &[f64]) -> Vec<i16> {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a + b).round().clamp(-32768.0, 32767.0) as i16)
+        .collect()
+}
+
+fn run<const N: usize>(suppressor: &mut Suppressor<N>, input: &[i16]) -> Vec<i16> {
+    let mut output = input.to_vec();
+    suppressor.process_block(&mut output);
+    output
+}
+
+/// Signal-to-noise ratio of `output` against `clean`, in dB, allowing for
+/// `delay`, from `from` on.
+fn snr(clean: &[f64], output: &[i16], delay: usize, from: usize) -> f64 {
+    let (mut signal, mut error) = (0.0, 0.0);
+    for i in from..output.len() {
+        let c = clean[i - delay];
+        signal += c * c;
+        error += (f64::from(output[i]) - c).powi(2);
+    }
+    10.0 * (signal / error).log10()
+}
+
+/// Mean power of `samples`.
+fn power(samples: &[i16]) -> f64 {
+    samples.iter().map(|&s| f64::from(s).powi(2)).sum::<f64>() / samples.len() as f64
+}
+
+/// How much `output` improves on `input`'s SNR against `clean`, in dB, once
+/// the noise estimate has settled.
+fn improvement<const N: usize>(
+    suppressor: &mut Suppressor<N>,
+    clean: &[f64],
+    noise: &[f64],
+) -> f64 {
+    let input = mix(clean, noise);
+    let output = run(suppressor, &input);
+    let delay = suppressor.latency();
+    snr(clean, &output, delay, 3 * SECOND) - snr(clean, &input, 0, 3 * SECOND)
+}
+
+#[test]
+fn hiss_under_speech_is_suppressed() {
+    let len = 8 * SECOND;
+    let clean = speech(len, 8000.0);
+    let hiss = noise(len, 3000.0, 3);
+    for rule in [Rule::Wiener, Rule::Subtraction] {
+        let config = Config {
+            rule,
+            ..Config::default()
+        };
+        let mut suppressor: Suppressor<256> = Suppressor::new(&config, RATE);
+        let gain = improvement(&mut suppressor, &clean, &hiss);
+        assert!(gain > 6.0, "{rule:?}: {gain:.1} dB");
+    }
+}
+
+#[test]
+fn hum_under_speech_is_suppressed() {
+    let len = 8 * SECOND;
+    let clean = speech(len, 8000.0);
+    let hum = hum(len, 3000.0);
+    for rule in [Rule::Wiener, Rule::Subtraction] {
+        let config = Config {
+            rule,
+            ..Config::default()
+        };
+        let mut suppressor: Suppressor<256> = Suppressor::new(&config, RATE);
+        let gain = improvement(&mut suppressor, &clean, &hum);
+        assert!(gain > 5.0, "{rule:?}: {gain:.1} dB");
+    }
+}
+
+#[test]
+fn clean_speech_is_left_alone() {
+    let len = 6 * SECOND;
+    let clean = speech(len, 8000.0);
+    // with a little hiss, already 20 dB down, it still helps a little
+    let mut suppressor: Suppressor<256> = Suppressor::new(&Config::default(), RATE);
+    let gain = improvement(&mut suppressor, &clean, &noise(len, 300.0, 5));
+    assert!(gain > 1.5, "{gain:.1} dB");
+
+    // and without any, the speech comes through all but untouched
+    let mut suppressor: Suppressor<256> = Suppressor::new(&Config::default(), RATE);
+    let output = run(&mut suppressor, &mix(&clean, &vec![0.0; len]));
+    let snr = snr(&clean, &output, 255, 3 * SECOND);
+    assert!(snr > 25.0, "{snr:.1} dB");
+}
+
+#[test]
+fn hiss_alone_comes_down_to_the_floor() {
+    let len = 6 * SECOND;
+    let input = mix(&noise(len, 1000.0, 3), &vec![0.0; len]);
+    for (attenuation, least) in [(10, 9.0), (15, 13.0), (25, 18.0)] {
+        let config = Config {
+            max_attenuation_db: attenuation,
+            ..Config::default()
+        };
+        let mut suppressor: Suppressor<256> = Suppressor::new(&config, RATE);
+        let output = run(&mut suppressor, &input);
+        let down = 10.0 * (power(&input[3 * SECOND..]) / power(&output[3 * SECOND..])).log10();
+        assert!(
+            down > least && down < attenuation as f64 + 0.5,
+            "{attenuation} dB: {down:.1} dB"
+        );
+
+        // and stays down evenly, without bursts of musical noise
+        let mean = power(&output[3 * SECOND..]);
+        let loudest = output[3 * SECOND..]
+            .chunks(128)
+            .map(power)
+            .fold(0.0, f64::max);
+        assert!(
+            loudest < mean * 2.5,
+            "{attenuation} dB: {:.1}",
+            loudest / mean
+        );
+    }
+}
+
+#[test]
+fn noise_estimate_follows_the_noise() {
+    // the expected power of a white noise bin, per point: its variance
+    // times the mean of the squared Hann window, 3/8
+    let expected = |amplitude: f64| amplitude * amplitude / 3.0 * 0.375;
+    let estimate = |suppressor: &Suppressor<256>, amplitude| {
+        (1..128)
+            .map(|k| suppressor.noise(k) as f64 / expected(amplitude))
+            .sum::<f64>()
+            / 127.0
+    };
+
+    let mut suppressor: Suppressor<256> = Suppressor::new(&Config::default(), RATE);
+    assert_eq!(suppressor.noise(5), 0);
+    run(
+        &mut suppressor,
+        &mix(&noise(3 * SECOND, 1000.0, 1), &[0.0; 3 * SECOND]),
+    );
+    let settled = estimate(&suppressor, 1000.0);
+    assert!((0.8..1.25).contains(&settled), "{settled:.2}");
+
+    // 10 dB louder: it takes the noise window for the old minima to go
+    run(
+        &mut suppressor,
+        &mix(&noise(SECOND, 3162.0, 2), &[0.0; SECOND]),
+    );
+    let early = estimate(&suppressor, 3162.0);
+    assert!(early < 0.5, "{early:.2}");
+    run(
+        &mut suppressor,
+        &mix(&noise(SECOND, 3162.0, 3), &[0.0; SECOND]),
+    );
+    let late = estimate(&suppressor, 3162.0);
+    assert!((0.8..1.25).contains(&late), "{late:.2}");
+
+    // and back down: the minimum follows within a second
+    run(
+        &mut suppressor,
+        &mix(&noise(SECOND, 1000.0, 4), &[0.0; SECOND]),
+    );
+    let quieter = estimate(&suppressor, 1000.0);
+    assert!((0.8..1.25).contains(&quieter), "{quieter:.2}");
+}
+
+#[test]
+fn switched_off_it_passes_samples_straight_through() {
+    let len = 2 * SECOND;
+    let input = mix(&speech(len, 8000.0), &noise(len, 3000.0, 7));
+    let mut suppressor: Suppressor<256> = Suppressor::new(&Config::default(), RATE);
+    suppressor.set_enabled(false);
+    assert!(!suppressor.is_enabled());
+    assert_eq!(suppressor.latency(), 0);
+    assert_eq!(run(&mut suppressor, &input), input);
+
+    // switching it on starts over, with its delay and a fresh estimate
+    suppressor.set_enabled(true);
+    assert_eq!(suppressor.latency(), 255);
+    let on = run(&mut suppressor, &input);
+    let mut fresh: Suppressor<256> = Suppressor::new(&Config::default(), RATE);
+    assert_eq!(on, run(&mut fresh, &input));
+}
+
+#[test]
+fn full_scale_fits_the_longest_frames() {
+    let len = SECOND;
+    let square: Vec<i16> = (0..len)
+        .map(|i| if i / 20 % 2 == 0 { i16::MAX } else { -i16::MAX })
+        .collect();
+    let sine = mix(&hum(len, 32767.0), &[0.0; SECOND]);
+    for input in [square, sine] {
+        let mut suppressor: Box<Suppressor<4096>> =
+            Box::new(Suppressor::new(&Config::default(), RATE));
+        let output = run(&mut suppressor, &input);
+        // a steady tone is taken for noise and brought down, not wrapped
+        // round into something louder
+        assert!(power(&output[SECOND / 2..]) <= power(&input) * 1.01);
+    }
+}
+
+#[test]
+fn reset_starts_over() {
+    let len = 2 * SECOND;
+    let input = mix(&speech(len, 8000.0), &noise(len, 3000.0, 7));
+    let mut suppressor: Suppressor<256> = Suppressor::new(&Config::default(), RATE);
+    let first = run(&mut suppressor, &input);
+    suppressor.reset();
+    assert_eq!(run(&mut suppressor, &input), first);
+}
@@ -0,0 +1,542 @@
+mod common;
+
+use std::f64::consts::PI;
+use std::path::PathBuf;
+
+use voice_core::effects::crush::{self, Bitcrusher};
+use voice_core::effects::echo::{self, Echo, Tap};
+use voice_core::effects::reverb::{self, Reverb};
+use voice_core::effects::ring::{self, RingModulator};
+use voice_core::effects::robot::{self, Robot};
+use voice_core::effects::shift::{self, PitchShifter};
+use voice_core::effects::{Config, Effect, Effects};
+use voice_core::pitch::{self, Yin};
+use voice_core::process::Processor;
+use voice_core::SampleRate;
+
+use common::noise;
+
+const RATE: SampleRate = SampleRate::Hz8000;
+
+/// Grain and frame length.
+const N: usize = 256;
+
+fn sine(hz: f64, len: usize) -> Vec<i16> {
+    (0..len)
+        .map(|i| (12000.0 * (2.0 * PI * hz * i as f64 / 8000.0).sin()).round() as i16)
+        .collect()
+}
+
+/// A vowel whose pitch glides from `from` to `to` Hz, peaking at `peak`.
+fn vowel(from: f64, to: f64, formants: [f64; 3], len: usize, peak: f64) -> Vec<f64> {
+    let signal = common::vowel(len, formants, |i| {
+        from + (to - from) * i as f64 / len as f64
+    });
+    // faded in and out over 10 ms
+    let fade = |i: usize| (i.min(len - 1 - i) as f64 / 80.0).min(1.0);
+    signal
+        .iter()
+        .enumerate()
+        .map(|(i, x)| peak * fade(i) * x)
+        .collect()
+}
+
+fn to_samples(signal: &[f64]) -> Vec<i16> {
+    signal
+        .iter()
+        .map(|x| x.round().clamp(-32768.0, 32767.0) as i16)
+        .collect()
+}
+
+/// About a second of something like speech: vowels at moving pitches
+/// between bursts of hiss, with gaps of silence.
+fn phrase() -> Vec<i16> {
+    let gap = |ms: usize| vec![0.0; ms * 8];
+    to_samples(
+        &[
+            gap(50),
+            noise(600, 2500.0, 1),
+            vowel(110.0, 140.0, [730.0, 1090.0, 2440.0], 2400, 14000.0),
+            gap(60),
+            vowel(150.0, 120.0, [270.0, 2290.0, 3010.0], 1600, 11000.0),
+            noise(900, 4000.0, 2),
+            vowel(210.0, 240.0, [300.0, 870.0, 2240.0], 1800, 16000.0),
+            gap(40),
+        ]
+        .concat(),
+    )
+}
+
+fn run<P: Processor>(processor: &mut P, input: &[i16]) -> Vec<i16> {
+    let mut output = input.to_vec();
+    processor.process_block(&mut output);
+    output
+}
+
+fn golden(name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests", "golden", name]
+        .iter()
+        .collect()
+}
+
+fn write_wav(name: &str, samples: &[i16]) {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: RATE.hz(),
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(golden(name), spec).unwrap();
+    for &s in samples {
+        writer.write_sample(s).unwrap();
+    }
+    writer.finalize().unwrap();
+}
+
+fn read_wav(name: &str) -> Vec<i16> {
+    let mut reader = hound::WavReader::open(golden(name))
+        .unwrap_or_else(|e| panic!("{name}: {e}; run with UPDATE_GOLDEN=1 to make it"));
+    assert_eq!(reader.spec().sample_rate, RATE.hz());
+    reader.samples().map(Result::unwrap).collect()
+}
+
+/// Every effect with its default settings on the phrase in
+/// `golden/input.wav` must give exactly what it did when the golden files
+/// were made. Set `UPDATE_GOLDEN` to make them again after changing an
+/// effect on purpose, and listen to them before committing.
+#[test]
+fn effects_match_their_golden_output() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    if update {
+        write_wav("input.wav", &phrase());
+    }
+    let input = read_wav("input.wav");
+    let mut effects: Effects<N> = Effects::new(&Config::default(), RATE);
+    for (effect, name) in [
+        (Effect::PitchShift, "pitch_shift.wav"),
+        (Effect::RingModulator, "ring_modulator.wav"),
+        (Effect::Robot, "robot.wav"),
+        (Effect::Bitcrusher, "bitcrusher.wav"),
+    ] {
+        effects.select(effect);
+        let output = run(&mut effects, &input);
+        if update {
+            write_wav(name, &output);
+        }
+        let expected = read_wav(name);
+        assert_eq!(output.len(), expected.len(), "{name}");
+        if let Some(i) = output.iter().zip(&expected).position(|(a, b)| a != b) {
+            panic!(
+                "{name} differs from sample {i}: {} for {}",
+                output[i], expected[i]
+            );
+        }
+    }
+}
+
+/// The mean pitch found over the frames of `signal` past the first `skip`
+/// samples, and how many frames had one.
+fn mean_pitch(signal: &[i16], skip: usize) -> (f64, usize) {
+    let mut yin: Yin<N> = Yin::new(&pitch::Config::default(), RATE);
+    let found: Vec<f64> = signal[skip..]
+        .chunks_exact(N)
+        .filter_map(|c| yin.estimate(c.try_into().unwrap()))
+        .map(|p| f64::from(p.millihertz) / 1000.0)
+        .collect();
+    (found.iter().sum::<f64>() / found.len() as f64, found.len())
+}
+
+#[test]
+fn shifting_moves_the_pitch_by_semitones() {
+    let input = sine(200.0, 16 * N);
+    for semitones in [-12, -5, 3, 7, 12] {
+        let mut shifter: PitchShifter<N> = PitchShifter::new(&shift::Config { semitones });
+        let output = run(&mut shifter, &input);
+        let (hz, frames) = mean_pitch(&output, N);
+        let expected = 200.0 * 2f64.powf(f64::from(semitones) / 12.0);
+        assert!(frames >= 13, "{semitones}: {frames} frames");
+        assert!(
+            (hz / expected - 1.0).abs() < 0.01,
+            "{semitones}: {hz:.1} Hz, expected {expected:.1}"
+        );
+    }
+}
+
+#[test]
+fn shifting_keeps_the_timing() {
+    // a burst goes in and comes out in the same place, give or take the
+    // grain
+    let input = [vec![0; 2000], sine(300.0, 800), vec![0; 2000]].concat();
+    let mut shifter: PitchShifter<N> = PitchShifter::new(&shift::Config { semitones: 7 });
+    let output = run(&mut shifter, &input);
+    let loud: Vec<usize> = (0..output.len())
+        .filter(|&i| output[i].unsigned_abs() > 1000)
+        .collect();
+    let latency = shifter.latency();
+    assert!(loud[0] + N / 2 >= 2000 + latency, "{}", loud[0]);
+    assert!(*loud.last().unwrap() <= 2800 + latency + N / 2);
+}
+
+#[test]
+fn no_shift_only_delays() {
+    let input = to_samples(&noise(4000, 20000.0, 3));
+    let mut shifter: PitchShifter<N> = PitchShifter::new(&shift::Config { semitones: 0 });
+    let output = run(&mut shifter, &input);
+    let latency = shifter.latency();
+    // the middle of the room the grains have to move in
+    assert_eq!(latency, 111);
+    assert!(output[..latency].iter().all(|&s| s == 0));
+    assert_eq!(output[latency..], input[..input.len() - latency]);
+}
+
+/// Amplitude of the `hz` component of `signal`.
+fn amplitude(signal: &[i16], hz: f64) -> f64 {
+    let w = 2.0 * PI * hz / f64::from(RATE.hz());
+    let (re, im) = signal
+        .iter()
+        .enumerate()
+        .fold((0.0, 0.0), |(re, im), (i, &s)| {
+            let (sin, cos) = (w * i as f64).sin_cos();
+            (re + f64::from(s) * cos, im - f64::from(s) * sin)
+        });
+    2.0 * (re * re + im * im).sqrt() / signal.len() as f64
+}
+
+#[test]
+fn ring_modulation_moves_a_tone_to_sum_and_difference() {
+    // a whole number of cycles of everything in 8000 samples
+    let input = sine(1000.0, 8000);
+    let config = ring::Config {
+        carrier_hz: 300,
+        mix_percent: 100,
+    };
+    let mut ring = RingModulator::new(&config, RATE);
+    let output = run(&mut ring, &input);
+    for hz in [700.0, 1300.0] {
+        let a = amplitude(&output, hz);
+        assert!((a / 6000.0 - 1.0).abs() < 0.01, "{hz} Hz: {a:.0}");
+    }
+    assert!(amplitude(&output, 1000.0) < 6.0);
+
+    // half wet: the tone at half, and the sidebands at a quarter
+    let config = ring::Config {
+        mix_percent: 50,
+        ..config
+    };
+    let mut ring = RingModulator::new(&config, RATE);
+    let output = run(&mut ring, &input);
+    let a = amplitude(&output, 1000.0);
+    assert!((a / 6000.0 - 1.0).abs() < 0.01, "{a:.0}");
+    let a = amplitude(&output, 1300.0);
+    assert!((a / 3000.0 - 1.0).abs() < 0.01, "{a:.0}");
+}
+
+#[test]
+fn the_robot_speaks_at_its_own_pitch() {
+    let input = to_samples(&vowel(
+        180.0,
+        230.0,
+        [730.0, 1090.0, 2440.0],
+        16 * N,
+        14000.0,
+    ));
+    for pitch_hz in [100, 120, 150] {
+        let mut robot: Robot<N> = Robot::new(&robot::Config { pitch_hz }, RATE);
+        let output = run(&mut robot, &input);
+        // a whole number of samples a period
+        let expected = 8000.0 / f64::from(8000 / pitch_hz);
+        let (hz, frames) = mean_pitch(&output[..14 * N], 2 * N);
+        assert!(frames >= 11, "{pitch_hz}: {frames} frames");
+        assert!(
+            (hz / expected - 1.0).abs() < 0.005,
+            "{pitch_hz}: {hz:.1} Hz, expected {expected:.1}"
+        );
+        // at about the level of the voice
+        let power = |s: &[i16]| s.iter().map(|&x| f64::from(x).powi(2)).sum::<f64>();
+        let ratio = power(&output[2 * N..]) / power(&input[N..input.len() - N]);
+        assert!((0.25..4.0).contains(&ratio), "{pitch_hz}: {ratio:.2}");
+    }
+}
+
+#[test]
+fn silence_makes_no_robot() {
+    let mut robot: Robot<N> = Robot::new(&robot::Config::default(), RATE);
+    assert!(run(&mut robot, &[0; 4 * N]).iter().all(|&s| s == 0));
+}
+
+#[test]
+fn crushing_quantizes_and_holds() {
+    let input = to_samples(&noise(4000, 40000.0, 4));
+    let config = crush::Config {
+        bits: 6,
+        rate_hz: 3000,
+    };
+    let mut crusher = Bitcrusher::new(&config, RATE);
+    let output = run(&mut crusher, &input);
+    // 64 levels, 1024 apart, rounded to the nearest short of going over
+    // full scale
+    let quantize = |x: i16| ((i32::from(x) + 512) >> 10 << 10).min(31744) as i16;
+    assert!(output.contains(&-32768) && output.contains(&31744));
+    // a new sample every 8/3, so held for two or three
+    let mut held = 0;
+    for (i, (&x, &y)) in input.iter().zip(&output).enumerate() {
+        if i == 0 || i * 3 / 8 != (i - 1) * 3 / 8 {
+            held = quantize(x);
+        }
+        assert_eq!(y, held, "{i}");
+    }
+}
+
+#[test]
+fn full_resolution_at_the_full_rate_changes_nothing() {
+    let input = to_samples(&noise(1000, 40000.0, 5));
+    let config = crush::Config {
+        bits: 16,
+        rate_hz: RATE.hz(),
+    };
+    let mut crusher = Bitcrusher::new(&config, RATE);
+    assert_eq!(run(&mut crusher, &input), input);
+}
+
+#[test]
+fn the_selector_runs_one_effect_at_a_time() {
+    let input = phrase();
+    let config = Config::default();
+    let mut effects: Effects<N> = Effects::new(&config, RATE);
+    assert_eq!(effects.effect(), Effect::Off);
+    assert_eq!(run(&mut effects, &input), input);
+
+    // each the same as the effect on its own, started afresh
+    effects.select(Effect::RingModulator);
+    assert_eq!(effects.effect(), Effect::RingModulator);
+    let mut ring = RingModulator::new(&config.ring, RATE);
+    assert_eq!(run(&mut effects, &input), run(&mut ring, &input));
+    effects.select(Effect::Bitcrusher);
+    let mut crusher = Bitcrusher::new(&config.crush, RATE);
+    let crushed = run(&mut crusher, &input);
+    assert_eq!(run(&mut effects, &input), crushed);
+
+    // and sample by sample the same as a block at a time
+    effects.select(Effect::Off);
+    effects.select(Effect::Bitcrusher);
+    let one_by_one: Vec<i16> = input.iter().map(|&s| effects.process(s)).collect();
+    assert_eq!(one_by_one, crushed);
+
+    // selecting what is already running doesn't interrupt it
+    effects.select(Effect::PitchShift);
+    let first = run(&mut effects, &input[..1000]);
+    effects.select(Effect::PitchShift);
+    let second = run(&mut effects, &input[1000..]);
+    let mut shifter: PitchShifter<N> = PitchShifter::new(&config.shift);
+    assert_eq!([first, second].concat(), run(&mut shifter, &input));
+}
+
+#[test]
+#[should_panic]
+fn shifts_are_at_most_an_octave() {
+    let _: PitchShifter<N> = PitchShifter::new(&shift::Config { semitones: 13 });
+}
+
+/// An impulse of `amplitude` followed by `len - 1` samples of silence.
+fn impulse(amplitude: i16, len: usize) -> Vec<i16> {
+    let mut signal = vec![0; len];
+    signal[0] = amplitude;
+    signal
+}
+
+#[test]
+fn an_echo_comes_back_quieter_each_time() {
+    let config = echo::Config {
+        mix_percent: 50,
+        ..echo::Config::default()
+    };
+    // 250 ms is 2000 samples
+    let mut echo: Echo<2000> = Echo::new(&config, RATE);
+    let output = run(&mut echo, &impulse(20000, 12000));
+    assert_eq!(output[0], 10000);
+    for k in 1..6 {
+        // each at 40% of the last, rounded down at every pass
+        let expected = 10000.0 * 0.4f64.powi(k - 1);
+        let y = f64::from(output[2000 * k as usize]);
+        assert!((y - expected).abs() <= 1.0 + expected / 5000.0, "{k}: {y}");
+    }
+    let echoes: Vec<usize> = (0..output.len()).filter(|&i| output[i] != 0).collect();
+    assert_eq!(echoes, (0..=5).map(|k| 2000 * k).collect::<Vec<_>>());
+}
+
+#[test]
+fn taps_give_an_echo_each() {
+    let tap = |delay_ms, gain_percent| Tap {
+        delay_ms,
+        gain_percent,
+    };
+    let config = echo::Config {
+        delay_ms: 200,
+        feedback_percent: 0,
+        taps: [tap(50, 100), tap(120, 60), tap(0, 0), tap(200, 30)],
+        mix_percent: 100,
+    };
+    let mut echo: Echo<1600> = Echo::new(&config, RATE);
+    let output = run(&mut echo, &impulse(-10000, 3000));
+    let echoes: Vec<(usize, i16)> = output
+        .iter()
+        .enumerate()
+        .filter(|&(_, &y)| y != 0)
+        .map(|(i, &y)| (i, y))
+        .collect();
+    assert_eq!(echoes, [(400, -10000), (960, -6000), (1600, -3000)]);
+}
+
+#[test]
+fn feedback_dies_away_to_nothing() {
+    let config = echo::Config {
+        delay_ms: 10,
+        feedback_percent: 99,
+        ..echo::Config::default()
+    };
+    let mut echo: Echo<2000> = Echo::new(&config, RATE);
+    let input = [to_samples(&noise(8000, 32767.0, 6)), vec![0; 200_000]].concat();
+    let output = run(&mut echo, &input);
+    assert!(output[output.len() - 4000..].iter().all(|&y| y == 0));
+}
+
+#[test]
+fn a_dry_echo_passes_the_voice_through() {
+    let config = echo::Config {
+        mix_percent: 0,
+        ..echo::Config::default()
+    };
+    let mut echo: Echo<2000> = Echo::new(&config, RATE);
+    let input = phrase();
+    assert_eq!(run(&mut echo, &input), input);
+}
+
+#[test]
+fn a_second_of_echo_fits_in_ram() {
+    // a second at 16 kHz, with the RP2040's 256 KB to share
+    assert!(core::mem::size_of::<Echo<16000>>() < 33 * 1024);
+    let _: Echo<16000> = Echo::new(&echo::Config::default(), SampleRate::Hz16000);
+}
+
+#[test]
+#[should_panic]
+fn echoes_must_fit_the_buffer() {
+    // 250 ms is 2000 samples
+    let _: Echo<1999> = Echo::new(&echo::Config::default(), RATE);
+}
+
+/// Energy of each 50 ms of `signal`.
+fn energies(signal: &[i16]) -> Vec<f64> {
+    signal
+        .chunks(400)
+        .map(|c| c.iter().map(|&x| f64::from(x).powi(2)).sum())
+        .collect()
+}
+
+/// How long the tail of `output` takes to fall 30 dB from its loudest
+/// 50 ms, in ms.
+fn decay_ms(output: &[i16]) -> usize {
+    let energy = energies(output);
+    let peak = energy.iter().cloned().fold(0.0, f64::max);
+    let loudest = energy.iter().position(|&e| e == peak).unwrap();
+    let quiet = energy[loudest..]
+        .iter()
+        .position(|&e| e < peak / 1000.0)
+        .unwrap();
+    50 * quiet
+}
+
+#[test]
+fn the_reverb_starts_after_the_shortest_comb() {
+    let config = reverb::Config {
+        mix_percent: 100,
+        ..reverb::Config::default()
+    };
+    for (rate, predelay) in [(RATE, 202), (SampleRate::Hz16000, 405)] {
+        let mut reverb = Reverb::new(&config, rate);
+        assert_eq!(reverb.predelay(), predelay);
+        let output = run(&mut reverb, &impulse(20000, 4000));
+        let first = output.iter().position(|&y| y != 0).unwrap();
+        assert_eq!(first, predelay);
+    }
+}
+
+#[test]
+fn the_reverb_tail_fades_with_the_room_size() {
+    let decay = |room_percent| {
+        let config = reverb::Config {
+            room_percent,
+            mix_percent: 100,
+            ..reverb::Config::default()
+        };
+        let mut reverb = Reverb::new(&config, RATE);
+        let output = run(&mut reverb, &impulse(30000, 20 * 8000));
+        // and dies away to nothing in the end
+        assert!(output[output.len() - 8000..].iter().all(|&y| y == 0));
+        decay_ms(&output)
+    };
+    let (small, medium, large) = (decay(0), decay(50), decay(90));
+    assert!(small < medium && medium < large, "{small} {medium} {large}");
+    assert!((150..600).contains(&small), "{small}");
+    assert!(large > 1000, "{large}");
+}
+
+#[test]
+fn damping_darkens_the_tail() {
+    // the share of the tail's energy in its differences, which weigh the
+    // top end
+    let brightness = |damping_percent| {
+        let config = reverb::Config {
+            damping_percent,
+            mix_percent: 100,
+            ..reverb::Config::default()
+        };
+        let mut reverb = Reverb::new(&config, RATE);
+        let input = [to_samples(&noise(800, 20000.0, 7)), vec![0; 8000]].concat();
+        let tail = run(&mut reverb, &input).split_off(2400);
+        let power = |s: &[i16]| s.iter().map(|&x| f64::from(x).powi(2)).sum::<f64>();
+        let differences: Vec<i16> = tail.windows(2).map(|w| w[1] - w[0]).collect();
+        power(&differences) / power(&tail)
+    };
+    let (bright, dark) = (brightness(0), brightness(100));
+    assert!(dark < bright / 2.0, "{bright:.3} {dark:.3}");
+}
+
+#[test]
+fn the_reverb_is_about_as_loud_as_the_voice() {
+    let config = reverb::Config {
+        mix_percent: 100,
+        ..reverb::Config::default()
+    };
+    let mut reverb = Reverb::new(&config, RATE);
+    let input = to_samples(&noise(4 * 8000, 8000.0, 8));
+    let output = run(&mut reverb, &input);
+    let rms = |s: &[i16]| (energies(s).iter().sum::<f64>() / s.len() as f64).sqrt();
+    let ratio = rms(&output[8000..]) / rms(&input[8000..]);
+    assert!((0.5..2.0).contains(&ratio), "{ratio:.2}");
+}
+
+#[test]
+fn a_dry_reverb_passes_the_voice_through() {
+    let config = reverb::Config {
+        mix_percent: 0,
+        ..reverb::Config::default()
+    };
+    let mut reverb = Reverb::new(&config, RATE);
+    let input = phrase();
+    assert_eq!(run(&mut reverb, &input), input);
+    // and reset empties the room
+    reverb.reset();
+    assert_eq!(reverb, Reverb::new(&config, RATE));
+}
+
+#[test]
+fn the_reverb_fits_in_ram() {
+    assert!(core::mem::size_of::<Reverb>() < 12 * 1024);
+}
+
+#[test]
+#[should_panic]
+fn the_reverb_runs_at_up_to_16_khz() {
+    Reverb::new(&reverb::Config::default(), SampleRate::Hz22050);
+}
@@ -0,0 +1,345 @@
+mod common;
+
+use std::f64::consts::PI;
+
+use voice_core::fft::{self, Bin, Fft, Frames, Window};
+
+/// A cheap pseudo-random full-scale sequence.
+fn noise(len: usize, seed: u32) -> Vec<i16> {
+    common::random(seed)
+        .take(len)
+        .map(|x| (x >> 16) as i16)
+        .collect()
+}
+
+/// A sine of `amplitude` at `f` cycles per sample.
+fn sine(f: f64, amplitude: f64, len: usize) -> Vec<i16> {
+    (0..len)
+        .map(|i| (amplitude * (2.0 * PI * f * i as f64).sin()).round() as i16)
+        .collect()
+}
+
+/// Recursive radix-2 FFT in floating point.
+fn float_fft(x: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let n = x.len();
+    if n == 1 {
+        return x.to_vec();
+    }
+    let even: Vec<_> = x.iter().step_by(2).copied().collect();
+    let odd: Vec<_> = x.iter().skip(1).step_by(2).copied().collect();
+    let (even, odd) = (float_fft(&even), float_fft(&odd));
+    let mut out = vec![(0.0, 0.0); n];
+    for k in 0..n / 2 {
+        let (s, c) = (-2.0 * PI * k as f64 / n as f64).sin_cos();
+        let (re, im) = odd[k];
+        let t = (re * c - im * s, re * s + im * c);
+        out[k] = (even[k].0 + t.0, even[k].1 + t.1);
+        out[k + n / 2] = (even[k].0 - t.0, even[k].1 - t.1);
+    }
+    out
+}
+
+/// The first `N/2 + 1` bins of the float FFT of `frame` tapered by
+/// `window`, with the window quantized as the fixed-point one is.
+fn reference(frame: &[i16], window: Window) -> Vec<(f64, f64)> {
+    let n = frame.len();
+    let x: Vec<_> = frame
+        .iter()
+        .enumerate()
+        .map(|(i, &s)| {
+            let w = (window.coefficient(i, n) * 32768.0).round() / 32768.0;
+            (f64::from(s) * w, 0.0)
+        })
+        .collect();
+    float_fft(&x)[..=n / 2].to_vec()
+}
+
+/// Transform `frame` with an `N` point FFT.
+fn transform<const N: usize>(frame: &[i16], window: Window) -> Vec<Bin> {
+    let fft: Fft<N> = Fft::new(window);
+    let mut bins = vec![Bin::default(); fft.bins()];
+    fft.transform(frame.try_into().unwrap(), &mut bins);
+    bins
+}
+
+/// Largest difference between `bins` and `expected`, in the units of the
+/// bins.
+fn worst(bins: &[Bin], expected: &[(f64, f64)]) -> f64 {
+    assert_eq!(bins.len(), expected.len());
+    bins.iter()
+        .zip(expected)
+        .map(|(b, e)| (f64::from(b.re) - e.0).hypot(f64::from(b.im) - e.1))
+        .fold(0.0, f64::max)
+}
+
+/// How far the largest error in `bins` is below the RMS of `expected`, in
+/// dB.
+fn accuracy(bins: &[Bin], expected: &[(f64, f64)]) -> f64 {
+    let rms = expected.iter().map(|e| e.0 * e.0 + e.1 * e.1).sum::<f64>() / expected.len() as f64;
+    10.0 * rms.log10() - 20.0 * worst(bins, expected).log10()
+}
+
+#[test]
+fn matches_a_float_fft() {
+    for window in [
+        Window::Rectangular,
+        Window::Hann,
+        Window::Hamming,
+        Window::Blackman,
+    ] {
+        let frame = noise(512, 5);
+        let bins = transform::<512>(&frame, window);
+        let expected = reference(&frame, window);
+        let db = accuracy(&bins, &expected);
+        assert!(db > 80.0, "{window:?}: {db:.1} dB");
+    }
+}
+
+#[test]
+fn every_size_matches() {
+    let frame = noise(4096, 9);
+    macro_rules! check {
+        ($($n:literal)*) => {$(
+            let bins = transform::<$n>(&frame[..$n], Window::Hann);
+            let expected = reference(&frame[..$n], Window::Hann);
+            // or within a step, for the shortest, where the bins are small
+            let db = accuracy(&bins, &expected);
+            assert!(db > 80.0 || worst(&bins, &expected) < 1.0, "{}: {db:.1} dB", $n);
+        )*};
+    }
+    check!(4 8 16 32 64 128 256 1024 2048 4096);
+}
+
+#[test]
+fn full_scale_does_not_overflow() {
+    // a full-scale square wave at the highest frequency puts everything
+    // into the last bin, and constant full scale everything into the first
+    let nyquist: Vec<i16> = (0..4096)
+        .map(|i| if i % 2 == 0 { i16::MIN } else { i16::MAX })
+        .collect();
+    let bins = transform::<4096>(&nyquist, Window::Rectangular);
+    assert_eq!(
+        bins[2048],
+        Bin {
+            re: -2048 * 65535,
+            im: 0
+        }
+    );
+    // with the other bins down by the Q15 twiddles' -90 dB or so
+    assert!(bins[..2048].iter().all(|b| b.magnitude() < 1 << 12));
+
+    let dc = vec![i16::MIN; 4096];
+    let bins = transform::<4096>(&dc, Window::Rectangular);
+    assert_eq!(
+        bins[0],
+        Bin {
+            re: -4096 * 32768,
+            im: 0
+        }
+    );
+    assert!(bins[1..].iter().all(|b| b.magnitude() < 1 << 12));
+}
+
+#[test]
+fn quiet_signals_keep_their_detail() {
+    // a couple of steps of noise, where scaling by 1/2 a stage would have
+    // left nothing, comes out to within a step
+    let frame: Vec<i16> = noise(256, 3).into_iter().map(|s| s >> 14).collect();
+    let bins = transform::<256>(&frame, Window::Rectangular);
+    assert!(worst(&bins, &reference(&frame, Window::Rectangular)) < 1.0);
+}
+
+#[test]
+fn sine_peaks_at_its_bin() {
+    let fft: Fft<256> = Fft::new(Window::Hann);
+    let frame = sine(20.0 / 256.0, 10_000.0, 256);
+    let mut bins = vec![Bin::default(); 129];
+    fft.transform(frame[..].try_into().unwrap(), &mut bins);
+
+    let mut magnitude = [0; 129];
+    fft::magnitude(&bins, &mut magnitude);
+    let peak = (0..129).max_by_key(|&k| magnitude[k]).unwrap();
+    assert_eq!(peak, 20);
+    // A·S/2, with S the window sum; the Hann window's is N/2
+    assert_eq!(fft.window_sum(), 128 << 15);
+    let expected = 10_000.0 * 128.0 / 2.0;
+    let error = f64::from(magnitude[20]) - expected;
+    assert!(error.abs() < expected * 1e-4, "{}", magnitude[20]);
+    // the Hann window leaks into the bins either side, and the rest is down
+    // at the rounding of the samples
+    let floor = magnitude[20] / 10_000;
+    assert!(magnitude
+        .iter()
+        .enumerate()
+        .all(|(k, &m)| k.abs_diff(20) <= 1 || m < floor));
+
+    let mut power = [0; 129];
+    fft::power(&bins, &mut power);
+    assert_eq!(power[20], bins[20].power());
+    assert_eq!(u64::from(magnitude[20]), power[20].isqrt());
+}
+
+#[test]
+fn windows_lower_leakage() {
+    // a sine between bins, and how far it leaks into a bin `away` off
+    let frame = sine(40.5 / 512.0, 10_000.0, 512);
+    let leak = |window, away: usize| {
+        let bins = transform::<512>(&frame, window);
+        let ratio = f64::from(bins[40 + away].magnitude()) / f64::from(bins[40].magnitude());
+        20.0 * ratio.log10()
+    };
+    let rectangular = leak(Window::Rectangular, 5);
+    let hann = leak(Window::Hann, 5);
+    let blackman = leak(Window::Blackman, 5);
+    assert!(rectangular > -30.0, "{rectangular:.1} dB");
+    assert!(hann < rectangular - 15.0, "{hann:.1} dB");
+    assert!(blackman < hann - 8.0, "{blackman:.1} dB");
+    // Hamming trades the nearest sidelobe for the further ones
+    assert!(leak(Window::Hamming, 3) < leak(Window::Hann, 3) - 15.0);
+    assert!(leak(Window::Hamming, 20) > leak(Window::Hann, 20) + 20.0);
+}
+
+#[test]
+fn inverse_undoes_the_transform() {
+    let frame = noise(512, 4);
+    for window in [Window::Rectangular, Window::Hann] {
+        let fft: Fft<512> = Fft::new(window);
+        let mut bins = vec![Bin::default(); 257];
+        fft.transform(frame[..].try_into().unwrap(), &mut bins);
+        let mut back = [0; 512];
+        fft.inverse(&mut bins, &mut back);
+        // the frame comes back tapered, to within the rounding of the Q15
+        // twiddles both ways
+        for (n, (&x, &y)) in frame.iter().zip(&back).enumerate() {
+            let expected = f64::from(x) * window.coefficient(n, 512);
+            let error = (f64::from(y) - expected).abs();
+            assert!(error <= 3.0, "{window:?} {n}: {y}, {expected:.1}");
+        }
+    }
+}
+
+#[test]
+fn inverse_matches_a_float_fft() {
+    // a spectrum scaled bin by bin, as a noise suppressor would
+    let fft: Fft<256> = Fft::new(Window::Hann);
+    let frame = noise(256, 8);
+    let mut bins = vec![Bin::default(); 129];
+    fft.transform(frame[..].try_into().unwrap(), &mut bins);
+    for (k, bin) in bins.iter_mut().enumerate() {
+        let gain = (k % 7) as i64;
+        bin.re = (i64::from(bin.re) * gain / 7) as i32;
+        bin.im = (i64::from(bin.im) * gain / 7) as i32;
+    }
+
+    // the whole conjugate-symmetric spectrum, inverted with the forward
+    // FFT of its conjugate
+    let full: Vec<_> = (0..256)
+        .map(|k| {
+            let b = if k <= 128 { bins[k] } else { bins[256 - k] };
+            let im = if k <= 128 { -b.im } else { b.im };
+            (f64::from(b.re), f64::from(im))
+        })
+        .collect();
+    let expected: Vec<f64> = float_fft(&full).iter().map(|z| z.0 / 256.0).collect();
+
+    let mut back = [0; 256];
+    fft.inverse(&mut bins, &mut back);
+    for (n, (&y, &e)) in back.iter().zip(&expected).enumerate() {
+        assert!((f64::from(y) - e).abs() <= 1.5, "{n}: {y}, {e:.1}");
+    }
+}
+
+#[test]
+fn inverse_holds_full_scale() {
+    // the largest bins there are, at the longest length, where there are
+    // no guard bits to spare
+    let fft: Fft<4096> = Fft::new(Window::Rectangular);
+    for frame in [
+        vec![i16::MIN; 4096],
+        (0..4096)
+            .map(|i| if i % 2 == 0 { i16::MIN } else { i16::MAX })
+            .collect(),
+        noise(4096, 6),
+    ] {
+        let mut bins = vec![Bin::default(); 2049];
+        fft.transform(frame[..].try_into().unwrap(), &mut bins);
+        let mut back = [0; 4096];
+        fft.inverse(&mut bins, &mut back);
+        for (&x, &y) in frame.iter().zip(&back) {
+            assert!((i32::from(x) - y).abs() <= 4, "{x} {y}");
+        }
+    }
+}
+
+#[test]
+fn windows_are_periodic() {
+    for n in [0, 1, 63, 64, 100] {
+        let hann = Window::Hann.coefficient(n, 128);
+        let hamming = Window::Hamming.coefficient(n, 128);
+        let blackman = Window::Blackman.coefficient(n, 128);
+        let x = 2.0 * PI * n as f64 / 128.0;
+        assert!((hann - (0.5 - 0.5 * x.cos())).abs() < 1e-12);
+        assert!((hamming - (0.54 - 0.46 * x.cos())).abs() < 1e-12);
+        assert!((blackman - (0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos())).abs() < 1e-12);
+    }
+    // Hann windows overlapped by half add up to one
+    for n in 0..64 {
+        let sum = Window::Hann.coefficient(n, 128) + Window::Hann.coefficient(n + 64, 128);
+        assert!((sum - 1.0).abs() < 1e-12);
+    }
+    assert_eq!(Window::Rectangular.coefficient(7, 128), 1.0);
+}
+
+#[test]
+fn frames_overlap_by_the_hop() {
+    let input = noise(1000, 1);
+    let mut frames: Frames<64> = Frames::new(16);
+    let mut seen = Vec::new();
+    for (i, &s) in input.iter().enumerate() {
+        if let Some(frame) = frames.push(s) {
+            seen.push((i, *frame));
+        }
+    }
+    assert_eq!(seen.len(), 1000 / 16);
+    for (i, frame) in &seen {
+        // the frame ends with the sample that completed it, after silence
+        // before the start
+        let end = i + 1;
+        let start = end.saturating_sub(64);
+        assert_eq!(frame[64 - (end - start)..], input[start..end]);
+        assert!(frame[..64 - (end - start)].iter().all(|&s| s == 0));
+    }
+    assert_eq!(seen[0].0, 15);
+
+    frames.reset();
+    let first = (0..16)
+        .filter_map(|i| frames.push(input[i]).copied())
+        .next();
+    assert_eq!(first.unwrap(), seen[0].1);
+}
+
+#[test]
+fn frames_without_overlap() {
+    let input = noise(256, 2);
+    let mut frames: Frames<64> = Frames::new(64);
+    let seen: Vec<_> = input
+        .iter()
+        .filter_map(|&s| frames.push(s).copied())
+        .collect();
+    assert_eq!(seen.len(), 4);
+    for (frame, chunk) in seen.iter().zip(input.chunks(64)) {
+        assert_eq!(frame[..], *chunk);
+    }
+}
+
+#[test]
+#[should_panic]
+fn length_must_be_a_power_of_two() {
+    Fft::<96>::new(Window::Hann);
+}
+
+#[test]
+#[should_panic]
+fn hop_must_fit_the_frame() {
+    Frames::<64>::new(65);
+}
@@ -0,0 +1,117 @@
+use voice_core::g711::{self, Law};
+
+// ADC samples and what the ITU reference coder (as in CPython's audioop)
+// makes of them.
+const SAMPLES: [u16; 16] = [
+    0, 260, 520, 780, 1040, 1300, 1560, 1820, 2080, 2340, 2600, 2860, 3120, 3380, 3640, 4095,
+];
+const ULAW: [u8; 16] = [
+    0, 3, 7, 12, 16, 24, 32, 50, 219, 173, 158, 150, 143, 139, 134, 128,
+];
+const ALAW: [u8; 16] = [
+    42, 46, 34, 38, 58, 50, 11, 25, 245, 135, 180, 188, 165, 161, 173, 170,
+];
+
+// codes and the 16-bit samples the reference decodes them to
+const CODES: [u8; 16] = [
+    0, 17, 34, 51, 68, 85, 102, 119, 136, 153, 170, 187, 204, 221, 238, 255,
+];
+const ULAW_DECODED: [i16; 16] = [
+    -32124, -15484, -7420, -3516, -1628, -716, -276, -64, 23932, 11388, 5372, 2492, 1116, 460, 148,
+    0,
+];
+const ALAW_DECODED: [i16; 16] = [
+    -5504, -2624, -24064, -11520, -280, -8, -1248, -592, 7552, 3648, 32256, 15616, 408, 136, 1760,
+    848,
+];
+
+const LAWS: [Law; 2] = [Law::MuLaw, Law::ALaw];
+
+#[test]
+fn encoders_match_the_reference() {
+    let ulaw: Vec<_> = SAMPLES.iter().map(|&s| Law::MuLaw.encode(s)).collect();
+    let alaw: Vec<_> = SAMPLES.iter().map(|&s| Law::ALaw.encode(s)).collect();
+    assert_eq!(ulaw, ULAW);
+    assert_eq!(alaw, ALAW);
+}
+
+#[test]
+fn decoders_match_the_reference() {
+    let ulaw: Vec<_> = CODES.iter().map(|&c| g711::ulaw_decode(c)).collect();
+    let alaw: Vec<_> = CODES.iter().map(|&c| g711::alaw_decode(c)).collect();
+    assert_eq!(ulaw, ULAW_DECODED);
+    assert_eq!(alaw, ALAW_DECODED);
+}
+
+#[test]
+fn silence_is_the_idle_code() {
+    assert_eq!(Law::MuLaw.encode(2048), 0xff);
+    assert_eq!(Law::ALaw.encode(2048), 0xd5);
+    assert_eq!(Law::MuLaw.decode(0xff), 2048);
+    assert_eq!(Law::ALaw.decode(0xd5), 2048);
+}
+
+#[test]
+fn every_sample_round_trips_within_its_segment_step() {
+    for law in LAWS {
+        for sample in 0..=4095u16 {
+            let decoded = law.decode(law.encode(sample));
+            let magnitude = sample.abs_diff(2048);
+            let error = decoded.abs_diff(sample);
+            // half a step of the segment, and the 16 to 12 bit truncation
+            assert!(
+                32 * error <= magnitude + 64,
+                "{law:?}: {sample} came back as {decoded}"
+            );
+            // quiet samples come back exactly
+            if magnitude < 16 {
+                assert_eq!(decoded, sample, "{law:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn encoding_is_monotonic_in_every_sample() {
+    for law in LAWS {
+        let decoded: Vec<_> = (0..=4095).map(|s| law.decode(law.encode(s))).collect();
+        assert!(decoded.windows(2).all(|w| w[0] <= w[1]), "{law:?}");
+    }
+}
+
+#[test]
+fn sign_only_flips_the_sign_bit() {
+    for magnitude in 1..2048u16 {
+        // µ-law is symmetric about mid-rail, A-law about half an ADC step
+        // below it
+        let (up, down) = (2048 + magnitude, 2048 - magnitude);
+        assert_eq!(Law::MuLaw.encode(up) ^ Law::MuLaw.encode(down), 0x80);
+        assert_eq!(Law::ALaw.encode(up - 1) ^ Law::ALaw.encode(down), 0x80);
+    }
+}
+
+#[test]
+fn decoding_every_code_is_stable() {
+    for law in LAWS {
+        for code in 0..=255u8 {
+            let sample = law.decode(code);
+            assert_eq!(
+                law.decode(law.encode(sample)),
+                sample,
+                "{law:?} code {code:#04x}"
+            );
+        }
+    }
+}
+
+#[test]
+fn blocks_convert_as_far_as_both_go() {
+    let mut codes = [0; 4];
+    Law::MuLaw.encode_block(&SAMPLES[..6], &mut codes);
+    assert_eq!(codes, ULAW[..4]);
+    let mut samples = [0; 8];
+    Law::MuLaw.decode_block(&codes, &mut samples);
+    assert_eq!(samples[4..], [0; 4]);
+    assert_eq!(Law::MuLaw.tag(), 7);
+    assert_eq!(Law::ALaw.tag(), 6);
+}
@@ -0,0 +1,277 @@
+mod common;
+
+use std::f64::consts::PI;
+
+use voice_core::hum::{Config, HumRemover, Mains};
+use voice_core::process::Processor;
+use voice_core::SampleRate;
+
+use common::{noise, speech};
+
+const RATE: SampleRate = SampleRate::Hz8000;
+
+/// Samples per second at `RATE`.
+const SECOND: usize = 8000;
+
+/// Hum at `hz` with its next three harmonics, each half the last.
+fn hum(len: usize, hz: f64, amplitude: f64) -> Vec<f64> {
+    (0..len)
+        .map(|i| {
+            let w = 2.0 * PI * hz * i as f64 / SECOND as f64;
+            amplitude
+                * (1..=4)
+                    .map(|k| (k as f64 * w + 0.3 * k as f64).sin() / (1 << (k - 1)) as f64)
+                    .sum::<f64>()
+        })
+        .collect()
+}
+
+fn mix(a: &[f64], b: &[f64]) -> Vec<i16> {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a + b).round().clamp(-32768.0, 32767.0) as i16)
+        .collect()
+}
+
+fn run(remover: &mut HumRemover, input: &[i16]) -> Vec<i16> {
+    let mut output = input.to_vec();
+    remover.process_block(&mut output);
+    output
+}
+
+/// Amplitude of `samples` at `hz`, by correlating with a sine and cosine.
+fn amplitude(samples: &[f64], hz: f64) -> f64 {
+    let (mut re, mut im) = (0.0, 0.0);
+    for (i, &s) in samples.iter().enumerate() {
+        let w = 2.0 * PI * hz * i as f64 / SECOND as f64;
+        re += s * w.cos();
+        im += s * w.sin();
+    }
+    2.0 * re.hypot(im) / samples.len() as f64
+}
+
+/// Signal-to-noise ratio of `output` against `clean`, in dB, from `from`
+/// on.
+fn snr(clean: &[f64], output: &[i16], from: usize) -> f64 {
+    let signal: f64 = clean[from..].iter().map(|c| c * c).sum();
+    let error: f64 = clean[from..]
+        .iter()
+        .zip(&output[from..])
+        .map(|(c, &y)| (f64::from(y) - c).powi(2))
+        .sum();
+    10.0 * (signal / error).log10()
+}
+
+#[test]
+fn mains_frequencies() {
+    assert_eq!(Mains::Hz50.hz(), 50);
+    assert_eq!(Mains::Hz60.hz(), 60);
+}
+
+#[test]
+fn hum_is_detected_and_notched_out() {
+    let len = 4 * SECOND;
+    for (mains, hz) in [(Mains::Hz50, 50.0), (Mains::Hz60, 60.0)] {
+        let input = mix(&noise(len, 300.0, 1), &hum(len, hz, 3000.0));
+        let mut remover = HumRemover::new(&Config::default(), RATE);
+        let output = run(&mut remover, &input[..SECOND / 2]);
+        assert_eq!(remover.detected(), None, "{hz} Hz");
+        assert_eq!(output, input[..SECOND / 2], "{hz} Hz");
+
+        // two half-second blocks to be sure of it
+        run(&mut remover, &input[SECOND / 2..SECOND]);
+        assert_eq!(remover.detected(), Some(mains), "{hz} Hz");
+
+        // each harmonic comes down by 30 dB once the notches have rung in
+        let output = run(&mut remover, &input[SECOND..]);
+        let output: Vec<f64> = output[SECOND..].iter().map(|&s| f64::from(s)).collect();
+        for k in 1..=4 {
+            let before = 3000.0 / (1 << (k - 1)) as f64;
+            let after = amplitude(&output, k as f64 * hz);
+            let down = 20.0 * (before / after).log10();
+            assert!(down > 30.0, "{hz} Hz ×{k}: {down:.1} dB");
+        }
+    }
+}
+
+#[test]
+fn speech_survives_the_notches() {
+    let len = 4 * SECOND;
+    let clean = speech(len, 8000.0);
+    for (mains, hz) in [(Mains::Hz50, 50.0), (Mains::Hz60, 60.0)] {
+        let input = mix(&clean, &hum(len, hz, 3000.0));
+        let mut remover = HumRemover::new(&Config::default(), RATE);
+        let output = run(&mut remover, &input);
+        assert_eq!(remover.detected(), Some(mains), "{hz} Hz");
+
+        // what's left of the error is mostly the speech's own harmonics
+        // gliding through the notches, which takes out little of it
+        let before = snr(&clean, &input, 2 * SECOND);
+        let after = snr(&clean, &output, 2 * SECOND);
+        assert!(
+            after > before + 10.0,
+            "{hz} Hz: {before:.1} → {after:.1} dB"
+        );
+        let clean: f64 = clean[2 * SECOND..].iter().map(|c| c * c).sum();
+        let output: f64 = output[2 * SECOND..]
+            .iter()
+            .map(|&y| f64::from(y).powi(2))
+            .sum();
+        let loss = 10.0 * (clean / output).log10();
+        assert!(loss.abs() < 1.0, "{hz} Hz: {loss:.2} dB");
+    }
+}
+
+#[test]
+fn mains_off_frequency_is_still_removed() {
+    let len = 4 * SECOND;
+    for (mains, hz) in [(Mains::Hz50, 49.7), (Mains::Hz60, 60.3)] {
+        let input = mix(&noise(len, 300.0, 1), &hum(len, hz, 3000.0));
+        let mut remover = HumRemover::new(&Config::default(), RATE);
+        let output = run(&mut remover, &input);
+        assert_eq!(remover.detected(), Some(mains), "{hz} Hz");
+        let before = amplitude(
+            &input[2 * SECOND..]
+                .iter()
+                .map(|&s| f64::from(s))
+                .collect::<Vec<_>>(),
+            hz,
+        );
+        let after = amplitude(
+            &output[2 * SECOND..]
+                .iter()
+                .map(|&s| f64::from(s))
+                .collect::<Vec<_>>(),
+            hz,
+        );
+        let down = 20.0 * (before / after).log10();
+        assert!(down > 15.0, "{hz} Hz: {down:.1} dB");
+    }
+}
+
+#[test]
+fn speech_and_noise_alone_pass_untouched() {
+    let len = 6 * SECOND;
+    let inputs = [
+        mix(&speech(len, 8000.0), &noise(len, 1000.0, 2)),
+        mix(&noise(len, 8000.0, 3), &vec![0.0; len]),
+        mix(&vec![0.0; len], &vec![0.0; len]),
+    ];
+    for input in inputs {
+        let mut remover = HumRemover::new(&Config::default(), RATE);
+        let output = run(&mut remover, &input);
+        assert_eq!(remover.detected(), None);
+        assert_eq!(output, input);
+    }
+}
+
+#[test]
+fn quiet_hum_is_ignored() {
+    let len = 3 * SECOND;
+    let input = mix(&hum(len, 50.0, 10.0), &vec![0.0; len]);
+    let mut remover = HumRemover::new(&Config::default(), RATE);
+    run(&mut remover, &input);
+    assert_eq!(remover.detected(), None);
+
+    let config = Config {
+        min_level_dbfs: -80,
+        ..Config::default()
+    };
+    let mut remover = HumRemover::new(&config, RATE);
+    run(&mut remover, &input);
+    assert_eq!(remover.detected(), Some(Mains::Hz50));
+}
+
+#[test]
+fn detection_follows_the_mains() {
+    let len = 3 * SECOND;
+    let clean = speech(3 * len, 8000.0);
+    let mut remover = HumRemover::new(&Config::default(), RATE);
+    run(&mut remover, &mix(&clean[..len], &hum(len, 50.0, 2000.0)));
+    assert_eq!(remover.detected(), Some(Mains::Hz50));
+
+    // moving to 60 Hz takes two blocks, and then the new hum comes out
+    let input = mix(&clean[len..2 * len], &hum(len, 60.0, 2000.0));
+    run(&mut remover, &input[..SECOND / 2]);
+    assert_eq!(remover.detected(), Some(Mains::Hz50));
+    run(&mut remover, &input[SECOND / 2..SECOND]);
+    assert_eq!(remover.detected(), Some(Mains::Hz60));
+    let output = run(&mut remover, &input[SECOND..]);
+    let after = snr(&clean[len + SECOND..2 * len], &output, SECOND / 2);
+    assert!(after > 15.0, "{after:.1} dB");
+
+    // and once the hum stops, the signal passes untouched again
+    let input = mix(&clean[2 * len..], &noise(len, 100.0, 4));
+    run(&mut remover, &input[..SECOND]);
+    assert_eq!(remover.detected(), None);
+    assert_eq!(run(&mut remover, &input[SECOND..]), input[SECOND..]);
+}
+
+#[test]
+fn reset_forgets_the_hum() {
+    let len = 2 * SECOND;
+    let input = mix(&speech(len, 8000.0), &hum(len, 60.0, 3000.0));
+    let mut remover = HumRemover::new(&Config::default(), RATE);
+    let first = run(&mut remover, &input);
+    assert_eq!(remover.detected(), Some(Mains::Hz60));
+    remover.reset();
+    assert_eq!(remover.detected(), None);
+    assert_eq!(run(&mut remover, &input), first);
+}
+
+#[test]
+#[should_panic]
+fn blocks_must_fit() {
+    let config = Config {
+        block_tenths: 50,
+        ..Config::default()
+    };
+    HumRemover::new(&config, SampleRate::Hz16000);
+}
+
+/// A sine at `hz` and `rate`, at `amplitude`.
+fn tone(len: usize, hz: f64, rate: SampleRate, amplitude: f64) -> Vec<i16> {
+    (0..len)
+        .map(|i| {
+            (amplitude * (2.0 * PI * hz * i as f64 / f64::from(rate.hz())).sin()).round() as i16
+        })
+        .collect()
+}
+
+#[test]
+fn hum_near_full_scale_is_detected_at_higher_rates() {
+    // the longest blocks that fit at 44.1 kHz, where the resonators grow
+    // the most
+    let config = Config {
+        block_tenths: 7,
+        ..Config::default()
+    };
+    for rate in [SampleRate::Hz16000, SampleRate::Hz44100] {
+        let second = rate.hz() as usize;
+        for (mains, hz) in [(Mains::Hz50, 50.0), (Mains::Hz60, 60.0)] {
+            let input = tone(3 * second, hz, rate, 32000.0);
+            let mut remover = HumRemover::new(&config, rate);
+            let output = run(&mut remover, &input);
+            assert_eq!(remover.detected(), Some(mains), "{hz} Hz at {rate:?}");
+
+            // and notched out once the notches have rung in
+            let tail = &output[output.len() - second / 2..];
+            let peak = tail.iter().map(|s| s.unsigned_abs()).max().unwrap();
+            assert!(peak < 1000, "{hz} Hz at {rate:?}: {peak}");
+        }
+    }
+}
+
+#[test]
+fn full_scale_below_the_mains_is_not_hum() {
+    // right on the lowest probe, which rings up the most
+    let rate = SampleRate::Hz44100;
+    let config = Config {
+        block_tenths: 7,
+        ..Config::default()
+    };
+    let input = tone(3 * 44_100, 30.0, rate, 32767.0);
+    let mut remover = HumRemover::new(&config, rate);
+    assert_eq!(run(&mut remover, &input), input);
+    assert_eq!(remover.detected(), None);
+}
@@ -0,0 +1,66 @@
+use voice_core::journal::{self, Chunk, PAYLOAD};
+use voice_core::store::PAGE_SIZE;
+
+fn sealed(len: usize) -> [u8; PAGE_SIZE] {
+    let mut page = [0; PAGE_SIZE];
+    for (i, byte) in page[..len].iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    journal::seal(&mut page, 0x0001_0000_0003_0007, 12, len);
+    page
+}
+
+/// `page` as it reads after programming `bytes` over it.
+fn program(page: &mut [u8; PAGE_SIZE], bytes: &[u8; PAGE_SIZE]) {
+    for (cell, byte) in page.iter_mut().zip(bytes) {
+        *cell &= byte;
+    }
+}
+
+#[test]
+fn crc32_matches_the_check_value() {
+    assert_eq!(journal::crc32(b"123456789"), 0xcbf4_3926);
+    assert_eq!(journal::crc32(b""), 0);
+}
+
+#[test]
+fn chunk_counts_only_once_committed() {
+    let mut page = sealed(100);
+    assert_eq!(journal::open(&page), None);
+    program(&mut page, &journal::commit_marker());
+    assert_eq!(
+        journal::open(&page),
+        Some(Chunk {
+            tag: 0x0001_0000_0003_0007,
+            seq: 12,
+            len: 100
+        })
+    );
+    // the payload is padded as if never programmed
+    assert!(page[100..PAYLOAD].iter().all(|&b| b == 0xff));
+}
+
+#[test]
+fn torn_or_damaged_chunks_are_rejected() {
+    let mut page = sealed(PAYLOAD);
+    program(&mut page, &journal::commit_marker());
+    assert!(journal::open(&page).is_some());
+
+    for at in 0..PAGE_SIZE {
+        let mut damaged = page;
+        damaged[at] ^= 0x10;
+        assert_eq!(journal::open(&damaged), None, "flip at {at}");
+    }
+
+    // committed, but only half programmed
+    let mut torn = [0xff; PAGE_SIZE];
+    torn[..PAGE_SIZE / 2].copy_from_slice(&page[..PAGE_SIZE / 2]);
+    program(&mut torn, &journal::commit_marker());
+    assert_eq!(journal::open(&torn), None);
+}
+
+#[test]
+fn erased_page_is_not_a_chunk() {
+    assert_eq!(journal::open(&[0xff; PAGE_SIZE]), None);
+    assert_eq!(journal::open(&journal::commit_marker()), None);
+}
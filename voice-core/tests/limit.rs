@@ -0,0 +1,188 @@
+mod common;
+
+use std::f64::consts::PI;
+
+use voice_core::compress::{self, Compressor};
+use voice_core::limit::{Config, Limiter};
+use voice_core::process::Processor;
+use voice_core::SampleRate;
+
+const RATE: SampleRate = SampleRate::Hz16000;
+
+/// Samples per millisecond at `RATE`.
+const MS: usize = 16;
+
+/// The default ceiling, -1 dBFS.
+const CEILING: i32 = 29204;
+
+/// A 440 Hz tone at `dbfs`, `ms` long.
+fn tone(dbfs: f64, ms: usize) -> Vec<i16> {
+    let amplitude = 32767.0 * 10f64.powf(dbfs / 20.0);
+    let w = 2.0 * PI * 440.0 / f64::from(RATE.hz());
+    (0..ms * MS)
+        .map(|i| {
+            (amplitude * (w * i as f64).sin())
+                .round()
+                .clamp(-32768.0, 32767.0) as i16
+        })
+        .collect()
+}
+
+/// A cheap pseudo-random sequence, uniform over ±`amplitude`.
+fn noise(len: usize, amplitude: f64, seed: u32) -> Vec<i16> {
+    common::noise(len, amplitude, seed)
+        .into_iter()
+        .map(|x| x.round().clamp(-32768.0, 32767.0) as i16)
+        .collect()
+}
+
+fn run<const N: usize>(limiter: &mut Limiter<N>, input: &[i16]) -> Vec<i16> {
+    let mut output = input.to_vec();
+    limiter.process_block(&mut output);
+    output
+}
+
+fn peak(samples: &[i16]) -> i32 {
+    samples
+        .iter()
+        .map(|&s| i32::from(s.unsigned_abs()))
+        .max()
+        .unwrap()
+}
+
+#[test]
+fn nothing_gets_over_the_ceiling() {
+    let inputs = [
+        tone(6.0, 200),
+        noise(200 * MS, 40000.0, 1),
+        [
+            vec![0; 100],
+            vec![i16::MIN; 3],
+            vec![i16::MAX; 3],
+            vec![0; 100],
+        ]
+        .concat(),
+    ];
+    for input in inputs {
+        let mut limiter: Limiter<32> = Limiter::new(&Config::default(), RATE);
+        let output = run(&mut limiter, &input);
+        assert!(peak(&output) <= CEILING, "{}", peak(&output));
+        // and the loudest peaks come out right at it
+        assert!(peak(&output) >= CEILING - 2, "{}", peak(&output));
+    }
+}
+
+#[test]
+fn quiet_signals_are_only_delayed() {
+    let input = noise(100 * MS, 29000.0, 2);
+    let mut limiter: Limiter<32> = Limiter::new(&Config::default(), RATE);
+    let output = run(&mut limiter, &input);
+    assert_eq!(limiter.latency(), 31);
+    assert!(output[..31].iter().all(|&s| s == 0));
+    assert_eq!(output[31..], input[..input.len() - 31]);
+    assert_eq!(limiter.gain(), 32768);
+}
+
+#[test]
+fn the_gain_ramps_down_ahead_of_a_peak() {
+    // a steady level with one sample twice over a -7 dBFS ceiling
+    let config = Config {
+        ceiling_dbfs: -7,
+        ..Config::default()
+    };
+    let ceiling = (32767.0 * 10f64.powf(-7.0 / 20.0)) as i32;
+    let level = 8000;
+    let mut input = vec![level; 400];
+    input[200] = 2 * ceiling as i16;
+    let mut limiter: Limiter<32> = Limiter::new(&config, RATE);
+    let output = run(&mut limiter, &input);
+    let at = 200 + 31;
+    assert!(
+        (i32::from(output[at]) - ceiling).abs() <= 2,
+        "{}",
+        output[at]
+    );
+    // untouched until the peak enters the window, then an even ramp down
+    // to half gain as it comes out
+    assert_eq!(output[at - 32], level);
+    for (k, &y) in output[at - 31..at].iter().enumerate() {
+        let gain = 1.0 - 0.5 * (k + 1) as f64 / 32.0;
+        let expected = f64::from(level) * gain;
+        assert!(
+            (f64::from(y) - expected).abs() < 2.0,
+            "{k}: {y} {expected:.0}"
+        );
+    }
+    // and nothing before it touched at all
+    assert!(output[31..at - 31].iter().all(|&s| s == level));
+}
+
+#[test]
+fn the_gain_releases_over_the_release_time() {
+    let config = Config {
+        ceiling_dbfs: -7,
+        ..Config::default()
+    };
+    let release = config.release_ms as usize;
+    let input = [tone(-1.0, 100), tone(-20.0, 5 * release)].concat();
+    let mut limiter: Limiter<32> = Limiter::new(&config, RATE);
+    run(&mut limiter, &input[..100 * MS]);
+    // 6 dB down, to bring the crests under the ceiling
+    let low = f64::from(limiter.gain());
+    assert!((low / 32768.0 - 0.5).abs() < 0.01, "{low}");
+    // the held minimum lets go after two windows, then it releases
+    run(&mut limiter, &input[100 * MS..(100 + release) * MS + 64]);
+    let released = (f64::from(limiter.gain()) - low) / (32768.0 - low);
+    assert!((0.55..0.7).contains(&released), "{released:.2}");
+    run(&mut limiter, &input[(100 + release) * MS + 64..]);
+    assert!(limiter.gain() > 32768 - 400, "{}", limiter.gain());
+}
+
+#[test]
+fn after_a_compressor_loud_speech_never_clips() {
+    // the makeup gain takes the loudest crests over full scale, and the
+    // limiter catches them
+    let compressor = Compressor::new(&compress::Config::default(), RATE);
+    let limiter: Limiter<32> = Limiter::new(&Config::default(), RATE);
+    let mut chain = compressor.then(limiter);
+    let input = [
+        tone(-30.0, 100),
+        noise(100 * MS, 32767.0, 4),
+        tone(0.0, 100),
+    ]
+    .concat();
+    let mut output = input.clone();
+    chain.process_block(&mut output);
+    assert!(peak(&output) <= CEILING, "{}", peak(&output));
+    assert!(peak(&output[..100 * MS]) < peak(&input[..100 * MS]) * 2 + 2);
+}
+
+#[test]
+fn a_window_of_one_limits_without_delay() {
+    let input = tone(6.0, 50);
+    let mut limiter: Limiter<1> = Limiter::new(&Config::default(), RATE);
+    let output = run(&mut limiter, &input);
+    assert_eq!(limiter.latency(), 0);
+    assert!(peak(&output) <= CEILING);
+    assert_eq!(output[1], input[1]);
+}
+
+#[test]
+fn reset_starts_over() {
+    let input = noise(50 * MS, 50000.0, 3);
+    let mut limiter: Limiter<32> = Limiter::new(&Config::default(), RATE);
+    let first = run(&mut limiter, &input);
+    limiter.reset();
+    assert_eq!(limiter.gain(), 32768);
+    assert_eq!(run(&mut limiter, &input), first);
+}
+
+#[test]
+#[should_panic]
+fn ceiling_must_be_at_most_full_scale() {
+    let config = Config {
+        ceiling_dbfs: 1,
+        ..Config::default()
+    };
+    let _: Limiter<32> = Limiter::new(&config, RATE);
+}
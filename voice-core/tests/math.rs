@@ -0,0 +1,93 @@
+use std::f64::consts::PI;
+
+use voice_core::math;
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-14 * b.abs().max(1.0)
+}
+
+#[test]
+fn sine_and_cosine_match_std() {
+    for i in -2000..=2000 {
+        let x = i as f64 * 0.01;
+        assert!(close(math::sin(x), x.sin()), "sin {x}");
+        assert!(close(math::cos(x), x.cos()), "cos {x}");
+    }
+    assert_eq!(math::sin_cos(0.0), (0.0, 1.0));
+    assert!(math::cos(PI / 2.0).abs() < 1e-15);
+}
+
+#[test]
+fn square_root_matches_std() {
+    for x in [1e-9, 0.02, 0.5, 1.0, 2.0, 3.999, 10.0, 12345.678, 1e12] {
+        assert!(close(math::sqrt(x), x.sqrt()), "sqrt {x}");
+    }
+    assert_eq!(math::sqrt(0.0), 0.0);
+    assert!(math::sqrt(-1.0).is_nan());
+}
+
+#[test]
+fn exponential_matches_std() {
+    for i in -400..=400 {
+        let x = i as f64 * 0.05;
+        assert!(close(math::exp(x), x.exp()), "exp {x}");
+    }
+    assert_eq!(math::exp(-1000.0), 0.0);
+    assert_eq!(math::exp(1000.0), f64::INFINITY);
+}
+
+#[test]
+fn logarithm_matches_std() {
+    for i in 1..=2000 {
+        let x = i as f64 * 0.013;
+        assert!(close(math::ln(x), x.ln()), "ln {x}");
+    }
+    for x in [1e-300, 5e-320, 1e-9, 1.0, 2.0, 1e300] {
+        assert!(close(math::ln(x), x.ln()), "ln {x}");
+    }
+    assert_eq!(math::ln(1.0), 0.0);
+    assert_eq!(math::ln(f64::INFINITY), f64::INFINITY);
+    assert!(math::ln(0.0).is_nan());
+    assert!(math::ln(-1.0).is_nan());
+}
+
+#[test]
+fn fixed_point_logarithm_is_close() {
+    for x in (1..70000).chain([1 << 20, 3_000_000_000, u32::MAX]) {
+        let expected = (x as f64).log2() * 65536.0;
+        let error = f64::from(math::log2_q16(x)) - expected;
+        assert!(error.abs() < 16.0, "log2 {x}: {error:.1}");
+    }
+    assert_eq!(math::log2_q16(1), 0);
+    assert_eq!(math::log2_q16(1024), 10 << 16);
+}
+
+#[test]
+fn fixed_point_exponential_is_close() {
+    for x in (-20 << 16..15 << 16).step_by(977) {
+        let expected = (f64::from(x) / 65536.0).exp2() * 65536.0;
+        let error = f64::from(math::exp2_q16(x)) - expected;
+        assert!(
+            error.abs() <= expected / 4000.0 + 1.0,
+            "exp2 {x}: {error:.1}"
+        );
+    }
+    assert_eq!(math::exp2_q16(0), 65536);
+    assert_eq!(math::exp2_q16(3 << 16), 8 << 16);
+    assert_eq!(math::exp2_q16(-40 << 16), 0);
+    assert_eq!(math::exp2_q16(20 << 16), u32::MAX);
+}
+
+#[test]
+fn decibels_convert_to_gain() {
+    assert!(close(math::db_to_gain(0.0), 1.0));
+    assert!(close(math::db_to_gain(20.0), 10.0));
+    assert!(close(math::db_to_gain(-6.0), 10f64.powf(-0.3)));
+    assert!(close(math::omega(1000.0, 8000.0), PI / 4.0));
+}
+
+#[test]
+fn usable_in_const_context() {
+    const HALF: f64 = math::sin(PI / 6.0);
+    assert!(close(HALF, 0.5));
+}
@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+
+use voice_core::adc::OVERSAMPLE;
+use voice_core::dc::{DcBlocker, VOICE_CORNER_HZ};
+use voice_core::decimate::Decimator;
+use voice_core::denoise::{self, Suppressor};
+use voice_core::host::{WavSink, WavSource};
+use voice_core::pipeline::Config;
+use voice_core::process::Chain;
+use voice_core::{from_pcm16, AudioSink, AudioSource, Pipeline, SampleRate, Window};
+
+/// Source whose queued samples are all available at once.
+#[derive(Default)]
+struct Ready(VecDeque<u16>);
+
+impl AudioSource for Ready {
+    type Error = Infallible;
+
+    fn available(&mut self) -> usize {
+        self.0.len()
+    }
+
+    fn read(&mut self) -> Result<u16, Infallible> {
+        Ok(self.0.pop_front().unwrap())
+    }
+}
+
+#[derive(Default)]
+struct Collect(Vec<u16>);
+
+impl AudioSink for Collect {
+    type Error = Infallible;
+
+    fn write(&mut self, sample: u16) -> Result<(), Infallible> {
+        self.0.push(sample);
+        Ok(())
+    }
+}
+
+fn config(window_len: usize) -> Config {
+    Config {
+        window_len,
+        ..Config::default()
+    }
+}
+
+#[test]
+fn window_averages_last_len_samples() {
+    let mut window: Window<8> = Window::new(4);
+    assert_eq!(window.average(), 0);
+    for s in [100, 200, 300, 400] {
+        window.push(s);
+    }
+    assert_eq!(window.average(), 250);
+}
+
+#[test]
+fn window_average_does_not_overflow() {
+    let mut window: Window<105> = Window::new(100);
+    for _ in 0..200 {
+        window.push(u16::MAX);
+    }
+    assert_eq!(window.average(), u16::MAX);
+}
+
+#[test]
+fn window_matches_a_naive_average() {
+    let mut window: Window<64> = Window::new(10);
+    let mut history = vec![0u16; 64];
+    let mut seed = 5u32;
+    for i in 0..5000 {
+        seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        window.push((seed >> 20) as u16);
+        history.push((seed >> 20) as u16);
+        // retune now and then, including to the whole buffer
+        if i % 700 == 699 {
+            window.set_len([1, 64, 37, 2, 63, 10, 50][i / 700]);
+        }
+        let len = window.len();
+        let sum: usize = history[history.len() - len..]
+            .iter()
+            .map(|&s| usize::from(s))
+            .sum();
+        assert_eq!(usize::from(window.average()), sum / len, "sample {i}");
+    }
+}
+
+#[test]
+#[should_panic]
+fn window_length_must_fit_the_buffer() {
+    Window::<8>::new(9);
+}
+
+#[test]
+fn pipeline_writes_a_sample_per_oversampled_sample() {
+    let mut source = Ready::default();
+    let mut sink = Collect::default();
+    let mut pipeline: Pipeline<8> = Pipeline::new(&config(4), SampleRate::Hz16000);
+    assert_eq!(OVERSAMPLE, 4);
+
+    source.0.extend([4000; 3]);
+    assert_eq!(pipeline.step(&mut source, &mut sink), Ok(3));
+    assert_eq!(pipeline.step(&mut source, &mut sink), Ok(0));
+    assert!(sink.0.is_empty());
+    source.0.push_back(4000);
+    assert_eq!(pipeline.step(&mut source, &mut sink), Ok(1));
+    assert_eq!(sink.0.len(), 1);
+    source.0.extend([4000; 100]);
+    assert_eq!(pipeline.step(&mut source, &mut sink), Ok(100));
+    assert_eq!(sink.0.len(), 26);
+}
+
+#[test]
+fn pipeline_takes_the_bias_out_before_averaging() {
+    let mut source = Ready::default();
+    let mut sink = Collect::default();
+    let mut pipeline: Pipeline<8> = Pipeline::new(&config(4), SampleRate::Hz16000);
+
+    // a microphone sitting well off mid-rail still comes out at mid-rail,
+    // once the filters have settled, with no level
+    source.0.extend([3000; 16000]);
+    pipeline.step(&mut source, &mut sink).unwrap();
+    assert!(sink.0[3000..].iter().all(|&s| s == 2048));
+    assert_eq!(pipeline.window().average(), 0);
+    // while a step on top of the bias comes straight through the noise
+    // suppressor, switched off
+    source.0.extend([3400; 2000]);
+    pipeline.step(&mut source, &mut sink).unwrap();
+    assert!(sink.0[4000..].iter().max().unwrap() > &(2048 + 300));
+}
+
+#[test]
+fn window_meters_the_level() {
+    let mut source = Ready::default();
+    let mut sink = Collect::default();
+    let mut pipeline: Pipeline<100> = Pipeline::new(&config(100), SampleRate::Hz16000);
+
+    // a 1 kHz square wave at half of full scale, OVERSAMPLE conversions to
+    // a sample
+    source
+        .0
+        .extend((0..32000).map(|i| if i / 32 % 2 == 0 { 3072 } else { 1024 }));
+    pipeline.step(&mut source, &mut sink).unwrap();
+    // half way to a full-scale square wave, less what the filters round
+    // off the edges
+    let level = pipeline.window().average();
+    assert!((1850..=2048).contains(&level), "{level}");
+}
+
+#[test]
+fn wav_round_trip_through_pipeline() {
+    let dir = std::env::temp_dir().join(format!("voice-core-pipeline-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("in.wav");
+    let output = dir.join("out.wav");
+
+    let mut sink = WavSink::create(&input, SampleRate::Hz16000).unwrap();
+    for n in 0..4000u16 {
+        sink.write(n).unwrap();
+    }
+    sink.finalize().unwrap();
+
+    let mut source = WavSource::open(&input).unwrap();
+    assert_eq!(source.sample_rate(), 16_000);
+    assert_eq!(source.available(), 4000);
+    assert_eq!(source.samples()[3999], 3999);
+
+    let mut sink = WavSink::create(&output, SampleRate::Hz16000).unwrap();
+    let mut pipeline: Pipeline<8> = Pipeline::new(&config(4), SampleRate::Hz16000);
+    while !source.is_finished() {
+        let mut burst = Ready((0..10).map(|_| source.read().unwrap()).collect());
+        pipeline.step(&mut burst, &mut sink).unwrap();
+    }
+    sink.finalize().unwrap();
+
+    // the same as running the decimator and the chain by hand, with the
+    // noise suppressor switched off
+    let mut decimator: Decimator<3, 9> = Decimator::new(OVERSAMPLE, 0.425);
+    let mut suppressor: Suppressor<256> =
+        Suppressor::new(&denoise::Config::default(), SampleRate::Hz16000);
+    suppressor.set_enabled(false);
+    let mut chain =
+        Chain::new(DcBlocker::new(VOICE_CORNER_HZ, SampleRate::Hz16000)).then(suppressor);
+    let expected: Vec<u16> = (0..4000u16)
+        .filter_map(|n| decimator.push(n))
+        .map(|sample| from_pcm16(chain.push_pcm16(sample)))
+        .collect();
+    assert_eq!(expected.len(), 1000);
+    // which passes the signal straight through: the input starts far below
+    // mid-rail, which shows as soon as it is through the decimator
+    assert!(expected[..10].iter().any(|&s| s < 1024));
+    let averaged = WavSource::open(&output).unwrap();
+    assert_eq!(averaged.samples(), expected);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
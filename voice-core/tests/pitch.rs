@@ -0,0 +1,243 @@
+mod common;
+
+use std::f64::consts::PI;
+
+use voice_core::pitch::{note, Config, Note, Yin};
+use voice_core::SampleRate;
+
+use common::noise;
+
+/// A capture block's worth at 8 kHz.
+const N: usize = 256;
+
+fn sine(hz: f64, rate: u32, len: usize) -> Vec<f64> {
+    (0..len)
+        .map(|i| 12000.0 * (2.0 * PI * hz * i as f64 / f64::from(rate) + 0.4).sin())
+        .collect()
+}
+
+/// A band-limited sawtooth: every harmonic below Nyquist, falling as 1/k.
+fn sawtooth(hz: f64, rate: u32, len: usize) -> Vec<f64> {
+    let harmonics = (f64::from(rate) / 2.0 / hz) as usize;
+    (0..len)
+        .map(|i| {
+            let w = 2.0 * PI * hz * i as f64 / f64::from(rate);
+            (1..=harmonics)
+                .map(|k| 8000.0 * (k as f64 * w).sin() / k as f64)
+                .sum()
+        })
+        .collect()
+}
+
+/// A sung vowel, with a little vibrato.
+fn vowel(hz: f64, formants: [f64; 3], len: usize) -> Vec<f64> {
+    let signal = common::vowel(len, formants, |i| {
+        let t = i as f64 / 8000.0;
+        hz * (1.0 + 0.01 * (2.0 * PI * 5.0 * t).sin())
+    });
+    signal.iter().map(|x| 16000.0 * x).collect()
+}
+
+fn frames<const N: usize>(signal: &[f64]) -> impl Iterator<Item = [i16; N]> + '_ {
+    signal
+        .chunks_exact(N)
+        .map(|c| core::array::from_fn(|i| c[i].round().clamp(-32768.0, 32767.0) as i16))
+}
+
+/// Every estimate over `signal`, frame by frame.
+fn track<const N: usize>(yin: &mut Yin<N>, signal: &[f64]) -> Vec<Option<(f64, f64)>> {
+    frames::<N>(signal)
+        .map(|frame| {
+            yin.estimate(&frame).map(|p| {
+                (
+                    f64::from(p.millihertz) / 1000.0,
+                    f64::from(p.confidence) / 32768.0,
+                )
+            })
+        })
+        .collect()
+}
+
+/// Asserts that every frame of `signal` finds `hz` to within `percent`,
+/// confidently.
+fn assert_finds<const N: usize>(yin: &mut Yin<N>, signal: &[f64], hz: f64, percent: f64) {
+    for (i, estimate) in track(yin, signal).into_iter().enumerate() {
+        let (found, confidence) = estimate.unwrap_or_else(|| panic!("{hz} Hz: frame {i}"));
+        let error = 100.0 * (found / hz - 1.0);
+        assert!(error.abs() < percent, "{hz} Hz: frame {i} found {found:.2}");
+        assert!(confidence > 0.8, "{hz} Hz: frame {i} {confidence:.2}");
+    }
+}
+
+#[test]
+fn sines_are_found_precisely() {
+    let mut yin: Yin<N> = Yin::new(&Config::default(), SampleRate::Hz8000);
+    for hz in [72.0, 98.0, 110.0, 147.3, 220.0, 261.63, 440.0, 700.0, 987.0] {
+        assert_finds(&mut yin, &sine(hz, 8000, 8 * N), hz, 0.2);
+    }
+
+    // and at 16 kHz, with twice the frame for the same low end
+    let mut yin: Yin<512> = Yin::new(&Config::default(), SampleRate::Hz16000);
+    for hz in [72.0, 130.0, 440.0, 987.0] {
+        assert_finds(&mut yin, &sine(hz, 16000, 8 * 512), hz, 0.2);
+    }
+}
+
+#[test]
+fn sawtooths_find_the_fundamental_not_a_harmonic() {
+    let mut yin: Yin<N> = Yin::new(&Config::default(), SampleRate::Hz8000);
+    for hz in [75.0, 100.0, 123.0, 196.0, 330.0, 523.0, 880.0] {
+        // the sharp corners make the dip less of a parabola at high
+        // pitches, where there are few samples to a period
+        assert_finds(&mut yin, &sawtooth(hz, 8000, 8 * N), hz, 0.6);
+    }
+}
+
+#[test]
+fn vowels_are_tracked() {
+    // /a/, /i/ and /u/, from low male to high female voices
+    let vowels = [
+        [730.0, 1090.0, 2440.0],
+        [270.0, 2290.0, 3010.0],
+        [300.0, 870.0, 2240.0],
+    ];
+    let mut yin: Yin<N> = Yin::new(&Config::default(), SampleRate::Hz8000);
+    for formants in vowels {
+        for hz in [85.0, 120.0, 180.0, 250.0, 400.0] {
+            // the vibrato moves it by up to 1% either way
+            assert_finds(&mut yin, &vowel(hz, formants, 16 * N), hz, 2.0);
+        }
+    }
+}
+
+#[test]
+fn noise_and_silence_have_no_pitch() {
+    let mut yin: Yin<N> = Yin::new(&Config::default(), SampleRate::Hz8000);
+    let len = 20 * N;
+    assert!(track(&mut yin, &vec![0.0; len]).iter().all(Option::is_none));
+    let hiss = track(&mut yin, &noise(len, 8000.0, 1));
+    assert!(hiss.iter().all(Option::is_none), "{hiss:?}");
+}
+
+#[test]
+fn confidence_falls_with_noise() {
+    let mut yin: Yin<N> = Yin::new(&Config::default(), SampleRate::Hz8000);
+    let clean = sawtooth(150.0, 8000, 8 * N);
+    let confidence = |signal: &[f64], yin: &mut Yin<N>| {
+        let estimates = track(yin, signal);
+        estimates
+            .iter()
+            .map(|e| e.map_or(0.0, |e| e.1))
+            .sum::<f64>()
+            / estimates.len() as f64
+    };
+    let quiet: Vec<f64> = clean
+        .iter()
+        .zip(noise(clean.len(), 1500.0, 2))
+        .map(|(s, n)| s + n)
+        .collect();
+    let loud: Vec<f64> = clean
+        .iter()
+        .zip(noise(clean.len(), 5000.0, 3))
+        .map(|(s, n)| s + n)
+        .collect();
+    let (a, b, c) = (
+        confidence(&clean, &mut yin),
+        confidence(&quiet, &mut yin),
+        confidence(&loud, &mut yin),
+    );
+    assert!(a > 0.97 && a > b && b > c, "{a:.3} {b:.3} {c:.3}");
+    // through the noise, what is found is still right
+    for (hz, _) in track(&mut yin, &quiet).into_iter().flatten() {
+        assert!((hz / 150.0 - 1.0).abs() < 0.01, "{hz:.2}");
+    }
+}
+
+#[test]
+fn out_of_range_pitches_are_not_reported_as_others() {
+    // above the top of the range, and below the bottom: nothing, or an
+    // octave-related guess, but never something unrelated
+    let config = Config {
+        min_hz: 100,
+        max_hz: 400,
+        ..Config::default()
+    };
+    let mut yin: Yin<N> = Yin::new(&config, SampleRate::Hz8000);
+    for (hz, _) in track(&mut yin, &sine(600.0, 8000, 8 * N))
+        .into_iter()
+        .flatten()
+    {
+        let ratio = hz / 600.0;
+        assert!(
+            ((1.0 / ratio).round() - 1.0 / ratio).abs() < 0.02,
+            "{hz:.1}"
+        );
+    }
+}
+
+#[test]
+fn frequencies_map_to_notes() {
+    let a4 = note(440_000).unwrap();
+    assert_eq!(
+        a4,
+        Note {
+            number: 69,
+            cents: 0
+        }
+    );
+    assert_eq!((a4.name(), a4.octave()), ("A", 4));
+    assert_eq!(a4.to_string(), "A4");
+
+    let c4 = note(261_626).unwrap();
+    assert_eq!((c4.to_string(), c4.cents), ("C4".to_string(), 0));
+    assert_eq!(note(277_183).unwrap().to_string(), "C#4");
+    assert_eq!(note(82_407).unwrap().to_string(), "E2");
+    assert_eq!(note(8_176).unwrap().to_string(), "C-1");
+
+    // sharp and flat of A4
+    assert_eq!(
+        note(445_000).unwrap(),
+        Note {
+            number: 69,
+            cents: 20
+        }
+    );
+    assert_eq!(
+        note(435_000).unwrap(),
+        Note {
+            number: 69,
+            cents: -20
+        }
+    );
+    // and more than half a semitone sharp is the flat side of the next
+    let a_sharp = note(456_000).unwrap();
+    assert_eq!(
+        (a_sharp.to_string(), a_sharp.cents),
+        ("A#4".to_string(), -38)
+    );
+
+    assert_eq!(note(0), None);
+    assert_eq!(note(5_000), None);
+    assert_eq!(note(20_000_000), None);
+}
+
+#[test]
+fn estimates_give_notes() {
+    let mut yin: Yin<N> = Yin::new(&Config::default(), SampleRate::Hz8000);
+    let frame: Vec<[i16; N]> = frames::<N>(&sawtooth(196.0, 8000, 2 * N)).collect();
+    let pitch = yin.estimate(&frame[1]).unwrap();
+    assert_eq!(pitch.hz(), 196);
+    let note = pitch.note().unwrap();
+    assert_eq!(note.to_string(), "G3");
+    assert!(note.cents.abs() <= 2, "{}", note.cents);
+}
+
+#[test]
+#[should_panic]
+fn the_lowest_period_must_fit() {
+    let config = Config {
+        min_hz: 40,
+        ..Config::default()
+    };
+    let _: Yin<N> = Yin::new(&config, SampleRate::Hz8000);
+}
@@ -0,0 +1,168 @@
+use voice_core::capture::Block;
+use voice_core::playback::{DmaPlayback, PlaybackDma};
+use voice_core::AudioSink;
+
+const N: usize = 16;
+
+/// DMA channel that plays its block one value per pacer wrap. Wraps with no
+/// block in flight leave the output where it was.
+#[derive(Default)]
+struct MockDma {
+    block: Option<Block<N>>,
+    played: usize,
+    done: bool,
+    output: Vec<u16>,
+    held: usize,
+}
+
+impl MockDma {
+    /// Run one pacer wrap, returning whether it raised the completion
+    /// interrupt.
+    fn wrap(&mut self) -> bool {
+        match &self.block {
+            Some(block) if !self.done => {
+                self.output.push(block[self.played]);
+                self.played += 1;
+                self.done = self.played == N;
+                self.done
+            }
+            _ => {
+                self.held += 1;
+                false
+            }
+        }
+    }
+}
+
+impl PlaybackDma<N> for MockDma {
+    fn start(&mut self, block: Block<N>) {
+        assert!(
+            self.block.is_none(),
+            "started while a transfer is in flight"
+        );
+        self.block = Some(block);
+        self.played = 0;
+        self.done = false;
+    }
+
+    fn take_done(&mut self) -> Option<Block<N>> {
+        if self.done {
+            self.done = false;
+            self.block.take()
+        } else {
+            None
+        }
+    }
+}
+
+fn block() -> Block<N> {
+    Box::leak(Box::new([0; N]))
+}
+
+fn playback() -> DmaPlayback<MockDma, N> {
+    DmaPlayback::new(MockDma::default(), 4095, [block(), block(), block()])
+}
+
+/// Let `n` pacer wraps pass, running the completion interrupt.
+fn wraps(playback: &mut DmaPlayback<MockDma, N>, n: usize) {
+    for _ in 0..n {
+        if playback.dma().wrap() {
+            playback.on_complete();
+        }
+    }
+}
+
+fn ramp(from: u16, len: usize) -> Vec<u16> {
+    (from..from + len as u16).collect()
+}
+
+#[test]
+fn output_starts_once_two_blocks_are_queued() {
+    let mut playback = playback();
+    assert_eq!(playback.write_block(&ramp(0, N)), N);
+    assert!(!playback.is_playing());
+    assert_eq!(playback.write_block(&ramp(N as u16, N)), N);
+    assert!(playback.is_playing());
+    assert_eq!(playback.queued(), 1);
+
+    wraps(&mut playback, 2 * N);
+    assert_eq!(playback.dma().output, ramp(0, 2 * N));
+    assert_eq!(playback.dma().held, 0);
+}
+
+#[test]
+fn writer_a_block_behind_never_underruns() {
+    let mut playback = playback();
+    playback.write_block(&ramp(0, 2 * N));
+    for n in 2..20 {
+        wraps(&mut playback, N);
+        // the writer delivers a block per block time, wherever DMA is
+        playback.write_block(&ramp((n * N) as u16, N));
+    }
+    assert_eq!(playback.underruns(), 0);
+    assert_eq!(playback.overruns(), 0);
+    assert_eq!(playback.dma().output, ramp(0, 18 * N));
+}
+
+#[test]
+fn samples_are_scaled_to_the_carrier() {
+    let mut playback = DmaPlayback::new(MockDma::default(), 1023, [block(), block(), block()]);
+    playback.write_block(&[4095; 2 * N]);
+    wraps(&mut playback, 1);
+    assert_eq!(playback.dma().output, [1023]);
+}
+
+#[test]
+fn running_dry_holds_the_output_and_reprimes() {
+    let mut playback = playback();
+    playback.write_block(&ramp(0, 2 * N));
+    wraps(&mut playback, 3 * N);
+    assert!(!playback.is_playing());
+    assert_eq!(playback.underruns(), 1);
+    assert_eq!(playback.dma().held, N);
+
+    playback.write_block(&ramp(100, N));
+    assert!(!playback.is_playing());
+    playback.write_block(&ramp(200, N));
+    assert!(playback.is_playing());
+    wraps(&mut playback, 2 * N);
+    assert_eq!(&playback.dma().output[2 * N..3 * N], ramp(100, N));
+}
+
+#[test]
+fn writer_too_far_ahead_drops_samples() {
+    let mut playback = playback();
+    // two blocks queued behind the one playing, nowhere to put the rest
+    assert_eq!(playback.write_block(&ramp(0, 4 * N)), 3 * N);
+    assert_eq!(playback.overruns(), N as u32);
+
+    wraps(&mut playback, N);
+    assert_eq!(playback.write_block(&ramp(1000, N)), N);
+    wraps(&mut playback, 3 * N);
+    assert_eq!(&playback.dma().output[..3 * N], ramp(0, 3 * N));
+    assert_eq!(&playback.dma().output[3 * N..], ramp(1000, N));
+}
+
+#[test]
+fn audio_sink_writes_go_through_the_blocks() {
+    let mut playback = playback();
+    for sample in ramp(0, 2 * N) {
+        playback.write(sample).unwrap();
+    }
+    assert!(playback.is_playing());
+}
+
+#[test]
+fn space_is_what_fits_without_dropping() {
+    let mut playback = playback();
+    assert_eq!(playback.space(), 3 * N);
+    playback.write_block(&ramp(0, N + 5));
+    assert_eq!(playback.space(), 2 * N - 5);
+    playback.write_block(&ramp(0, 2 * N - 5));
+    assert_eq!(playback.space(), 0);
+    assert_eq!(playback.overruns(), 0);
+
+    // a played block comes back to be written
+    wraps(&mut playback, N);
+    assert_eq!(playback.space(), N);
+}
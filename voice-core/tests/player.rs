@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::f64::consts::PI;
+
+use voice_core::stretch::{Config, Mode, Stretcher};
+use voice_core::{from_pcm16, to_pcm16, AudioSink, AudioSource};
+
+/// Stretcher buffer, for grains of 16 ms at 16 kHz.
+const N: usize = 1024;
+
+/// A player with room to resample between 16 and 44.1 kHz.
+type Player = voice_core::player::Player<N, 64, 441>;
+
+/// A clip whose samples are all there to be read.
+struct Clip(VecDeque<u16>);
+
+impl AudioSource for Clip {
+    type Error = Infallible;
+
+    fn available(&mut self) -> usize {
+        self.0.len()
+    }
+
+    fn read(&mut self) -> Result<u16, Infallible> {
+        Ok(self.0.pop_front().unwrap())
+    }
+}
+
+#[derive(Default)]
+struct Collect(Vec<u16>);
+
+impl AudioSink for Collect {
+    type Error = Infallible;
+
+    fn write(&mut self, sample: u16) -> Result<(), Infallible> {
+        self.0.push(sample);
+        Ok(())
+    }
+}
+
+/// A 200 Hz tone with a few harmonics, at 16 kHz.
+fn tone(len: usize) -> Vec<u16> {
+    tone_at(16_000.0, len)
+}
+
+/// The same tone at `rate`.
+fn tone_at(rate: f64, len: usize) -> Vec<u16> {
+    (0..len)
+        .map(|i| sample(i as f64, rate).round() as u16)
+        .collect()
+}
+
+/// The tone at `t` samples at `rate`.
+fn sample(t: f64, rate: f64) -> f64 {
+    let w = 2.0 * PI * 200.0 * t / rate;
+    2048.0 + 900.0 * (w.sin() + (2.0 * w).sin() / 2.0 + (3.0 * w).sin() / 3.0)
+}
+
+/// Everything `player` plays of `clip`, `room` samples at a time.
+fn play(player: &mut Player, clip: &[u16], room: usize) -> Vec<u16> {
+    let mut clip = Clip(clip.iter().copied().collect());
+    let mut sink = Collect::default();
+    while !player.is_finished() {
+        let before = sink.0.len();
+        player.step(&mut clip, &mut sink, room).unwrap();
+        assert!(sink.0.len() - before <= room);
+    }
+    assert_eq!(clip.available(), 0);
+    sink.0
+}
+
+#[test]
+fn normal_speed_plays_the_clip_through_the_stretcher() {
+    let clip = tone(8000);
+    let mut player = Player::new(&Config::default(), 16_000, 16_000);
+    let played = play(&mut player, &clip, 100);
+
+    // what the stretcher makes of the clip and the silence after it
+    let mut stretcher = Stretcher::<N>::new(&Config::default());
+    let input: Vec<i16> = clip
+        .iter()
+        .map(|&s| to_pcm16(s))
+        .chain([0; N / 4])
+        .collect();
+    let mut expected = Vec::new();
+    let mut buf = [0; 64];
+    let mut rest = &input[..];
+    loop {
+        let (read, written) = stretcher.push_block(rest, &mut buf);
+        expected.extend(buf[..written].iter().map(|&x| from_pcm16(x)));
+        rest = &rest[read..];
+        if read == 0 && written == 0 {
+            break;
+        }
+    }
+    assert_eq!(played, expected);
+    // which is all of the clip
+    assert!(played.len() >= clip.len());
+}
+
+#[test]
+fn speed_scales_the_duration() {
+    let clip = tone(16_000);
+    for (mode, percent) in [
+        (Mode::Wsola, 200),
+        (Mode::Wsola, 50),
+        (Mode::Resample, 200),
+        (Mode::Resample, 50),
+    ] {
+        let config = Config {
+            speed_percent: percent,
+            mode,
+        };
+        let played = play(&mut Player::new(&config, 16_000, 16_000), &clip, 37);
+        let expected = clip.len() * 100 / percent as usize;
+        assert!(
+            played.len().abs_diff(expected) < N / 2,
+            "{mode:?} at {percent}%: {} samples",
+            played.len()
+        );
+    }
+}
+
+#[test]
+fn room_limits_each_step() {
+    let mut clip = Clip(tone(4000).into());
+    let mut sink = Collect::default();
+    let mut player = Player::new(&Config::default(), 16_000, 16_000);
+    // reading the clip a block at a time, as far as it takes to fill the
+    // stretcher
+    let read = player.step(&mut clip, &mut sink, 10).unwrap();
+    assert!(read > 0 && read.is_multiple_of(32), "{read}");
+    assert_eq!(player.step(&mut clip, &mut sink, 0), Ok(0));
+    assert_eq!(sink.0.len(), 10);
+    assert!(!player.is_finished());
+
+    // and a reset player starts the next clip afresh
+    while !player.is_finished() {
+        player.step(&mut clip, &mut sink, 64).unwrap();
+    }
+    player.reset();
+    assert!(!player.is_finished());
+    let again = play(&mut player, &tone(4000), 64);
+    assert_eq!(again, sink.0);
+}
+
+#[test]
+fn clips_at_other_rates_are_resampled_to_the_output() {
+    for rate in [8_000, 44_100] {
+        let clip = tone_at(f64::from(rate), rate as usize);
+        let mut player = Player::new(&Config::default(), rate, 16_000);
+        let played = play(&mut player, &clip, 64);
+
+        // a second of the clip is a second of output
+        assert!(
+            played.len().abs_diff(16_000) < N / 2,
+            "{rate} Hz: {}",
+            played.len()
+        );
+        // and the same tone, once the filters have filled, but for the
+        // delay through them, which needn't be a whole number of samples
+        let played: Vec<f64> = played[4000..6000].iter().map(|&s| f64::from(s)).collect();
+        let error = (0..256 * 16)
+            .map(|sixteenths| {
+                let delay = f64::from(sixteenths) / 16.0;
+                played
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &s)| (s - sample(4000.0 + i as f64 - delay, 16_000.0)).abs())
+                    .fold(0.0, f64::max)
+            })
+            .fold(f64::INFINITY, f64::min);
+        assert!(error < 4.0, "{rate} Hz: off by {error:.1}");
+    }
+}
@@ -0,0 +1,107 @@
+use voice_core::dc::{DcBlocker, VOICE_CORNER_HZ};
+use voice_core::process::{Chain, Processor};
+use voice_core::SampleRate;
+
+/// Multiplies by a whole number, saturating.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Gain(i16);
+
+impl Processor for Gain {
+    fn process(&mut self, sample: i16) -> i16 {
+        sample.saturating_mul(self.0)
+    }
+}
+
+/// Adds up what it has seen; tells a reset apart from a fresh stage.
+#[derive(Debug, Default)]
+struct Sum(i16);
+
+impl Processor for Sum {
+    fn process(&mut self, sample: i16) -> i16 {
+        self.0 = self.0.saturating_add(sample);
+        self.0
+    }
+
+    fn reset(&mut self) {
+        self.0 = 0;
+    }
+}
+
+fn dc() -> DcBlocker {
+    DcBlocker::new(VOICE_CORNER_HZ, SampleRate::Hz16000)
+}
+
+fn input() -> Vec<u16> {
+    (0..300)
+        .map(|i| (2100.0 + 800.0 * (i as f64 * 0.3).sin()) as u16)
+        .collect()
+}
+
+#[test]
+fn bare_chain_only_removes_dc() {
+    let mut chain = Chain::new(dc());
+    let mut reference = dc();
+    for s in input() {
+        assert_eq!(chain.push(s), reference.push(s));
+    }
+}
+
+#[test]
+fn stages_run_in_order() {
+    let mut chain = Chain::new(dc()).then(Gain(2)).then(Sum::default());
+    let mut reference = dc();
+    let mut sum = 0i16;
+    for s in input() {
+        sum = sum.saturating_add(reference.push(s).saturating_mul(2));
+        assert_eq!(chain.push(s), sum);
+    }
+    assert_eq!(chain.stages().next().0, sum);
+    assert_eq!(chain.stages().first().next(), &Gain(2));
+}
+
+#[test]
+fn blocks_run_like_single_samples() {
+    let input = input();
+    let mut one = Chain::new(dc()).then(Gain(3)).then(Sum::default());
+    let mut block = Chain::new(dc()).then(Gain(3)).then(Sum::default());
+    let expected: Vec<_> = input.iter().map(|&s| one.push(s)).collect();
+
+    let mut output = vec![0; input.len() + 5];
+    let mut done = 0;
+    for piece in input.chunks(17) {
+        done += block.push_block(piece, &mut output[done..]);
+    }
+    assert_eq!(done, input.len());
+    assert_eq!(output[..done], expected);
+    // as far as the output goes
+    assert_eq!(block.push_block(&input, &mut output[..4]), 4);
+}
+
+#[test]
+fn reset_reaches_every_stage() {
+    let mut chain = Chain::new(dc()).then(Sum::default());
+    for s in input() {
+        chain.push(s);
+    }
+    chain.reset();
+    assert_eq!(chain.stages().next().0, 0);
+    // the DC blocker starts over from the next sample's level
+    assert_eq!(chain.push(3000), 0);
+}
+
+#[test]
+fn stages_can_be_borrowed_and_retuned() {
+    let mut gain = Gain(1);
+    let mut chain = Chain::new(dc()).then(&mut gain);
+    let mut reference = dc();
+    for (i, s) in input().into_iter().enumerate() {
+        if i == 100 {
+            chain.stages_mut().next_mut().0 = 4;
+        }
+        let factor = if i < 100 { 1 } else { 4 };
+        assert_eq!(chain.push(s), reference.push(s).saturating_mul(factor));
+    }
+    *chain.dc_blocker_mut() = DcBlocker::new(100, SampleRate::Hz16000);
+    assert_eq!(chain.push(2049), 0);
+    assert_eq!(gain, Gain(4));
+}
@@ -0,0 +1,86 @@
+use voice_core::pwm::{self, Slice, SYS_CLOCK_HZ};
+use voice_core::SampleRate;
+
+#[test]
+fn carrier_is_ultrasonic_with_twelve_bits() {
+    let carrier = Slice::CARRIER;
+    assert_eq!(carrier.top, 4095);
+    assert_eq!(carrier.rate_millihz(SYS_CLOCK_HZ), 30_517_578);
+    for sample in 0..=4095 {
+        assert_eq!(carrier.duty(sample), sample);
+    }
+}
+
+#[test]
+fn scale_spans_the_slice() {
+    assert_eq!(pwm::scale(0, 6249), 0);
+    assert_eq!(pwm::scale(2048, 6249), 3125);
+    assert_eq!(pwm::scale(4095, 6249), 6248);
+    // out of range samples are clamped rather than wrapping
+    assert_eq!(pwm::scale(u16::MAX, 1023), 1023);
+}
+
+#[test]
+fn unscale_inverts_scale() {
+    for top in [4095, 6249, 15624, u16::MAX] {
+        for sample in 0..=4095 {
+            assert_eq!(pwm::unscale(pwm::scale(sample, top), top), sample, "{top}");
+        }
+    }
+}
+
+#[test]
+fn pacers_for_supported_rates() {
+    let expected = [
+        (SampleRate::Hz8000, 1, 0, 15624),
+        (SampleRate::Hz11025, 2, 3, 5182),
+        (SampleRate::Hz16000, 1, 4, 6249),
+        (SampleRate::Hz22050, 1, 2, 5038),
+        (SampleRate::Hz32000, 1, 4, 3124),
+        (SampleRate::Hz44100, 1, 9, 1813),
+    ];
+    for (rate, div_int, div_frac, top) in expected {
+        let pacer = Slice {
+            div_int,
+            div_frac,
+            top,
+        };
+        assert_eq!(rate.pacer(), pacer, "{rate:?}");
+    }
+}
+
+#[test]
+fn pacers_that_divide_the_clock_are_exact() {
+    for rate in [SampleRate::Hz8000, SampleRate::Hz16000, SampleRate::Hz32000] {
+        let pacer = rate.pacer();
+        assert_eq!(
+            pacer.rate_millihz(SYS_CLOCK_HZ),
+            u64::from(rate.hz()) * 1000
+        );
+        assert_eq!(pacer.error_ppm(SYS_CLOCK_HZ, rate.hz()), 0);
+    }
+}
+
+#[test]
+fn cd_family_pacers_are_close() {
+    assert_eq!(
+        SampleRate::Hz44100.pacer().rate_millihz(SYS_CLOCK_HZ),
+        44_101_433
+    );
+    for rate in SampleRate::ALL {
+        let error = rate.pacer().error_ppm(SYS_CLOCK_HZ, rate.hz());
+        assert!(error.abs() <= 40, "{rate:?}: {error} ppm");
+    }
+}
+
+#[test]
+fn pacer_period_is_in_sixteenths() {
+    // 1.25 × 6250 system clock cycles
+    assert_eq!(SampleRate::Hz16000.pacer().period(), 20 * 6250);
+}
+
+#[test]
+#[should_panic(expected = "rate too low for a PWM slice")]
+fn pacer_slower_than_a_slice_panics() {
+    Slice::pacer(SYS_CLOCK_HZ, 1);
+}
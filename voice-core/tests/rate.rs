@@ -0,0 +1,91 @@
+use voice_core::adc;
+use voice_core::rate::Divider;
+use voice_core::SampleRate;
+
+#[test]
+fn dividers_for_supported_rates() {
+    let expected = [
+        (SampleRate::Hz8000, 5999, 0),
+        (SampleRate::Hz11025, 4352, 190),
+        (SampleRate::Hz16000, 2999, 0),
+        (SampleRate::Hz22050, 2175, 223),
+        (SampleRate::Hz32000, 1499, 0),
+        (SampleRate::Hz44100, 1087, 111),
+    ];
+    for (rate, int, frac) in expected {
+        assert_eq!(rate.divider(), Divider { int, frac }, "{rate:?}");
+    }
+}
+
+#[test]
+fn rates_that_divide_the_clock_are_exact() {
+    for rate in [SampleRate::Hz8000, SampleRate::Hz16000, SampleRate::Hz32000] {
+        assert_eq!(rate.achieved_millihz(), u64::from(rate.hz()) * 1000);
+        assert_eq!(rate.error_ppm(), 0);
+    }
+}
+
+#[test]
+fn cd_family_rates_are_within_a_few_ppm() {
+    assert_eq!(SampleRate::Hz11025.achieved_millihz(), 11_024_998);
+    assert_eq!(SampleRate::Hz11025.error_ppm(), 0);
+    assert_eq!(SampleRate::Hz22050.achieved_millihz(), 22_049_997);
+    assert_eq!(SampleRate::Hz44100.achieved_millihz(), 44_100_072);
+    assert_eq!(SampleRate::Hz44100.error_ppm(), 2);
+    for rate in SampleRate::ALL {
+        assert!(rate.error_ppm().abs() <= 2, "{rate:?}");
+    }
+}
+
+#[test]
+fn oversampled_dividers_run_the_adc_oversample_times_faster() {
+    for rate in SampleRate::ALL {
+        let divider = rate.oversampled_divider();
+        assert!(divider.period() >= Divider::MIN_PERIOD, "{rate:?}");
+        let target = rate.hz() * adc::OVERSAMPLE;
+        assert!(
+            divider.error_ppm(adc::CLOCK_HZ, target).abs() <= 10,
+            "{rate:?}"
+        );
+    }
+    assert_eq!(
+        SampleRate::Hz16000.oversampled_divider(),
+        Divider { int: 749, frac: 0 }
+    );
+}
+
+#[test]
+fn error_ppm_has_the_right_sign() {
+    // one cycle longer than 8 kHz is 1/6000 slow, one cycle shorter 1/6000 fast
+    let slow = Divider { int: 6000, frac: 0 };
+    let fast = Divider { int: 5998, frac: 0 };
+    assert_eq!(slow.error_ppm(adc::CLOCK_HZ, 8000), -167);
+    assert_eq!(fast.error_ppm(adc::CLOCK_HZ, 8000), 167);
+}
+
+#[test]
+fn old_hard_coded_divider_was_10ksps() {
+    let divider = Divider { int: 4799, frac: 0 };
+    assert_eq!(divider.rate_millihz(adc::CLOCK_HZ), 10_000_000);
+}
+
+#[test]
+fn period_round_trips_through_divider() {
+    for period in [Divider::MIN_PERIOD, 278_639, 1_536_000, 65_536 * 256] {
+        assert_eq!(Divider::from_period(period).period(), period);
+    }
+}
+
+#[test]
+#[should_panic(expected = "period shorter than a conversion")]
+fn divider_faster_than_a_conversion_panics() {
+    Divider::for_rate(adc::CLOCK_HZ, 600_000);
+}
+
+#[test]
+fn from_hz_matches_hz() {
+    for rate in SampleRate::ALL {
+        assert_eq!(SampleRate::from_hz(rate.hz()), Some(rate));
+    }
+    assert_eq!(SampleRate::from_hz(10_000), None);
+}
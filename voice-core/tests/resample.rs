@@ -0,0 +1,197 @@
+use std::f64::consts::PI;
+
+use voice_core::resample::Resampler;
+
+fn sine(hz: f64, rate: u32, len: usize) -> Vec<i16> {
+    (0..len)
+        .map(|i| (16000.0 * (2.0 * PI * hz * i as f64 / f64::from(rate)).sin()).round() as i16)
+        .collect()
+}
+
+/// Run `input` through a resampler a block at a time.
+fn resample<const TAPS: usize, const PHASES: usize>(from: u32, to: u32, input: &[i16]) -> Vec<i16> {
+    let mut resampler: Resampler<TAPS, PHASES> = Resampler::new(from, to);
+    let mut output = Vec::new();
+    let mut block = [0; 64];
+    let mut read = 0;
+    while read < input.len() {
+        let (n, written) = resampler.push_block(&input[read..], &mut block);
+        read += n;
+        output.extend_from_slice(&block[..written]);
+    }
+    output
+}
+
+/// Amplitude of the component at `hz` in `signal`, sampled at `rate`,
+/// leaving out the first and last quarter second.
+fn level(signal: &[i16], hz: f64, rate: u32) -> f64 {
+    let skip = rate as usize / 4;
+    let signal = &signal[skip..signal.len() - skip];
+    let (mut re, mut im) = (0.0, 0.0);
+    for (i, &x) in signal.iter().enumerate() {
+        let w = 2.0 * PI * hz * i as f64 / f64::from(rate);
+        re += f64::from(x) * w.cos();
+        im += f64::from(x) * w.sin();
+    }
+    2.0 * (re * re + im * im).sqrt() / signal.len() as f64
+}
+
+fn db(ratio: f64) -> f64 {
+    20.0 * ratio.log10()
+}
+
+/// Gain in dB of a tone at `hz` going from `from` to `to`.
+fn gain<const TAPS: usize, const PHASES: usize>(from: u32, to: u32, hz: f64) -> f64 {
+    let output = resample::<TAPS, PHASES>(from, to, &sine(hz, from, from as usize));
+    db(level(&output, hz, to) / 16000.0)
+}
+
+/// Level in dB, relative to the tone, of what a tone at `hz` going from
+/// `from` to `to` leaves at `alias`.
+fn alias<const TAPS: usize, const PHASES: usize>(from: u32, to: u32, hz: f64, alias: f64) -> f64 {
+    let output = resample::<TAPS, PHASES>(from, to, &sine(hz, from, from as usize));
+    db(level(&output, alias, to) / 16000.0)
+}
+
+#[test]
+fn ratios_are_in_lowest_terms() {
+    assert_eq!(Resampler::<40, 4>::new(10000, 8000).ratio(), (4, 5));
+    assert_eq!(Resampler::<40, 5>::new(8000, 10000).ratio(), (5, 4));
+    assert_eq!(Resampler::<64, 1>::new(16000, 8000).ratio(), (1, 2));
+    assert_eq!(Resampler::<32, 2>::new(8000, 16000).ratio(), (2, 1));
+    assert_eq!(Resampler::<160, 80>::new(44100, 8000).ratio(), (80, 441));
+    assert_eq!(Resampler::<8, 1>::new(8000, 8000).ratio(), (1, 1));
+}
+
+#[test]
+fn output_length_follows_the_ratio() {
+    let input = sine(440.0, 44100, 44100);
+    for (from, to) in [
+        (10000, 8000),
+        (8000, 10000),
+        (16000, 8000),
+        (8000, 16000),
+        (44100, 8000),
+    ] {
+        let len = from as usize / 2;
+        let out = resample::<32, 80>(from, to, &input[..len]);
+        let expected = len * to as usize / from as usize;
+        assert!(
+            out.len().abs_diff(expected) <= 1,
+            "{from} -> {to}: {} samples, expected {expected}",
+            out.len()
+        );
+    }
+}
+
+#[test]
+fn passband_is_flat() {
+    for hz in [300.0, 1000.0, 2000.0, 3000.0, 3400.0] {
+        let gains = [
+            gain::<40, 4>(10000, 8000, hz),
+            gain::<40, 5>(8000, 10000, hz),
+            gain::<64, 1>(16000, 8000, hz),
+            gain::<32, 2>(8000, 16000, hz),
+            gain::<160, 80>(44100, 8000, hz),
+        ];
+        for g in gains {
+            assert!(g.abs() < 0.1, "{hz} Hz: {gains:.3?} dB");
+        }
+    }
+}
+
+#[test]
+fn tones_above_the_new_nyquist_frequency_dont_alias_into_the_passband() {
+    // each tone would fold down to 3 kHz, or 3.3 kHz from 10 kHz
+    let aliases = [
+        alias::<40, 4>(10000, 8000, 4700.0, 3300.0),
+        alias::<64, 1>(16000, 8000, 5000.0, 3000.0),
+        alias::<64, 1>(16000, 8000, 7000.0, 1000.0),
+        alias::<160, 80>(44100, 8000, 5000.0, 3000.0),
+        alias::<160, 80>(44100, 8000, 13000.0, 3000.0),
+        alias::<160, 80>(44100, 8000, 20000.0, 4000.0),
+    ];
+    for a in aliases {
+        assert!(a < -60.0, "{aliases:.1?} dB");
+    }
+}
+
+#[test]
+fn interpolation_leaves_no_images() {
+    // going up, a tone at f shows up mirrored about the old Nyquist
+    // frequency unless the filter takes it out
+    let images = [
+        alias::<32, 2>(8000, 16000, 1000.0, 7000.0),
+        alias::<32, 2>(8000, 16000, 3000.0, 5000.0),
+        alias::<40, 5>(8000, 10000, 3000.0, 5000.0),
+        alias::<32, 441>(8000, 44100, 1000.0, 7000.0),
+    ];
+    for image in images {
+        assert!(image < -60.0, "{images:.1?} dB");
+    }
+}
+
+#[test]
+fn a_steady_level_comes_through_exactly() {
+    // every phase passes DC the same, so nothing wobbles at the input rate
+    let input = vec![10000; 8000];
+    for out in [
+        resample::<40, 5>(8000, 10000, &input),
+        resample::<32, 441>(8000, 44100, &input),
+        resample::<160, 80>(44100, 8000, &input),
+    ] {
+        assert!(out[200..out.len() - 200].iter().all(|&x| x == 10000));
+    }
+}
+
+#[test]
+fn block_size_doesnt_matter() {
+    let input: Vec<i16> = sine(440.0, 10000, 5000)
+        .iter()
+        .zip(sine(2900.0, 10000, 5000))
+        .map(|(a, b)| a / 2 + b / 2)
+        .collect();
+    let whole = resample::<40, 5>(10000, 8000, &input);
+
+    let mut resampler: Resampler<40, 5> = Resampler::new(10000, 8000);
+    let mut pieces = Vec::new();
+    let mut block = [0; 5];
+    for chunk in input.chunks(7) {
+        let mut read = 0;
+        while read < chunk.len() {
+            let (n, written) = resampler.push_block(&chunk[read..], &mut block);
+            read += n;
+            pieces.extend_from_slice(&block[..written]);
+        }
+    }
+    assert_eq!(pieces, whole);
+}
+
+#[test]
+fn output_stops_when_the_buffer_is_full() {
+    let mut resampler: Resampler<32, 2> = Resampler::new(8000, 16000);
+    let mut output = [0; 9];
+    // two outputs for each input, so the fifth input can only give one
+    assert_eq!(resampler.push_block(&[1000; 10], &mut output), (5, 9));
+    assert_eq!(resampler.push_block(&[], &mut output), (0, 1));
+    assert_eq!(resampler.push_block(&[], &mut output), (0, 0));
+}
+
+#[test]
+fn reset_forgets_the_signal() {
+    let input = sine(1000.0, 16000, 4000);
+    let mut resampler: Resampler<64, 1> = Resampler::new(16000, 8000);
+    let mut first = [0; 2000];
+    let mut again = [0; 2000];
+    resampler.push_block(&input, &mut first);
+    resampler.push_block(&[3000; 123], &mut again);
+    resampler.reset();
+    resampler.push_block(&input, &mut again);
+    assert_eq!(first, again);
+}
+
+#[test]
+#[should_panic(expected = "ratio needs more phases")]
+fn too_few_phases_panics() {
+    let _ = Resampler::<160, 64>::new(44100, 8000);
+}
@@ -0,0 +1,240 @@
+mod common;
+
+use std::f64::consts::PI;
+
+use voice_core::pitch::{self, Yin};
+use voice_core::stretch::{Config, Mode, Stretcher};
+use voice_core::SampleRate;
+
+/// Input buffer, for grains of 32 ms at 8 kHz.
+const N: usize = 1024;
+
+/// Pitch-tracking frames.
+const FRAME: usize = 256;
+
+fn sine(hz: f64, len: usize) -> Vec<i16> {
+    (0..len)
+        .map(|i| (12000.0 * (2.0 * PI * hz * i as f64 / 8000.0).sin()).round() as i16)
+        .collect()
+}
+
+/// Every harmonic of `hz` below 1.9 kHz, falling as 1/k: rich, but with
+/// nothing for resampling at double speed to alias.
+fn harmonics(hz: f64, len: usize) -> Vec<i16> {
+    (0..len)
+        .map(|i| {
+            let w = 2.0 * PI * hz * i as f64 / 8000.0;
+            let x: f64 = (1..)
+                .take_while(|&k| f64::from(k) * hz < 1900.0)
+                .map(|k| (f64::from(k) * w).sin() / f64::from(k))
+                .sum();
+            (7000.0 * x).round() as i16
+        })
+        .collect()
+}
+
+/// A vowel /a/ at `hz`.
+fn vowel(hz: f64, len: usize) -> Vec<i16> {
+    common::vowel(len, [730.0, 1090.0, 2440.0], |_| hz)
+        .iter()
+        .map(|x| (14000.0 * x).round() as i16)
+        .collect()
+}
+
+/// Everything `input` gives, followed by enough silence to push it all
+/// out, in blocks of `block` samples out.
+fn stretch(stretcher: &mut Stretcher<N>, input: &[i16], block: usize) -> Vec<i16> {
+    let input = [input, &[0; N / 4]].concat();
+    let mut output = Vec::new();
+    let mut buf = vec![0; block];
+    let mut rest = &input[..];
+    loop {
+        let (read, written) = stretcher.push_block(rest, &mut buf);
+        output.extend_from_slice(&buf[..written]);
+        rest = &rest[read..];
+        if read == 0 && written == 0 {
+            break;
+        }
+    }
+    output
+}
+
+/// The mean pitch found over the frames of `signal` between `from` and
+/// `to`, requiring one in every frame.
+fn mean_pitch(signal: &[i16], from: usize, to: usize) -> f64 {
+    let mut yin: Yin<FRAME> = Yin::new(&pitch::Config::default(), SampleRate::Hz8000);
+    let found: Vec<f64> = signal[from..to]
+        .chunks_exact(FRAME)
+        .map(|c| {
+            let p = yin.estimate(c.try_into().unwrap()).expect("a pitch");
+            f64::from(p.millihertz) / 1000.0
+        })
+        .collect();
+    found.iter().sum::<f64>() / found.len() as f64
+}
+
+const SPEEDS: [u32; 6] = [50, 67, 80, 125, 150, 200];
+
+#[test]
+fn durations_scale_with_the_speed() {
+    let input = vowel(150.0, 2 * 8000);
+    for mode in [Mode::Wsola, Mode::Resample] {
+        for speed_percent in SPEEDS.into_iter().chain([100]) {
+            let mut stretcher = Stretcher::new(&Config {
+                speed_percent,
+                mode,
+            });
+            let output = stretch(&mut stretcher, &input, 100);
+            // WSOLA holds back a grain and the room to search ahead of it,
+            // which the silence after the input doesn't quite push out
+            let held = match mode {
+                Mode::Wsola => N / 4,
+                Mode::Resample => 0,
+            };
+            let expected = (input.len() + N / 4 - held) as f64 * 100.0 / f64::from(speed_percent);
+            let error = output.len() as f64 - expected;
+            assert!(
+                error.abs() <= (N / 8) as f64,
+                "{mode:?} at {speed_percent}%: {} for {expected:.0}",
+                output.len()
+            );
+        }
+    }
+}
+
+#[test]
+fn wsola_keeps_the_pitch() {
+    for (input, hz) in [
+        (sine(220.0, 3 * 8000), 220.0),
+        (vowel(130.0, 3 * 8000), 130.0),
+    ] {
+        for speed_percent in SPEEDS {
+            let mut stretcher = Stretcher::new(&Config {
+                speed_percent,
+                mode: Mode::Wsola,
+            });
+            let output = stretch(&mut stretcher, &input, 256);
+            let found = mean_pitch(&output, N, output.len() - N);
+            assert!(
+                (found / hz - 1.0).abs() < 0.01,
+                "{hz} Hz at {speed_percent}%: {found:.1}"
+            );
+        }
+    }
+}
+
+#[test]
+fn resampling_moves_the_pitch_with_the_speed() {
+    let input = harmonics(170.0, 3 * 8000);
+    for speed_percent in SPEEDS {
+        let mut stretcher = Stretcher::new(&Config {
+            speed_percent,
+            mode: Mode::Resample,
+        });
+        let output = stretch(&mut stretcher, &input, 256);
+        let expected = 170.0 * f64::from(speed_percent) / 100.0;
+        let found = mean_pitch(&output, FRAME, output.len() - N);
+        assert!(
+            (found / expected - 1.0).abs() < 0.01,
+            "{speed_percent}%: {found:.1} for {expected:.1}"
+        );
+    }
+}
+
+#[test]
+fn events_keep_their_place_in_time() {
+    // a click a second in comes out a second over the speed in, give or
+    // take a grain
+    let mut input: Vec<i16> = vowel(120.0, 2 * 8000).iter().map(|x| x / 4).collect();
+    for x in &mut input[8000..8010] {
+        *x = 30000;
+    }
+    for speed_percent in SPEEDS {
+        for mode in [Mode::Wsola, Mode::Resample] {
+            let mut stretcher = Stretcher::new(&Config {
+                speed_percent,
+                mode,
+            });
+            let output = stretch(&mut stretcher, &input, 64);
+            let click = output.iter().position(|&y| y > 12000).unwrap();
+            let expected = 8000.0 * 100.0 / f64::from(speed_percent);
+            assert!(
+                (click as f64 - expected).abs() <= (N / 4) as f64,
+                "{mode:?} at {speed_percent}%: {click} for {expected:.0}"
+            );
+        }
+    }
+}
+
+#[test]
+fn normal_speed_changes_nothing() {
+    let input = vowel(170.0, 8000);
+    // but the fade in of the first grain
+    let mut wsola = Stretcher::new(&Config::default());
+    let output = stretch(&mut wsola, &input, 100);
+    assert_eq!(output[N / 8..input.len()], input[N / 8..]);
+
+    let mut resample = Stretcher::new(&Config {
+        mode: Mode::Resample,
+        ..Config::default()
+    });
+    let output = stretch(&mut resample, &input, 100);
+    assert_eq!(output[..input.len()], input);
+}
+
+#[test]
+fn blocks_of_any_size_give_the_same() {
+    let input = vowel(140.0, 8000);
+    for mode in [Mode::Wsola, Mode::Resample] {
+        let config = Config {
+            speed_percent: 75,
+            mode,
+        };
+        let whole = stretch(&mut Stretcher::new(&config), &input, 20000);
+        for block in [1, 7, 128, 1000] {
+            let output = stretch(&mut Stretcher::new(&config), &input, block);
+            assert_eq!(output, whole, "{mode:?} in {block}");
+        }
+    }
+}
+
+#[test]
+fn the_speed_can_change_while_playing() {
+    let input = sine(200.0, 4 * 8000);
+    let mut stretcher: Stretcher<N> = Stretcher::new(&Config::default());
+    let mut output = vec![0; 8000];
+    let (read, written) = stretcher.push_block(&input, &mut output);
+    assert_eq!(written, 8000);
+    stretcher.set_speed(200);
+    assert_eq!(stretcher.speed_percent(), 200);
+    let mut faster = vec![0; 8000];
+    let (more, written) = stretcher.push_block(&input[read..], &mut faster);
+    assert_eq!(written, 8000);
+    // twice as much input for the same output, but for what was already
+    // buffered, and the same pitch
+    assert!(more.abs_diff(16000) <= N, "{more}");
+    assert!((mean_pitch(&faster, 0, 8000) / 200.0 - 1.0).abs() < 0.01);
+}
+
+#[test]
+fn reset_starts_over() {
+    let input = vowel(110.0, 8000);
+    let config = Config {
+        speed_percent: 150,
+        mode: Mode::Wsola,
+    };
+    let mut stretcher = Stretcher::new(&config);
+    let first = stretch(&mut stretcher, &input, 300);
+    stretcher.reset();
+    assert_eq!(stretcher, Stretcher::new(&config));
+    assert_eq!(stretch(&mut stretcher, &input, 300), first);
+}
+
+#[test]
+#[should_panic]
+fn speeds_are_from_half_to_double() {
+    let _: Stretcher<N> = Stretcher::new(&Config {
+        speed_percent: 201,
+        mode: Mode::Wsola,
+    });
+}
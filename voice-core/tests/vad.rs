@@ -0,0 +1,284 @@
+use std::f64::consts::PI;
+
+use voice_core::process::{Chain, Processor};
+use voice_core::vad::{Config, Frame, Vad, FRAME_MS};
+use voice_core::SampleRate;
+
+/// Builds test signals: background noise with speech-like bursts on top.
+struct Signal {
+    rate: SampleRate,
+    samples: Vec<f64>,
+    seed: u32,
+}
+
+impl Signal {
+    fn new(rate: SampleRate) -> Self {
+        Self {
+            rate,
+            samples: Vec::new(),
+            seed: 1,
+        }
+    }
+
+    fn ms(&self, ms: usize) -> usize {
+        ms * self.rate.hz() as usize / 1000
+    }
+
+    /// Uniform noise between -1 and 1.
+    fn noise(&mut self) -> f64 {
+        self.seed = self
+            .seed
+            .wrapping_mul(1_664_525)
+            .wrapping_add(1_013_904_223);
+        f64::from(self.seed >> 8) / f64::from(1u32 << 23) - 1.0
+    }
+
+    /// `ms` of background noise at `dbfs` peak.
+    fn silence(mut self, ms: usize, dbfs: f64) -> Self {
+        let amplitude = 32767.0 * 10f64.powf(dbfs / 20.0);
+        for _ in 0..self.ms(ms) {
+            let n = self.noise();
+            self.samples.push(amplitude * n);
+        }
+        self
+    }
+
+    /// `ms` of a voiced, speech-like burst at `dbfs` peak over noise at
+    /// `noise_dbfs`: a 140 Hz buzz with falling harmonics, swelling and
+    /// fading like a syllable.
+    fn speech(mut self, ms: usize, dbfs: f64, noise_dbfs: f64) -> Self {
+        let amplitude = 32767.0 * 10f64.powf(dbfs / 20.0) / 2.0;
+        let noise = 32767.0 * 10f64.powf(noise_dbfs / 20.0);
+        let len = self.ms(ms);
+        let w = 2.0 * PI * 140.0 / f64::from(self.rate.hz());
+        for i in 0..len {
+            let envelope = (PI * i as f64 / len as f64).sin().sqrt();
+            let buzz: f64 = (1..=12)
+                .map(|k| (w * k as f64 * i as f64).sin() / k as f64)
+                .sum();
+            let n = self.noise();
+            self.samples.push(amplitude * envelope * buzz + noise * n);
+        }
+        self
+    }
+
+    /// `ms` of low-pass noise at roughly `dbfs` peak, like traffic rumble,
+    /// which crosses zero as rarely as speech.
+    fn rumble(mut self, ms: usize, dbfs: f64) -> Self {
+        let amplitude = 32767.0 * 10f64.powf(dbfs / 20.0);
+        let mut level = 0.0;
+        for _ in 0..self.ms(ms) {
+            level += 0.05 * (self.noise() - level);
+            self.samples.push(4.0 * amplitude * level);
+        }
+        self
+    }
+
+    /// `ms` of loud broadband noise at `dbfs`, like wind on the microphone.
+    fn rustle(mut self, ms: usize, dbfs: f64) -> Self {
+        self = self.silence(ms, dbfs);
+        self
+    }
+
+    fn build(&self) -> Vec<i16> {
+        self.samples
+            .iter()
+            .map(|&s| s.round().clamp(-32768.0, 32767.0) as i16)
+            .collect()
+    }
+}
+
+fn frames(vad: &mut Vad, samples: &[i16]) -> Vec<Frame> {
+    samples.iter().filter_map(|&s| vad.push(s)).collect()
+}
+
+/// Decisions for the frames from `from_ms` up to `to_ms`.
+fn decisions(frames: &[Frame], from_ms: usize, to_ms: usize) -> &[Frame] {
+    &frames[from_ms / FRAME_MS as usize..to_ms / FRAME_MS as usize]
+}
+
+fn all_speech(frames: &[Frame]) -> bool {
+    frames.iter().all(|f| f.speech)
+}
+
+fn no_speech(frames: &[Frame]) -> bool {
+    frames.iter().all(|f| !f.speech)
+}
+
+#[test]
+fn bursts_in_noise_are_found() {
+    for rate in [SampleRate::Hz8000, SampleRate::Hz16000, SampleRate::Hz44100] {
+        let noise = -50.0;
+        let samples = Signal::new(rate)
+            .silence(1000, noise)
+            .speech(600, -20.0, noise)
+            .silence(1000, noise)
+            .speech(300, -30.0, noise)
+            .silence(1000, noise)
+            .build();
+        let hangover = Config::default().hangover_ms as usize;
+        let mut vad = Vad::new(&Config::default(), rate);
+        let frames = frames(&mut vad, &samples);
+        assert_eq!(frames.len(), 3900 / FRAME_MS as usize);
+        assert!(no_speech(decisions(&frames, 0, 1000)), "{rate:?}");
+        // found within a few frames of the start, and held through the
+        // hangover after the end
+        assert!(
+            all_speech(decisions(&frames, 1050, 1600 + hangover - 20)),
+            "{rate:?}"
+        );
+        assert!(
+            no_speech(decisions(&frames, 1600 + hangover + 40, 2600)),
+            "{rate:?}"
+        );
+        // a quieter burst only 20 dB over the noise
+        assert!(
+            all_speech(decisions(&frames, 2650, 2900 + hangover - 20)),
+            "{rate:?}"
+        );
+        assert!(
+            no_speech(decisions(&frames, 2900 + hangover + 40, 3900)),
+            "{rate:?}"
+        );
+    }
+}
+
+#[test]
+fn onset_is_reported_promptly() {
+    let rate = SampleRate::Hz16000;
+    let samples = Signal::new(rate)
+        .silence(500, -50.0)
+        .speech(500, -20.0, -50.0)
+        .build();
+    let mut vad = Vad::new(&Config::default(), rate);
+    let frames = frames(&mut vad, &samples);
+    let onset = frames.iter().position(|f| f.speech).unwrap();
+    assert!((50..53).contains(&onset), "frame {onset}");
+}
+
+#[test]
+fn the_floor_adapts_to_louder_noise() {
+    let rate = SampleRate::Hz16000;
+    // the room gets 12 dB noisier, and after a while the detector settles
+    // down again and still hears speech over it
+    let signal = Signal::new(rate)
+        .rumble(1000, -60.0)
+        .rumble(20_000, -48.0)
+        .speech(500, -15.0, -60.0)
+        .build();
+    let mut vad = Vad::new(&Config::default(), rate);
+    let frames = frames(&mut vad, &signal);
+    // at first the rumble sounds like speech...
+    assert!(all_speech(decisions(&frames, 1000, 1500)));
+    // ...until the floor has crept up to it
+    assert!(no_speech(decisions(&frames, 16_000, 21_000)));
+    assert!(all_speech(decisions(&frames, 21_050, 21_500)));
+
+    // the estimate ends up a little under the rumble's average energy,
+    // following its quieter frames, and well above where it started
+    let energy = |from: usize, to: usize| {
+        let samples = &signal[from * 16..to * 16];
+        let mean_square =
+            samples.iter().map(|&s| f64::from(s).powi(2)).sum::<f64>() / samples.len() as f64;
+        10.0 * (mean_square / 2f64.powi(30)).log10()
+    };
+    let floor = f64::from(frames[2099].floor) / 256.0;
+    let rumble = energy(16_000, 21_000);
+    assert!(
+        (rumble - 8.0..rumble).contains(&floor),
+        "{floor:.2} dB, rumble {rumble:.2} dB"
+    );
+    assert!(floor > energy(0, 1000) + 6.0);
+}
+
+#[test]
+fn the_floor_drops_at_once() {
+    let rate = SampleRate::Hz8000;
+    let samples = Signal::new(rate)
+        .silence(1000, -30.0)
+        .silence(200, -60.0)
+        .speech(300, -40.0, -60.0)
+        .build();
+    let mut vad = Vad::new(&Config::default(), rate);
+    let frames = frames(&mut vad, &samples);
+    assert!(no_speech(decisions(&frames, 0, 1200)));
+    assert!(all_speech(decisions(&frames, 1250, 1500)));
+}
+
+#[test]
+fn broadband_noise_is_not_speech() {
+    let rate = SampleRate::Hz16000;
+    let samples = Signal::new(rate)
+        .silence(1000, -50.0)
+        .rustle(300, -15.0)
+        .silence(500, -50.0)
+        .build();
+    let mut vad = Vad::new(&Config::default(), rate);
+    let frames = frames(&mut vad, &samples);
+    assert!(no_speech(&frames));
+    // loud enough, but crossing zero far too often
+    let rustle = &decisions(&frames, 1000, 1300)[1];
+    assert!(rustle.energy - rustle.floor > 30 * 256);
+    assert!(rustle.crossings > 60);
+}
+
+#[test]
+fn hangover_bridges_gaps_between_words() {
+    let rate = SampleRate::Hz16000;
+    let samples = Signal::new(rate)
+        .silence(500, -50.0)
+        .speech(200, -20.0, -50.0)
+        .silence(150, -50.0)
+        .speech(200, -20.0, -50.0)
+        .silence(1000, -50.0)
+        .build();
+    let config = Config {
+        hangover_ms: 200,
+        ..Config::default()
+    };
+    let mut vad = Vad::new(&config, rate);
+    let frames = frames(&mut vad, &samples);
+    assert!(all_speech(decisions(&frames, 550, 1250)));
+    assert!(no_speech(decisions(&frames, 1300, 2050)));
+    // the gap itself isn't active, only held
+    assert!(decisions(&frames, 720, 830).iter().all(|f| !f.active));
+}
+
+#[test]
+fn passes_samples_through_a_chain() {
+    let rate = SampleRate::Hz16000;
+    let speech = Signal::new(rate)
+        .silence(500, -50.0)
+        .speech(300, -10.0, -50.0)
+        .build();
+    let input: Vec<u16> = speech
+        .iter()
+        .map(|&s| (2048 + i32::from(s) / 16) as u16)
+        .collect();
+
+    let vad = Vad::new(&Config::default(), rate);
+    let mut with = Chain::new(voice_core::dc::DcBlocker::new(20, rate)).then(vad);
+    let mut without = Chain::new(voice_core::dc::DcBlocker::new(20, rate));
+    let mut heard = false;
+    for &s in &input {
+        assert_eq!(with.push(s), without.push(s));
+        heard |= with.stages().next().is_speech();
+    }
+    assert!(heard);
+
+    with.reset();
+    assert!(!with.stages().next().is_speech());
+    assert_eq!(with.stages().next().floor(), None);
+}
+
+#[test]
+fn frames_are_ten_milliseconds() {
+    for rate in SampleRate::ALL {
+        let mut vad = Vad::new(&Config::default(), rate);
+        assert_eq!(vad.frame_len(), rate.hz() / 100);
+        let len = vad.frame_len() as usize;
+        let decided: Vec<_> = (0..len * 3).map(|_| vad.process(0)).collect();
+        assert_eq!(decided, vec![0; len * 3]);
+        assert!(vad.floor().is_some());
+    }
+}
@@ -0,0 +1,291 @@
+use std::convert::Infallible;
+use std::f64::consts::PI;
+
+use voice_core::vad::{self, Vad};
+use voice_core::vox::{Config, Sink, State, Vox};
+use voice_core::SampleRate;
+
+const RATE: SampleRate = SampleRate::Hz8000;
+
+/// Samples per millisecond at `RATE`.
+const MS: usize = 8;
+
+/// Pre-roll buffer for half a second at `RATE`.
+const PRE_ROLL: usize = 4000;
+
+/// A script of noise and speech-like bursts, in ms.
+#[derive(Clone, Copy)]
+enum Part {
+    Silence(usize),
+    Speech(usize),
+}
+
+/// Render a script: noise at -50 dBFS, with bursts of a 140 Hz buzz at
+/// -20 dBFS on top.
+fn render(script: &[Part]) -> Vec<i16> {
+    let mut seed = 9u32;
+    let mut samples = Vec::new();
+    let noise = 32767.0 * 10f64.powf(-50.0 / 20.0);
+    let speech = 32767.0 * 10f64.powf(-20.0 / 20.0) / 2.0;
+    let w = 2.0 * PI * 140.0 / f64::from(RATE.hz());
+    for part in script {
+        let (ms, loud) = match *part {
+            Part::Silence(ms) => (ms, false),
+            Part::Speech(ms) => (ms, true),
+        };
+        for i in 0..ms * MS {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let mut s = noise * (f64::from(seed >> 8) / f64::from(1u32 << 23) - 1.0);
+            if loud {
+                let t = i as f64;
+                s += speech
+                    * (1..=8)
+                        .map(|k| (w * k as f64 * t).sin() / k as f64)
+                        .sum::<f64>();
+            }
+            samples.push(s.round() as i16);
+        }
+    }
+    samples
+}
+
+/// What happened to the sink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Event {
+    Start,
+    Stop,
+}
+
+/// Collects recordings.
+#[derive(Default)]
+struct Tape {
+    events: Vec<Event>,
+    /// One entry per recording.
+    recordings: Vec<Vec<i16>>,
+}
+
+impl Sink for Tape {
+    type Error = Infallible;
+
+    fn start(&mut self) -> Result<(), Infallible> {
+        self.events.push(Event::Start);
+        self.recordings.push(Vec::new());
+        Ok(())
+    }
+
+    fn write(&mut self, samples: &[i16]) -> Result<(), Infallible> {
+        assert_eq!(self.events.last(), Some(&Event::Start));
+        self.recordings
+            .last_mut()
+            .unwrap()
+            .extend_from_slice(samples);
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), Infallible> {
+        self.events.push(Event::Stop);
+        Ok(())
+    }
+}
+
+fn vad() -> Vad {
+    Vad::new(&vad::Config::default(), RATE)
+}
+
+fn vox(config: &Config) -> Vox<PRE_ROLL> {
+    let mut vox = Vox::new(config, vad(), RATE);
+    vox.arm();
+    vox
+}
+
+/// Index of the last sample of each frame where a bare detector starts or
+/// stops reporting speech.
+fn edges(samples: &[i16]) -> Vec<(usize, bool)> {
+    let mut vad = vad();
+    let mut speech = false;
+    let mut edges = Vec::new();
+    for (i, &s) in samples.iter().enumerate() {
+        if let Some(frame) = vad.push(s) {
+            if frame.speech != speech {
+                speech = frame.speech;
+                edges.push((i, speech));
+            }
+        }
+    }
+    edges
+}
+
+#[test]
+fn a_burst_is_recorded_with_its_pre_roll() {
+    let input = render(&[Part::Silence(2000), Part::Speech(700), Part::Silence(3000)]);
+    let config = Config::default();
+    let mut vox = vox(&config);
+    let mut tape = Tape::default();
+    vox.push_block(&input, &mut tape).unwrap();
+
+    let edges = edges(&input);
+    let [(start, true), (end, false)] = edges[..] else {
+        panic!("{edges:?}");
+    };
+    // half a second before the detector was sure, up to the silence timeout
+    // after it wasn't any more
+    let first = start + 1 - 500 * MS;
+    let last = end + 1500 * MS;
+    assert_eq!(tape.events, [Event::Start, Event::Stop]);
+    assert_eq!(tape.recordings[0], input[first..=last]);
+    assert_eq!(vox.state(), State::Armed);
+}
+
+#[test]
+fn short_pauses_stay_in_one_recording() {
+    let input = render(&[
+        Part::Silence(2000),
+        Part::Speech(500),
+        Part::Silence(1000),
+        Part::Speech(500),
+        Part::Silence(3000),
+    ]);
+    let mut vox = vox(&Config::default());
+    let mut tape = Tape::default();
+    let mut states = vec![vox.state()];
+    for &s in &input {
+        vox.push(s, &mut tape).unwrap();
+        if states.last() != Some(&vox.state()) {
+            states.push(vox.state());
+        }
+    }
+    assert_eq!(tape.events, [Event::Start, Event::Stop]);
+    assert_eq!(
+        states,
+        [
+            State::Armed,
+            State::Recording,
+            State::Hangover,
+            State::Recording,
+            State::Hangover,
+            State::Armed,
+        ]
+    );
+}
+
+#[test]
+fn long_pauses_split_recordings() {
+    let input = render(&[
+        Part::Silence(2000),
+        Part::Speech(500),
+        Part::Silence(2200),
+        Part::Speech(500),
+        Part::Silence(3000),
+    ]);
+    let mut vox = vox(&Config::default());
+    let mut tape = Tape::default();
+    vox.push_block(&input, &mut tape).unwrap();
+    assert_eq!(
+        tape.events,
+        [Event::Start, Event::Stop, Event::Start, Event::Stop]
+    );
+
+    // the second recording's pre-roll only goes back to where the first
+    // one stopped
+    let first = &tape.recordings[0];
+    let second = &tape.recordings[1];
+    let start = input
+        .windows(first.len())
+        .position(|w| w == &first[..])
+        .unwrap();
+    let resume = input[start + first.len()..]
+        .windows(second.len())
+        .position(|w| w == &second[..])
+        .unwrap();
+    assert!(resume < 500 * MS);
+}
+
+#[test]
+fn speech_soon_after_arming_has_a_short_pre_roll() {
+    let input = render(&[Part::Silence(1000), Part::Speech(500), Part::Silence(2000)]);
+    let mut vox = Vox::<PRE_ROLL>::new(&Config::default(), vad(), RATE);
+    let mut tape = Tape::default();
+    // the detector learns the noise while idle, then the VOX is armed just
+    // before the speech
+    vox.push_block(&input[..900 * MS], &mut tape).unwrap();
+    assert!(tape.events.is_empty());
+    vox.arm();
+    vox.push_block(&input[900 * MS..], &mut tape).unwrap();
+
+    let start = edges(&input)[0].0;
+    assert_eq!(
+        tape.recordings[0][..start + 1 - 900 * MS],
+        input[900 * MS..=start]
+    );
+}
+
+#[test]
+fn disarming_stops_a_recording() {
+    let input = render(&[Part::Silence(1000), Part::Speech(1000)]);
+    let mut vox = vox(&Config::default());
+    let mut tape = Tape::default();
+    vox.push_block(&input, &mut tape).unwrap();
+    assert_eq!(vox.state(), State::Recording);
+    assert!(vox.is_recording());
+    vox.disarm(&mut tape).unwrap();
+    assert_eq!(tape.events, [Event::Start, Event::Stop]);
+
+    // idle records nothing, and disarming again does nothing
+    vox.push_block(&input, &mut tape).unwrap();
+    vox.disarm(&mut tape).unwrap();
+    assert_eq!(tape.events.len(), 2);
+    assert_eq!(vox.state(), State::Idle);
+}
+
+#[test]
+fn no_pre_roll_starts_at_the_trigger() {
+    let input = render(&[Part::Silence(1000), Part::Speech(500), Part::Silence(1000)]);
+    let config = Config {
+        pre_roll_ms: 0,
+        silence_timeout_ms: 200,
+    };
+    let mut vox = vox(&config);
+    let mut tape = Tape::default();
+    vox.push_block(&input, &mut tape).unwrap();
+    let edges = edges(&input);
+    let (start, end) = (edges[0].0, edges[1].0);
+    assert_eq!(tape.recordings[0], input[start..=end + 200 * MS]);
+}
+
+#[test]
+fn sink_errors_are_returned() {
+    struct Full;
+
+    impl Sink for Full {
+        type Error = &'static str;
+
+        fn start(&mut self) -> Result<(), &'static str> {
+            Err("full")
+        }
+
+        fn write(&mut self, _: &[i16]) -> Result<(), &'static str> {
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<(), &'static str> {
+            Ok(())
+        }
+    }
+
+    let input = render(&[Part::Silence(1000), Part::Speech(500)]);
+    let mut vox = vox(&Config::default());
+    assert_eq!(vox.push_block(&input, &mut Full), Err("full"));
+    // a recording that didn't start isn't in progress
+    assert_eq!(vox.state(), State::Armed);
+    assert!(!vox.is_recording());
+}
+
+#[test]
+#[should_panic]
+fn pre_roll_must_fit() {
+    let config = Config {
+        pre_roll_ms: 600,
+        ..Config::default()
+    };
+    Vox::<PRE_ROLL>::new(&config, vad(), RATE);
+}
@@ -0,0 +1,216 @@
+use std::io::Cursor;
+
+use voice_core::adpcm::{self, Encoder};
+use voice_core::g711::Law;
+use voice_core::store::Codec;
+use voice_core::wav::{BufferFull, Error, SliceSink, WavReader, WavWriter};
+use voice_core::{from_pcm16, to_pcm16};
+
+fn tone(len: usize) -> Vec<u16> {
+    (0..len)
+        .map(|i| (2048.0 + 1500.0 * (i as f64 * 0.05).sin()) as u16)
+        .collect()
+}
+
+fn wav(codec: Codec, samples: &[u16]) -> Vec<u8> {
+    let mut writer = WavWriter::new(Vec::new(), codec, 8000).unwrap();
+    // in uneven pieces
+    for chunk in samples.chunks(77) {
+        writer.write_samples(chunk).unwrap();
+    }
+    assert_eq!(writer.len(), samples.len() as u32);
+    writer.finalize().unwrap()
+}
+
+fn read_all(bytes: &[u8]) -> (WavReader<&[u8]>, Vec<u16>) {
+    let mut reader = WavReader::new(bytes).unwrap();
+    let mut samples = vec![0; reader.len() as usize + 10];
+    let mut n = 0;
+    // in uneven pieces
+    loop {
+        let end = (n + 33).min(samples.len());
+        match reader.read_samples(&mut samples[n..end]).unwrap() {
+            0 => break,
+            got => n += got,
+        }
+    }
+    samples.truncate(n);
+    (reader, samples)
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+#[test]
+fn pcm16_header_is_canonical() {
+    let bytes = wav(Codec::Pcm16, &[0, 2048, 4095]);
+    assert_eq!(bytes.len(), 44 + 6);
+    assert_eq!(&bytes[..4], b"RIFF");
+    assert_eq!(u32_at(&bytes, 4), 42);
+    assert_eq!(&bytes[8..16], b"WAVEfmt ");
+    assert_eq!(u32_at(&bytes, 16), 16);
+    assert_eq!(
+        bytes[20..36],
+        [1, 0, 1, 0, 0x40, 0x1f, 0, 0, 0x80, 0x3e, 0, 0, 2, 0, 16, 0]
+    );
+    assert_eq!(&bytes[36..40], b"data");
+    assert_eq!(u32_at(&bytes, 40), 6);
+    assert_eq!(bytes[44..], [0x00, 0x80, 0, 0, 0xf0, 0x7f]);
+}
+
+#[test]
+fn pcm16_files_read_back_in_hound() {
+    let samples = tone(1000);
+    let bytes = wav(Codec::Pcm16, &samples);
+    let reader = hound::WavReader::new(Cursor::new(&bytes)).unwrap();
+    assert_eq!(reader.spec().sample_rate, 8000);
+    let decoded: Vec<_> = reader
+        .into_samples::<i16>()
+        .map(|s| from_pcm16(s.unwrap()))
+        .collect();
+    assert_eq!(decoded, samples);
+}
+
+#[test]
+fn every_codec_round_trips() {
+    let samples = tone(1234);
+    for codec in [Codec::Pcm16, Codec::MuLaw, Codec::ALaw, Codec::ImaAdpcm] {
+        let bytes = wav(codec, &samples);
+        assert_eq!(u32_at(&bytes, 4) as usize, bytes.len() - 8, "{codec:?}");
+        let (reader, decoded) = read_all(&bytes);
+        assert_eq!(reader.codec(), codec);
+        assert_eq!(reader.sample_rate(), 8000);
+        assert_eq!(decoded.len(), samples.len(), "{codec:?}");
+
+        let expected: Vec<_> = match codec {
+            Codec::Pcm16 => samples.clone(),
+            Codec::MuLaw => samples
+                .iter()
+                .map(|&s| Law::MuLaw.decode(Law::MuLaw.encode(s)))
+                .collect(),
+            Codec::ALaw => samples
+                .iter()
+                .map(|&s| Law::ALaw.decode(Law::ALaw.encode(s)))
+                .collect(),
+            Codec::ImaAdpcm => {
+                let pcm: Vec<_> = samples.iter().map(|&s| to_pcm16(s)).collect();
+                let mut blocks = Vec::new();
+                let mut encoder = Encoder::<{ adpcm::BLOCK_ALIGN }>::new();
+                encoder.encode(&pcm, |block| blocks.push(block.to_vec()));
+                blocks.push(encoder.flush().unwrap().to_vec());
+                let mut decoded = Vec::new();
+                for block in blocks {
+                    let mut out = [0; adpcm::samples_per_block(adpcm::BLOCK_ALIGN)];
+                    let n = adpcm::decode_block(&block, &mut out).unwrap();
+                    decoded.extend(out[..n].iter().map(|&s| from_pcm16(s)));
+                }
+                decoded.truncate(samples.len());
+                decoded
+            }
+        };
+        assert_eq!(decoded, expected, "{codec:?}");
+    }
+}
+
+#[test]
+fn compressed_formats_have_a_fact_chunk() {
+    let bytes = wav(Codec::MuLaw, &tone(101));
+    assert_eq!(u32_at(&bytes, 16), 18);
+    assert_eq!(&bytes[38..42], b"fact");
+    assert_eq!(u32_at(&bytes, 46), 101);
+    assert_eq!(&bytes[50..54], b"data");
+    assert_eq!(u32_at(&bytes, 54), 101);
+    // padded to an even length
+    assert_eq!(bytes.len(), 58 + 102);
+
+    let bytes = wav(Codec::ImaAdpcm, &tone(1200));
+    assert_eq!(u32_at(&bytes, 16), 20);
+    // extra fields: their size, and samples per block
+    assert_eq!(bytes[36..40], [2, 0, 0xf9, 0x01]);
+    // 4055 bytes a second for 8 kHz, as other tools write
+    assert_eq!(u32_at(&bytes, 28), 4055);
+    assert_eq!(u32_at(&bytes, 48), 1200);
+    // two full blocks and one of 190 samples
+    assert_eq!(u32_at(&bytes, 56), 2 * 256 + 4 + 95);
+}
+
+#[test]
+fn encoded_data_passes_through() {
+    let data: Vec<u8> = (0..=255).collect();
+    let mut writer = WavWriter::new(Vec::new(), Codec::ALaw, 16000).unwrap();
+    writer.write_data(&data, 256).unwrap();
+    let bytes = writer.finalize().unwrap();
+
+    let mut reader = WavReader::new(&bytes[..]).unwrap();
+    assert_eq!(reader.len(), 256);
+    let mut read = vec![0; 300];
+    assert_eq!(reader.read_data(&mut read).unwrap(), 256);
+    assert_eq!(read[..256], data);
+}
+
+#[test]
+fn reader_skips_unknown_chunks() {
+    let bytes = wav(Codec::Pcm16, &[100, 200, 300]);
+    let mut patched = bytes[..36].to_vec();
+    // an odd-sized chunk, with its pad byte
+    patched.extend_from_slice(b"LIST");
+    patched.extend_from_slice(&3u32.to_le_bytes());
+    patched.extend_from_slice(&[1, 2, 3, 0]);
+    patched.extend_from_slice(&bytes[36..]);
+    let (_, samples) = read_all(&patched);
+    assert_eq!(samples, [100, 200, 300]);
+}
+
+#[test]
+fn reader_stops_at_the_end_of_a_short_file() {
+    let bytes = wav(Codec::Pcm16, &tone(100));
+    let (_, samples) = read_all(&bytes[..bytes.len() - 21]);
+    assert_eq!(samples, tone(100)[..89]);
+}
+
+#[test]
+fn bad_files_are_rejected() {
+    let bytes = wav(Codec::Pcm16, &[1, 2]);
+    assert!(matches!(
+        WavReader::new(&b"RIFX\0\0\0\0WAVE"[..]),
+        Err(Error::NotWav)
+    ));
+    assert!(matches!(
+        WavReader::new(&bytes[..30]),
+        Err(Error::Truncated)
+    ));
+
+    let mut stereo = bytes.clone();
+    stereo[22] = 2;
+    assert!(matches!(
+        WavReader::new(&stereo[..]),
+        Err(Error::Unsupported)
+    ));
+    let mut float = bytes.clone();
+    float[20] = 3;
+    assert!(matches!(
+        WavReader::new(&float[..]),
+        Err(Error::Unsupported)
+    ));
+
+    let mut bytes = wav(Codec::ImaAdpcm, &[1, 2, 3]);
+    // step index past the table
+    bytes[62] = 89;
+    let mut reader = WavReader::new(&bytes[..]).unwrap();
+    assert_eq!(reader.read_samples(&mut [0; 3]), Err(Error::Corrupt));
+}
+
+#[test]
+fn writer_works_into_a_fixed_buffer() {
+    let mut buf = [0; 64];
+    let mut writer = WavWriter::new(SliceSink::new(&mut buf), Codec::MuLaw, 8000).unwrap();
+    writer.write_samples(&[2048; 5]).unwrap();
+    let sink = writer.finalize().unwrap();
+    assert_eq!(sink.written().len(), 58 + 6);
+    assert_eq!(sink.written()[58..], [0xff, 0xff, 0xff, 0xff, 0xff, 0]);
+
+    let mut buf = [0; 64];
+    let mut writer = WavWriter::new(SliceSink::new(&mut buf), Codec::Pcm16, 8000).unwrap();
+    assert_eq!(writer.write_samples(&[0; 11]), Err(Error::Io(BufferFull)));
+}
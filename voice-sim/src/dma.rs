@@ -0,0 +1,74 @@
+//! Emulation of the DMA channel that drains the ADC FIFO into capture
+//! blocks.
+
+use voice_core::capture::{Block, CaptureDma};
+use voice_core::AudioSource;
+
+use crate::AdcFifo;
+
+/// A DMA channel paced by the ADC: it moves every sample out of the FIFO as
+/// soon as it is converted, as long as it has a block to put it in.
+#[derive(Debug)]
+pub struct FifoDma<'a, const N: usize> {
+    fifo: AdcFifo<'a>,
+    block: Option<Block<N>>,
+    filled: usize,
+    done: bool,
+}
+
+impl<'a, const N: usize> FifoDma<'a, N> {
+    /// Create an idle channel reading from `fifo`.
+    pub fn new(fifo: AdcFifo<'a>) -> Self {
+        Self {
+            fifo,
+            block: None,
+            filled: 0,
+            done: false,
+        }
+    }
+
+    /// The FIFO the channel reads from.
+    pub fn fifo(&self) -> &AdcFifo<'a> {
+        &self.fifo
+    }
+
+    /// Mutable access to the FIFO, to let time pass.
+    pub fn fifo_mut(&mut self) -> &mut AdcFifo<'a> {
+        &mut self.fifo
+    }
+
+    /// Move samples from the FIFO into the block in flight.
+    ///
+    /// Returns whether the block is complete, i.e. whether the completion
+    /// interrupt is pending.
+    pub fn transfer(&mut self) -> bool {
+        if let Some(block) = &mut self.block {
+            while !self.done && self.fifo.available() > 0 {
+                block[self.filled] = self.fifo.read().expect("FIFO has a sample");
+                self.filled += 1;
+                self.done = self.filled == N;
+            }
+        }
+        self.done
+    }
+}
+
+impl<const N: usize> CaptureDma<N> for FifoDma<'_, N> {
+    fn start(&mut self, block: Block<N>) {
+        assert!(self.block.is_none(), "DMA transfer already in flight");
+        self.block = Some(block);
+        self.filled = 0;
+        self.done = false;
+        // the ADC DREQ is still asserted if samples piled up in the meantime
+        self.transfer();
+    }
+
+    fn take_done(&mut self) -> Option<Block<N>> {
+        if self.done {
+            self.done = false;
+            self.block.take()
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Emulation of the RP2040 ADC in free-running mode.
+
+use std::collections::VecDeque;
+
+use voice_core::rate::Divider;
+use voice_core::{adc, AudioSource};
+
+/// Returned when reading from an empty FIFO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Underflow;
+
+impl std::fmt::Display for Underflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("read from empty ADC FIFO")
+    }
+}
+
+impl std::error::Error for Underflow {}
+
+/// An ADC sampling a recorded signal into a FIFO.
+///
+/// Time is counted in 1/256ths of an ADC clock cycle, the resolution of the
+/// clock divider. Conversions happen every [`Divider::period`] and take
+/// the input sample that is current at that instant, so the input does not
+/// have to be recorded at the ADC rate. A conversion that finds the FIFO full
+/// is dropped, like the hardware does when the `OVER` flag gets set.
+#[derive(Debug, Clone)]
+pub struct AdcFifo<'a> {
+    input: &'a [u16],
+    input_rate: u32,
+    period: u32,
+    now: u64,
+    conversions: u64,
+    fifo: VecDeque<u16>,
+    dropped: usize,
+}
+
+impl<'a> AdcFifo<'a> {
+    /// Start sampling `input`, recorded at `input_rate` Hz, with the given
+    /// clock divider.
+    pub fn new(input: &'a [u16], input_rate: u32, divider: Divider) -> Self {
+        let mut fifo = Self {
+            input,
+            input_rate,
+            period: divider.period(),
+            now: 0,
+            conversions: 0,
+            fifo: VecDeque::with_capacity(adc::FIFO_DEPTH),
+            dropped: 0,
+        };
+        // the first conversion happens as soon as the ADC is started
+        fifo.advance_to(0);
+        fifo
+    }
+
+    /// Time between conversions, in 1/256ths of an ADC clock cycle.
+    pub fn period(&self) -> u32 {
+        self.period
+    }
+
+    /// Current time, in 1/256ths of an ADC clock cycle.
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Number of conversions made so far.
+    pub fn conversions(&self) -> usize {
+        self.conversions as usize
+    }
+
+    /// Number of conversions lost because the FIFO was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Index of the input sample converted at time `t`, if the input lasts
+    /// that long.
+    fn input_index(&self, t: u64) -> Option<usize> {
+        let ticks_per_second = u128::from(adc::CLOCK_HZ) * 256;
+        let index = u128::from(t) * u128::from(self.input_rate) / ticks_per_second;
+        usize::try_from(index)
+            .ok()
+            .filter(|&i| i < self.input.len())
+    }
+
+    /// Time of the next conversion, unless the input has been fully
+    /// converted.
+    pub fn next_conversion(&self) -> Option<u64> {
+        let t = self.conversions * u64::from(self.period);
+        self.input_index(t).map(|_| t)
+    }
+
+    /// Whether the input has been fully converted.
+    pub fn input_exhausted(&self) -> bool {
+        self.next_conversion().is_none()
+    }
+
+    /// Whether the input has been fully converted and read.
+    pub fn is_finished(&self) -> bool {
+        self.input_exhausted() && self.fifo.is_empty()
+    }
+
+    /// Let `ticks` 1/256ths of an ADC clock cycle pass, running every
+    /// conversion that falls into that time.
+    pub fn advance(&mut self, ticks: u64) {
+        self.advance_to(self.now + ticks);
+    }
+
+    /// Let time pass up to `t`, running every conversion until then.
+    pub fn advance_to(&mut self, t: u64) {
+        self.now = self.now.max(t);
+        while let Some(t) = self.next_conversion() {
+            if t > self.now {
+                break;
+            }
+            let index = self.input_index(t).unwrap();
+            if self.fifo.len() < adc::FIFO_DEPTH {
+                self.fifo.push_back(self.input[index]);
+            } else {
+                self.dropped += 1;
+            }
+            self.conversions += 1;
+        }
+    }
+}
+
+impl AudioSource for AdcFifo<'_> {
+    type Error = Underflow;
+
+    fn available(&mut self) -> usize {
+        self.fifo.len()
+    }
+
+    fn read(&mut self) -> Result<u16, Underflow> {
+        self.fifo.pop_front().ok_or(Underflow)
+    }
+}
@@ -0,0 +1,169 @@
+//! Emulation of the Pico's QSPI NOR flash.
+
+use voice_core::flash::{self, NorFlash, ERASED};
+
+/// In-memory NOR flash with the geometry of the W25Q16 on the Pico: 256 byte
+/// pages and 4 KiB sectors.
+///
+/// Like the real part, erasing sets whole sectors to `0xff` and programming
+/// can only clear bits. Operations that break the granularity rules fail
+/// instead of doing something the hardware would not.
+///
+/// Power can be cut in the middle of an operation with
+/// [`cut_power_after`](Self::cut_power_after), to check that what is on the
+/// flash afterwards can be recovered.
+#[derive(Debug, Clone)]
+pub struct MemFlash {
+    data: Vec<u8>,
+    erases: Vec<u32>,
+    programs: usize,
+    operations: usize,
+    // operations left before the power is cut, and whether it has been
+    power: Option<usize>,
+    cut: bool,
+}
+
+/// Errors from [`MemFlash`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The operation broke the flash's rules.
+    Flash(flash::Error),
+    /// The power was cut during or before the operation.
+    PowerCut,
+}
+
+impl From<flash::Error> for Error {
+    fn from(e: flash::Error) -> Self {
+        Error::Flash(e)
+    }
+}
+
+impl MemFlash {
+    /// Flash of `capacity` bytes, fully erased.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is not a whole number of sectors.
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity.is_multiple_of(Self::ERASE_SIZE),
+            "capacity must be whole sectors"
+        );
+        Self {
+            data: vec![ERASED; capacity],
+            erases: vec![0; capacity / Self::ERASE_SIZE],
+            programs: 0,
+            operations: 0,
+            power: None,
+            cut: false,
+        }
+    }
+
+    /// The whole contents.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Number of times each sector was erased.
+    pub fn erase_counts(&self) -> &[u32] {
+        &self.erases
+    }
+
+    /// Number of page programs so far.
+    pub fn programs(&self) -> usize {
+        self.programs
+    }
+
+    /// Number of erase and program operations so far, each counting once
+    /// however many sectors or pages it covers.
+    pub fn operations(&self) -> usize {
+        self.operations
+    }
+
+    /// Let `ops` more erase or program operations complete, then cut the
+    /// power in the middle of the next one.
+    ///
+    /// The interrupted operation gets halfway: a program clears the bits of
+    /// the first half of its bytes, an erase erases the first half of its
+    /// range. It and everything after it fail with [`Error::PowerCut`] until
+    /// [`power_on`](Self::power_on).
+    pub fn cut_power_after(&mut self, ops: usize) {
+        self.power = Some(ops);
+    }
+
+    /// Restore the power.
+    pub fn power_on(&mut self) {
+        self.power = None;
+        self.cut = false;
+    }
+
+    /// Whether the power has been cut.
+    pub fn is_cut(&self) -> bool {
+        self.cut
+    }
+
+    /// Start an operation, returning whether the power goes in the middle
+    /// of it.
+    fn operate(&mut self) -> Result<bool, Error> {
+        if self.cut {
+            return Err(Error::PowerCut);
+        }
+        self.operations += 1;
+        match &mut self.power {
+            Some(0) => self.cut = true,
+            Some(left) => *left -= 1,
+            None => {}
+        }
+        Ok(self.cut)
+    }
+}
+
+impl NorFlash for MemFlash {
+    type Error = Error;
+    const WRITE_SIZE: usize = 256;
+    const ERASE_SIZE: usize = 4096;
+
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Error> {
+        flash::check_read(self.capacity(), offset, bytes.len())?;
+        if self.cut {
+            return Err(Error::PowerCut);
+        }
+        let offset = offset as usize;
+        bytes.copy_from_slice(&self.data[offset..offset + bytes.len()]);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Error> {
+        flash::check_write::<Self>(self.capacity(), offset, bytes.len())?;
+        let cut = self.operate()?;
+        let len = if cut { bytes.len() / 2 } else { bytes.len() };
+        let offset = offset as usize;
+        for (cell, &byte) in self.data[offset..offset + len].iter_mut().zip(bytes) {
+            *cell &= byte;
+        }
+        self.programs += bytes.len() / Self::WRITE_SIZE;
+        if cut {
+            return Err(Error::PowerCut);
+        }
+        Ok(())
+    }
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Error> {
+        flash::check_erase::<Self>(self.capacity(), from, to)?;
+        let cut = self.operate()?;
+        let (from, to) = (from as usize, to as usize);
+        let end = if cut { from + (to - from) / 2 } else { to };
+        self.data[from..end].fill(ERASED);
+        for count in &mut self.erases[from / Self::ERASE_SIZE..to / Self::ERASE_SIZE] {
+            *count += 1;
+        }
+        if cut {
+            return Err(Error::PowerCut);
+        }
+        Ok(())
+    }
+}
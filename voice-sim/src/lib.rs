@@ -0,0 +1,323 @@
+//! # voice-sim
+//!
+//! Runs the firmware's signal chain on a PC. A recorded signal is sampled by
+//! an emulated ADC FIFO at the firmware's oversampled clock divider rate,
+//! captured into blocks by an emulated DMA channel through the same
+//! [`DmaCapture`](voice_core::capture::DmaCapture) hand-off the firmware
+//! uses, decimated and fed through the same [`voice_core::Pipeline`], and
+//! played back through the same [`DmaPlayback`] hand-off by an emulated DMA
+//! channel paced by the pacer slice. Every compare value written to the PWM
+//! carrier is recorded with its time, so the output can be written out as
+//! WAV, in any of the clip codecs, or CSV.
+//!
+//! [`play`] runs clip playback the same way: the recording is taken as a
+//! clip and played through a [`Player`] at the configured speed, resampled
+//! to the sample rate if it was recorded at another, into the same playback
+//! hand-off.
+//!
+//! The [`flash`] module emulates the flash chip the clip store lives on.
+
+use std::io::{self, Write};
+use std::path::Path;
+
+use voice_core::capture::{DmaCapture, BLOCK_LEN};
+use voice_core::pipeline::{self, Pipeline, WINDOW_CAPACITY};
+use voice_core::playback::DmaPlayback;
+use voice_core::player::Player;
+use voice_core::store::Codec;
+use voice_core::stretch;
+use voice_core::wav::WavWriter;
+use voice_core::{adc, pwm, AudioSource, SampleRate};
+
+pub mod dma;
+pub mod fifo;
+pub mod flash;
+pub mod playback;
+
+pub use dma::FifoDma;
+pub use fifo::AdcFifo;
+pub use flash::MemFlash;
+pub use playback::{Output, PacedDma};
+
+/// Settings for a simulation run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Rate the ADC samples at.
+    pub sample_rate: SampleRate,
+    /// Settings for the pipeline.
+    pub pipeline: pipeline::Config,
+    /// Speed clips are played at, and how.
+    pub stretch: stretch::Config,
+    /// How long one iteration of the firmware main loop takes, in ns.
+    pub loop_time_ns: u32,
+}
+
+impl Default for Config {
+    /// The configuration the firmware runs with.
+    fn default() -> Self {
+        Self {
+            sample_rate: adc::SAMPLE_RATE,
+            pipeline: pipeline::Config::default(),
+            stretch: stretch::Config::default(),
+            // roughly what averaging 100 samples costs at 125 MHz
+            loop_time_ns: 10_000,
+        }
+    }
+}
+
+/// One iteration of the main loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    /// When the iteration started, in 1/256ths of an ADC clock cycle.
+    pub time: u64,
+    /// Samples taken from the capture blocks, [`OVERSAMPLE`](adc::OVERSAMPLE)
+    /// for each one written to playback, or from the clip when playing one.
+    pub samples_read: usize,
+}
+
+/// Everything recorded during a simulation run.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    /// Every main loop iteration, in order.
+    pub steps: Vec<Step>,
+    /// Every compare value written to the carrier, in order.
+    pub output: Vec<Output>,
+    /// Number of conversions the ADC made, [`OVERSAMPLE`](adc::OVERSAMPLE)
+    /// per sample; none when playing a clip.
+    pub conversions: usize,
+    /// Conversions lost because the FIFO was full.
+    pub dropped: usize,
+    /// Times DMA ran out of blocks because the main loop fell behind.
+    pub stalls: u32,
+    /// Times playback ran out of queued blocks, including at the end.
+    pub underruns: u32,
+    /// Samples playback dropped because every block was full.
+    pub overruns: u32,
+    /// Rate the pipeline ran at.
+    pub sample_rate: SampleRate,
+    /// Time between conversions, in 1/256ths of an ADC clock cycle, or
+    /// between samples at the sample rate when playing a clip.
+    pub period: u32,
+    /// Setup of the carrier slice.
+    pub carrier: pwm::Slice,
+    /// Setup of the slice pacing playback.
+    pub pacer: pwm::Slice,
+}
+
+/// Convert a time in 1/256ths of an ADC clock cycle to nanoseconds.
+pub fn ticks_to_ns(ticks: u64) -> u64 {
+    (u128::from(ticks) * 1_000_000_000 / (u128::from(adc::CLOCK_HZ) * 256)) as u64
+}
+
+/// Convert a time in nanoseconds to 1/256ths of an ADC clock cycle.
+pub fn ns_to_ticks(ns: u64) -> u64 {
+    (u128::from(ns) * u128::from(adc::CLOCK_HZ) * 256 / 1_000_000_000) as u64
+}
+
+/// Firmware capture, with DMA draining the emulated FIFO.
+type Capture<'a> = DmaCapture<FifoDma<'a, BLOCK_LEN>, BLOCK_LEN>;
+
+/// Firmware playback, with DMA paced by the pacer slice.
+type Playback = DmaPlayback<PacedDma<BLOCK_LEN>, BLOCK_LEN>;
+
+/// Stretcher length clips are played with: grains of 32 ms at 16 kHz.
+const STRETCH_LEN: usize = 2048;
+
+/// Resampler taps and phases clips are played with: enough to go between
+/// any two of the sample rates, or 44.1 kHz, and keep 3.4 kHz flat.
+const RESAMPLE_TAPS: usize = 64;
+const RESAMPLE_PHASES: usize = 441;
+
+/// Firmware playback on the carrier slice, paced at `rate`.
+fn playback(rate: SampleRate) -> Playback {
+    DmaPlayback::new(
+        PacedDma::new(rate.pacer(), pwm::SYS_CLOCK_HZ),
+        pwm::Slice::CARRIER.top,
+        [leak_block(), leak_block(), leak_block()],
+    )
+}
+
+/// Let time pass up to `t`, running conversions and pacer wraps in order and
+/// the DMA completion interrupt whenever one of them completes a block.
+fn advance_to(capture: &mut Capture<'_>, playback: &mut Playback, t: u64) {
+    loop {
+        let conversion = capture.dma().fifo().next_conversion().filter(|&c| c <= t);
+        let wrap = Some(playback.dma().next_wrap()).filter(|&w| w <= t);
+        match (conversion, wrap) {
+            (Some(c), w) if w.is_none_or(|w| c <= w) => {
+                capture.dma().fifo_mut().advance_to(c);
+                if capture.dma().transfer() {
+                    capture.on_complete();
+                }
+            }
+            (_, Some(_)) => {
+                if playback.dma().wrap() {
+                    playback.on_complete();
+                }
+            }
+            _ => break,
+        }
+    }
+    capture.dma().fifo_mut().advance_to(t);
+}
+
+/// Blocks that live until the process exits.
+///
+/// On the device the blocks are statics; leaking them is fine for the
+/// handful of runs a test or the CLI makes.
+fn leak_block() -> &'static mut [u16; BLOCK_LEN] {
+    Box::leak(Box::new([0; BLOCK_LEN]))
+}
+
+/// Run the firmware pipeline over `input`, a signal recorded at
+/// `input_rate` Hz, until every complete block the ADC captured has been
+/// consumed and everything queued for playback has been played.
+///
+/// # Panics
+///
+/// Panics if `config.pipeline.window_len` does not fit the firmware's window
+/// buffer.
+pub fn run(input: &[u16], input_rate: u32, config: &Config) -> Trace {
+    let divider = config.sample_rate.oversampled_divider();
+    let mut fifo = FifoDma::new(AdcFifo::new(input, input_rate, divider));
+    fifo.transfer();
+    let mut capture = DmaCapture::new(fifo, leak_block(), leak_block());
+    let mut playback = playback(config.sample_rate);
+    let mut pipeline: Pipeline<WINDOW_CAPACITY> =
+        Pipeline::new(&config.pipeline, config.sample_rate);
+    let loop_ticks = ns_to_ticks(config.loop_time_ns.into()).max(1);
+
+    let mut steps = Vec::new();
+    loop {
+        let time = capture.dma().fifo().now();
+        let samples_read = pipeline
+            .step(&mut capture, &mut playback)
+            .expect("the pipeline only reads captured samples");
+        steps.push(Step { time, samples_read });
+        if capture.dma().fifo().input_exhausted()
+            && capture.available() == 0
+            && !playback.is_playing()
+        {
+            break;
+        }
+        advance_to(&mut capture, &mut playback, time + loop_ticks);
+    }
+
+    let stalls = capture.stalls();
+    let fifo = capture.dma().fifo();
+    Trace {
+        steps,
+        output: playback.dma().take_output(),
+        conversions: fifo.conversions(),
+        dropped: fifo.dropped(),
+        stalls,
+        underruns: playback.underruns(),
+        overruns: playback.overruns(),
+        sample_rate: config.sample_rate,
+        period: fifo.period(),
+        carrier: pwm::Slice::CARRIER,
+        pacer: config.sample_rate.pacer(),
+    }
+}
+
+/// A clip whose samples can all be read at once, as they can from the
+/// store.
+struct Clip<'a>(&'a [u16]);
+
+impl AudioSource for Clip<'_> {
+    type Error = core::convert::Infallible;
+
+    fn available(&mut self) -> usize {
+        self.0.len()
+    }
+
+    fn read(&mut self) -> Result<u16, Self::Error> {
+        let (&sample, rest) = self.0.split_first().expect("read past the end of the clip");
+        self.0 = rest;
+        Ok(sample)
+    }
+}
+
+/// Play `clip`, recorded at `clip_rate` Hz, at the sample rate and
+/// `config.stretch`'s speed, until all of it has been played.
+///
+/// Each iteration of the main loop writes as much as the playback blocks
+/// have room for, so nothing is dropped.
+///
+/// # Panics
+///
+/// Panics if going from `clip_rate` to the sample rate needs more than 441
+/// phases.
+pub fn play(clip: &[u16], clip_rate: u32, config: &Config) -> Trace {
+    let mut playback = playback(config.sample_rate);
+    let mut player: Player<STRETCH_LEN, RESAMPLE_TAPS, RESAMPLE_PHASES> =
+        Player::new(&config.stretch, clip_rate, config.sample_rate.hz());
+    let mut clip = Clip(clip);
+    let loop_ticks = ns_to_ticks(config.loop_time_ns.into()).max(1);
+
+    let mut steps = Vec::new();
+    let mut time = 0;
+    loop {
+        let room = playback.space();
+        let samples_read = player
+            .step(&mut clip, &mut playback, room)
+            .expect("playback takes what it has room for");
+        steps.push(Step { time, samples_read });
+        if player.is_finished() && !playback.is_playing() {
+            break;
+        }
+        time += loop_ticks;
+        while playback.dma().next_wrap() <= time {
+            if playback.dma().wrap() {
+                playback.on_complete();
+            }
+        }
+    }
+
+    Trace {
+        steps,
+        output: playback.dma().take_output(),
+        conversions: 0,
+        dropped: 0,
+        stalls: 0,
+        underruns: playback.underruns(),
+        overruns: playback.overruns(),
+        sample_rate: config.sample_rate,
+        period: config.sample_rate.divider().period(),
+        carrier: pwm::Slice::CARRIER,
+        pacer: config.sample_rate.pacer(),
+    }
+}
+
+impl Trace {
+    /// The played samples, recovered from the compare values.
+    pub fn output_samples(&self) -> Vec<u16> {
+        self.output
+            .iter()
+            .map(|o| pwm::unscale(o.duty, self.carrier.top))
+            .collect()
+    }
+
+    /// Write one CSV row per compare value written to the carrier.
+    pub fn write_csv<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "time_ns,duty")?;
+        for output in &self.output {
+            writeln!(w, "{},{}", ticks_to_ns(output.time), output.duty)?;
+        }
+        Ok(())
+    }
+
+    /// The [played samples](Trace::output_samples) as a WAV file of
+    /// `codec` data at the sample rate.
+    pub fn to_wav(&self, codec: Codec) -> Vec<u8> {
+        let mut writer = WavWriter::new(Vec::new(), codec, self.sample_rate.hz()).unwrap();
+        writer.write_samples(&self.output_samples()).unwrap();
+        writer.finalize().unwrap()
+    }
+
+    /// Write the [played samples](Trace::output_samples) as a WAV file of
+    /// `codec` data at the sample rate.
+    pub fn write_wav<P: AsRef<Path>>(&self, path: P, codec: Codec) -> io::Result<()> {
+        std::fs::write(path, self.to_wav(codec))
+    }
+}
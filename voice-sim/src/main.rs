@@ -0,0 +1,194 @@
+//! Command line front end for the simulator.
+//!
+//! ```text
+//! voice-sim <input.wav> [--wav <output.wav>] [--codec <codec>]
+//!           [--csv <output.csv>] [--rate <hz>] [--loop-ns <ns>]
+//!           [--window <len>] [--denoise] [--effect <effect>]
+//!           [--play] [--speed <percent>] [--stretch wsola|resample]
+//! ```
+//!
+//! The output WAV is 16-bit PCM unless `--codec` picks `ulaw`, `alaw` or
+//! `ima`. `--denoise` switches the pipeline's noise suppressor on, and
+//! `--effect` runs one of its voice changers: `pitch`, `ring`, `robot` or
+//! `crush`, or `off`.
+//!
+//! `--play` plays the input as a recorded clip instead of capturing it,
+//! at `--speed` percent of normal, from 50 to 200. `--stretch` picks how:
+//! `wsola` keeps the pitch, `resample` plays it like a tape. A clip
+//! recorded at another rate than `--rate` is resampled to it.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::process::ExitCode;
+
+use voice_core::effects::Effect;
+use voice_core::host::WavSource;
+use voice_core::store::Codec;
+use voice_core::stretch::Mode;
+use voice_core::SampleRate;
+use voice_sim::Config;
+
+const USAGE: &str = "usage: voice-sim <input.wav> [--wav <output.wav>] [--codec pcm16|ulaw|alaw|ima] [--csv <output.csv>] [--rate <hz>] [--loop-ns <ns>] [--window <len>] [--denoise] [--effect off|pitch|ring|robot|crush] [--play] [--speed <percent>] [--stretch wsola|resample]";
+
+struct Args {
+    input: String,
+    wav: Option<String>,
+    codec: Codec,
+    csv: Option<String>,
+    play: bool,
+    config: Config,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let mut input = None;
+    let mut wav = None;
+    let mut codec = Codec::Pcm16;
+    let mut csv = None;
+    let mut play = false;
+    let mut config = Config::default();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--wav" => wav = Some(value()?),
+            "--codec" => {
+                codec = match value()?.as_str() {
+                    "pcm16" => Codec::Pcm16,
+                    "ulaw" => Codec::MuLaw,
+                    "alaw" => Codec::ALaw,
+                    "ima" => Codec::ImaAdpcm,
+                    _ => return Err("--codec must be one of pcm16, ulaw, alaw, ima".into()),
+                }
+            }
+            "--csv" => csv = Some(value()?),
+            "--rate" => {
+                let hz = value()?.parse().map_err(|e| format!("--rate: {e}"))?;
+                config.sample_rate = SampleRate::from_hz(hz).ok_or_else(|| {
+                    let rates: Vec<_> =
+                        SampleRate::ALL.iter().map(|r| r.hz().to_string()).collect();
+                    format!("--rate must be one of {}", rates.join(", "))
+                })?
+            }
+            "--loop-ns" => {
+                config.loop_time_ns = value()?.parse().map_err(|e| format!("--loop-ns: {e}"))?
+            }
+            "--window" => {
+                config.pipeline.window_len =
+                    value()?.parse().map_err(|e| format!("--window: {e}"))?
+            }
+            "--denoise" => config.pipeline.suppress_noise = true,
+            "--effect" => {
+                config.pipeline.effect = match value()?.as_str() {
+                    "off" => Effect::Off,
+                    "pitch" => Effect::PitchShift,
+                    "ring" => Effect::RingModulator,
+                    "robot" => Effect::Robot,
+                    "crush" => Effect::Bitcrusher,
+                    _ => {
+                        return Err("--effect must be one of off, pitch, ring, robot, crush".into())
+                    }
+                }
+            }
+            "--play" => play = true,
+            "--speed" => {
+                config.stretch.speed_percent =
+                    value()?.parse().map_err(|e| format!("--speed: {e}"))?
+            }
+            "--stretch" => {
+                config.stretch.mode = match value()?.as_str() {
+                    "wsola" => Mode::Wsola,
+                    "resample" => Mode::Resample,
+                    _ => return Err("--stretch must be one of wsola, resample".into()),
+                }
+            }
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(arg),
+            _ => return Err(format!("unexpected argument {arg}\n{USAGE}")),
+        }
+    }
+    let input = input.ok_or(USAGE)?;
+    let window_len = config.pipeline.window_len;
+    if window_len == 0 || window_len > voice_core::pipeline::WINDOW_CAPACITY {
+        return Err(format!(
+            "--window must be between 1 and {}",
+            voice_core::pipeline::WINDOW_CAPACITY
+        ));
+    }
+    if !(50..=200).contains(&config.stretch.speed_percent) {
+        return Err("--speed must be between 50 and 200".into());
+    }
+    Ok(Args {
+        input,
+        wav,
+        codec,
+        csv,
+        play,
+        config,
+    })
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match simulate(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("voice-sim: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn simulate(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let source = WavSource::open(&args.input)?;
+    if args.play {
+        let trace = voice_sim::play(source.samples(), source.sample_rate(), &args.config);
+        eprintln!(
+            "{} clip samples at {} Hz played as {} at {} Hz, {} underruns, {} overruns",
+            source.samples().len(),
+            source.sample_rate(),
+            trace.output.len(),
+            trace.sample_rate.hz(),
+            trace.underruns,
+            trace.overruns
+        );
+        return write(&trace, args);
+    }
+    let trace = voice_sim::run(source.samples(), source.sample_rate(), &args.config);
+
+    eprintln!(
+        "{} conversions at {} Hz, {} loop iterations, {} dropped, {} DMA stalls",
+        trace.conversions,
+        trace.sample_rate.hz(),
+        trace.steps.len(),
+        trace.dropped,
+        trace.stalls
+    );
+    eprintln!(
+        "{} samples played at {} mHz ({:+} ppm), {} underruns, {} overruns",
+        trace.output.len(),
+        trace.pacer.rate_millihz(voice_core::pwm::SYS_CLOCK_HZ),
+        trace
+            .pacer
+            .error_ppm(voice_core::pwm::SYS_CLOCK_HZ, trace.sample_rate.hz()),
+        trace.underruns,
+        trace.overruns
+    );
+    write(&trace, args)
+}
+
+/// Write `trace` out wherever `args` asks.
+fn write(trace: &voice_sim::Trace, args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(path) = &args.wav {
+        trace.write_wav(path, args.codec)?;
+    }
+    if let Some(path) = &args.csv {
+        trace.write_csv(BufWriter::new(File::create(path)?))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,104 @@
+//! Emulation of the DMA channel that feeds the PWM carrier, paced by the
+//! wraps of the pacer slice.
+
+use voice_core::capture::Block;
+use voice_core::playback::PlaybackDma;
+use voice_core::{adc, pwm};
+
+/// A compare value written to the carrier slice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Output {
+    /// When the pacer wrapped, in 1/256ths of an ADC clock cycle.
+    pub time: u64,
+    /// Compare value DMA wrote.
+    pub duty: u16,
+}
+
+/// A DMA channel paced by the pacer slice: at every wrap it writes the next
+/// value of the block in flight to the carrier, if it has one.
+///
+/// The pacer runs from the system clock and the rest of the simulation from
+/// the ADC clock, so wrap times are converted to ADC clock ticks, rounded
+/// down.
+#[derive(Debug)]
+pub struct PacedDma<const N: usize> {
+    pacer: pwm::Slice,
+    sys_clock_hz: u32,
+    wraps: u64,
+    block: Option<Block<N>>,
+    played: usize,
+    done: bool,
+    output: Vec<Output>,
+}
+
+impl<const N: usize> PacedDma<N> {
+    /// Create an idle channel paced by `pacer`, running from a
+    /// `sys_clock_hz` system clock.
+    pub fn new(pacer: pwm::Slice, sys_clock_hz: u32) -> Self {
+        Self {
+            pacer,
+            sys_clock_hz,
+            wraps: 0,
+            block: None,
+            played: 0,
+            done: false,
+            output: Vec::new(),
+        }
+    }
+
+    /// Time of the next pacer wrap, in 1/256ths of an ADC clock cycle.
+    pub fn next_wrap(&self) -> u64 {
+        // the pacer period is in 1/16ths of a system clock cycle
+        let sixteenths = u128::from(self.wraps + 1) * u128::from(self.pacer.period());
+        let ticks = sixteenths * u128::from(adc::CLOCK_HZ) * 256;
+        (ticks / (u128::from(self.sys_clock_hz) * 16)) as u64
+    }
+
+    /// Run the next pacer wrap, writing a value if a block is in flight.
+    ///
+    /// Returns whether the block is complete, i.e. whether the completion
+    /// interrupt is pending.
+    pub fn wrap(&mut self) -> bool {
+        let time = self.next_wrap();
+        self.wraps += 1;
+        if let Some(block) = &self.block {
+            if !self.done {
+                self.output.push(Output {
+                    time,
+                    duty: block[self.played],
+                });
+                self.played += 1;
+                self.done = self.played == N;
+            }
+        }
+        self.done
+    }
+
+    /// Every value written to the carrier so far.
+    pub fn output(&self) -> &[Output] {
+        &self.output
+    }
+
+    /// Take the values written so far.
+    pub fn take_output(&mut self) -> Vec<Output> {
+        std::mem::take(&mut self.output)
+    }
+}
+
+impl<const N: usize> PlaybackDma<N> for PacedDma<N> {
+    fn start(&mut self, block: Block<N>) {
+        assert!(self.block.is_none(), "DMA transfer already in flight");
+        self.block = Some(block);
+        self.played = 0;
+        self.done = false;
+    }
+
+    fn take_done(&mut self) -> Option<Block<N>> {
+        if self.done {
+            self.done = false;
+            self.block.take()
+        } else {
+            None
+        }
+    }
+}
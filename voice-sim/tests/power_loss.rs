@@ -0,0 +1,173 @@
+//! Power cuts at every erase and program step of recording, checking that a
+//! remount always gets back everything that was finished and a prefix of
+//! what was not.
+
+use voice_core::journal::PAYLOAD;
+use voice_core::store::{Codec, Error, Store};
+use voice_core::SampleRate;
+use voice_sim::{flash, MemFlash};
+
+const BASE: u32 = 1024 * 1024;
+const LEN: u32 = 64 * 1024;
+
+type Result<T> = core::result::Result<T, Error<flash::Error>>;
+
+fn pattern(seed: u8, len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i as u8).wrapping_mul(7) ^ seed).collect()
+}
+
+/// What the scenario got done before the power went.
+#[derive(Debug, Default)]
+struct Progress {
+    // seed and length of the clips that were finished
+    finished: Vec<(u8, usize)>,
+    deleted: Vec<u8>,
+    // seed of the clip being recorded, and how much of it had been written
+    recording: Option<(u8, usize)>,
+}
+
+fn record(
+    store: &mut Store<&mut MemFlash>,
+    seed: u8,
+    len: usize,
+    progress: &mut Progress,
+) -> Result<u16> {
+    let mut recorder = store.record(SampleRate::Hz16000, Codec::Pcm16, u32::from(seed))?;
+    progress.recording = Some((seed, 0));
+    for chunk in pattern(seed, len).chunks(100) {
+        recorder.write(chunk)?;
+        progress.recording = Some((seed, recorder.len() as usize));
+    }
+    let clip = recorder.finish()?;
+    progress.recording = None;
+    progress.finished.push((seed, len));
+    Ok(clip.id)
+}
+
+/// A store holding two clips, with the chunks of a deleted third one still
+/// programmed past them, where the next recording goes.
+fn prepared() -> MemFlash {
+    let mut flash = MemFlash::new(2 * 1024 * 1024);
+    let mut store = Store::format(&mut flash, BASE, LEN).unwrap();
+    let mut progress = Progress::default();
+    record(&mut store, 1, 600, &mut progress).unwrap();
+    record(&mut store, 2, 300, &mut progress).unwrap();
+    let c = record(&mut store, 3, 9000, &mut progress).unwrap();
+    store.delete(c).unwrap();
+    store.compact().unwrap();
+    store.into_inner();
+    flash
+}
+
+/// Record over the stale chunks, across sector boundaries, and delete a
+/// clip.
+fn scenario(flash: &mut MemFlash, progress: &mut Progress) -> Result<()> {
+    progress.finished = vec![(1, 600), (2, 300)];
+    let mut store = Store::mount(flash, BASE, LEN)?;
+    record(&mut store, 4, 9000, progress)?;
+    store.delete(1)?;
+    progress.deleted.push(2);
+    record(&mut store, 5, 5000, progress)?;
+    Ok(())
+}
+
+/// The clips of a mounted store as seeds and contents.
+fn clips(store: &mut Store<&mut MemFlash>) -> Vec<(u8, Vec<u8>)> {
+    let clips: Vec<_> = store.clips().map(|c| c.unwrap()).collect();
+    clips
+        .iter()
+        .map(|clip| {
+            let mut data = vec![0; clip.len as usize];
+            assert_eq!(store.read(clip, 0, &mut data).unwrap(), data.len());
+            (clip.timestamp as u8, data)
+        })
+        .collect()
+}
+
+fn operations() -> usize {
+    let mut flash = prepared();
+    let before = flash.operations();
+    scenario(&mut flash, &mut Progress::default()).unwrap();
+    flash.operations() - before
+}
+
+#[test]
+fn recording_survives_a_power_cut_at_every_step() {
+    let total = operations();
+    for cut in 0..total {
+        let mut flash = prepared();
+        flash.cut_power_after(cut);
+        let mut progress = Progress::default();
+        assert_eq!(
+            scenario(&mut flash, &mut progress),
+            Err(Error::Flash(flash::Error::PowerCut)),
+            "cut {cut}"
+        );
+        flash.power_on();
+
+        let mut store = Store::mount(&mut flash, BASE, LEN).unwrap();
+        let found = clips(&mut store);
+
+        for (seed, data) in &found {
+            let finished = progress.finished.iter().find(|(s, _)| s == seed);
+            match (finished, progress.recording) {
+                (Some(&(_, len)), _) => {
+                    assert!(!progress.deleted.contains(seed), "cut {cut}");
+                    assert_eq!(*data, pattern(*seed, len), "cut {cut}");
+                }
+                // cut back to its last chunk, or finished just as the power
+                // went
+                (None, Some((recording, written))) if recording == *seed => {
+                    assert!(data.len() >= written / PAYLOAD * PAYLOAD, "cut {cut}");
+                    assert!(
+                        data.len().is_multiple_of(PAYLOAD) || data.len() == written,
+                        "cut {cut}"
+                    );
+                    assert_eq!(*data, pattern(*seed, data.len()), "cut {cut}");
+                }
+                _ => panic!("cut {cut}: unexpected clip {seed}"),
+            }
+        }
+        for &(seed, _) in &progress.finished {
+            // a delete that was in progress may or may not have happened
+            let gone = progress.deleted.contains(&seed) || seed == 2;
+            assert!(gone || found.iter().any(|(s, _)| *s == seed), "cut {cut}");
+        }
+
+        // recovery is stable, and recording carries on after it
+        let id = record(&mut store, 9, 1000, &mut Progress::default()).unwrap();
+        store.into_inner();
+        let mut store = Store::mount(&mut flash, BASE, LEN).unwrap();
+        let mut again = found.clone();
+        again.push((9, pattern(9, 1000)));
+        assert_eq!(clips(&mut store), again, "cut {cut}");
+        assert_eq!(store.clip(id).unwrap().timestamp, 9);
+    }
+}
+
+#[test]
+fn a_cut_while_recovering_is_recovered_from() {
+    let mut flash = MemFlash::new(2 * 1024 * 1024);
+    let mut store = Store::format(&mut flash, BASE, LEN).unwrap();
+    record(&mut store, 1, 600, &mut Progress::default()).unwrap();
+    // cut off before its first chunk, so mounting has to delete it
+    store
+        .record(SampleRate::Hz16000, Codec::Pcm16, 2)
+        .unwrap()
+        .write(&[0; 100])
+        .unwrap();
+    store.into_inner();
+
+    flash.cut_power_after(0);
+    assert!(Store::mount(&mut flash, BASE, LEN).is_err());
+    flash.power_on();
+    let mut store = Store::mount(&mut flash, BASE, LEN).unwrap();
+    assert_eq!(clips(&mut store), [(1, pattern(1, 600))]);
+    store.into_inner();
+    let ops = flash.operations();
+    let mut store = Store::mount(&mut flash, BASE, LEN).unwrap();
+    assert_eq!(clips(&mut store), [(1, pattern(1, 600))]);
+    // once done, recovery leaves the flash alone
+    store.into_inner();
+    assert_eq!(flash.operations(), ops);
+}
@@ -0,0 +1,562 @@
+use std::process::Command;
+
+use voice_core::adc::OVERSAMPLE;
+use voice_core::capture::BLOCK_LEN;
+use voice_core::effects::Effect;
+use voice_core::g711::Law;
+use voice_core::host::{WavSink, WavSource};
+use voice_core::store::Codec;
+use voice_core::stretch::{self, Mode};
+use voice_core::wav::{WavReader, WavWriter};
+use voice_core::{pwm, AudioSink, SampleRate};
+use voice_sim::{ns_to_ticks, AdcFifo, Config};
+
+/// Samples played when the ADC makes `conversions` conversions: every
+/// complete capture block is decimated, and every complete block that makes
+/// is played, but the partial ones at the end are not.
+fn played(conversions: usize) -> usize {
+    conversions / BLOCK_LEN * BLOCK_LEN / OVERSAMPLE as usize / BLOCK_LEN * BLOCK_LEN
+}
+
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("voice-sim-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn constant_input_gives_constant_duty() {
+    let input = vec![2000; 10_000];
+    let trace = voice_sim::run(&input, 16_000, &Config::default());
+
+    assert_eq!(trace.sample_rate, SampleRate::Hz16000);
+    assert_eq!(trace.conversions, input.len() * OVERSAMPLE as usize);
+    assert_eq!(trace.dropped, 0);
+    assert_eq!(trace.output.len(), played(trace.conversions));
+    // the DC blocker takes the level out, leaving mid-rail
+    assert_eq!(trace.output.last().unwrap().duty, 2048);
+    assert_eq!(trace.overruns, 0);
+    assert_eq!(trace.underruns, 1);
+}
+
+#[test]
+fn fast_loop_reads_every_complete_block() {
+    let input: Vec<u16> = (0..5000).map(|n| (n % 4096) as u16).collect();
+    let trace = voice_sim::run(&input, 16_000, &Config::default());
+
+    let read: usize = trace.steps.iter().map(|s| s.samples_read).sum();
+    assert_eq!(read, trace.conversions / BLOCK_LEN * BLOCK_LEN);
+    assert!(trace
+        .steps
+        .iter()
+        .all(|s| s.samples_read == 0 || s.samples_read == BLOCK_LEN));
+    assert_eq!(trace.stalls, 0);
+    assert_eq!(trace.dropped, 0);
+}
+
+#[test]
+fn slow_loop_stalls_capture() {
+    let input = vec![1000; 10_000];
+    let config = Config {
+        // longer than it takes to fill a block
+        loop_time_ns: 40_000_000,
+        ..Config::default()
+    };
+    let trace = voice_sim::run(&input, 16_000, &config);
+
+    assert_eq!(trace.conversions, input.len() * OVERSAMPLE as usize);
+    assert!(trace.stalls > 0);
+    assert!(trace.dropped > 0);
+}
+
+#[test]
+fn loop_slower_than_fifo_but_faster_than_blocks_loses_nothing() {
+    let input = vec![1000; 10_000];
+    let config = Config {
+        // 64 conversions per iteration, more than the FIFO holds
+        loop_time_ns: 1_000_000,
+        ..Config::default()
+    };
+    let trace = voice_sim::run(&input, 16_000, &config);
+
+    assert_eq!(trace.stalls, 0);
+    assert_eq!(trace.dropped, 0);
+}
+
+#[test]
+fn input_is_sampled_at_adc_rate() {
+    let input: Vec<u16> = (0..4000).map(|n| (n % 4096) as u16).collect();
+    let mut fifo = AdcFifo::new(&input, 32_000, SampleRate::Hz8000.divider());
+    // one conversion at start, then one every 125 µs
+    fifo.advance(fifo.period() as u64 * 3);
+    let mut samples = Vec::new();
+    use voice_core::AudioSource;
+    while fifo.available() > 0 {
+        samples.push(fifo.read().unwrap());
+    }
+    assert_eq!(samples, [0, 4, 8, 12]);
+}
+
+#[test]
+fn output_is_paced_at_the_sample_rate() {
+    let input = vec![3000; 4000];
+    let trace = voice_sim::run(&input, 16_000, &Config::default());
+
+    assert_eq!(trace.pacer, SampleRate::Hz16000.pacer());
+    // both clocks divide down to exactly 16 kHz, the ADC's from 64 kHz
+    for pair in trace.output.windows(2) {
+        assert_eq!(
+            pair[1].time - pair[0].time,
+            u64::from(trace.period * OVERSAMPLE)
+        );
+    }
+}
+
+#[test]
+fn playback_starts_two_blocks_after_capture() {
+    let input = vec![3000; 4000];
+    let trace = voice_sim::run(&input, 16_000, &Config::default());
+
+    let first = trace.output[0].time;
+    // the first conversion is at time 0, and two blocks of output take
+    // OVERSAMPLE times as many conversions
+    let conversions = 2 * BLOCK_LEN as u64 * u64::from(OVERSAMPLE);
+    let two_blocks = (conversions - 1) * u64::from(trace.period);
+    assert!(first > two_blocks);
+    assert!(first < two_blocks + ns_to_ticks(100_000));
+}
+
+#[test]
+fn cd_rate_capture_and_playback_stay_in_step() {
+    // one second at 44.1 kHz, where the pacer is 31 ppm faster than the ADC
+    let input: Vec<u16> = (0..44_100).map(|n| (n % 4096) as u16).collect();
+    let config = Config {
+        sample_rate: SampleRate::Hz44100,
+        ..Config::default()
+    };
+    let trace = voice_sim::run(&input, 44_100, &config);
+
+    assert_eq!(trace.stalls, 0);
+    assert_eq!(trace.overruns, 0);
+    assert_eq!(trace.underruns, 1);
+    assert_eq!(trace.output.len(), played(trace.conversions));
+}
+
+#[test]
+fn output_samples_recover_the_pipeline_output() {
+    let input = vec![3000; 4000];
+    let trace = voice_sim::run(&input, 16_000, &Config::default());
+    let samples = trace.output_samples();
+
+    assert_eq!(samples.len(), trace.output.len());
+    assert_eq!(trace.carrier, pwm::Slice::CARRIER);
+    assert_eq!(*samples.last().unwrap(), 2048);
+}
+
+#[test]
+fn csv_has_a_row_per_output_sample() {
+    let input = vec![3000; 4000];
+    let trace = voice_sim::run(&input, 16_000, &Config::default());
+    let mut csv = Vec::new();
+    trace.write_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("time_ns,duty"));
+    assert_eq!(lines.count(), trace.output.len());
+    assert!(csv.ends_with(",2048\n"));
+}
+
+/// `len` samples of white noise, ±128 about mid-rail.
+fn hiss(len: usize, seed: u32) -> Vec<u16> {
+    let mut seed = seed;
+    (0..len)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            2048 + (seed >> 24) as u16 - 128
+        })
+        .collect()
+}
+
+/// Power of `samples` about mid-rail.
+fn power(samples: &[u16]) -> f64 {
+    samples
+        .iter()
+        .map(|&s| (f64::from(s) - 2048.0).powi(2))
+        .sum::<f64>()
+        / samples.len() as f64
+}
+
+#[test]
+fn noise_suppression_takes_steady_noise_down() {
+    // three seconds of hiss, long enough for the noise estimate to settle
+    let input = hiss(48_000, 1);
+    let plain = voice_sim::run(&input, 16_000, &Config::default());
+    let mut config = Config::default();
+    config.pipeline.suppress_noise = true;
+    let quiet = voice_sim::run(&input, 16_000, &config);
+
+    let (plain, quiet) = (plain.output_samples(), quiet.output_samples());
+    assert_eq!(plain.len(), quiet.len());
+    let tail = plain.len() - 8000;
+    // the suppressor takes it down by most of its 15 dB
+    let db = 10.0 * (power(&plain[tail..]) / power(&quiet[tail..])).log10();
+    assert!(db > 10.0, "{db:.1} dB");
+}
+
+#[test]
+fn every_effect_changes_the_output() {
+    // a second of a vowel-ish buzz at 150 Hz
+    let input: Vec<u16> = (0..16_000)
+        .map(|i| {
+            let t = f64::from(i) / 16_000.0;
+            let buzz: f64 = (1..=8)
+                .map(|k| {
+                    (2.0 * std::f64::consts::PI * 150.0 * f64::from(k) * t).sin() / f64::from(k)
+                })
+                .sum();
+            (2048.0 + 600.0 * buzz) as u16
+        })
+        .collect();
+    let plain = voice_sim::run(&input, 16_000, &Config::default()).output_samples();
+    for effect in [
+        Effect::PitchShift,
+        Effect::RingModulator,
+        Effect::Robot,
+        Effect::Bitcrusher,
+    ] {
+        let mut config = Config::default();
+        config.pipeline.effect = effect;
+        let changed = voice_sim::run(&input, 16_000, &config).output_samples();
+        assert_eq!(changed.len(), plain.len(), "{effect:?}");
+        assert_ne!(changed, plain, "{effect:?}");
+        // and something still comes out once it has settled
+        let tail = changed.len() - 4000;
+        assert!(
+            power(&changed[tail..]) > 0.01 * power(&plain[tail..]),
+            "{effect:?}"
+        );
+    }
+}
+
+#[test]
+fn command_line_writes_wav_and_csv() {
+    let dir = temp_dir("cli");
+    let input = dir.join("in.wav");
+    let wav = dir.join("out.wav");
+    let csv = dir.join("out.csv");
+
+    let mut sink = WavSink::create(&input, SampleRate::Hz32000).unwrap();
+    for _ in 0..8000 {
+        sink.write(1234).unwrap();
+    }
+    sink.finalize().unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_voice-sim"))
+        .arg(&input)
+        .arg("--wav")
+        .arg(&wav)
+        .arg("--csv")
+        .arg(&csv)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let output = WavSource::open(&wav).unwrap();
+    assert_eq!(output.sample_rate(), 16_000);
+    // the 16000 conversions decimate to fifteen blocks and a bit
+    assert_eq!(output.samples().len(), 15 * BLOCK_LEN);
+    assert_eq!(*output.samples().last().unwrap(), 2048);
+    assert!(std::fs::read_to_string(&csv)
+        .unwrap()
+        .starts_with("time_ns,"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn command_line_reads_and_writes_compressed_clips() {
+    let dir = temp_dir("codec");
+    let input = dir.join("in.wav");
+    let wav = dir.join("out.wav");
+    let tone: Vec<u16> = (0..4000)
+        .map(|i| (2048.0 + 1000.0 * (i as f64 * 0.02).sin()) as u16)
+        .collect();
+
+    let mut writer = WavWriter::new(Vec::new(), Codec::MuLaw, 32_000).unwrap();
+    writer.write_samples(&tone).unwrap();
+    std::fs::write(&input, writer.finalize().unwrap()).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_voice-sim"))
+        .arg(&input)
+        .arg("--wav")
+        .arg(&wav)
+        .arg("--codec")
+        .arg("ima")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // the input decodes as it would on the device
+    let source = WavSource::open(&input).unwrap();
+    let decoded: Vec<_> = tone
+        .iter()
+        .map(|&s| Law::MuLaw.decode(Law::MuLaw.encode(s)))
+        .collect();
+    assert_eq!(source.samples(), decoded);
+
+    // and the output is the simulated trace, IMA coded
+    let trace = voice_sim::run(source.samples(), source.sample_rate(), &Config::default());
+    let bytes = std::fs::read(&wav).unwrap();
+    assert_eq!(bytes, trace.to_wav(Codec::ImaAdpcm));
+    let mut reader = WavReader::new(&bytes[..]).unwrap();
+    assert_eq!(reader.codec(), Codec::ImaAdpcm);
+    let mut output = vec![0; reader.len() as usize];
+    assert_eq!(reader.read_samples(&mut output).unwrap(), output.len());
+    let expected = trace.output_samples();
+    assert_eq!(output.len(), expected.len());
+    // IMA takes a few samples to catch up with the step at the start
+    let error: f64 = output
+        .iter()
+        .zip(&expected)
+        .map(|(&a, &b)| (f64::from(a) - f64::from(b)).powi(2))
+        .sum();
+    let signal: f64 = expected
+        .iter()
+        .map(|&s| (f64::from(s) - 2048.0).powi(2))
+        .sum();
+    assert!(signal > 100.0 * error, "SNR {:.1}", signal / error);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn command_line_switches_noise_suppression_on() {
+    let dir = temp_dir("denoise");
+    let input = dir.join("in.wav");
+    let wav = dir.join("out.wav");
+    let hiss = hiss(16_000, 7);
+    let mut writer = WavWriter::new(Vec::new(), Codec::Pcm16, 16_000).unwrap();
+    writer.write_samples(&hiss).unwrap();
+    std::fs::write(&input, writer.finalize().unwrap()).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_voice-sim"))
+        .arg(&input)
+        .arg("--wav")
+        .arg(&wav)
+        .arg("--denoise")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let mut config = Config::default();
+    config.pipeline.suppress_noise = true;
+    let source = WavSource::open(&input).unwrap();
+    let trace = voice_sim::run(source.samples(), 16_000, &config);
+    assert_eq!(std::fs::read(&wav).unwrap(), trace.to_wav(Codec::Pcm16));
+    let plain = voice_sim::run(source.samples(), 16_000, &Config::default());
+    assert_ne!(plain.output_samples(), trace.output_samples());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn command_line_selects_an_effect() {
+    let dir = temp_dir("effect");
+    let input = dir.join("in.wav");
+    let wav = dir.join("out.wav");
+    let hiss = hiss(16_000, 3);
+    let mut writer = WavWriter::new(Vec::new(), Codec::Pcm16, 16_000).unwrap();
+    writer.write_samples(&hiss).unwrap();
+    std::fs::write(&input, writer.finalize().unwrap()).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_voice-sim"))
+        .arg(&input)
+        .arg("--wav")
+        .arg(&wav)
+        .arg("--effect")
+        .arg("ring")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let mut config = Config::default();
+    config.pipeline.effect = Effect::RingModulator;
+    let source = WavSource::open(&input).unwrap();
+    let trace = voice_sim::run(source.samples(), 16_000, &config);
+    assert_eq!(std::fs::read(&wav).unwrap(), trace.to_wav(Codec::Pcm16));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_voice-sim"))
+        .arg(&input)
+        .arg("--effect")
+        .arg("chorus")
+        .stderr(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// A 250 Hz tone with a couple of harmonics, at 16 kHz.
+fn hum(len: usize) -> Vec<u16> {
+    (0..len)
+        .map(|i| {
+            let w = 2.0 * std::f64::consts::PI * 250.0 * i as f64 / 16_000.0;
+            (2048.0 + 800.0 * (w.sin() + (2.0 * w).sin() / 3.0)) as u16
+        })
+        .collect()
+}
+
+/// Upward crossings of mid-rail.
+fn crossings(samples: &[u16]) -> usize {
+    samples
+        .windows(2)
+        .filter(|w| w[0] < 2048 && w[1] >= 2048)
+        .count()
+}
+
+#[test]
+fn clips_play_at_normal_speed_untouched() {
+    let clip = hum(16_000);
+    let trace = voice_sim::play(&clip, 16_000, &Config::default());
+
+    assert_eq!(trace.overruns, 0);
+    assert_eq!(trace.underruns, 1);
+    let read: usize = trace.steps.iter().map(|s| s.samples_read).sum();
+    assert_eq!(read, clip.len());
+    let output = trace.output_samples();
+    // all of the clip, less the partial block at the end
+    assert!(output.len() >= clip.len() - BLOCK_LEN);
+    // the tone comes through at its own pitch
+    assert_eq!(
+        crossings(&output[..16_000 - BLOCK_LEN]),
+        crossings(&clip[..16_000 - BLOCK_LEN])
+    );
+}
+
+#[test]
+fn speed_changes_the_duration_and_only_resampling_the_pitch() {
+    let clip = hum(16_000);
+    for (mode, percent) in [
+        (Mode::Wsola, 200),
+        (Mode::Wsola, 50),
+        (Mode::Resample, 200),
+        (Mode::Resample, 50),
+    ] {
+        let config = Config {
+            stretch: stretch::Config {
+                speed_percent: percent,
+                mode,
+            },
+            ..Config::default()
+        };
+        let trace = voice_sim::play(&clip, 16_000, &config);
+        assert_eq!(trace.overruns, 0);
+        let output = trace.output_samples();
+        let expected = clip.len() * 100 / percent as usize;
+        assert!(
+            output.len().abs_diff(expected) < 1024,
+            "{mode:?} at {percent}%: {} samples",
+            output.len()
+        );
+        // a second of output
+        let len = output.len().min(16_000) - 1024;
+        let hz = crossings(&output[..len]) as f64 * 16_000.0 / len as f64;
+        let pitch = match mode {
+            Mode::Wsola => 250.0,
+            Mode::Resample => 2.5 * f64::from(percent),
+        };
+        assert!(
+            (hz - pitch).abs() < 0.05 * pitch,
+            "{mode:?} at {percent}%: {hz:.0} Hz"
+        );
+    }
+}
+
+#[test]
+fn command_line_plays_clips_at_speed() {
+    let dir = temp_dir("play");
+    let input = dir.join("in.wav");
+    let wav = dir.join("out.wav");
+    let clip = hum(16_000);
+    let mut writer = WavWriter::new(Vec::new(), Codec::Pcm16, 16_000).unwrap();
+    writer.write_samples(&clip).unwrap();
+    std::fs::write(&input, writer.finalize().unwrap()).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_voice-sim"))
+        .arg(&input)
+        .arg("--wav")
+        .arg(&wav)
+        .arg("--play")
+        .arg("--speed")
+        .arg("150")
+        .arg("--stretch")
+        .arg("resample")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let config = Config {
+        stretch: stretch::Config {
+            speed_percent: 150,
+            mode: Mode::Resample,
+        },
+        ..Config::default()
+    };
+    let source = WavSource::open(&input).unwrap();
+    let trace = voice_sim::play(source.samples(), 16_000, &config);
+    assert_eq!(std::fs::read(&wav).unwrap(), trace.to_wav(Codec::Pcm16));
+
+    for speed in ["20", "fast"] {
+        let status = Command::new(env!("CARGO_BIN_EXE_voice-sim"))
+            .arg(&input)
+            .arg("--play")
+            .arg("--speed")
+            .arg(speed)
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(!status.success(), "--speed {speed}");
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn command_line_resamples_clips_recorded_at_other_rates() {
+    let dir = temp_dir("resample");
+    let input = dir.join("in.wav");
+    let wav = dir.join("out.wav");
+    // a second of the tone at 44.1 kHz
+    let clip: Vec<u16> = (0..44_100)
+        .map(|i| {
+            let w = 2.0 * std::f64::consts::PI * 250.0 * f64::from(i) / 44_100.0;
+            (2048.0 + 800.0 * (w.sin() + (2.0 * w).sin() / 3.0)) as u16
+        })
+        .collect();
+    let mut writer = WavWriter::new(Vec::new(), Codec::Pcm16, 44_100).unwrap();
+    writer.write_samples(&clip).unwrap();
+    std::fs::write(&input, writer.finalize().unwrap()).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_voice-sim"))
+        .arg(&input)
+        .arg("--wav")
+        .arg(&wav)
+        .arg("--play")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let trace = voice_sim::play(&clip, 44_100, &Config::default());
+    assert_eq!(std::fs::read(&wav).unwrap(), trace.to_wav(Codec::Pcm16));
+    // still a second of the same tone, now at 16 kHz
+    let output = WavSource::open(&wav).unwrap();
+    assert_eq!(output.sample_rate(), 16_000);
+    let output = output.samples();
+    assert!(output.len().abs_diff(16_000) < 1024, "{}", output.len());
+    let len = 15_000;
+    assert_eq!(crossings(&output[..len]), crossings(&hum(len)));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
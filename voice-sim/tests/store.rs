@@ -0,0 +1,350 @@
+use voice_core::flash::{self, NorFlash};
+use voice_core::journal::PAYLOAD;
+use voice_core::store::{Codec, Error, Store, PAGE_SIZE};
+use voice_core::SampleRate;
+use voice_sim::MemFlash;
+
+const BASE: u32 = 1024 * 1024;
+const LEN: u32 = 64 * 1024;
+const SECTOR: u32 = 4096;
+
+/// Clip data that fits in `sectors` sectors.
+fn capacity(sectors: u32) -> u32 {
+    sectors * SECTOR / PAGE_SIZE as u32 * PAYLOAD as u32
+}
+
+fn flash() -> MemFlash {
+    MemFlash::new(2 * 1024 * 1024)
+}
+
+/// Bytes that differ from clip to clip and from page to page.
+fn pattern(seed: u8, len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i as u8).wrapping_mul(7) ^ seed).collect()
+}
+
+fn record(store: &mut Store<&mut MemFlash>, seed: u8, len: usize) -> u16 {
+    let mut recorder = store
+        .record(SampleRate::Hz16000, Codec::Pcm16, 1000 + u32::from(seed))
+        .unwrap();
+    // in odd sized pieces, like a codec would hand them over
+    for chunk in pattern(seed, len).chunks(100) {
+        recorder.write(chunk).unwrap();
+    }
+    recorder.finish().unwrap().id
+}
+
+fn contents(store: &mut Store<&mut MemFlash>, id: u16) -> Vec<u8> {
+    let clip = store.clip(id).unwrap();
+    let mut data = vec![0; clip.len as usize];
+    assert_eq!(store.read(&clip, 0, &mut data).unwrap(), data.len());
+    data
+}
+
+#[test]
+fn flash_only_clears_bits_until_erased() {
+    let mut flash = flash();
+    flash.write(0, &[0x0f; 256]).unwrap();
+    flash.write(0, &[0xf3; 256]).unwrap();
+    let mut byte = [0];
+    flash.read(0, &mut byte).unwrap();
+    assert_eq!(byte, [0x03]);
+    flash.erase(0, SECTOR).unwrap();
+    flash.read(0, &mut byte).unwrap();
+    assert_eq!(byte, [0xff]);
+    assert_eq!(flash.erase_counts()[0], 1);
+}
+
+#[test]
+fn flash_enforces_granularity() {
+    let mut flash = flash();
+    assert_eq!(
+        flash.write(1, &[0; 256]),
+        Err(flash::Error::NotAligned.into())
+    );
+    assert_eq!(
+        flash.write(0, &[0; 100]),
+        Err(flash::Error::NotAligned.into())
+    );
+    assert_eq!(
+        flash.erase(256, SECTOR),
+        Err(flash::Error::NotAligned.into())
+    );
+    assert_eq!(
+        flash.write(2 * 1024 * 1024, &[0; 256]),
+        Err(flash::Error::OutOfBounds.into())
+    );
+    assert_eq!(
+        flash.read(2 * 1024 * 1024 - 1, &mut [0; 2]),
+        Err(flash::Error::OutOfBounds.into())
+    );
+}
+
+#[test]
+fn recorded_clip_reads_back() {
+    let mut flash = flash();
+    let mut store = Store::format(&mut flash, BASE, LEN).unwrap();
+    let id = record(&mut store, 1, 5000);
+
+    let clip = store.clip(id).unwrap();
+    assert_eq!(clip.len, 5000);
+    assert_eq!(clip.start, 2 * SECTOR);
+    assert_eq!(clip.sample_rate, SampleRate::Hz16000);
+    assert_eq!(clip.codec, Codec::Pcm16);
+    assert_eq!(clip.timestamp, 1001);
+    assert_eq!(contents(&mut store, id), pattern(1, 5000));
+
+    let mut tail = [0; 100];
+    assert_eq!(store.read(&clip, 4950, &mut tail).unwrap(), 50);
+    assert_eq!(tail[..50], pattern(1, 5000)[4950..]);
+}
+
+#[test]
+fn clips_start_on_sector_boundaries_and_survive_a_remount() {
+    let mut flash = flash();
+    let mut store = Store::format(&mut flash, BASE, LEN).unwrap();
+    let a = record(&mut store, 1, 300);
+    let b = record(&mut store, 2, 9000);
+    let c = record(&mut store, 3, 0);
+    store.into_inner();
+
+    let mut store = Store::mount(&mut flash, BASE, LEN).unwrap();
+    let clips: Vec<_> = store.clips().map(Result::unwrap).collect();
+    assert_eq!(
+        clips
+            .iter()
+            .map(|c| (c.id, c.start, c.len))
+            .collect::<Vec<_>>(),
+        [
+            (a, 2 * SECTOR, 300),
+            (b, 3 * SECTOR, 9000),
+            (c, 6 * SECTOR, 0)
+        ]
+    );
+    assert_eq!(contents(&mut store, b), pattern(2, 9000));
+    assert_eq!(store.free(), capacity(LEN / SECTOR - 6));
+}
+
+#[test]
+fn sectors_are_erased_only_when_recording_reaches_them() {
+    let mut flash = flash();
+    let first = (BASE / SECTOR) as usize;
+    let store = Store::format(&mut flash, BASE, LEN).unwrap();
+    store.into_inner();
+    // formatting erases the whole region, once
+    let formatted = flash.erase_counts()[first..first + 5].to_vec();
+    assert_eq!(formatted, [1; 5]);
+
+    let mut store = Store::mount(&mut flash, BASE, LEN).unwrap();
+    record(&mut store, 1, 5000);
+    store.into_inner();
+    // then only the two sectors the clip covers
+    let erased: Vec<_> = flash.erase_counts()[first..first + 5]
+        .iter()
+        .zip(&formatted)
+        .map(|(after, before)| after - before)
+        .collect();
+    assert_eq!(erased, [0, 0, 1, 1, 0]);
+}
+
+#[test]
+fn deleted_clips_disappear() {
+    let mut flash = flash();
+    let mut store = Store::format(&mut flash, BASE, LEN).unwrap();
+    let a = record(&mut store, 1, 300);
+    let b = record(&mut store, 2, 300);
+    store.delete(a).unwrap();
+
+    assert_eq!(store.clip(a), Err(Error::NoSuchClip));
+    assert_eq!(store.delete(a), Err(Error::NoSuchClip));
+    store.into_inner();
+    let mut store = Store::mount(&mut flash, BASE, LEN).unwrap();
+    let ids: Vec<_> = store.clips().map(|c| c.unwrap().id).collect();
+    assert_eq!(ids, [b]);
+}
+
+#[test]
+fn compact_moves_clips_over_deleted_ones() {
+    let mut flash = flash();
+    let mut store = Store::format(&mut flash, BASE, LEN).unwrap();
+    let a = record(&mut store, 1, 9000);
+    record(&mut store, 2, 5000);
+    let c = record(&mut store, 3, 300);
+    record(&mut store, 4, 8192);
+    store.delete(a).unwrap();
+    store.delete(c).unwrap();
+    let free = store.free();
+
+    store.compact().unwrap();
+    assert_eq!(store.slots(), 2);
+    assert_eq!(store.free(), free + capacity(4));
+    let clips: Vec<_> = store.clips().map(Result::unwrap).collect();
+    assert_eq!(
+        clips
+            .iter()
+            .map(|c| (c.id, c.start, c.len))
+            .collect::<Vec<_>>(),
+        [(0, 2 * SECTOR, 5000), (1, 4 * SECTOR, 8192)]
+    );
+    assert_eq!(clips[0].timestamp, 1002);
+    assert_eq!(contents(&mut store, 0), pattern(2, 5000));
+    assert_eq!(contents(&mut store, 1), pattern(4, 8192));
+
+    // the compacted index is the one a remount uses, and recording goes on
+    // after the moved clips
+    store.into_inner();
+    let mut store = Store::mount(&mut flash, BASE, LEN).unwrap();
+    assert_eq!(store.clips().count(), 2);
+    let e = record(&mut store, 5, 100);
+    assert_eq!(store.clip(e).unwrap().start, 7 * SECTOR);
+    assert_eq!(contents(&mut store, 1), pattern(4, 8192));
+}
+
+#[test]
+fn compact_twice_alternates_index_sectors() {
+    let mut flash = flash();
+    let mut store = Store::format(&mut flash, BASE, LEN).unwrap();
+    for round in 0..3 {
+        let a = record(&mut store, round, 300);
+        record(&mut store, round + 10, 300);
+        store.delete(a).unwrap();
+        store.compact().unwrap();
+    }
+    store.into_inner();
+    let mut store = Store::mount(&mut flash, BASE, LEN).unwrap();
+    let timestamps: Vec<_> = store.clips().map(|c| c.unwrap().timestamp).collect();
+    assert_eq!(timestamps, [1010, 1011, 1012]);
+}
+
+#[test]
+fn recording_stops_when_the_region_is_full() {
+    let mut flash = flash();
+    let mut store = Store::format(&mut flash, BASE, LEN).unwrap();
+    let mut recorder = store.record(SampleRate::Hz8000, Codec::Pcm16, 0).unwrap();
+    let data = pattern(9, LEN as usize);
+    assert_eq!(recorder.write(&data), Err(Error::Full));
+    let clip = recorder.finish().unwrap();
+    assert_eq!(clip.len, capacity(LEN / SECTOR - 2));
+    assert_eq!(store.free(), 0);
+    assert!(matches!(
+        store.record(SampleRate::Hz8000, Codec::Pcm16, 0),
+        Err(Error::Full)
+    ));
+}
+
+#[test]
+fn index_fills_up_until_compacted() {
+    let mut flash = flash();
+    let mut store = Store::format(&mut flash, BASE, 1024 * 1024).unwrap();
+    for n in 0..store.max_slots() {
+        let id = record(&mut store, n as u8, 0);
+        store.delete(id).unwrap();
+    }
+    assert!(matches!(
+        store.record(SampleRate::Hz8000, Codec::Pcm16, 0),
+        Err(Error::IndexFull)
+    ));
+    store.compact().unwrap();
+    assert_eq!(store.slots(), 0);
+    record(&mut store, 0, 10);
+}
+
+#[test]
+fn unfinished_recording_ends_at_its_last_chunk() {
+    let mut flash = flash();
+    let mut store = Store::format(&mut flash, BASE, LEN).unwrap();
+    let a = record(&mut store, 1, 300);
+    let b = {
+        // abandoned without finishing, leaving a partial chunk unwritten
+        let mut recorder = store.record(SampleRate::Hz16000, Codec::Pcm16, 2).unwrap();
+        recorder.write(&pattern(2, 5000)).unwrap();
+        recorder.id()
+    };
+    store.into_inner();
+
+    let mut store = Store::mount(&mut flash, BASE, LEN).unwrap();
+    let clips: Vec<_> = store.clips().map(Result::unwrap).collect();
+    let kept = 5000 / PAYLOAD * PAYLOAD;
+    assert_eq!(
+        clips.iter().map(|c| (c.id, c.len)).collect::<Vec<_>>(),
+        [(a, 300), (b, kept as u32)]
+    );
+    assert_eq!(contents(&mut store, b), pattern(2, kept));
+    // the next clip starts past what the unfinished one programmed
+    let c = record(&mut store, 3, 300);
+    assert_eq!(store.clip(c).unwrap().start, 5 * SECTOR);
+    assert_eq!(contents(&mut store, a), pattern(1, 300));
+
+    // compaction finishes it for good
+    store.delete(a).unwrap();
+    store.compact().unwrap();
+    assert_eq!(store.clip(0).unwrap().len, kept as u32);
+    assert_eq!(contents(&mut store, 0), pattern(2, kept));
+}
+
+#[test]
+fn unfinished_recording_without_chunks_is_dropped() {
+    let mut flash = flash();
+    let mut store = Store::format(&mut flash, BASE, LEN).unwrap();
+    store
+        .record(SampleRate::Hz16000, Codec::Pcm16, 0)
+        .unwrap()
+        .write(&[1; 100])
+        .unwrap();
+    store.into_inner();
+
+    let mut store = Store::mount(&mut flash, BASE, LEN).unwrap();
+    assert_eq!(store.clips().count(), 0);
+    assert_eq!(store.slots(), 1);
+    let a = record(&mut store, 1, 300);
+    assert_eq!(store.clip(a).unwrap().start, 2 * SECTOR);
+}
+
+#[test]
+fn reformatting_leaves_no_stale_chunks_behind() {
+    let mut flash = flash();
+    let mut store = Store::format(&mut flash, BASE, LEN).unwrap();
+    // three sectors of chunks, tagged for slot 0 of generation 0
+    record(&mut store, 1, 9000);
+    store.into_inner();
+
+    // the first clip after formatting again gets the same slot and
+    // generation, and is cut off right at the end of its first sector
+    let mut store = Store::format(&mut flash, BASE, LEN).unwrap();
+    let id = {
+        let mut recorder = store.record(SampleRate::Hz16000, Codec::Pcm16, 2).unwrap();
+        recorder.write(&pattern(2, capacity(1) as usize)).unwrap();
+        recorder.id()
+    };
+    store.into_inner();
+
+    let mut store = Store::mount(&mut flash, BASE, LEN).unwrap();
+    assert_eq!(id, 0);
+    assert_eq!(store.clip(id).unwrap().len, capacity(1));
+    assert_eq!(contents(&mut store, id), pattern(2, capacity(1) as usize));
+}
+
+#[test]
+fn blank_flash_is_not_formatted() {
+    let mut flash = flash();
+    assert!(matches!(
+        Store::mount(&mut flash, BASE, LEN),
+        Err(Error::NotFormatted)
+    ));
+}
+
+#[test]
+fn region_must_be_whole_sectors_inside_the_flash() {
+    let mut flash = flash();
+    assert!(matches!(
+        Store::format(&mut flash, BASE + 256, LEN),
+        Err(Error::Geometry)
+    ));
+    assert!(matches!(
+        Store::format(&mut flash, BASE, 2 * SECTOR),
+        Err(Error::Geometry)
+    ));
+    assert!(matches!(
+        Store::format(&mut flash, BASE, 2 * 1024 * 1024),
+        Err(Error::Geometry)
+    ));
+}